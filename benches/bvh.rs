@@ -0,0 +1,124 @@
+//! BVH build and traversal over the built-in scenes. Traversal throughput is
+//! reported in rays per second.
+
+use criterion::{BatchSize, BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use ray::{
+    Float,
+    bvh::{Bvh, BvhBuildStrategy},
+    geo::Sphere,
+    hit::{Hit, HitList},
+    interval::Interval,
+    material::Material,
+    primitive::Primitive,
+    ray::Ray,
+    sampler::{RandomSampler, Sampler},
+    scenes::Preset,
+    vec3::Vec3,
+};
+
+/// Rays traced per traversal iteration.
+const RAYS: usize = 4096;
+
+fn build(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bvh_build");
+    for preset in Preset::ALL {
+        let primitives = preset.build(0).world().primitives().to_vec();
+        group.throughput(Throughput::Elements(primitives.len() as u64));
+        for strategy in [BvhBuildStrategy::Median, BvhBuildStrategy::Fast] {
+            let id = BenchmarkId::new(format!("{strategy:?}"), preset);
+            group.bench_function(id, |b| {
+                b.iter_batched_ref(
+                    || primitives.clone(),
+                    |list| Bvh::from_list_with(list, strategy),
+                    BatchSize::SmallInput,
+                );
+            });
+        }
+    }
+    group.finish();
+}
+
+/// Rays from a sphere around `world` towards random points inside its bounds,
+/// the same on every run.
+fn rays<P: Hit>(world: &Bvh<P>) -> Vec<Ray> {
+    let bbox = world.aabb();
+    let center = bbox.centroid();
+    let radius = (Vec3::new(bbox.x().max(), bbox.y().max(), bbox.z().max()) - center).length();
+    let mut sampler = RandomSampler::new(0);
+    (0..RAYS)
+        .map(|_| {
+            let origin = center + Vec3::random_unit(&mut sampler) * radius * 2.0;
+            let target = Vec3::new(
+                sampler.next_range(bbox.x()),
+                sampler.next_range(bbox.y()),
+                sampler.next_range(bbox.z()),
+            );
+            Ray::new(origin, target - origin, 0.0)
+        })
+        .collect()
+}
+
+fn traverse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bvh_traverse");
+    group.throughput(Throughput::Elements(RAYS as u64));
+    for preset in Preset::ALL {
+        let scene = preset.build(0);
+        let rays = rays(scene.world());
+        let ray_t = Interval::new(0.001, Float::INFINITY);
+        group.bench_function(BenchmarkId::new("closest", preset), |b| {
+            b.iter(|| {
+                rays.iter()
+                    .filter(|ray| scene.world().hit(ray, ray_t).is_some())
+                    .count()
+            });
+        });
+        group.bench_function(BenchmarkId::new("any", preset), |b| {
+            b.iter(|| {
+                rays.iter()
+                    .filter(|ray| scene.world().hit_any(ray, ray_t))
+                    .count()
+            });
+        });
+    }
+    group.finish();
+}
+
+/// Closest hit traversal of the same spheres stored as trait objects and by
+/// value.
+fn storage(c: &mut Criterion) {
+    let mut sampler = RandomSampler::new(0);
+    let material = Material::lambertian(Vec3::new(0.5, 0.5, 0.5));
+    let spheres: Vec<_> = (0..10_000)
+        .map(|_| {
+            let center = Vec3::random_range(Interval::new(-50.0, 50.0), &mut sampler);
+            Sphere::new(center, 0.5, material)
+        })
+        .collect();
+    let mut list: HitList = spheres.iter().copied().collect();
+    let dynamic = Bvh::from_list(list.list_mut());
+    let mut primitives: Vec<Primitive> = spheres.into_iter().map(Primitive::from).collect();
+    let by_value = Bvh::from_list(&mut primitives);
+
+    let rays = rays(&dynamic);
+    let ray_t = Interval::new(0.001, Float::INFINITY);
+    let mut group = c.benchmark_group("bvh_storage");
+    group.throughput(Throughput::Elements(RAYS as u64));
+    group.bench_function("dyn", |b| {
+        b.iter(|| {
+            rays.iter()
+                .filter(|ray| dynamic.hit(ray, ray_t).is_some())
+                .count()
+        });
+    });
+    group.bench_function("primitive", |b| {
+        b.iter(|| {
+            rays.iter()
+                .filter(|ray| by_value.hit(ray, ray_t).is_some())
+                .count()
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(benches, build, traverse, storage);
+criterion_main!(benches);
@@ -0,0 +1,32 @@
+//! Small fixed-seed renders of the built-in scenes, with throughput reported
+//! in camera rays per second.
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use ray::scenes::Preset;
+
+const WIDTH: u32 = 64;
+const SAMPLES: u32 = 4;
+const MAX_DEPTH: u32 = 8;
+
+fn render(c: &mut Criterion) {
+    let mut group = c.benchmark_group("render");
+    group.sample_size(10);
+    for preset in Preset::ALL {
+        let scene = preset.build(0);
+        let camera = scene
+            .camera()
+            .aspect_ratio(1.0)
+            .img_width(WIDTH)
+            .build()
+            .with_seed(0);
+        let rays = camera.img_width() as u64 * camera.img_height() as u64 * SAMPLES as u64;
+        group.throughput(Throughput::Elements(rays));
+        group.bench_function(BenchmarkId::from_parameter(preset), |b| {
+            b.iter(|| camera.render_frame(scene.world(), scene.lights(), SAMPLES, MAX_DEPTH));
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, render);
+criterion_main!(benches);
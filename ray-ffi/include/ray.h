@@ -0,0 +1,84 @@
+/* C interface of the ray renderer, implemented by the ray-ffi crate. */
+
+#ifndef RAY_H
+#define RAY_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define RAY_LAMBERTIAN 0u
+#define RAY_METAL 1u
+#define RAY_DIELECTRIC 2u
+#define RAY_DIFFUSE_LIGHT 3u
+
+typedef enum RayStatus {
+    RAY_OK = 0,
+    RAY_NULL_POINTER = 1,
+    RAY_INVALID_PARAMETER = 2,
+    RAY_BUFFER_TOO_SMALL = 3,
+    RAY_RENDER_FAILED = 4,
+} RayStatus;
+
+typedef struct RayVec3 {
+    double x;
+    double y;
+    double z;
+} RayVec3;
+
+/* `color` is the albedo, or the emitted radiance of RAY_DIFFUSE_LIGHT.
+ * `param` is the fuzz of RAY_METAL and the index of refraction of
+ * RAY_DIELECTRIC, and is ignored otherwise. */
+typedef struct RayMaterial {
+    uint32_t kind;
+    RayVec3 color;
+    double param;
+} RayMaterial;
+
+/* Angles are in degrees; a defocus_angle of zero disables defocus blur. */
+typedef struct RayCamera {
+    uint32_t width;
+    uint32_t height;
+    RayVec3 look_from;
+    RayVec3 look_at;
+    RayVec3 vup;
+    double fov;
+    double defocus_angle;
+    double focus_dist;
+} RayCamera;
+
+typedef struct RayScene RayScene;
+
+RayScene *ray_scene_new(void);
+void ray_scene_free(RayScene *scene);
+
+RayStatus ray_scene_add_sphere(RayScene *scene, RayVec3 center, double radius,
+                               RayMaterial material);
+/* index_count / 3 counterclockwise triangles indexing into positions. */
+RayStatus ray_scene_add_mesh(RayScene *scene, const RayVec3 *positions,
+                             size_t vertex_count, const uint32_t *indices,
+                             size_t index_count, RayMaterial material);
+RayStatus ray_scene_set_camera(RayScene *scene, const RayCamera *camera);
+RayStatus ray_scene_set_background(RayScene *scene, RayVec3 color);
+
+/* Size of the rendered image, which may differ by a pixel from the camera's
+ * because of rounding. */
+RayStatus ray_scene_image_size(const RayScene *scene, uint32_t *width,
+                               uint32_t *height);
+
+/* Writes width * height * 4 bytes of gamma encoded RGBA, rows from the top. */
+RayStatus ray_render(const RayScene *scene, uint32_t samples,
+                     uint32_t max_depth, uint8_t *rgba, size_t len);
+
+/* Last failure on the calling thread, or NULL. Valid until the next failing
+ * call on the thread. */
+const char *ray_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* RAY_H */
@@ -0,0 +1,376 @@
+//! C interface of the renderer, declared in `include/ray.h`.
+//!
+//! A scene is created with [`ray_scene_new`], filled with spheres and
+//! triangle meshes, given a camera and rendered into a caller-provided RGBA
+//! buffer. Functions report failures through their [`RayStatus`], with a
+//! description available from [`ray_last_error`] on the same thread.
+
+use std::{
+    cell::RefCell,
+    ffi::{CString, c_char},
+    panic::{self, AssertUnwindSafe},
+    ptr, slice,
+};
+
+use ray::{
+    Float,
+    background::Background,
+    bvh::Bvh,
+    camera::{Camera, CameraBuilder},
+    geo::{Sphere, Triangle},
+    hit::HitList,
+    light::LightList,
+    material::{Material, Scatter},
+    vec3::Vec3,
+};
+
+pub const RAY_LAMBERTIAN: u32 = 0;
+pub const RAY_METAL: u32 = 1;
+pub const RAY_DIELECTRIC: u32 = 2;
+pub const RAY_DIFFUSE_LIGHT: u32 = 3;
+
+/// Outcome of a call.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RayStatus {
+    Ok = 0,
+    NullPointer = 1,
+    InvalidParameter = 2,
+    BufferTooSmall = 3,
+    RenderFailed = 4,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayVec3 {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+/// Surface material. `color` is the albedo, or the emitted radiance of
+/// [`RAY_DIFFUSE_LIGHT`]. `param` is the fuzz of [`RAY_METAL`] and the index
+/// of refraction of [`RAY_DIELECTRIC`], and is ignored otherwise.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayMaterial {
+    pub kind:  u32,
+    pub color: RayVec3,
+    pub param: f64,
+}
+
+/// Pinhole or thin lens camera. Angles are in degrees; a `defocus_angle` of
+/// zero disables defocus blur.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayCamera {
+    pub width:         u32,
+    pub height:        u32,
+    pub look_from:     RayVec3,
+    pub look_at:       RayVec3,
+    pub vup:           RayVec3,
+    pub fov:           f64,
+    pub defocus_angle: f64,
+    pub focus_dist:    f64,
+}
+
+/// Objects, emitters and camera of a render.
+#[derive(Debug)]
+pub struct RayScene {
+    world:  HitList,
+    lights: HitList,
+    camera: CameraBuilder,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn fail(status: RayStatus, message: impl Into<String>) -> RayStatus {
+    let message = CString::new(message.into()).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+    status
+}
+
+impl From<RayVec3> for Vec3 {
+    fn from(v: RayVec3) -> Self {
+        Vec3::new(v.x as Float, v.y as Float, v.z as Float)
+    }
+}
+
+impl TryFrom<RayMaterial> for Material {
+    type Error = RayStatus;
+
+    fn try_from(material: RayMaterial) -> Result<Self, Self::Error> {
+        let color = Vec3::from(material.color);
+        let param = material.param as Float;
+        match material.kind {
+            RAY_LAMBERTIAN => Ok(Material::lambertian(color)),
+            RAY_METAL => Ok(Material::metal(color, param)),
+            RAY_DIELECTRIC => Ok(Material::dialectric(param)),
+            RAY_DIFFUSE_LIGHT => Ok(Material::diffuse_light(color)),
+            kind => Err(fail(
+                RayStatus::InvalidParameter,
+                format!("unknown material kind {kind}"),
+            )),
+        }
+    }
+}
+
+impl RayScene {
+    fn camera(&self) -> Camera {
+        self.camera.clone().build()
+    }
+}
+
+/// Creates an empty scene with a black background, seen by the default
+/// camera of a 400 by 225 image. Free it with [`ray_scene_free`].
+#[unsafe(no_mangle)]
+pub extern "C" fn ray_scene_new() -> *mut RayScene {
+    let scene = RayScene {
+        world:  HitList::new(),
+        lights: HitList::new(),
+        camera: Camera::builder(),
+    };
+    Box::into_raw(Box::new(scene))
+}
+
+/// Frees a scene. Null is ignored.
+///
+/// # Safety
+///
+/// `scene` must be null or come from [`ray_scene_new`] and not have been
+/// freed already.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ray_scene_free(scene: *mut RayScene) {
+    if !scene.is_null() {
+        drop(unsafe { Box::from_raw(scene) });
+    }
+}
+
+/// Adds a sphere of `radius` around `center`.
+///
+/// # Safety
+///
+/// `scene` must be null or a live scene from [`ray_scene_new`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ray_scene_add_sphere(
+    scene: *mut RayScene,
+    center: RayVec3,
+    radius: f64,
+    material: RayMaterial,
+) -> RayStatus {
+    let Some(scene) = (unsafe { scene.as_mut() }) else {
+        return fail(RayStatus::NullPointer, "scene is null");
+    };
+    let material = match Material::try_from(material) {
+        Ok(material) => material,
+        Err(status) => return status,
+    };
+    let sphere = match Sphere::try_new(center.into(), radius as Float, material) {
+        Ok(sphere) => sphere,
+        Err(err) => return fail(RayStatus::InvalidParameter, err.to_string()),
+    };
+
+    scene.world.push(sphere);
+    if material.emit().is_some() {
+        scene.lights.push(sphere);
+    }
+    RayStatus::Ok
+}
+
+/// Adds a triangle mesh of `index_count / 3` triangles, each given by three
+/// indices into the `vertex_count` `positions`, wound counterclockwise
+/// around their front.
+///
+/// # Safety
+///
+/// `scene` must be null or a live scene from [`ray_scene_new`].
+/// `positions` and `indices` must be null or point to `vertex_count` and
+/// `index_count` readable elements.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ray_scene_add_mesh(
+    scene: *mut RayScene,
+    positions: *const RayVec3,
+    vertex_count: usize,
+    indices: *const u32,
+    index_count: usize,
+    material: RayMaterial,
+) -> RayStatus {
+    let Some(scene) = (unsafe { scene.as_mut() }) else {
+        return fail(RayStatus::NullPointer, "scene is null");
+    };
+    if positions.is_null() || indices.is_null() {
+        return fail(RayStatus::NullPointer, "mesh positions or indices are null");
+    }
+    let positions = unsafe { slice::from_raw_parts(positions, vertex_count) };
+    let indices = unsafe { slice::from_raw_parts(indices, index_count) };
+
+    if !index_count.is_multiple_of(3) {
+        return fail(
+            RayStatus::InvalidParameter,
+            "mesh index count is not a multiple of 3",
+        );
+    }
+    if indices.iter().any(|&index| index as usize >= vertex_count) {
+        return fail(RayStatus::InvalidParameter, "mesh index out of range");
+    }
+    let material = match Material::try_from(material) {
+        Ok(material) => material,
+        Err(status) => return status,
+    };
+
+    for face in indices.chunks_exact(3) {
+        let [a, b, c] = [face[0], face[1], face[2]].map(|index| positions[index as usize].into());
+        let triangle = Triangle::new(a, b, c, material);
+        scene.world.push(triangle);
+        if material.emit().is_some() {
+            scene.lights.push(triangle);
+        }
+    }
+    RayStatus::Ok
+}
+
+/// Sets the camera the scene is rendered from.
+///
+/// # Safety
+///
+/// `scene` and `camera` must be null or valid pointers, `scene` to a live
+/// scene from [`ray_scene_new`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ray_scene_set_camera(
+    scene: *mut RayScene,
+    camera: *const RayCamera,
+) -> RayStatus {
+    let (Some(scene), Some(camera)) = (unsafe { scene.as_mut() }, unsafe { camera.as_ref() })
+    else {
+        return fail(RayStatus::NullPointer, "scene or camera is null");
+    };
+    if camera.width == 0 || camera.height == 0 {
+        return fail(RayStatus::InvalidParameter, "image size must not be zero");
+    }
+
+    scene.camera = scene
+        .camera
+        .clone()
+        .img_width(camera.width)
+        .aspect_ratio(camera.width as Float / camera.height as Float)
+        .look_from(camera.look_from.into())
+        .look_at(camera.look_at.into())
+        .vup(camera.vup.into())
+        .fov(camera.fov as Float)
+        .defocus_angle(camera.defocus_angle as Float)
+        .focus_dist(camera.focus_dist as Float);
+    RayStatus::Ok
+}
+
+/// Sets the radiance arriving from directions that hit nothing.
+///
+/// # Safety
+///
+/// `scene` must be null or a live scene from [`ray_scene_new`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ray_scene_set_background(
+    scene: *mut RayScene,
+    color: RayVec3,
+) -> RayStatus {
+    let Some(scene) = (unsafe { scene.as_mut() }) else {
+        return fail(RayStatus::NullPointer, "scene is null");
+    };
+    scene.camera = scene
+        .camera
+        .clone()
+        .background(Background::Color(color.into()));
+    RayStatus::Ok
+}
+
+/// Writes the size of the rendered image to `width` and `height`, which
+/// may differ by a pixel from the camera's because of rounding.
+///
+/// # Safety
+///
+/// All pointers must be null or valid, `scene` pointing to a live scene
+/// from [`ray_scene_new`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ray_scene_image_size(
+    scene: *const RayScene,
+    width: *mut u32,
+    height: *mut u32,
+) -> RayStatus {
+    let Some(scene) = (unsafe { scene.as_ref() }) else {
+        return fail(RayStatus::NullPointer, "scene is null");
+    };
+    if width.is_null() || height.is_null() {
+        return fail(RayStatus::NullPointer, "size output is null");
+    }
+    let camera = scene.camera();
+    unsafe {
+        *width = camera.img_width();
+        *height = camera.img_height();
+    }
+    RayStatus::Ok
+}
+
+/// Renders the scene with `samples` samples per pixel and paths of up to
+/// `max_depth` bounces into `rgba`, as gamma encoded 8-bit RGBA rows from
+/// the top. The buffer must hold at least `width * height * 4` bytes, see
+/// [`ray_scene_image_size`].
+///
+/// # Safety
+///
+/// `scene` must be null or a live scene from [`ray_scene_new`], and `rgba`
+/// null or writable for `len` bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ray_render(
+    scene: *const RayScene,
+    samples: u32,
+    max_depth: u32,
+    rgba: *mut u8,
+    len: usize,
+) -> RayStatus {
+    let Some(scene) = (unsafe { scene.as_ref() }) else {
+        return fail(RayStatus::NullPointer, "scene is null");
+    };
+    if rgba.is_null() {
+        return fail(RayStatus::NullPointer, "output buffer is null");
+    }
+    let camera = scene.camera();
+    let needed = camera.img_width() as usize * camera.img_height() as usize * 4;
+    if len < needed {
+        return fail(
+            RayStatus::BufferTooSmall,
+            format!("output buffer holds {len} bytes, the image needs {needed}"),
+        );
+    }
+
+    let rendered = panic::catch_unwind(AssertUnwindSafe(|| {
+        let lights = LightList::from_list(&scene.lights);
+        let frame = if scene.world.is_empty() {
+            camera.render_frame(&scene.world, &lights, samples, max_depth)
+        } else {
+            let mut objects = scene.world.list().to_vec();
+            let bvh = Bvh::from_list(&mut objects);
+            camera.render_frame(&bvh, &lights, samples, max_depth)
+        };
+        frame.beauty().to_rgba8()
+    }));
+    let bytes = match rendered {
+        Ok(bytes) => bytes,
+        Err(_) => return fail(RayStatus::RenderFailed, "rendering panicked"),
+    };
+
+    unsafe { ptr::copy_nonoverlapping(bytes.as_ptr(), rgba, bytes.len()) };
+    RayStatus::Ok
+}
+
+/// Description of the last failure on the calling thread, or null if there
+/// was none. The string stays valid until the next failing call on the
+/// thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn ray_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
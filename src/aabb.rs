@@ -1,8 +1,14 @@
 use std::cmp::Ordering;
 
-use crate::{interval::Interval, ray::Ray, vec3::Vec3};
+use crate::{Float, interval::Interval, ray::Ray, vec3::Vec3};
+
+/// Factor covering the rounding error of the slab distances, `1 + 2γ(3)`
+/// with `γ(n) = nε / (1 - nε)` and `ε` the unit roundoff.
+const SLAB_ERROR: Float = 1.0 + 2.0 * (3.0 * EPSILON) / (1.0 - 3.0 * EPSILON);
+const EPSILON: Float = Float::EPSILON / 2.0;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Axis {
     X,
     Y,
@@ -18,6 +24,7 @@ impl Axis {
 }
 
 #[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Aabb {
     x: Interval,
     y: Interval,
@@ -26,29 +33,23 @@ pub struct Aabb {
 
 impl Aabb {
     pub const fn new(x: Interval, y: Interval, z: Interval) -> Self {
-        const DELTA: f64 = 0.0001; // padding 0 sized intervals
-        let x = if x.size() < DELTA { x.expand(DELTA) } else { x };
-        let y = if y.size() < DELTA { y.expand(DELTA) } else { y };
-        let z = if z.size() < DELTA { z.expand(DELTA) } else { z };
-        Self { x, y, z }
+        const DELTA: Float = 0.0001; // padding 0 sized intervals
+        Self { x, y, z }.pad(DELTA)
+    }
+
+    /// Box containing nothing, the identity of [`Aabb::merge`].
+    pub const fn empty() -> Self {
+        Self {
+            x: Interval::empty(),
+            y: Interval::empty(),
+            z: Interval::empty(),
+        }
     }
 
     pub const fn from_points(a: Vec3, b: Vec3) -> Self {
-        let x = if a.x() <= b.x() {
-            Interval::new(a.x(), b.x())
-        } else {
-            Interval::new(b.x(), a.x())
-        };
-        let y = if a.y() <= b.y() {
-            Interval::new(a.y(), b.y())
-        } else {
-            Interval::new(b.y(), a.y())
-        };
-        let z = if a.z() <= b.z() {
-            Interval::new(a.z(), b.z())
-        } else {
-            Interval::new(b.z(), a.z())
-        };
+        let x = Interval::spanning(a.x(), b.x());
+        let y = Interval::spanning(a.y(), b.y());
+        let z = Interval::spanning(a.z(), b.z());
         Self::new(x, y, z)
     }
 
@@ -89,45 +90,79 @@ impl Aabb {
         }
     }
 
+    /// Part of `ray_t` during which `ray` is inside the box, from the
+    /// parameter where it enters to the one where it leaves, or `None` if it
+    /// misses the box.
+    ///
+    /// The slab planes are ordered by the sign of the direction, so a
+    /// ray parallel to a slab and starting on its boundary gives a NaN
+    /// distance, which is ignored rather than rejecting the box. The exit
+    /// distance is widened by the worst case rounding error so grazing rays
+    /// are never missed.
     #[inline(always)]
-    pub fn hit(&self, ray: &Ray, mut ray_t: Interval) -> bool {
+    pub fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<Interval> {
         let origin = ray.origin();
-        let dir = ray.direction();
+        let inv_dir = ray.inv_direction();
+        let negative = ray.negative();
 
+        let mut min = ray_t.min();
+        let mut max = ray_t.max();
         for axis_i in Axis::all() {
             let axis = self.get(axis_i);
-            let adinv = 1.0 / dir.get(axis_i);
-
-            let t0 = (axis.min() - origin.get(axis_i)) * adinv;
-            let t1 = (axis.max() - origin.get(axis_i)) * adinv;
+            let (near, far) = if negative[axis_i as usize] {
+                (axis.max(), axis.min())
+            } else {
+                (axis.min(), axis.max())
+            };
 
-            let mut min = ray_t.min();
-            let mut max = ray_t.max();
+            let inv = inv_dir.get(axis_i);
+            let near = (near - origin.get(axis_i)) * inv;
+            let far = (far - origin.get(axis_i)) * inv * SLAB_ERROR;
 
-            if t0 < t1 {
-                if t0 > ray_t.min() {
-                    min = t0;
-                }
-                if t1 < ray_t.max() {
-                    max = t1;
-                }
-            } else {
-                if t1 > ray_t.min() {
-                    min = t1;
-                }
-                if t0 < ray_t.max() {
-                    max = t0;
-                }
+            // Comparisons with NaN are false and keep the current bounds.
+            if near > min {
+                min = near;
             }
-
-            if max <= min {
-                return false;
+            if far < max {
+                max = far;
+            }
+            if min > max {
+                return None;
             }
+        }
 
-            ray_t = Interval::new(min, max);
+        Some(Interval::new(min, max))
+    }
+
+    /// Widens every side thinner than `min_size` to `min_size`, around its
+    /// center, so boxes of flat primitives such as axis-aligned quads and
+    /// triangles still have a volume to hit.
+    pub const fn pad(self, min_size: Float) -> Self {
+        Self {
+            x: pad(self.x, min_size),
+            y: pad(self.y, min_size),
+            z: pad(self.z, min_size),
         }
+    }
+
+    pub const fn surface_area(&self) -> Float {
+        let (x, y, z) = (self.x.size(), self.y.size(), self.z.size());
+        2.0 * (x * y + y * z + z * x)
+    }
+
+    pub const fn centroid(&self) -> Vec3 {
+        Vec3::new(self.x.midpoint(), self.y.midpoint(), self.z.midpoint())
+    }
+
+    pub const fn contains_point(&self, point: Vec3) -> bool {
+        self.x.contains(point.x()) && self.y.contains(point.y()) && self.z.contains(point.z())
+    }
 
-        true
+    /// Whether `other` lies entirely inside the box.
+    pub const fn contains(&self, other: &Self) -> bool {
+        self.x.contains_interval(other.x)
+            && self.y.contains_interval(other.y)
+            && self.z.contains_interval(other.z)
     }
 
     pub fn compare(&self, other: &Self, axis: Axis) -> Ordering {
@@ -136,3 +171,11 @@ impl Aabb {
         axis_l.min().total_cmp(&axis_r.min())
     }
 }
+
+const fn pad(axis: Interval, min_size: Float) -> Interval {
+    if axis.size() < min_size {
+        axis.expand(min_size - axis.size())
+    } else {
+        axis
+    }
+}
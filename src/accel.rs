@@ -0,0 +1,47 @@
+//! Common interface of the acceleration structures, so scenes can be built
+//! and benchmarked with any of them.
+
+use std::sync::Arc;
+
+use crate::{
+    bvh::Bvh,
+    grid::Grid,
+    hit::{DynHit, Hit},
+    material::Material,
+    qbvh::Qbvh,
+};
+
+/// Acceleration structure built over a list of primitives.
+pub trait Accel: Hit<Material = Material> + Send + Sync {
+    /// Short name of the structure, for reports and benchmarks.
+    const NAME: &'static str;
+
+    /// Builds the structure, possibly reordering `hit_list`.
+    fn build(hit_list: &mut [Arc<dyn DynHit>]) -> Self
+    where
+        Self: Sized;
+}
+
+impl Accel for Bvh {
+    const NAME: &'static str = "bvh";
+
+    fn build(hit_list: &mut [Arc<dyn DynHit>]) -> Self {
+        Self::from_list(hit_list)
+    }
+}
+
+impl Accel for Qbvh {
+    const NAME: &'static str = "qbvh";
+
+    fn build(hit_list: &mut [Arc<dyn DynHit>]) -> Self {
+        Self::from_list(hit_list)
+    }
+}
+
+impl Accel for Grid {
+    const NAME: &'static str = "grid";
+
+    fn build(hit_list: &mut [Arc<dyn DynHit>]) -> Self {
+        Self::from_list(hit_list)
+    }
+}
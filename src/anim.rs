@@ -0,0 +1,234 @@
+//! Keyframe animation of transforms and camera parameters.
+
+use std::{
+    fs::File,
+    io,
+    ops::{Add, Mul, Range, Sub},
+    path::Path,
+};
+
+use crate::{
+    Float,
+    camera::{Camera, CameraBuilder},
+    hit::Hit,
+    sequence::frame_path,
+    transform::Transform,
+    vec3::Vec3,
+};
+
+/// How values between keyframes are computed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    /// Keeps the value of the previous key.
+    Step,
+    #[default]
+    Linear,
+    /// Catmull-Rom spline through the keys.
+    Cubic,
+}
+
+/// Value that can be interpolated between keyframes.
+pub trait Keyframe:
+    Copy + Add<Output = Self> + Sub<Output = Self> + Mul<Float, Output = Self>
+{
+}
+impl<T: Copy + Add<Output = T> + Sub<Output = T> + Mul<Float, Output = T>> Keyframe for T {}
+
+/// Keyframed value, constant before the first and after the last key.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track<T> {
+    keys:          Vec<(Float, T)>,
+    interpolation: Interpolation,
+}
+
+/// Keyframed [`Transform`], rotating around a fixed axis.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimatedTransform {
+    translation: Track<Vec3>,
+    axis:        Vec3,
+    rotation:    Track<Float>,
+    scale:       Track<Float>,
+}
+
+/// Keyframed camera parameters, overriding those of a [`CameraBuilder`].
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CameraAnimation {
+    look_from:  Option<Track<Vec3>>,
+    look_at:    Option<Track<Vec3>>,
+    fov:        Option<Track<Float>>,
+    focus_dist: Option<Track<Float>>,
+}
+
+impl<T: Keyframe> Track<T> {
+    pub const fn new(interpolation: Interpolation) -> Self {
+        Self {
+            keys: Vec::new(),
+            interpolation,
+        }
+    }
+
+    pub fn constant(value: T) -> Self {
+        Self::new(Interpolation::Step).with_key(0.0, value)
+    }
+
+    /// Adds a key of `value` at `time`, replacing any key at the same time.
+    pub fn with_key(mut self, time: Float, value: T) -> Self {
+        match self.keys.binary_search_by(|(t, _)| t.total_cmp(&time)) {
+            Ok(index) => self.keys[index].1 = value,
+            Err(index) => self.keys.insert(index, (time, value)),
+        }
+        self
+    }
+
+    pub fn keys(&self) -> &[(Float, T)] {
+        &self.keys
+    }
+
+    pub const fn interpolation(&self) -> Interpolation {
+        self.interpolation
+    }
+
+    /// Value of the track at `time`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the track has no keys.
+    pub fn sample(&self, time: Float) -> T {
+        assert!(!self.keys.is_empty(), "sampling a track without keys");
+
+        let next = self.keys.partition_point(|(t, _)| *t <= time);
+        if next == 0 {
+            return self.keys[0].1;
+        }
+        if next == self.keys.len() {
+            return self.keys[next - 1].1;
+        }
+
+        let (t1, p1) = self.keys[next - 1];
+        let (t2, p2) = self.keys[next];
+        let u = (time - t1) / (t2 - t1);
+        match self.interpolation {
+            Interpolation::Step => p1,
+            Interpolation::Linear => p1 + (p2 - p1) * u,
+            Interpolation::Cubic => {
+                let p0 = self.keys[next.saturating_sub(2)].1;
+                let p3 = self.keys[(next + 1).min(self.keys.len() - 1)].1;
+                catmull_rom(p0, p1, p2, p3, u)
+            }
+        }
+    }
+}
+
+impl Default for AnimatedTransform {
+    fn default() -> Self {
+        Self {
+            translation: Track::constant(Vec3::default()),
+            axis:        Vec3::new(0.0, 1.0, 0.0),
+            rotation:    Track::constant(0.0),
+            scale:       Track::constant(1.0),
+        }
+    }
+}
+
+impl AnimatedTransform {
+    pub fn with_translation(mut self, translation: Track<Vec3>) -> Self {
+        self.translation = translation;
+        self
+    }
+
+    /// Rotation in degrees around `axis`.
+    pub fn with_rotation(mut self, axis: Vec3, rotation: Track<Float>) -> Self {
+        self.axis = axis;
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: Track<Float>) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Transform at `time`.
+    pub fn at(&self, time: Float) -> Transform {
+        Transform::scaling(self.scale.sample(time))
+            .with_rotation(self.axis, self.rotation.sample(time))
+            .with_translation(self.translation.sample(time))
+    }
+}
+
+impl CameraAnimation {
+    pub fn with_look_from(mut self, track: Track<Vec3>) -> Self {
+        self.look_from = Some(track);
+        self
+    }
+
+    pub fn with_look_at(mut self, track: Track<Vec3>) -> Self {
+        self.look_at = Some(track);
+        self
+    }
+
+    pub fn with_fov(mut self, track: Track<Float>) -> Self {
+        self.fov = Some(track);
+        self
+    }
+
+    pub fn with_focus_dist(mut self, track: Track<Float>) -> Self {
+        self.focus_dist = Some(track);
+        self
+    }
+
+    /// Returns `builder` with the animated parameters set to their value at
+    /// `time`.
+    pub fn at(&self, builder: CameraBuilder, time: Float) -> CameraBuilder {
+        let mut builder = builder;
+        if let Some(track) = &self.look_from {
+            builder = builder.look_from(track.sample(time));
+        }
+        if let Some(track) = &self.look_at {
+            builder = builder.look_at(track.sample(time));
+        }
+        if let Some(track) = &self.fov {
+            builder = builder.fov(track.sample(time));
+        }
+        if let Some(track) = &self.focus_dist {
+            builder = builder.focus_dist(track.sample(time));
+        }
+        builder
+    }
+}
+
+/// Renders every frame in `frames` at `fps` frames per second. The camera
+/// and scene of each frame are built by `scene_at` from the frame time in
+/// seconds, the camera being set to that [frame](Camera::with_frame), and
+/// the beauty passes are written into `directory` as numbered PPMs named
+/// like those of [`render_sequence`](crate::sequence::render_sequence).
+pub fn render_animation<T: Hit + Sync, L: Hit + Sync>(
+    frames: Range<u32>,
+    fps: Float,
+    scene_at: impl Fn(Float) -> (Camera, T, L),
+    sample_count: u32,
+    max_depth: u32,
+    directory: impl AsRef<Path>,
+) -> io::Result<()>
+where
+    T::Material: Copy,
+{
+    for frame in frames {
+        let (camera, world, lights) = scene_at(frame as Float / fps);
+        let camera = camera.with_frame(frame);
+        let image = camera.render_frame(&world, &lights, sample_count, max_depth);
+        let file = File::create(frame_path(directory.as_ref(), frame))?;
+        image.beauty().write_ppm(file)?;
+    }
+    Ok(())
+}
+
+fn catmull_rom<T: Keyframe>(p0: T, p1: T, p2: T, p3: T, u: Float) -> T {
+    let u2 = u * u;
+    let u3 = u2 * u;
+    let a = p1 * 2.0;
+    let b = (p2 - p0) * u;
+    let c = (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * u2;
+    let d = (p1 * 3.0 - p0 - p2 * 3.0 + p3) * u3;
+    (a + b + c + d) * 0.5
+}
@@ -0,0 +1,83 @@
+use std::sync::Arc;
+
+use crate::{
+    Float,
+    consts::PI,
+    error::{Error, Result},
+    film::Image,
+    pdf::Distribution2D,
+    sampler::Sampler,
+    vec3::Vec3,
+};
+
+/// Shape of the lens aperture, which is the shape out of focus highlights
+/// take.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Aperture {
+    #[default]
+    Circle,
+    /// Regular polygon with `blades` sides, rotated by `rotation` degrees.
+    Polygon { blades: u32, rotation: Float },
+    /// Arbitrary shape given by the luminance of an image covering
+    /// `[-1, 1]²`.
+    Mask(Arc<Distribution2D>),
+}
+
+impl Aperture {
+    /// # Panics
+    ///
+    /// Panics with fewer than 3 `blades`, see [`Aperture::try_polygon`].
+    pub fn polygon(blades: u32, rotation: Float) -> Self {
+        Self::try_polygon(blades, rotation).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Regular polygon with `blades` sides, failing with fewer than 3.
+    pub fn try_polygon(blades: u32, rotation: Float) -> Result<Self> {
+        if blades < 3 {
+            return Err(Error::InvalidParameter(
+                "an aperture polygon needs at least 3 blades",
+            ));
+        }
+        Ok(Self::Polygon { blades, rotation })
+    }
+
+    /// Aperture whose transmission is the luminance of `image`, the image
+    /// spanning the whole lens.
+    pub fn mask(image: &Image) -> Self {
+        let weights: Vec<_> = image.pixels().iter().map(|p| p.luminance()).collect();
+        let distribution =
+            Distribution2D::new(&weights, image.width() as usize, image.height() as usize);
+        Self::Mask(Arc::new(distribution))
+    }
+
+    /// Samples a point of the aperture in lens coordinates, within the unit
+    /// square and with `z` zero.
+    pub fn sample(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        match self {
+            Self::Circle => Vec3::random_in_disk(sampler),
+            Self::Polygon { blades, rotation } => {
+                // Every blade spans a triangle with the center, all of them
+                // with the same area.
+                let blade = ((sampler.next_1d() * *blades as Float) as u32).min(blades - 1);
+                let step = 2.0 * PI / *blades as Float;
+                let angle = rotation.to_radians() + blade as Float * step;
+                let a = Vec3::new(angle.cos(), angle.sin(), 0.0);
+                let b = Vec3::new((angle + step).cos(), (angle + step).sin(), 0.0);
+
+                let mut r1 = sampler.next_1d();
+                let mut r2 = sampler.next_1d();
+                if r1 + r2 > 1.0 {
+                    r1 = 1.0 - r1;
+                    r2 = 1.0 - r2;
+                }
+                (a * r1) + (b * r2)
+            }
+            Self::Mask(distribution) => {
+                let uv = sampler.next_2d();
+                let (uv, _) = distribution.sample(uv.u(), uv.v());
+                Vec3::new(2.0 * uv.u() - 1.0, 1.0 - 2.0 * uv.v(), 0.0)
+            }
+        }
+    }
+}
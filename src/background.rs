@@ -0,0 +1,390 @@
+use std::{
+    fmt::Debug,
+    fs::File,
+    io::{self, BufRead, BufReader, Read},
+    path::Path,
+    sync::Arc,
+};
+
+use crate::{
+    Float,
+    consts::PI,
+    error::{Error, Result},
+    pdf::Distribution2D,
+    sampler::Sampler,
+    sky::Sky,
+    vec2::Vec2,
+    vec3::Vec3,
+};
+
+/// Axis environment maps turn around.
+const UP: Vec3 = Vec3::new(0.0, 1.0, 0.0);
+
+/// Most pixels an environment map read from a file may have, far above any
+/// real one but small enough that a corrupt header can't exhaust memory.
+const MAX_PIXELS: usize = 1 << 28;
+
+/// Radiance arriving along rays that escape the scene.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Background {
+    Color(Vec3),
+    /// Blend between `bottom` for rays pointing down and `top` for rays
+    /// pointing up.
+    Gradient {
+        top:    Vec3,
+        bottom: Vec3,
+    },
+    Environment(Arc<EnvMap>),
+    Sky(Sky),
+}
+
+/// Equirectangular (latitude/longitude) radiance map.
+#[derive(Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EnvMap {
+    width:  usize,
+    height: usize,
+    pixels: Vec<Vec3>,
+
+    distribution: Distribution2D,
+
+    #[cfg_attr(feature = "serde", serde(default))]
+    rotation:  Float,
+    #[cfg_attr(feature = "serde", serde(default = "default_intensity"))]
+    intensity: Float,
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Self::Color(Vec3::default())
+    }
+}
+
+impl From<Vec3> for Background {
+    fn from(color: Vec3) -> Self {
+        Self::Color(color)
+    }
+}
+
+impl From<EnvMap> for Background {
+    fn from(map: EnvMap) -> Self {
+        Self::Environment(Arc::new(map))
+    }
+}
+
+impl From<Sky> for Background {
+    fn from(sky: Sky) -> Self {
+        Self::Sky(sky)
+    }
+}
+
+impl Background {
+    pub const fn gradient(top: Vec3, bottom: Vec3) -> Self {
+        Self::Gradient { top, bottom }
+    }
+
+    /// Radiance seen along `direction`.
+    pub fn value(&self, direction: Vec3) -> Vec3 {
+        match self {
+            Self::Color(color) => *color,
+            Self::Gradient { top, bottom } => {
+                let a = 0.5 * (direction.unit().y() + 1.0);
+                (*bottom * (1.0 - a)) + (*top * a)
+            }
+            Self::Environment(map) => map.value(direction),
+            Self::Sky(sky) => sky.value(direction),
+        }
+    }
+
+    /// Whether [`Background::random_direction`] samples the background by
+    /// importance, in which case it is used for direct lighting.
+    pub const fn is_sampled(&self) -> bool {
+        matches!(self, Self::Environment(..) | Self::Sky(..))
+    }
+
+    /// Solid angle density of sampling `direction` with
+    /// [`Background::random_direction`].
+    pub fn pdf_value(&self, direction: Vec3) -> Float {
+        match self {
+            Self::Environment(map) => map.pdf_value(direction),
+            Self::Sky(sky) => sky.pdf_value(direction),
+            _ => 0.0,
+        }
+    }
+
+    /// Samples a direction towards the background, favoring bright regions.
+    pub fn random_direction(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        match self {
+            Self::Environment(map) => map.random_direction(sampler),
+            Self::Sky(sky) => sky.random_direction(sampler),
+            _ => Vec3::random_unit(sampler),
+        }
+    }
+}
+
+impl Debug for EnvMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EnvMap")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("rotation", &self.rotation)
+            .field("intensity", &self.intensity)
+            .finish_non_exhaustive()
+    }
+}
+
+impl EnvMap {
+    /// Creates a map from row-major `pixels`, the first row being the one
+    /// looking straight up.
+    pub fn new(width: usize, height: usize, pixels: Vec<Vec3>) -> Self {
+        assert_eq!(pixels.len(), width * height);
+
+        // Rows near the poles cover a smaller solid angle, so they are
+        // weighted by the sine of their polar angle.
+        let luminance: Vec<_> = pixels
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                let theta = ((i / width) as Float + 0.5) / height as Float * PI;
+                p.luminance() * theta.sin()
+            })
+            .collect();
+        let distribution = Distribution2D::new(&luminance, width, height);
+
+        Self {
+            width,
+            height,
+            pixels,
+            distribution,
+            rotation: 0.0,
+            intensity: 1.0,
+        }
+    }
+
+    /// Turns the map by `degrees` around the vertical axis, counterclockwise
+    /// when looking down from above, to move the lighting around the scene.
+    pub const fn with_rotation(mut self, degrees: Float) -> Self {
+        self.rotation = degrees;
+        self
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `intensity` is negative or not finite, see
+    /// [`EnvMap::try_with_intensity`].
+    pub fn with_intensity(self, intensity: Float) -> Self {
+        self.try_with_intensity(intensity)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Scales the radiance of the map by `intensity`, failing if it is
+    /// negative or not finite.
+    pub fn try_with_intensity(mut self, intensity: Float) -> Result<Self> {
+        if !intensity.is_finite() || intensity < 0.0 {
+            return Err(Error::InvalidParameter(
+                "environment intensity must be finite and non-negative",
+            ));
+        }
+        self.intensity = intensity;
+        Ok(self)
+    }
+
+    pub const fn rotation_degrees(&self) -> Float {
+        self.rotation
+    }
+
+    pub const fn intensity(&self) -> Float {
+        self.intensity
+    }
+
+    /// Loads a Radiance `.hdr` (RGBE) file.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        Self::from_hdr(BufReader::new(file))
+    }
+
+    /// Reads a Radiance `.hdr` (RGBE) image, flat or run-length encoded.
+    pub fn from_hdr<R: BufRead>(mut reader: R) -> io::Result<Self> {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        if !line.starts_with("#?") {
+            return Err(invalid_data("missing radiance header"));
+        }
+
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                return Err(invalid_data("unterminated radiance header"));
+            }
+            let line = line.trim();
+            if line.is_empty() {
+                break;
+            }
+            if let Some(format) = line.strip_prefix("FORMAT=")
+                && format != "32-bit_rle_rgbe"
+            {
+                return Err(invalid_data("unsupported radiance pixel format"));
+            }
+        }
+
+        line.clear();
+        reader.read_line(&mut line)?;
+        let (height, width) = match line.split_whitespace().collect::<Vec<_>>()[..] {
+            ["-Y", height, "+X", width] => (
+                height
+                    .parse::<usize>()
+                    .map_err(|_| invalid_data("bad image height"))?,
+                width
+                    .parse::<usize>()
+                    .map_err(|_| invalid_data("bad image width"))?,
+            ),
+            _ => return Err(invalid_data("unsupported radiance image orientation")),
+        };
+        if !(1..=MAX_PIXELS).contains(&width.saturating_mul(height)) {
+            return Err(invalid_data("image size out of range"));
+        }
+
+        // Grown with the scanlines actually read, as the header may promise
+        // more than the file holds.
+        let mut pixels = Vec::new();
+        let mut scanline = vec![[0u8; 4]; width];
+        for _ in 0..height {
+            read_scanline(&mut reader, &mut scanline)?;
+            pixels.extend(scanline.iter().map(|&rgbe| rgbe_to_vec3(rgbe)));
+        }
+
+        Ok(Self::new(width, height, pixels))
+    }
+
+    pub const fn width(&self) -> usize {
+        self.width
+    }
+
+    pub const fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Vec3 {
+        self.pixels[y * self.width + x]
+    }
+
+    /// Maps a direction to texture coordinates in `[0, 1]`, `u` following the
+    /// longitude and `v` going from straight up to straight down, before the
+    /// map is rotated.
+    pub fn direction_to_uv(direction: Vec3) -> Vec2 {
+        let d = direction.unit();
+        let theta = d.y().clamp(-1.0, 1.0).acos();
+        let phi = Float::atan2(-d.z(), d.x()) + PI;
+        Vec2::new(phi / (2.0 * PI), theta / PI)
+    }
+
+    /// Inverse of [`EnvMap::direction_to_uv`].
+    pub fn uv_to_direction(uv: Vec2) -> Vec3 {
+        let phi = uv.u() * 2.0 * PI - PI;
+        let theta = uv.v() * PI;
+        let sin_theta = theta.sin();
+        Vec3::new(phi.cos() * sin_theta, theta.cos(), -phi.sin() * sin_theta)
+    }
+
+    pub fn value(&self, direction: Vec3) -> Vec3 {
+        let uv = Self::direction_to_uv(self.to_map(direction));
+        let x = ((uv.u() * self.width as Float) as usize).min(self.width - 1);
+        let y = ((uv.v() * self.height as Float) as usize).min(self.height - 1);
+        self.pixel(x, y) * self.intensity
+    }
+
+    // Rotations keep solid angles, so densities carry over unchanged.
+    pub fn pdf_value(&self, direction: Vec3) -> Float {
+        let uv = Self::direction_to_uv(self.to_map(direction));
+        let sin_theta = (uv.v() * PI).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        self.distribution.pdf(uv) / (2.0 * PI * PI * sin_theta)
+    }
+
+    pub fn random_direction(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        let uv = sampler.next_2d();
+        let (uv, _) = self.distribution.sample(uv.u(), uv.v());
+        Self::uv_to_direction(uv).rotate(UP, self.rotation)
+    }
+
+    /// Direction in the frame of the unrotated map of the world `direction`.
+    fn to_map(&self, direction: Vec3) -> Vec3 {
+        if self.rotation == 0.0 {
+            direction
+        } else {
+            direction.rotate(UP, -self.rotation)
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+const fn default_intensity() -> Float {
+    1.0
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn rgbe_to_vec3([r, g, b, e]: [u8; 4]) -> Vec3 {
+    if e == 0 {
+        return Vec3::default();
+    }
+    let f = Float::powi(2.0, e as i32 - (128 + 8));
+    Vec3::new(r as Float * f, g as Float * f, b as Float * f)
+}
+
+fn read_scanline<R: Read>(reader: &mut R, scanline: &mut [[u8; 4]]) -> io::Result<()> {
+    let width = scanline.len();
+    let mut head = [0u8; 4];
+    reader.read_exact(&mut head)?;
+
+    let run_length_encoded =
+        (8..0x8000).contains(&width) && head[0] == 2 && head[1] == 2 && head[2] & 0x80 == 0;
+
+    if !run_length_encoded {
+        scanline[0] = head;
+        for pixel in &mut scanline[1..] {
+            reader.read_exact(pixel)?;
+        }
+        return Ok(());
+    }
+
+    if ((head[2] as usize) << 8 | head[3] as usize) != width {
+        return Err(invalid_data("scanline width mismatch"));
+    }
+
+    for channel in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let mut byte = [0u8; 1];
+            reader.read_exact(&mut byte)?;
+            let count = byte[0] as usize;
+            if count > 128 {
+                let count = count - 128;
+                if x + count > width {
+                    return Err(invalid_data("bad scanline run"));
+                }
+                reader.read_exact(&mut byte)?;
+                for pixel in &mut scanline[x..x + count] {
+                    pixel[channel] = byte[0];
+                }
+                x += count;
+            } else {
+                if count == 0 || x + count > width {
+                    return Err(invalid_data("bad scanline run"));
+                }
+                for pixel in &mut scanline[x..x + count] {
+                    reader.read_exact(&mut byte)?;
+                    pixel[channel] = byte[0];
+                }
+                x += count;
+            }
+        }
+    }
+
+    Ok(())
+}
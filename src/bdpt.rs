@@ -0,0 +1,668 @@
+//! Bidirectional path tracing.
+//!
+//! The [`PathTracer`](crate::integrator::PathTracer) only reaches a light by
+//! sampling it from a diffuse surface or by hitting it after a bounce, which
+//! almost never happens when the light enters a room through a small opening
+//! or off a mirror. The [`BidirectionalPathTracer`] also traces a subpath
+//! from a point on the lights and connects each of its vertices to each
+//! vertex of the camera subpath. Every light path can then be sampled by
+//! several strategies, whose estimates are combined with the power
+//! heuristic so that each path is left to the strategy sampling it best.
+//!
+//! Light subpaths leave from the emitters of `lights`. Light from the
+//! background and the delta lights of the camera is still sampled from the
+//! camera subpath alone, as the path tracer does. Light subpaths are never
+//! connected to the camera itself, which would scatter their contributions
+//! over other pixels, so caustics seen directly still converge slowly.
+
+use crate::{
+    Float,
+    camera::{Camera, power_heuristic},
+    consts::PI,
+    hit::{Hit, HitRecord},
+    integrator::{FirstHit, Integrator},
+    interior::Interior,
+    interval::Interval,
+    material::Scatter,
+    pdf::{CosinePdf, HittablePdf, Pdf},
+    ray::{Ray, RayKind},
+    sampler::Sampler,
+    stats::{self, Counter},
+    vec3::Vec3,
+};
+
+/// Bidirectional path tracing with multiple importance sampling of all
+/// the ways to connect a camera and a light subpath.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BidirectionalPathTracer;
+
+/// Surface hit of a camera or light subpath.
+#[derive(Debug, Clone, Copy)]
+struct Vertex<M> {
+    hit:         HitRecord<M>,
+    /// Ray the vertex was reached by.
+    ray:         Ray,
+    /// Throughput of the subpath up to the vertex over its density, without
+    /// the attenuation of the vertex itself.
+    beta:        Vec3,
+    /// Attenuation of the scatter sampled at the vertex, `None` where the
+    /// subpath ends.
+    attenuation: Option<Vec3>,
+    /// Whether the vertex scatters into a single direction, so that it
+    /// can't be connected to.
+    specular:    bool,
+    /// Density per area of sampling the vertex from its own subpath.
+    pdf_fwd:     Float,
+    /// Density per area of sampling the vertex from the other end of the
+    /// path.
+    pdf_rev:     Float,
+}
+
+/// Point on an emitter a light subpath leaves from.
+#[derive(Debug, Clone, Copy)]
+struct LightOrigin {
+    point:     Vec3,
+    /// Normal of the side the light is emitted from.
+    normal:    Vec3,
+    two_sided: bool,
+    /// Density per area of sampling the point from the light side, taken
+    /// as that of sampling it from the next vertex like
+    /// [`Camera::sample_light`] does, so that every strategy agrees on it.
+    pdf_fwd:   Float,
+    pdf_rev:   Float,
+}
+
+/// Subpath leaving the scene.
+#[derive(Debug, Clone, Copy)]
+struct Escape {
+    ray:  Ray,
+    beta: Vec3,
+    /// Density per solid angle of the direction of `ray`, zero after the
+    /// camera or a specular bounce.
+    pdf:  Float,
+}
+
+/// Reverse densities of the vertices next to a connection, which differ
+/// from those stored with the subpaths: those of the last and second to
+/// last camera vertex, then of the last and second to last light vertex.
+#[derive(Debug, Default, Clone, Copy)]
+struct ConnectionPdfs {
+    camera: [Float; 2],
+    light:  [Float; 2],
+}
+
+impl<M: Scatter> Vertex<M> {
+    fn point(&self) -> Vec3 {
+        self.hit.point()
+    }
+
+    fn normal(&self) -> Vec3 {
+        self.hit.normal()
+    }
+
+    /// Attenuation of diffuse vertices, the only ones paths can be
+    /// connected through.
+    fn diffuse(&self) -> Option<Vec3> {
+        self.attenuation.filter(|_| !self.specular)
+    }
+
+    /// Density per solid angle of scattering towards `point`. Diffuse
+    /// scattering is symmetric, so the density doesn't depend on which
+    /// side the path arrives from.
+    fn pdf_towards(&self, camera: &Camera, point: Vec3) -> Float {
+        if self.diffuse().is_none() {
+            return 0.0;
+        }
+        let towards = self.hit.spawn_ray(point - self.point(), self.ray.time());
+        camera.scattering_pdf(&self.ray, &self.hit, &towards)
+    }
+
+    /// Density per area of scattering towards `point` on a surface with
+    /// `normal`.
+    fn area_pdf_towards(&self, camera: &Camera, point: Vec3, normal: Vec3) -> Float {
+        let pdf = self.pdf_towards(camera, point);
+        solid_angle_to_area(pdf, self.point(), point, normal)
+    }
+}
+
+impl LightOrigin {
+    fn new<M: Scatter>(hit: &HitRecord<M>, normal: Vec3) -> Self {
+        Self {
+            point: hit.point(),
+            normal,
+            two_sided: hit.material().emission_sides().includes(false),
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+        }
+    }
+
+    /// Density per solid angle of emitting towards `direction`, cosine
+    /// distributed around the emitting sides.
+    fn emission_pdf(&self, direction: Vec3) -> Float {
+        let cosine = self.normal.dot(direction.unit());
+        if self.two_sided {
+            cosine.abs() / (2.0 * PI)
+        } else {
+            cosine.max(0.0) / PI
+        }
+    }
+}
+
+impl Integrator for BidirectionalPathTracer {
+    fn radiance<T: Hit + ?Sized, L: Hit + ?Sized>(
+        &self,
+        camera: &Camera,
+        ray: &Ray,
+        world: &T,
+        lights: &L,
+        max_depth: u32,
+        sampler: &mut dyn Sampler,
+    ) -> (Vec3, FirstHit)
+    where
+        T::Material: Copy,
+    {
+        let one = Vec3::new(1.0, 1.0, 1.0);
+        let mut camera_path = Vec::new();
+        let escape = random_walk(
+            camera,
+            world,
+            *ray,
+            one,
+            0.0,
+            max_depth,
+            sampler,
+            &mut camera_path,
+        );
+
+        let first_hit = match camera_path.first() {
+            Some(vertex) => FirstHit::new(
+                vertex
+                    .attenuation
+                    .or_else(|| vertex.hit.emit())
+                    .unwrap_or_default(),
+                vertex.normal(),
+                vertex.hit.t() * ray.direction().length(),
+                vertex.hit.object_id(),
+            ),
+            None => FirstHit::new(
+                camera.background().value(ray.direction()),
+                Vec3::default(),
+                Float::INFINITY,
+                None,
+            ),
+        };
+
+        let mut radiance = unidirectional(camera, world, &camera_path, escape, sampler);
+
+        let mut light_path = Vec::new();
+        let origin = light_subpath(
+            camera,
+            world,
+            lights,
+            ray.time(),
+            max_depth.saturating_sub(2),
+            sampler,
+            &mut light_path,
+        );
+
+        for t in 2..=camera_path.len() + 1 {
+            for s in 0..=light_path.len() + 1 {
+                let bounces = s + t - 2;
+                if bounces >= max_depth as usize {
+                    break;
+                }
+                let estimate = match s {
+                    0 => hit_light(lights, &camera_path, t),
+                    1 => sample_light(camera, world, lights, &camera_path, t, sampler),
+                    _ => match origin {
+                        Some(origin) => {
+                            connect(camera, world, &camera_path, &light_path, &origin, s, t)
+                        }
+                        None => break,
+                    },
+                };
+                radiance += camera.contribution(bounces as u32, estimate);
+            }
+        }
+
+        (radiance, first_hit)
+    }
+}
+
+/// Extends a subpath by up to `max_vertices` surface hits, starting with
+/// `ray` carrying throughput `beta` and sampled with density `pdf` per
+/// solid angle. Returns how the subpath left the scene, if it did.
+#[allow(clippy::too_many_arguments)]
+fn random_walk<T: Hit + ?Sized>(
+    camera: &Camera,
+    world: &T,
+    mut ray: Ray,
+    mut beta: Vec3,
+    mut pdf: Float,
+    max_vertices: u32,
+    sampler: &mut dyn Sampler,
+    vertices: &mut Vec<Vertex<T::Material>>,
+) -> Option<Escape>
+where
+    T::Material: Copy,
+{
+    let mut interior = Interior::new();
+
+    for _ in 0..max_vertices {
+        let Some(hit) = world.hit(&ray, Interval::new(0.0, Float::INFINITY)) else {
+            return Some(Escape { ray, beta, pdf });
+        };
+
+        let dielectric = hit.dielectric().filter(|_| camera.clay().is_none());
+        let scatter = match dielectric {
+            Some(dielectric) => {
+                let entering = hit.front_face();
+                match interior.crossing(dielectric, entering) {
+                    Some((from, to)) => {
+                        let (attenuation, scattered) =
+                            dielectric.scatter_between(&ray, &hit, from, to, sampler);
+                        if scattered.direction().dot(hit.normal()) < 0.0 {
+                            interior.cross(dielectric, entering);
+                        }
+                        Some((attenuation, scattered))
+                    }
+                    None => {
+                        // Surface hidden inside a higher priority dielectric.
+                        interior.cross(dielectric, entering);
+                        let continued = hit
+                            .spawn_ray(ray.direction(), ray.time())
+                            .with_kind(ray.kind());
+                        Some((Vec3::new(1.0, 1.0, 1.0), continued))
+                    }
+                }
+            }
+            None => camera.scatter(&ray, &hit, sampler),
+        };
+        let scattering_pdf = scatter.map_or(0.0, |(_, scattered)| {
+            camera.scattering_pdf(&ray, &hit, &scattered)
+        });
+
+        let vertex = Vertex {
+            hit,
+            ray,
+            beta,
+            attenuation: scatter.map(|(attenuation, _)| attenuation),
+            specular: scatter.is_some() && scattering_pdf <= 0.0,
+            pdf_fwd: solid_angle_to_area(pdf, ray.origin(), hit.point(), hit.normal()),
+            pdf_rev: 0.0,
+        };
+        if let Some(previous) = vertices.last_mut() {
+            previous.pdf_rev = vertex.area_pdf_towards(camera, previous.point(), previous.normal());
+        }
+        vertices.push(vertex);
+
+        let (attenuation, scattered) = scatter?;
+        beta *= attenuation;
+        pdf = scattering_pdf.max(0.0);
+        ray = scattered;
+        stats::count(Counter::SecondaryRays);
+    }
+    None
+}
+
+/// Samples a light subpath of up to `max_vertices` surface hits into
+/// `vertices`, returning the point on `lights` it leaves from.
+fn light_subpath<T: Hit + ?Sized, L: Hit + ?Sized>(
+    camera: &Camera,
+    world: &T,
+    lights: &L,
+    time: Float,
+    max_vertices: u32,
+    sampler: &mut dyn Sampler,
+    vertices: &mut Vec<Vertex<T::Material>>,
+) -> Option<LightOrigin>
+where
+    T::Material: Copy,
+{
+    let (rec, pdf) = lights.sample_surface(time, sampler)?;
+    let emitted = rec.emit()?;
+    if pdf <= 0.0 {
+        return None;
+    }
+
+    // Two sided emitters pick a side at random.
+    let two_sided = rec.material().emission_sides().includes(false);
+    let normal = if two_sided && sampler.next_1d() < 0.5 {
+        -rec.normal()
+    } else {
+        rec.normal()
+    };
+    let mut origin = LightOrigin::new(&rec, normal);
+    let direction = CosinePdf::new(normal).generate(sampler);
+    let sides = if two_sided { 2.0 } else { 1.0 };
+    let flux = emitted * (sides * PI / pdf);
+
+    let ray = rec.spawn_ray(direction, time);
+    let emission_pdf = origin.emission_pdf(direction);
+    random_walk(
+        camera,
+        world,
+        ray,
+        flux,
+        emission_pdf,
+        max_vertices,
+        sampler,
+        vertices,
+    );
+
+    if let Some(first) = vertices.first() {
+        let light_pdf =
+            HittablePdf::new(lights, first.point(), time).value(origin.point - first.point());
+        origin.pdf_fwd = solid_angle_to_area(light_pdf, first.point(), origin.point, normal);
+        origin.pdf_rev = first.area_pdf_towards(camera, origin.point, normal);
+    }
+    Some(origin)
+}
+
+/// Light from the background and the delta lights, which light subpaths
+/// don't leave from, estimated along the camera subpath like the path
+/// tracer does.
+fn unidirectional<T: Hit + ?Sized>(
+    camera: &Camera,
+    world: &T,
+    camera_path: &[Vertex<T::Material>],
+    escape: Option<Escape>,
+    sampler: &mut dyn Sampler,
+) -> Vec3
+where
+    T::Material: Copy,
+{
+    let sample_background = camera.background().is_sampled();
+    let mut radiance = Vec3::default();
+
+    for (bounce, vertex) in camera_path.iter().enumerate() {
+        let Some(attenuation) = vertex.diffuse() else {
+            continue;
+        };
+        let throughput = vertex.beta * attenuation;
+        let bounce = bounce as u32 + 1;
+        if sample_background {
+            let direct =
+                camera.sample_background(&vertex.ray, &vertex.hit, world, None, None, sampler);
+            radiance += camera.contribution(bounce, throughput * direct);
+        }
+        if !camera.delta_lights().is_empty() {
+            let direct = camera.sample_delta_lights(&vertex.ray, &vertex.hit, world, None, sampler);
+            radiance += camera.contribution(bounce, throughput * direct);
+        }
+    }
+
+    if let Some(escape) = escape {
+        let direction = escape.ray.direction();
+        let weight = if sample_background && escape.pdf > 0.0 {
+            power_heuristic(escape.pdf, camera.background().pdf_value(direction))
+        } else {
+            1.0
+        };
+        let background = camera.background().value(direction);
+        let bounce = camera_path.len() as u32;
+        radiance += camera.contribution(bounce, escape.beta.scale(background) * weight);
+    }
+    radiance
+}
+
+/// Strategy of the camera subpath hitting an emitter with its vertex `t`.
+fn hit_light<M: Scatter, L: Hit + ?Sized>(lights: &L, camera_path: &[Vertex<M>], t: usize) -> Vec3 {
+    let light = &camera_path[t - 2];
+    let Some(emitted) = light.hit.emit() else {
+        return Vec3::default();
+    };
+    let estimate = light.beta * emitted;
+    if t == 2 || !light.hit.is_emitter() {
+        // Seen directly or glowing in a medium, which no other strategy
+        // samples.
+        return estimate;
+    }
+
+    let previous = &camera_path[t - 3];
+    let origin = LightOrigin::new(&light.hit, light.normal());
+    let offset = light.point() - previous.point();
+    let light_pdf = HittablePdf::new(lights, previous.point(), previous.ray.time()).value(offset);
+    let pdfs = ConnectionPdfs {
+        camera: [
+            solid_angle_to_area(light_pdf, previous.point(), light.point(), light.normal()),
+            solid_angle_to_area(
+                origin.emission_pdf(-offset),
+                light.point(),
+                previous.point(),
+                previous.normal(),
+            ),
+        ],
+        light:  [0.0; 2],
+    };
+    estimate * mis_weight(camera_path, &[], None, 0, t, pdfs)
+}
+
+/// Strategy of sampling a point on `lights` from vertex `t` of the camera
+/// subpath, as [`Camera::sample_light`] does.
+fn sample_light<T: Hit + ?Sized, L: Hit + ?Sized>(
+    camera: &Camera,
+    world: &T,
+    lights: &L,
+    camera_path: &[Vertex<T::Material>],
+    t: usize,
+    sampler: &mut dyn Sampler,
+) -> Vec3
+where
+    T::Material: Copy,
+{
+    let vertex = &camera_path[t - 2];
+    let Some(attenuation) = vertex.diffuse() else {
+        return Vec3::default();
+    };
+    if lights.count() == 0 {
+        return Vec3::default();
+    }
+
+    let light = HittablePdf::new(lights, vertex.point(), vertex.ray.time());
+    let direction = light.generate(sampler);
+    let light_pdf = light.value(direction);
+    if light_pdf <= 0.0 {
+        return Vec3::default();
+    }
+    let shadow = vertex
+        .hit
+        .spawn_ray(direction, vertex.ray.time())
+        .with_kind(RayKind::Shadow);
+    let scattering_pdf = camera.scattering_pdf(&vertex.ray, &vertex.hit, &shadow);
+    if scattering_pdf <= 0.0 {
+        return Vec3::default();
+    }
+
+    stats::count(Counter::ShadowRays);
+    let Some(hit) = world.hit(&shadow, Interval::new(0.0, Float::INFINITY)) else {
+        return Vec3::default();
+    };
+    let Some(emitted) = hit.emit().filter(|_| hit.is_emitter()) else {
+        return Vec3::default();
+    };
+    let estimate = vertex.beta * attenuation * emitted * (scattering_pdf / light_pdf);
+
+    let mut origin = LightOrigin::new(&hit, hit.normal());
+    origin.pdf_fwd = solid_angle_to_area(light_pdf, vertex.point(), hit.point(), hit.normal());
+    let pdfs = ConnectionPdfs {
+        camera: [
+            solid_angle_to_area(
+                origin.emission_pdf(vertex.point() - hit.point()),
+                hit.point(),
+                vertex.point(),
+                vertex.normal(),
+            ),
+            previous_pdf(camera, camera_path, t),
+        ],
+        light:  [
+            solid_angle_to_area(scattering_pdf, vertex.point(), hit.point(), hit.normal()),
+            0.0,
+        ],
+    };
+    estimate * mis_weight(camera_path, &[], Some(&origin), 1, t, pdfs)
+}
+
+/// Strategy connecting vertex `s` of the light subpath to vertex `t` of the
+/// camera subpath with a shadow ray.
+fn connect<T: Hit + ?Sized>(
+    camera: &Camera,
+    world: &T,
+    camera_path: &[Vertex<T::Material>],
+    light_path: &[Vertex<T::Material>],
+    origin: &LightOrigin,
+    s: usize,
+    t: usize,
+) -> Vec3
+where
+    T::Material: Copy,
+{
+    let (light, vertex) = (&light_path[s - 2], &camera_path[t - 2]);
+    let (Some(light_attenuation), Some(attenuation)) = (light.diffuse(), vertex.diffuse()) else {
+        return Vec3::default();
+    };
+    let light_pdf = light.pdf_towards(camera, vertex.point());
+    let scattering_pdf = vertex.pdf_towards(camera, light.point());
+    if light_pdf <= 0.0 || scattering_pdf <= 0.0 {
+        return Vec3::default();
+    }
+
+    let time = vertex.ray.time();
+    let from = vertex
+        .hit
+        .spawn_ray(light.point() - vertex.point(), time)
+        .origin();
+    let to = light
+        .hit
+        .spawn_ray(vertex.point() - light.point(), time)
+        .origin();
+    let shadow = Ray::new(from, to - from, time).with_kind(RayKind::Shadow);
+    stats::count(Counter::ShadowRays);
+    if world.hit_any(&shadow, Interval::new(0.0, 1.0)) {
+        return Vec3::default();
+    }
+
+    let distance_squared = (light.point() - vertex.point()).length_squared();
+    let estimate = light.beta
+        * light_attenuation
+        * vertex.beta
+        * attenuation
+        * (light_pdf * scattering_pdf / distance_squared);
+
+    let light_previous_pdf = match s {
+        2 => light.area_pdf_towards(camera, origin.point, origin.normal),
+        _ => {
+            let previous = &light_path[s - 3];
+            light.area_pdf_towards(camera, previous.point(), previous.normal())
+        }
+    };
+    let pdfs = ConnectionPdfs {
+        camera: [
+            solid_angle_to_area(light_pdf, light.point(), vertex.point(), vertex.normal()),
+            previous_pdf(camera, camera_path, t),
+        ],
+        light:  [
+            solid_angle_to_area(
+                scattering_pdf,
+                vertex.point(),
+                light.point(),
+                light.normal(),
+            ),
+            light_previous_pdf,
+        ],
+    };
+    estimate * mis_weight(camera_path, light_path, Some(origin), s, t, pdfs)
+}
+
+/// Density per area of camera vertex `t - 1` sampled from vertex `t` by a
+/// light subpath connected to it, zero if vertex `t - 1` is the camera.
+fn previous_pdf<M: Scatter>(camera: &Camera, camera_path: &[Vertex<M>], t: usize) -> Float {
+    match t {
+        2 => 0.0,
+        _ => {
+            let previous = &camera_path[t - 3];
+            camera_path[t - 2].area_pdf_towards(camera, previous.point(), previous.normal())
+        }
+    }
+}
+
+/// Power heuristic weight of the strategy joining `s` light vertices, the
+/// first being `origin`, and `t` camera vertices, the first being the
+/// camera, among all strategies sampling the same path but for those
+/// connecting light subpaths to the camera.
+///
+/// Computed like PBRT does, from the ratios of the densities of the other
+/// strategies to this one, where densities of specular bounces count as
+/// one since every strategy samples them alike.
+fn mis_weight<M>(
+    camera_path: &[Vertex<M>],
+    light_path: &[Vertex<M>],
+    origin: Option<&LightOrigin>,
+    s: usize,
+    t: usize,
+    pdfs: ConnectionPdfs,
+) -> Float {
+    // Emitters the light side can't sample leave the path to the camera
+    // side alone.
+    let origin_pdf = origin.map_or(pdfs.camera[0], |origin| origin.pdf_fwd);
+    if origin_pdf <= 0.0 {
+        return if s == 0 { 1.0 } else { 0.0 };
+    }
+    let ratio = |rev: Float, fwd: Float| {
+        let remap = |pdf: Float| if pdf == 0.0 { 1.0 } else { pdf };
+        let ratio = remap(rev) / remap(fwd);
+        ratio * ratio
+    };
+
+    let mut sum = 0.0;
+    let mut ri = 1.0;
+    for i in (2..t).rev() {
+        let vertex = &camera_path[i - 1];
+        let pdf_rev = match t - i {
+            1 => pdfs.camera[0],
+            2 => pdfs.camera[1],
+            _ => vertex.pdf_rev,
+        };
+        ri *= ratio(pdf_rev, vertex.pdf_fwd);
+        if !vertex.specular && !camera_path[i - 2].specular {
+            sum += ri;
+        }
+    }
+
+    let mut ri = 1.0;
+    for i in (0..s).rev() {
+        let (pdf_rev, pdf_fwd, specular) = match i {
+            0 => {
+                let origin = origin.expect("light strategies have an origin");
+                (origin.pdf_rev, origin.pdf_fwd, false)
+            }
+            _ => {
+                let vertex = &light_path[i - 1];
+                (vertex.pdf_rev, vertex.pdf_fwd, vertex.specular)
+            }
+        };
+        let pdf_rev = match s - i {
+            1 => pdfs.light[0],
+            2 => pdfs.light[1],
+            _ => pdf_rev,
+        };
+        ri *= ratio(pdf_rev, pdf_fwd);
+        let previous_specular = i >= 2 && light_path[i - 2].specular;
+        if !specular && !previous_specular {
+            sum += ri;
+        }
+    }
+
+    1.0 / (1.0 + sum)
+}
+
+/// Converts the density per solid angle of sampling `to` from `from` into
+/// a density per area of the surface with `normal` at `to`.
+fn solid_angle_to_area(pdf: Float, from: Vec3, to: Vec3, normal: Vec3) -> Float {
+    let offset = to - from;
+    let distance_squared = offset.length_squared();
+    if distance_squared <= 0.0 {
+        return 0.0;
+    }
+    pdf * normal.dot(offset).abs() / (distance_squared * distance_squared.sqrt())
+}
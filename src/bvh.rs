@@ -1,98 +1,1207 @@
-use std::sync::Arc;
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    error::Error,
+    fmt,
+    io::{self, Read, Write},
+    mem,
+    sync::Arc,
+};
 
 use crate::{
+    Float,
     aabb::Aabb,
-    hit::{DynHit, Hit, HitRecord},
+    hit::{DynHit, Hit, HitRecord, ObjectId},
     interval::Interval,
-    material::Material,
     ray::Ray,
+    stats::{self, Counter},
+    vec3::Vec3,
 };
 
+thread_local! {
+    static NODES_VISITED: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Number of BVH nodes visited by the current thread since the last call to
+/// [`reset_nodes_visited`].
+pub fn nodes_visited() -> u64 {
+    NODES_VISITED.with(Cell::get)
+}
+
+pub fn reset_nodes_visited() {
+    NODES_VISITED.with(|count| count.set(0));
+}
+
+/// Counts a node visit for [`nodes_visited`].
+pub(crate) fn visit_node() {
+    NODES_VISITED.with(|count| count.set(count.get() + 1));
+    stats::count(Counter::NodesVisited);
+}
+
+/// Deepest tree the traversal stack can hold.
+const MAX_DEPTH: usize = 64;
+
+/// Node of a [`Bvh`], stored in depth-first order so that the first child of
+/// an interior node directly follows it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LinearBvhNode {
+    bbox: Aabb,
+    kind: NodeKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    /// Primitives `first..first + count` of the BVH.
+    Leaf { first: u32, count: u32 },
+    /// Interior node whose second child is at index `second_child`.
+    Interior { second_child: u32 },
+}
+
+/// How a [`Bvh`] is built.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BvhBuildStrategy {
+    /// Recursively sorts along the longest axis and splits at the median.
+    #[default]
+    Median,
+    /// Linear BVH: primitives are ordered along a Morton curve with a radix
+    /// sort and split where their codes first differ. Much faster to build
+    /// but slower to trace, for scenes rebuilt every frame.
+    Fast,
+}
+
+/// Shape and quality metrics of a [`Bvh`], see [`Bvh::stats`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BvhStats {
+    node_count:        usize,
+    leaf_count:        usize,
+    max_depth:         usize,
+    average_depth:     Float,
+    min_leaf_size:     usize,
+    max_leaf_size:     usize,
+    average_leaf_size: Float,
+    sah_cost:          Float,
+    average_overlap:   Float,
+}
+
+/// Inconsistency found by [`Bvh::validate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BvhError {
+    /// An interior node points to a child outside the node array or before
+    /// itself.
+    BadChild { node: usize },
+    /// A leaf refers to primitives outside the primitive array.
+    BadLeaf { node: usize },
+    /// A primitive's bounding box is not contained in that of `node`, one of
+    /// the ancestors of its leaf.
+    NotContained { primitive: usize, node: usize },
+    /// A primitive is referenced by no leaf or by several.
+    BadCoverage { primitive: usize },
+    /// Interior `node` lies too deep for the traversal stack.
+    TooDeep { node: usize },
+}
+
+/// Relative cost of visiting a node in the surface area heuristic.
+const TRAVERSAL_COST: Float = 1.0;
+/// Relative cost of intersecting a primitive in the surface area heuristic.
+const INTERSECTION_COST: Float = 1.0;
+
+/// Bounding volume hierarchy flattened into an array of nodes, over
+/// primitives of type `P`. By default these are shared trait objects, any
+/// shape at the cost of a pointer chase and a virtual call per test; a
+/// [`Primitive`](crate::primitive::Primitive) BVH stores the built-in shapes
+/// by value instead.
 #[derive(Debug, Clone)]
-pub enum BvhNode {
-    Leaf(Arc<dyn DynHit>),
-    Node { left: Arc<Bvh>, right: Arc<Bvh> },
+pub struct Bvh<P = Arc<dyn DynHit>> {
+    nodes:      Vec<LinearBvhNode>,
+    primitives: Vec<P>,
+    /// Index of every primitive in the list the BVH was built from, before
+    /// the build reordered it.
+    order:      Vec<u32>,
+    count:      usize,
+    /// Built by the first edit, which may leave primitive slots and nodes
+    /// unused until the tree is packed again.
+    edits:      Option<EditIndex>,
 }
 
+/// Marks a primitive slot that no leaf holds or a node without a parent in an
+/// [`EditIndex`].
+const UNUSED: u32 = u32::MAX;
+
+/// Lookup tables of an edited [`Bvh`], so that edits only touch the part of
+/// the tree around the primitives they change.
 #[derive(Debug, Clone)]
-pub struct Bvh {
-    node: BvhNode,
-    bbox: Aabb,
+struct EditIndex {
+    /// Slot of every primitive with an id.
+    slots:        HashMap<ObjectId, u32>,
+    /// Leaf holding every primitive slot.
+    leaves:       Vec<u32>,
+    /// Parent of every node, the root and the unused nodes having none.
+    parents:      Vec<u32>,
+    unused_slots: usize,
+    unused_nodes: usize,
+    /// Index in the list the BVH was built from given to the next inserted
+    /// primitive.
+    next_order:   u32,
 }
 
-impl Bvh {
-    pub fn new(node: BvhNode, bbox: Aabb) -> Self {
-        Self { node, bbox }
+impl LinearBvhNode {
+    pub const fn bbox(&self) -> Aabb {
+        self.bbox
+    }
+
+    pub const fn kind(&self) -> NodeKind {
+        self.kind
     }
+}
 
-    pub fn from_list(hit_list: &mut [Arc<dyn DynHit>]) -> Self {
+impl EditIndex {
+    fn new<P: Hit>(nodes: &[LinearBvhNode], primitives: &[P], order: &[u32]) -> Self {
+        let mut leaves = vec![UNUSED; primitives.len()];
+        let mut parents = vec![UNUSED; nodes.len()];
+        let mut used_nodes = 0;
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            used_nodes += 1;
+            match nodes[index].kind {
+                NodeKind::Leaf { first, count } => {
+                    leaves[first as usize..(first + count) as usize].fill(index as u32);
+                }
+                NodeKind::Interior { second_child } => {
+                    for child in [second_child as usize, index + 1] {
+                        parents[child] = index as u32;
+                        stack.push(child);
+                    }
+                }
+            }
+        }
+
+        let slots = leaves
+            .iter()
+            .zip(primitives)
+            .enumerate()
+            .filter(|&(_, (&leaf, _))| leaf != UNUSED)
+            .filter_map(|(slot, (_, obj))| Some((obj.object_id()?, slot as u32)))
+            .collect();
+        Self {
+            slots,
+            unused_slots: leaves.iter().filter(|&&leaf| leaf == UNUSED).count(),
+            unused_nodes: nodes.len() - used_nodes,
+            next_order: order.iter().max().map_or(0, |&i| i + 1),
+            leaves,
+            parents,
+        }
+    }
+}
+
+impl<P: Hit + Clone> Bvh<P> {
+    pub fn from_list(hit_list: &mut [P]) -> Self {
+        Self::from_list_with(hit_list, BvhBuildStrategy::default())
+    }
+
+    /// Builds a BVH over `hit_list` with `strategy`, reordering the list.
+    pub fn from_list_with(hit_list: &mut [P], strategy: BvhBuildStrategy) -> Self {
         assert!(!hit_list.is_empty());
 
-        let bbox = hit_list
-            .into_iter()
-            .fold(Aabb::default(), |bbox, obj| bbox.merge(obj.aabb()));
+        // The build sorts indices into the list, which end up in the order
+        // of the leaves.
+        let mut order: Vec<u32> = (0..hit_list.len() as u32).collect();
+        let mut nodes = Vec::with_capacity(2 * hit_list.len() - 1);
+        match strategy {
+            BvhBuildStrategy::Median => Self::build(hit_list, &mut order, 0, &mut nodes),
+            BvhBuildStrategy::Fast => {
+                let codes = sort_by_morton_code(hit_list, &mut order);
+                Self::build_linear(hit_list, &order, &codes, 0, &mut nodes);
+            }
+        }
+
+        let primitives: Vec<P> = order
+            .iter()
+            .map(|&i| hit_list[i as usize].clone())
+            .collect();
+        hit_list.clone_from_slice(&primitives);
+        Self {
+            count: primitives.iter().map(|obj| obj.count()).sum(),
+            nodes,
+            primitives,
+            order,
+            edits: None,
+        }
+    }
+
+    /// Writes the tree structure in a compact binary format. Primitives are
+    /// not stored, only their indices in the list the BVH was built from, so
+    /// [`Bvh::load`] needs the same list to restore it.
+    pub fn save<W: Write>(&self, writer: W) -> io::Result<()> {
+        let (nodes, slots) = self.packed();
+        // Edits leave gaps in the indices, which the ranks close as removing
+        // from the list does.
+        let mut by_order: Vec<usize> = (0..slots.len()).collect();
+        by_order.sort_unstable_by_key(|&i| self.order[slots[i]]);
+        let mut order = vec![0u32; slots.len()];
+        for (rank, &i) in by_order.iter().enumerate() {
+            order[i] = rank as u32;
+        }
+
+        let mut w = io::BufWriter::new(writer);
+        w.write_all(SAVE_MAGIC)?;
+        w.write_all(&(nodes.len() as u32).to_le_bytes())?;
+        w.write_all(&(order.len() as u32).to_le_bytes())?;
+
+        for node in &nodes {
+            for axis in [node.bbox.x(), node.bbox.y(), node.bbox.z()] {
+                write_f64(&mut w, axis.min())?;
+                write_f64(&mut w, axis.max())?;
+            }
+            let (tag, a, b) = match node.kind {
+                NodeKind::Leaf { first, count } => (0u8, first, count),
+                NodeKind::Interior { second_child } => (1u8, second_child, 0),
+            };
+            w.write_all(&[tag])?;
+            w.write_all(&a.to_le_bytes())?;
+            w.write_all(&b.to_le_bytes())?;
+        }
+        for index in &order {
+            w.write_all(&index.to_le_bytes())?;
+        }
+
+        w.flush()
+    }
+
+    /// Restores a BVH written by [`Bvh::save`] over `hit_list`, which must
+    /// hold the primitives the saved BVH was built from, in their original
+    /// order. The structure is checked with [`Bvh::validate`].
+    pub fn load<R: Read>(mut reader: R, hit_list: &[P]) -> io::Result<Self> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != SAVE_MAGIC {
+            return Err(invalid_data("not a saved BVH"));
+        }
+
+        let node_count = read_u32(&mut reader)? as usize;
+        let primitive_count = read_u32(&mut reader)? as usize;
+        if primitive_count != hit_list.len() {
+            return Err(invalid_data("primitive count mismatch"));
+        }
+        // A binary tree has fewer nodes than twice its leaves, which hold at
+        // least one primitive each but for a lone empty root.
+        if node_count > 2 * primitive_count.max(1) - 1 {
+            return Err(invalid_data("too many nodes"));
+        }
+
+        let mut nodes = Vec::with_capacity(node_count);
+        for _ in 0..node_count {
+            let mut axes = [Interval::empty(); 3];
+            for axis in &mut axes {
+                *axis = Interval::new(read_f64(&mut reader)?, read_f64(&mut reader)?);
+            }
+            let mut tag = [0u8; 1];
+            reader.read_exact(&mut tag)?;
+            let a = read_u32(&mut reader)?;
+            let b = read_u32(&mut reader)?;
+            let kind = match tag[0] {
+                0 => NodeKind::Leaf { first: a, count: b },
+                1 => NodeKind::Interior { second_child: a },
+                _ => return Err(invalid_data("bad node kind")),
+            };
+            let [x, y, z] = axes;
+            nodes.push(LinearBvhNode {
+                bbox: Aabb::new(x, y, z),
+                kind,
+            });
+        }
+
+        let mut order = Vec::with_capacity(primitive_count);
+        for _ in 0..primitive_count {
+            order.push(read_u32(&mut reader)?);
+        }
+        let primitives = order
+            .iter()
+            .map(|&i| hit_list.get(i as usize).cloned())
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| invalid_data("primitive index out of range"))?;
+        if nodes.is_empty() {
+            return Err(invalid_data("empty BVH"));
+        }
+
+        let bvh = Self {
+            count: primitives.iter().map(|obj| obj.count()).sum(),
+            nodes,
+            primitives,
+            order,
+            edits: None,
+        };
+        bvh.validate()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        Ok(bvh)
+    }
+
+    /// Appends the subtree over the primitives of `hit_list` at `indices`,
+    /// the first of which ends up at index `offset`, to `nodes`.
+    fn build(hit_list: &[P], indices: &mut [u32], offset: usize, nodes: &mut Vec<LinearBvhNode>) {
+        let bbox = indices.iter().fold(Aabb::empty(), |bbox, &i| {
+            bbox.merge(hit_list[i as usize].aabb())
+        });
+
+        if indices.len() == 1 {
+            nodes.push(LinearBvhNode {
+                bbox,
+                kind: NodeKind::Leaf {
+                    first: offset as u32,
+                    count: 1,
+                },
+            });
+            return;
+        }
 
         let axis = bbox.longest_axis();
+        indices.sort_by(|&a, &b| {
+            let a = hit_list[a as usize].aabb();
+            a.compare(&hit_list[b as usize].aabb(), axis)
+        });
+        let mid = indices.len() / 2;
+        let (left, right) = indices.split_at_mut(mid);
+
+        let index = nodes.len();
+        nodes.push(LinearBvhNode {
+            bbox,
+            kind: NodeKind::Interior { second_child: 0 },
+        });
+        Self::build(hit_list, left, offset, nodes);
+        let second_child = nodes.len() as u32;
+        Self::build(hit_list, right, offset + mid, nodes);
+        nodes[index].kind = NodeKind::Interior { second_child };
+    }
+
+    /// Appends the subtree over the primitives of `hit_list` at `indices`,
+    /// sorted by their Morton `codes`, to `nodes`, splitting where the
+    /// highest differing bit changes.
+    fn build_linear(
+        hit_list: &[P],
+        indices: &[u32],
+        codes: &[u32],
+        offset: usize,
+        nodes: &mut Vec<LinearBvhNode>,
+    ) {
+        let bbox = indices.iter().fold(Aabb::empty(), |bbox, &i| {
+            bbox.merge(hit_list[i as usize].aabb())
+        });
+
+        if indices.len() == 1 {
+            nodes.push(LinearBvhNode {
+                bbox,
+                kind: NodeKind::Leaf {
+                    first: offset as u32,
+                    count: 1,
+                },
+            });
+            return;
+        }
+
+        let first = codes[0];
+        let last = codes[codes.len() - 1];
+        let mid = if first == last {
+            codes.len() / 2
+        } else {
+            // Codes are sorted, so the ones with the highest differing bit
+            // set form a suffix.
+            let bit = 31 - (first ^ last).leading_zeros();
+            codes.partition_point(|code| code & (1 << bit) == 0)
+        };
+
+        let index = nodes.len();
+        nodes.push(LinearBvhNode {
+            bbox,
+            kind: NodeKind::Interior { second_child: 0 },
+        });
+        Self::build_linear(hit_list, &indices[..mid], &codes[..mid], offset, nodes);
+        let second_child = nodes.len() as u32;
+        Self::build_linear(
+            hit_list,
+            &indices[mid..],
+            &codes[mid..],
+            offset + mid,
+            nodes,
+        );
+        nodes[index].kind = NodeKind::Interior { second_child };
+    }
+
+    /// Removes the primitive with `id`, see [`Hit::object_id`], without
+    /// rebuilding the whole tree: its leaf shrinks and the bounds above it are
+    /// refit, or if the leaf is left empty, the subtree around it is rebuilt.
+    pub fn remove(&mut self, id: ObjectId) -> Option<P> {
+        let slot = self.edits().slots.remove(&id)? as usize;
+        let leaf = self.edits().leaves[slot] as usize;
+        let NodeKind::Leaf { first, count } = self.nodes[leaf].kind else {
+            unreachable!("primitives are held by leaves");
+        };
+
+        // The leaf gives up its last slot, where the primitive is moved.
+        let last = (first + count - 1) as usize;
+        self.swap_slots(slot, last);
+        self.nodes[leaf].kind = NodeKind::Leaf {
+            first,
+            count: count - 1,
+        };
+        let edits = self.edits();
+        edits.leaves[last] = UNUSED;
+        edits.unused_slots += 1;
+        let parent = edits.parents[leaf];
+
+        let removed = self.primitives[last].clone();
+        self.count -= removed.count();
+        if count == 1 && parent != UNUSED {
+            self.rebuild_around(parent as usize, None);
+        } else {
+            self.refit(leaf);
+        }
+        self.compact_if_sparse();
+        Some(removed)
+    }
+
+    /// Puts `object` in place of the primitive with `id` and refits the
+    /// bounds above it, returning the previous primitive.
+    pub fn replace(&mut self, id: ObjectId, object: P) -> Option<P> {
+        let slot = self.edits().slots.remove(&id)?;
+        if let Some(id) = object.object_id() {
+            self.edits().slots.insert(id, slot);
+        }
+        let slot = slot as usize;
+        self.count = self.count + object.count() - self.primitives[slot].count();
+        let replaced = mem::replace(&mut self.primitives[slot], object);
+        let leaf = self.edits().leaves[slot] as usize;
+        self.refit(leaf);
+        Some(replaced)
+    }
+
+    /// Adds `object` next to the leaf whose bounds grow the least on the way
+    /// down from the root, rebuilding a small subtree around that leaf. For
+    /// [`Bvh::save`] it counts as appended to the list the BVH was built from.
+    pub fn insert(&mut self, object: P) {
+        let bbox = object.aabb();
+        let growth =
+            |node: &LinearBvhNode| node.bbox.merge(bbox).surface_area() - node.bbox.surface_area();
+
+        let mut node = 0;
+        while let NodeKind::Interior { second_child } = self.nodes[node].kind {
+            let second_child = second_child as usize;
+            node = if growth(&self.nodes[node + 1]) <= growth(&self.nodes[second_child]) {
+                node + 1
+            } else {
+                second_child
+            };
+        }
+        self.rebuild_around(node, Some(object));
+        self.compact_if_sparse();
+    }
+
+    /// Lookup tables for editing, built by the first edit.
+    fn edits(&mut self) -> &mut EditIndex {
+        self.edits
+            .get_or_insert_with(|| EditIndex::new(&self.nodes, &self.primitives, &self.order))
+    }
+
+    /// Exchanges the primitives in slots `a` and `b` of the same leaf.
+    fn swap_slots(&mut self, a: usize, b: usize) {
+        self.primitives.swap(a, b);
+        self.order.swap(a, b);
+        for slot in [a, b] {
+            if let Some(id) = self.primitives[slot].object_id() {
+                self.edits().slots.insert(id, slot as u32);
+            }
+        }
+    }
+
+    /// Partially rebuilds the tree after an edit at `node`, adding `extra`.
+    ///
+    /// The rebuilt subtree is appended to the nodes and replaces the old one
+    /// as the second child of its parent, so it grows from `node` to the
+    /// closest ancestor that is a second child and shallow enough once
+    /// rebuilt to keep the whole tree balanced within a factor of two. Up to
+    /// the root, the whole tree is rebuilt.
+    fn rebuild_around(&mut self, mut node: usize, extra: Option<P>) {
+        let unused = self.edits().unused_slots;
+        let live = self.primitives.len() - unused + extra.is_some() as usize;
+        let max_depth = (2 * ceil_log2(live) + 2).min(MAX_DEPTH - 2);
 
-        let node = if hit_list.len() == 1 {
-            BvhNode::Leaf(hit_list[0].clone())
+        let mut depth = self.depth(node);
+        let mut size = self.size(node) + extra.is_some() as usize;
+        let parent = loop {
+            let parent = self.edits().parents[node];
+            if parent == UNUSED {
+                return self.rebuild_all(extra);
+            }
+            let parent = parent as usize;
+            let NodeKind::Interior { second_child } = self.nodes[parent].kind else {
+                unreachable!("parents are interior nodes");
+            };
+            let second_child = second_child as usize;
+            if node == second_child && size > 0 && depth + ceil_log2(size) <= max_depth {
+                break parent;
+            }
+            let sibling = if node == second_child {
+                parent + 1
+            } else {
+                second_child
+            };
+            size += self.size(sibling);
+            depth -= 1;
+            node = parent;
+        };
+
+        let mut slots = self.take_subtree(node);
+        let mut objects: Vec<P> = slots.iter().map(|&s| self.primitives[s].clone()).collect();
+        if let Some(object) = extra {
+            let edits = self.edits();
+            let order = edits.next_order;
+            edits.next_order += 1;
+            edits.leaves.push(UNUSED);
+            slots.push(self.primitives.len());
+            self.count += object.count();
+            self.primitives.push(object.clone());
+            self.order.push(order);
+            objects.push(object);
+        }
+
+        let base = self.nodes.len();
+        let mut indices: Vec<u32> = (0..objects.len() as u32).collect();
+        let mut nodes = Vec::with_capacity(2 * objects.len() - 1);
+        Self::build(&objects, &mut indices, 0, &mut nodes);
+        // Original indices of the objects in the order of the new leaves.
+        let orders: Vec<u32> = indices
+            .iter()
+            .map(|&i| self.order[slots[i as usize]])
+            .collect();
+
+        let edits = self.edits.as_mut().expect("edits are indexed");
+        edits.parents.resize(base + nodes.len(), UNUSED);
+        edits.parents[base] = parent as u32;
+        for (index, mut node) in nodes.into_iter().enumerate() {
+            let index = base + index;
+            match &mut node.kind {
+                NodeKind::Leaf { first, .. } => {
+                    let i = *first as usize;
+                    let slot = slots[i];
+                    *first = slot as u32;
+                    edits.leaves[slot] = index as u32;
+                    let object = &objects[indices[i] as usize];
+                    if let Some(id) = object.object_id() {
+                        edits.slots.insert(id, slot as u32);
+                    }
+                    self.primitives[slot] = object.clone();
+                    self.order[slot] = orders[i];
+                }
+                NodeKind::Interior { second_child } => {
+                    *second_child += base as u32;
+                    edits.parents[index + 1] = index as u32;
+                    edits.parents[*second_child as usize] = index as u32;
+                }
+            }
+            self.nodes.push(node);
+        }
+        self.nodes[parent].kind = NodeKind::Interior {
+            second_child: base as u32,
+        };
+        self.refit(parent);
+    }
+
+    /// Rebuilds the whole tree over its primitives and `extra`, packing them.
+    fn rebuild_all(&mut self, extra: Option<P>) {
+        let (_, slots) = self.packed();
+        let mut objects: Vec<P> = slots.iter().map(|&s| self.primitives[s].clone()).collect();
+        let mut order: Vec<u32> = slots.iter().map(|&s| self.order[s]).collect();
+        if let Some(object) = extra {
+            order.push(self.edits().next_order);
+            objects.push(object);
+        }
+
+        let mut indices: Vec<u32> = (0..objects.len() as u32).collect();
+        let mut nodes = Vec::with_capacity((2 * objects.len()).max(2) - 1);
+        if objects.is_empty() {
+            nodes.push(LinearBvhNode {
+                bbox: Aabb::empty(),
+                kind: NodeKind::Leaf { first: 0, count: 0 },
+            });
         } else {
-            hit_list.sort_by(|a, b| a.aabb().compare(&b.aabb(), axis));
-            let mid = hit_list.len() / 2;
-            let (left, right) = hit_list.split_at_mut(mid);
-            let left = Self::from_list(left);
-            let right = Self::from_list(right);
-            BvhNode::Node {
-                left:  Arc::new(left),
-                right: Arc::new(right),
+            Self::build(&objects, &mut indices, 0, &mut nodes);
+        }
+
+        self.primitives = indices
+            .iter()
+            .map(|&i| objects[i as usize].clone())
+            .collect();
+        self.order = indices.iter().map(|&i| order[i as usize]).collect();
+        self.count = self.primitives.iter().map(|obj| obj.count()).sum();
+        self.nodes = nodes;
+        self.edits = None;
+    }
+
+    /// Takes the subtree at `node` out of the tree, returning the slots of
+    /// its primitives.
+    fn take_subtree(&mut self, node: usize) -> Vec<usize> {
+        let edits = self.edits.as_mut().expect("edits are indexed");
+        let mut slots = Vec::new();
+        let mut stack = vec![node];
+        while let Some(index) = stack.pop() {
+            edits.parents[index] = UNUSED;
+            edits.unused_nodes += 1;
+            match self.nodes[index].kind {
+                NodeKind::Leaf { first, count } => {
+                    slots.extend(first as usize..(first + count) as usize);
+                }
+                NodeKind::Interior { second_child } => {
+                    stack.push(second_child as usize);
+                    stack.push(index + 1);
+                }
+            }
+        }
+        slots
+    }
+
+    /// Number of ancestors of `node`.
+    fn depth(&mut self, node: usize) -> usize {
+        let mut depth = 0;
+        let mut parent = self.edits().parents[node];
+        while parent != UNUSED {
+            parent = self.edits().parents[parent as usize];
+            depth += 1;
+        }
+        depth
+    }
+
+    /// Number of primitives under `node`.
+    fn size(&self, node: usize) -> usize {
+        let mut size = 0;
+        let mut stack = vec![node];
+        while let Some(index) = stack.pop() {
+            match self.nodes[index].kind {
+                NodeKind::Leaf { count, .. } => size += count as usize,
+                NodeKind::Interior { second_child } => {
+                    stack.push(second_child as usize);
+                    stack.push(index + 1);
+                }
             }
+        }
+        size
+    }
+
+    /// Recomputes the bounds of `node` and of its ancestors, from the bottom
+    /// up.
+    fn refit(&mut self, node: usize) {
+        let mut node = node as u32;
+        while node != UNUSED {
+            let index = node as usize;
+            self.nodes[index].bbox = match self.nodes[index].kind {
+                NodeKind::Leaf { first, count } => self.primitives
+                    [first as usize..(first + count) as usize]
+                    .iter()
+                    .fold(Aabb::empty(), |bbox, obj| bbox.merge(obj.aabb())),
+                NodeKind::Interior { second_child } => self.nodes[index + 1]
+                    .bbox
+                    .merge(self.nodes[second_child as usize].bbox),
+            };
+            node = self.edits().parents[index];
+        }
+    }
+
+    /// Packs the tree once edits have left more slots or nodes unused than
+    /// in use, which keeps their cost amortized over the edits.
+    fn compact_if_sparse(&mut self) {
+        let Some(edits) = &self.edits else {
+            return;
         };
+        if 2 * edits.unused_slots > self.primitives.len()
+            || 2 * edits.unused_nodes > self.nodes.len()
+        {
+            let (nodes, slots) = self.packed();
+            let mut primitives: Vec<_> = mem::take(&mut self.primitives)
+                .into_iter()
+                .map(Some)
+                .collect();
+            self.primitives = slots
+                .iter()
+                .map(|&s| primitives[s].take().expect("slots are used once"))
+                .collect();
+            self.order = slots.iter().map(|&s| self.order[s]).collect();
+            self.nodes = nodes;
+            self.edits = None;
+        }
+    }
 
-        Self::new(node, bbox)
+    /// Nodes of the tree in depth-first order, without those edits left
+    /// unused, with leaves referring to packed primitives, and the slot each
+    /// of these comes from.
+    fn packed(&self) -> (Vec<LinearBvhNode>, Vec<usize>) {
+        let mut nodes: Vec<LinearBvhNode> = Vec::with_capacity(self.nodes.len());
+        let mut slots = Vec::with_capacity(self.primitives.len());
+        // Nodes to copy with the copied parent whose second child they are.
+        let mut stack: Vec<(usize, Option<usize>)> = vec![(0, None)];
+        while let Some((index, parent)) = stack.pop() {
+            let position = nodes.len();
+            if let Some(parent) = parent {
+                nodes[parent].kind = NodeKind::Interior {
+                    second_child: position as u32,
+                };
+            }
+            let mut node = self.nodes[index];
+            match node.kind {
+                NodeKind::Leaf { first, count } => {
+                    node.kind = NodeKind::Leaf {
+                        first: slots.len() as u32,
+                        count,
+                    };
+                    slots.extend(first as usize..(first + count) as usize);
+                }
+                NodeKind::Interior { second_child } => {
+                    stack.push((second_child as usize, Some(position)));
+                    stack.push((index + 1, None));
+                }
+            }
+            nodes.push(node);
+        }
+        (nodes, slots)
+    }
+
+    /// Gathers metrics of the tree to compare build strategies.
+    pub fn stats(&self) -> BvhStats {
+        let root_area = self.nodes[0].bbox.surface_area();
+
+        let mut node_count = 0;
+        let mut leaf_count = 0;
+        let mut primitive_count = 0;
+        let mut max_depth = 0;
+        let mut depth_sum = 0;
+        let mut min_leaf_size = usize::MAX;
+        let mut max_leaf_size = 0;
+        let mut sah_cost = 0.0;
+        let mut overlap_sum = 0.0;
+
+        let mut stack = vec![(0, 0)];
+        while let Some((index, depth)) = stack.pop() {
+            let node = self.nodes[index];
+            node_count += 1;
+            let relative_area = if root_area > 0.0 {
+                node.bbox.surface_area() / root_area
+            } else {
+                1.0
+            };
+
+            match node.kind {
+                NodeKind::Leaf { count, .. } => {
+                    let count = count as usize;
+                    leaf_count += 1;
+                    primitive_count += count;
+                    max_depth = max_depth.max(depth);
+                    depth_sum += depth;
+                    min_leaf_size = min_leaf_size.min(count);
+                    max_leaf_size = max_leaf_size.max(count);
+                    sah_cost += relative_area * count as Float * INTERSECTION_COST;
+                }
+                NodeKind::Interior { second_child } => {
+                    let second_child = second_child as usize;
+                    sah_cost += relative_area * TRAVERSAL_COST;
+
+                    let area = node.bbox.surface_area();
+                    let overlap =
+                        overlap(self.nodes[index + 1].bbox, self.nodes[second_child].bbox);
+                    if area > 0.0 {
+                        overlap_sum += overlap.map_or(0.0, |bbox| bbox.surface_area()) / area;
+                    }
+
+                    stack.push((second_child, depth + 1));
+                    stack.push((index + 1, depth + 1));
+                }
+            }
+        }
+
+        let interior_count = node_count - leaf_count;
+        BvhStats {
+            node_count,
+            leaf_count,
+            max_depth,
+            average_depth: depth_sum as Float / leaf_count as Float,
+            min_leaf_size,
+            max_leaf_size,
+            average_leaf_size: primitive_count as Float / leaf_count as Float,
+            sah_cost,
+            average_overlap: if interior_count > 0 {
+                overlap_sum / interior_count as Float
+            } else {
+                0.0
+            },
+        }
+    }
+
+    /// Checks that the node array is a tree shallow enough to traverse, that
+    /// every primitive is in exactly one leaf and that its bounding box is
+    /// contained in those of all the ancestors of that leaf.
+    pub fn validate(&self) -> Result<(), BvhError> {
+        let mut references = vec![0u32; self.primitives.len()];
+        let mut visited = vec![false; self.nodes.len()];
+        // Depth-first, with the ancestors of the node on top of the stack
+        // down to the root.
+        let mut ancestors = Vec::new();
+        let mut stack = vec![(0, 0)];
+        while let Some((index, depth)) = stack.pop() {
+            if mem::replace(&mut visited[index], true) {
+                return Err(BvhError::BadChild { node: index });
+            }
+            ancestors.truncate(depth);
+            ancestors.push(index);
+
+            match self.nodes[index].kind {
+                NodeKind::Leaf { first, count } => {
+                    let (first, count) = (first as usize, count as usize);
+                    if first + count > self.primitives.len() {
+                        return Err(BvhError::BadLeaf { node: index });
+                    }
+                    let range = first..first + count;
+                    for (primitive, obj) in range.clone().zip(&self.primitives[range]) {
+                        references[primitive] += 1;
+                        let bbox = obj.aabb();
+                        if let Some(&node) = ancestors
+                            .iter()
+                            .find(|&&ancestor| !self.nodes[ancestor].bbox.contains(&bbox))
+                        {
+                            return Err(BvhError::NotContained { primitive, node });
+                        }
+                    }
+                }
+                NodeKind::Interior { second_child } => {
+                    let second_child = second_child as usize;
+                    if second_child <= index + 1 || second_child >= self.nodes.len() {
+                        return Err(BvhError::BadChild { node: index });
+                    }
+                    // Traversal keeps at most one pending node per level
+                    // above and both children.
+                    if depth + 2 > MAX_DEPTH {
+                        return Err(BvhError::TooDeep { node: index });
+                    }
+                    stack.push((second_child, depth + 1));
+                    stack.push((index + 1, depth + 1));
+                }
+            }
+        }
+
+        // Slots edits left unused belong to no leaf.
+        let used = |slot: usize| {
+            self.edits
+                .as_ref()
+                .is_none_or(|edits| edits.leaves[slot] != UNUSED)
+        };
+        match (0..references.len()).position(|slot| references[slot] != used(slot) as u32) {
+            Some(primitive) => Err(BvhError::BadCoverage { primitive }),
+            None => Ok(()),
+        }
+    }
+
+    /// Nodes of the tree, the root first. Edits may leave nodes that are no
+    /// longer in the tree until it is packed again.
+    pub fn nodes(&self) -> &[LinearBvhNode] {
+        &self.nodes
+    }
+
+    /// Primitives in the order of the leaves holding them. Edits may leave
+    /// slots that no leaf holds until the tree is packed again.
+    pub fn primitives(&self) -> &[P] {
+        &self.primitives
     }
 }
 
-impl Hit for Bvh {
-    type Material = Material;
+impl BvhStats {
+    pub const fn node_count(&self) -> usize {
+        self.node_count
+    }
+
+    pub const fn leaf_count(&self) -> usize {
+        self.leaf_count
+    }
+
+    /// Depth of the deepest leaf, the root being at depth 0.
+    pub const fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    pub const fn average_depth(&self) -> Float {
+        self.average_depth
+    }
+
+    /// Fewest primitives held by a leaf.
+    pub const fn min_leaf_size(&self) -> usize {
+        self.min_leaf_size
+    }
+
+    /// Most primitives held by a leaf.
+    pub const fn max_leaf_size(&self) -> usize {
+        self.max_leaf_size
+    }
+
+    pub const fn average_leaf_size(&self) -> Float {
+        self.average_leaf_size
+    }
+
+    /// Expected cost of tracing a random ray according to the surface area
+    /// heuristic, in units of primitive intersections.
+    pub const fn sah_cost(&self) -> Float {
+        self.sah_cost
+    }
+
+    /// Average, over interior nodes, of the surface area of the overlap of
+    /// the two children relative to the node's own. Lower is better.
+    pub const fn average_overlap(&self) -> Float {
+        self.average_overlap
+    }
+}
+
+impl fmt::Display for BvhError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::BadChild { node } => write!(f, "node {node} has an invalid child index"),
+            Self::BadLeaf { node } => write!(f, "leaf {node} has an invalid primitive range"),
+            Self::NotContained { primitive, node } => {
+                write!(f, "primitive {primitive} is not contained in node {node}")
+            }
+            Self::BadCoverage { primitive } => {
+                write!(f, "primitive {primitive} is not in exactly one leaf")
+            }
+            Self::TooDeep { node } => write!(f, "node {node} is too deep to traverse"),
+        }
+    }
+}
+
+impl Error for BvhError {}
+
+impl<P: Hit> Hit for Bvh<P> {
+    type Material = P::Material;
 
     fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<Self::Material>> {
-        if !self.bbox.hit(ray, ray_t) {
-            return None;
-        }
-
-        match self.node {
-            BvhNode::Leaf(ref arc) => arc.hit(ray, ray_t),
-            BvhNode::Node {
-                ref left,
-                ref right,
-            } => {
-                let left = left.hit(ray, ray_t);
-                let max = match left {
-                    Some(ref hit) => hit.t(),
-                    None => ray_t.max(),
-                };
-                let right = right.hit(ray, Interval::new(ray_t.min(), max));
+        let mut closest = None;
+        let mut max = ray_t.max();
+
+        // Nodes to visit with the parameter at which the ray enters them,
+        // the nearest on top.
+        let mut stack = [(0u32, 0.0); MAX_DEPTH];
+        stack[0].1 = self.nodes[0].bbox.hit(ray, ray_t)?.min();
+        let mut len = 1;
+        while len > 0 {
+            len -= 1;
+            let (index, t) = stack[len];
+            if t > max {
+                continue;
+            }
+            let node = &self.nodes[index as usize];
+            visit_node();
 
-                match (left, right) {
-                    (_, Some(right)) => Some(right),
-                    (Some(left), None) => Some(left),
-                    (None, None) => None,
+            match node.kind {
+                NodeKind::Leaf { first, count } => {
+                    let range = first as usize..(first + count) as usize;
+                    for obj in &self.primitives[range] {
+                        if let Some(hit) = obj.hit(ray, ray_t.with_max(max)) {
+                            max = hit.t();
+                            closest = Some(hit);
+                        }
+                    }
+                }
+                NodeKind::Interior { second_child } => {
+                    let ray_t = ray_t.with_max(max);
+                    let first = index + 1;
+                    let near = self.nodes[first as usize].bbox.hit(ray, ray_t);
+                    let far = self.nodes[second_child as usize].bbox.hit(ray, ray_t);
+                    match (near.map(|t| t.min()), far.map(|t| t.min())) {
+                        (Some(a), Some(b)) => {
+                            if a <= b {
+                                stack[len] = (second_child, b);
+                                stack[len + 1] = (first, a);
+                            } else {
+                                stack[len] = (first, a);
+                                stack[len + 1] = (second_child, b);
+                            }
+                            len += 2;
+                        }
+                        (Some(a), None) => {
+                            stack[len] = (first, a);
+                            len += 1;
+                        }
+                        (None, Some(b)) => {
+                            stack[len] = (second_child, b);
+                            len += 1;
+                        }
+                        (None, None) => (),
+                    }
                 }
             }
         }
+
+        closest
+    }
+
+    fn hit_any(&self, ray: &Ray, ray_t: Interval) -> bool {
+        let mut stack = [0u32; MAX_DEPTH];
+        let mut len = 1;
+        while len > 0 {
+            len -= 1;
+            let index = stack[len];
+            let node = &self.nodes[index as usize];
+            visit_node();
+
+            if node.bbox.hit(ray, ray_t).is_none() {
+                continue;
+            }
+
+            match node.kind {
+                NodeKind::Leaf { first, count } => {
+                    let range = first as usize..(first + count) as usize;
+                    if self.primitives[range]
+                        .iter()
+                        .any(|obj| obj.hit_any(ray, ray_t))
+                    {
+                        return true;
+                    }
+                }
+                NodeKind::Interior { second_child } => {
+                    stack[len] = second_child;
+                    stack[len + 1] = index + 1;
+                    len += 2;
+                }
+            }
+        }
+
+        false
     }
 
     fn aabb(&self) -> Aabb {
-        self.bbox
+        self.nodes[0].bbox
     }
 
     fn count(&self) -> usize {
-        match self.node {
-            BvhNode::Leaf(ref arc) => arc.count(),
-            BvhNode::Node {
-                ref left,
-                ref right,
-            } => left.count() + right.count(),
+        self.count
+    }
+
+    fn triangulate(&self, triangles: &mut Vec<[Vec3; 3]>) {
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            match self.nodes[index].kind {
+                NodeKind::Leaf { first, count } => {
+                    for obj in &self.primitives[first as usize..(first + count) as usize] {
+                        obj.triangulate(triangles);
+                    }
+                }
+                NodeKind::Interior { second_child } => {
+                    stack.push(second_child as usize);
+                    stack.push(index + 1);
+                }
+            }
+        }
+    }
+}
+
+/// Sorts `indices` into `hit_list` along a Morton curve through the
+/// centroids of the bounding boxes and returns the sorted codes.
+fn sort_by_morton_code<P: Hit>(hit_list: &[P], indices: &mut [u32]) -> Vec<u32> {
+    let centroids: Vec<_> = hit_list.iter().map(|obj| obj.aabb().centroid()).collect();
+    let first = Aabb::from_points(centroids[0], centroids[0]);
+    let bounds = centroids
+        .iter()
+        .fold(first, |bbox, &c| bbox.merge(Aabb::from_points(c, c)));
+
+    let quantize = |x: Float, axis: Interval| -> u32 {
+        let size = axis.size();
+        let t = if size > 0.0 {
+            (x - axis.min()) / size
+        } else {
+            0.5
+        };
+        ((t * 1024.0) as u32).min(1023)
+    };
+    let mut keys: Vec<(u32, u32)> = centroids
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            let code = (spread_bits(quantize(c.x(), bounds.x())) << 2)
+                | (spread_bits(quantize(c.y(), bounds.y())) << 1)
+                | spread_bits(quantize(c.z(), bounds.z()));
+            (code, i as u32)
+        })
+        .collect();
+
+    radix_sort(&mut keys);
+
+    for (index, &(_, i)) in indices.iter_mut().zip(&keys) {
+        *index = i;
+    }
+    keys.into_iter().map(|(code, _)| code).collect()
+}
+
+/// Height of a tree split at the median over `n` primitives.
+fn ceil_log2(n: usize) -> usize {
+    n.max(1).next_power_of_two().trailing_zeros() as usize
+}
+
+/// Magic bytes starting a saved BVH, with the format version.
+const SAVE_MAGIC: &[u8; 4] = b"BVH1";
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+/// Writes a bound, always stored in double precision.
+#[allow(clippy::unnecessary_cast, reason = "`Float` may be `f32`")]
+fn write_f64<W: Write>(writer: &mut W, x: Float) -> io::Result<()> {
+    writer.write_all(&(x as f64).to_le_bytes())
+}
+
+/// Reads a bound, always stored in double precision.
+fn read_f64<R: Read>(reader: &mut R) -> io::Result<Float> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(f64::from_le_bytes(bytes) as Float)
+}
+
+/// Intersection of two boxes, if not empty.
+fn overlap(a: Aabb, b: Aabb) -> Option<Aabb> {
+    let axis = |a: Interval, b: Interval| {
+        let overlap = a.intersect(b);
+        (overlap.size() > 0.0).then_some(overlap)
+    };
+    Some(Aabb::new(
+        axis(a.x(), b.x())?,
+        axis(a.y(), b.y())?,
+        axis(a.z(), b.z())?,
+    ))
+}
+
+/// Spreads the low 10 bits of `x` so that two zero bits separate each.
+fn spread_bits(x: u32) -> u32 {
+    let mut x = x & 0x3ff;
+    x = (x | (x << 16)) & 0x0300_00ff;
+    x = (x | (x << 8)) & 0x0300_f00f;
+    x = (x | (x << 4)) & 0x030c_30c3;
+    x = (x | (x << 2)) & 0x0924_9249;
+    x
+}
+
+/// Stable least significant digit radix sort on the first element, one byte
+/// at a time.
+fn radix_sort(keys: &mut Vec<(u32, u32)>) {
+    let mut buffer = vec![(0, 0); keys.len()];
+    for shift in (0..32).step_by(8) {
+        let mut offsets = [0usize; 257];
+        for &(code, _) in keys.iter() {
+            offsets[((code >> shift) & 0xff) as usize + 1] += 1;
+        }
+        for i in 1..257 {
+            offsets[i] += offsets[i - 1];
+        }
+        for &key in keys.iter() {
+            let digit = ((key.0 >> shift) & 0xff) as usize;
+            buffer[offsets[digit]] = key;
+            offsets[digit] += 1;
         }
+        std::mem::swap(keys, &mut buffer);
     }
 }
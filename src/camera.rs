@@ -1,50 +1,516 @@
+#[cfg(feature = "parallel")]
+use std::sync::Arc;
 use std::{
     fmt::Debug,
-    io::{BufWriter, Write},
+    io::Write,
+    ops::{Add, Range},
+    time::Duration,
 };
 
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
+#[cfg(feature = "parallel")]
+use rayon::{
+    ThreadPool, ThreadPoolBuilder,
+    iter::{IntoParallelIterator, ParallelIterator},
+};
+
+use crate::{
+    Float,
+    aperture::Aperture,
+    background::Background,
+    bdpt::BidirectionalPathTracer,
+    bvh::{nodes_visited, reset_nodes_visited},
+    clock::{self, Stopwatch},
+    consts::PI,
+    error::{Error, Result},
+    film::{Frame, Image},
+    filter::{Filter, FilterSampler},
+    guiding::{DirectionTree, GuidedPathTracer, PathGuide, PathGuiding, mixed_pdf},
+    hit::{Hit, HitRecord, ObjectId},
+    integrator::{
+        self, AmbientOcclusion, DebugNormals, DirectLighting, FirstHit, Integrator, LightPath,
+        PathTracer, PhotonMap, PhotonMapper, PhotonMapping, Transport,
+    },
+    interval::Interval,
+    light::Light,
+    material::{Lambertian, Scatter},
+    medium::Fog,
+    pdf::{HittablePdf, Pdf},
+    progress::{ProgressCallback, RenderEvent},
+    ray::{Ray, RayKind},
+    reservoir::{LightResampling, Reservoir},
+    sampler::{RandomSampler, Sampler, hash_seed},
+    spectrum::SpectralPathTracer,
+    stats::{self, Counter},
+    vec3::Vec3,
+};
+
+/// Fraction of the distance to a delta light checked for occluders, leaving
+/// out surfaces the light lies on.
+const SHADOW_END: Float = 1.0 - 1e-6;
+
+/// Filter weighted sums over the samples of a pixel, along with the sums of
+/// the squared colors and weights giving its variance. Depth and object id
+/// keep the closest sample instead, since averaging them across an edge is
+/// meaningless.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct PixelSum {
+    color:          Vec3,
+    color_squared:  Vec3,
+    albedo:         Vec3,
+    normal:         Vec3,
+    light_paths:    [Vec3; LightPath::ALL.len()],
+    depth:          Float,
+    id:             Option<ObjectId>,
+    weight:         Float,
+    weight_squared: Float,
+}
+
+impl Default for PixelSum {
+    fn default() -> Self {
+        Self {
+            color:          Vec3::default(),
+            color_squared:  Vec3::default(),
+            albedo:         Vec3::default(),
+            normal:         Vec3::default(),
+            light_paths:    [Vec3::default(); LightPath::ALL.len()],
+            depth:          Float::INFINITY,
+            id:             None,
+            weight:         0.0,
+            weight_squared: 0.0,
+        }
+    }
+}
+
+impl Add for PixelSum {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        let closest = if rhs.depth < self.depth { rhs } else { self };
+        Self {
+            color:          self.color + rhs.color,
+            color_squared:  self.color_squared + rhs.color_squared,
+            albedo:         self.albedo + rhs.albedo,
+            normal:         self.normal + rhs.normal,
+            light_paths:    std::array::from_fn(|i| self.light_paths[i] + rhs.light_paths[i]),
+            depth:          closest.depth,
+            id:             closest.id,
+            weight:         self.weight + rhs.weight,
+            weight_squared: self.weight_squared + rhs.weight_squared,
+        }
+    }
+}
+
+/// Path traced for one sample of a pixel, see [`Camera::debug_ray`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct DebugPath<M> {
+    ray:      Option<Ray>,
+    vertices: Vec<PathVertex<M>>,
+    radiance: Vec3,
+}
+
+/// Surface hit along a [`DebugPath`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PathVertex<M> {
+    point:      Vec3,
+    normal:     Vec3,
+    front_face: bool,
+    object_id:  Option<ObjectId>,
+    material:   M,
+    throughput: Vec3,
+    emitted:    Option<Vec3>,
+    scattered:  Option<(Vec3, Ray)>,
+}
+
+impl<M> DebugPath<M> {
+    /// Camera ray of the sample, `None` where the projection covers no
+    /// direction.
+    pub const fn ray(&self) -> Option<Ray> {
+        self.ray
+    }
+
+    /// Surfaces hit, in order. The path escaped to the background unless
+    /// the last one scattered nothing or the path reached the maximum
+    /// depth.
+    pub fn vertices(&self) -> &[PathVertex<M>] {
+        &self.vertices
+    }
+
+    /// Radiance carried back to the camera, before clamping and exposure.
+    pub const fn radiance(&self) -> Vec3 {
+        self.radiance
+    }
+}
+
+impl<M: Copy + Scatter> PathVertex<M> {
+    pub(crate) fn new(
+        hit: &HitRecord<M>,
+        throughput: Vec3,
+        emitted: Option<Vec3>,
+        scattered: Option<(Vec3, Ray)>,
+    ) -> Self {
+        Self {
+            point: hit.point(),
+            normal: hit.normal(),
+            front_face: hit.front_face(),
+            object_id: hit.object_id(),
+            material: *hit.material(),
+            throughput,
+            emitted,
+            scattered,
+        }
+    }
+}
+
+impl<M> PathVertex<M> {
+    pub const fn point(&self) -> Vec3 {
+        self.point
+    }
+
+    pub const fn normal(&self) -> Vec3 {
+        self.normal
+    }
+
+    pub const fn front_face(&self) -> bool {
+        self.front_face
+    }
+
+    pub const fn object_id(&self) -> Option<ObjectId> {
+        self.object_id
+    }
+
+    pub const fn material(&self) -> &M {
+        &self.material
+    }
+
+    /// Product of the attenuations of the vertices before this one.
+    pub const fn throughput(&self) -> Vec3 {
+        self.throughput
+    }
 
-use crate::{hit::Hit, interval::Interval, random_0_1, ray::Ray, vec3::Vec3};
+    /// Light emitted towards the incoming ray.
+    pub const fn emitted(&self) -> Option<Vec3> {
+        self.emitted
+    }
+
+    /// Attenuation and ray the path continues with, `None` where it was
+    /// absorbed. Surfaces hidden inside a higher priority dielectric pass
+    /// the ray on unattenuated.
+    pub const fn scattered(&self) -> Option<(Vec3, Ray)> {
+        self.scattered
+    }
+}
+
+/// How pixels are mapped to ray directions.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Projection {
+    /// Pinhole or thin lens perspective with the field of view and focus
+    /// given to [`Camera::new`].
+    #[default]
+    Perspective,
+    /// Latitude/longitude panorama covering 360° horizontally and 180°
+    /// vertically, centered on the view direction. A 2:1 aspect ratio gives
+    /// square pixels, and the result can be used as an environment map.
+    Equirectangular,
+    /// Circular fisheye covering `fov` degrees (possibly more than 180)
+    /// across the largest circle that fits in the image. Pixels outside the
+    /// circle are black.
+    Fisheye {
+        fov:     Float,
+        mapping: FisheyeMapping,
+    },
+}
+
+/// How the angle from the view direction grows with the distance from the
+/// center of a fisheye image.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FisheyeMapping {
+    /// Distance proportional to the angle.
+    #[default]
+    Equidistant,
+    /// Equal areas of the image cover equal solid angles.
+    Equisolid,
+}
+
+/// What the camera renders for each pixel.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RenderMode {
+    /// Full path traced image.
+    #[default]
+    Beauty,
+    /// Full image rendered by bidirectional path tracing, see
+    /// [`BidirectionalPathTracer`].
+    Bidirectional,
+    /// Emission and direct light at the first diffuse surface seen, see
+    /// [`DirectLighting`].
+    DirectLighting,
+    /// Full image path traced at sampled wavelengths, see
+    /// [`SpectralPathTracer`].
+    Spectral,
+    /// Shading normal of the first hit, mapped from `[-1, 1]` to `[0, 1]`.
+    Normals,
+    /// Distance to the first hit, white at the camera fading to black at
+    /// `far`.
+    Depth { far: Float },
+    /// Number of BVH nodes visited by the camera ray, from blue for none to
+    /// red for `max_nodes` or more.
+    BvhCost { max_nodes: u32 },
+    /// Ambient occlusion: each sample casts one cosine distributed ray from
+    /// the first hit and is white unless something lies within
+    /// `max_distance`.
+    AmbientOcclusion { max_distance: Float },
+}
+
+/// Photographic exposure settings scaling scene radiance to pixel values.
+///
+/// Follows the saturation based sensitivity convention: the radiance that
+/// saturates the sensor is `1.2 * 2^EV100`, so with `f/16`, `1/100 s` and
+/// ISO 100 a radiance of about 30000 maps to a pixel value of 1. Emitters
+/// given in physical units then need no manual rescaling.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Exposure {
+    iso:           Float,
+    shutter_speed: Float,
+    f_number:      Float,
+}
+
+impl Exposure {
+    /// `shutter_speed` is the exposure time in seconds.
+    pub const fn new(iso: Float, shutter_speed: Float, f_number: Float) -> Self {
+        Self {
+            iso,
+            shutter_speed,
+            f_number,
+        }
+    }
+
+    pub const fn iso(&self) -> Float {
+        self.iso
+    }
+
+    pub const fn shutter_speed(&self) -> Float {
+        self.shutter_speed
+    }
+
+    pub const fn f_number(&self) -> Float {
+        self.f_number
+    }
+
+    /// Exposure value at ISO 100.
+    pub fn ev100(&self) -> Float {
+        (self.f_number * self.f_number / self.shutter_speed * 100.0 / self.iso).log2()
+    }
+
+    /// Factor applied to radiance to obtain pixel values.
+    pub fn scale(&self) -> Float {
+        1.0 / (1.2 * self.ev100().exp2())
+    }
+}
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Camera {
-    aspect_ratio:  f64,
+    aspect_ratio:  Float,
     img_width:     u32,
     img_height:    u32,
     pixel00_loc:   Vec3,
     pixel_delta_u: Vec3,
     pixel_delta_v: Vec3,
 
-    defocous_angle: f64,
+    defocous_angle: Float,
     disk_u:         Vec3,
     disk_v:         Vec3,
+    aperture:       Aperture,
 
     center:     Vec3,
     w:          Vec3,
     u:          Vec3,
     v:          Vec3,
-    background: Vec3,
+    projection: Projection,
+    background: Background,
+    filter:     FilterSampler,
+    seed:       u64,
+    #[cfg_attr(feature = "serde", serde(default))]
+    frame:      u32,
+
+    delta_lights: Vec<Light>,
+    mode:         RenderMode,
+    clay:         Option<Vec3>,
+
+    max_radiance:     Option<Float>,
+    indirect_clamp:   Option<Float>,
+    exposure:         Option<Exposure>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    photon_mapping:   Option<PhotonMapping>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    path_guiding:     Option<PathGuiding>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    light_resampling: Option<LightResampling>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    fog:              Option<Fog>,
+
+    shutter_open:  Float,
+    shutter_close: Float,
+
+    #[cfg_attr(feature = "serde", serde(skip))]
+    progress:    Option<ProgressCallback>,
+    #[cfg(feature = "parallel")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    thread_pool: Option<Arc<ThreadPool>>,
+}
+
+/// Builder for [`Camera`], created by [`Camera::builder`].
+///
+/// Defaults to a 400 pixel wide 16:9 image looking from the origin towards
+/// `-z` with a 90° vertical field of view, `+y` up, no defocus blur and a
+/// black background.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CameraBuilder {
+    aspect_ratio:  Float,
+    img_width:     u32,
+    fov:           Float,
+    look_from:     Vec3,
+    look_at:       Vec3,
+    vup:           Vec3,
+    defocus_angle: Float,
+    focus_dist:    Float,
+    aperture:      Aperture,
+    background:    Background,
+}
+
+impl Default for CameraBuilder {
+    fn default() -> Self {
+        Self {
+            aspect_ratio:  16.0 / 9.0,
+            img_width:     400,
+            fov:           90.0,
+            look_from:     Vec3::new(0.0, 0.0, 0.0),
+            look_at:       Vec3::new(0.0, 0.0, -1.0),
+            vup:           Vec3::new(0.0, 1.0, 0.0),
+            defocus_angle: 0.0,
+            focus_dist:    10.0,
+            aperture:      Aperture::default(),
+            background:    Background::default(),
+        }
+    }
+}
+
+impl CameraBuilder {
+    pub fn aspect_ratio(mut self, aspect_ratio: Float) -> Self {
+        self.aspect_ratio = aspect_ratio;
+        self
+    }
+
+    pub fn img_width(mut self, img_width: u32) -> Self {
+        self.img_width = img_width;
+        self
+    }
+
+    /// Vertical field of view, in degrees.
+    pub fn fov(mut self, fov: Float) -> Self {
+        self.fov = fov;
+        self
+    }
+
+    pub fn look_from(mut self, look_from: Vec3) -> Self {
+        self.look_from = look_from;
+        self
+    }
+
+    pub fn look_at(mut self, look_at: Vec3) -> Self {
+        self.look_at = look_at;
+        self
+    }
+
+    pub fn vup(mut self, vup: Vec3) -> Self {
+        self.vup = vup;
+        self
+    }
+
+    /// Angle, in degrees, of the cone from each pixel to the lens aperture.
+    /// Zero disables defocus blur.
+    pub fn defocus_angle(mut self, defocus_angle: Float) -> Self {
+        self.defocus_angle = defocus_angle;
+        self
+    }
+
+    /// Distance from the camera to the plane of perfect focus.
+    pub fn focus_dist(mut self, focus_dist: Float) -> Self {
+        self.focus_dist = focus_dist;
+        self
+    }
+
+    /// Shape of the lens aperture used for defocus blur.
+    pub fn aperture(mut self, aperture: Aperture) -> Self {
+        self.aperture = aperture;
+        self
+    }
+
+    pub fn background(mut self, background: Background) -> Self {
+        self.background = background;
+        self
+    }
+
+    /// Moves the eye around the look-at point by `degrees` about the up
+    /// vector, keeping its distance and height.
+    pub fn orbit(mut self, degrees: Float) -> Self {
+        let offset = self.look_from - self.look_at;
+        self.look_from = self.look_at + offset.rotate(self.vup.unit(), degrees);
+        self
+    }
+
+    /// Eye position, look-at point and up vector.
+    #[cfg_attr(not(feature = "preview"), allow(dead_code))]
+    pub(crate) const fn view(&self) -> (Vec3, Vec3, Vec3) {
+        (self.look_from, self.look_at, self.vup)
+    }
+
+    pub fn build(self) -> Camera {
+        let mut camera = Camera::new(
+            self.aspect_ratio,
+            self.img_width,
+            self.fov,
+            self.look_from,
+            self.look_at,
+            self.vup,
+            self.defocus_angle,
+            self.focus_dist,
+            self.background,
+        );
+        camera.aperture = self.aperture;
+        camera
+    }
 }
 
 impl Camera {
+    pub fn builder() -> CameraBuilder {
+        CameraBuilder::default()
+    }
+
+    /// Creates a camera from positional parameters. [`Camera::builder`] is
+    /// usually more convenient.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        aspect_ratio: f64,
+        aspect_ratio: Float,
         img_width: u32,
-        fov: f64,
+        fov: Float,
         lookfrom: Vec3,
         lookat: Vec3,
         vup: Vec3,
-        defocous_angle: f64,
-        focus_dist: f64,
-        background: Vec3,
+        defocous_angle: Float,
+        focus_dist: Float,
+        background: Background,
     ) -> Self {
-        let img_height = (img_width as f64 / aspect_ratio) as u32;
+        let img_height = (img_width as Float / aspect_ratio) as u32;
 
         let theta = fov.to_radians();
         let h = (theta / 2.0).tan();
         let viewport_height = 2.0 * h * focus_dist;
-        let viewport_width = viewport_height * (img_width as f64 / img_height as f64);
+        let viewport_width = viewport_height * (img_width as Float / img_height as Float);
 
         let w = (lookfrom - lookat).unit();
         let u = vup.cross(w).unit();
@@ -54,8 +520,8 @@ impl Camera {
         let viewport_u = u * viewport_width;
         let viewport_v = -v * viewport_height;
 
-        let pixel_delta_u = viewport_u / (img_width as f64);
-        let pixel_delta_v = viewport_v / (img_height as f64);
+        let pixel_delta_u = viewport_u / (img_width as Float);
+        let pixel_delta_v = viewport_v / (img_height as Float);
 
         let viewport_upper_left = center - (w * focus_dist) - viewport_u / 2.0 - viewport_v / 2.0;
         let pixel00_loc = viewport_upper_left + (pixel_delta_u + pixel_delta_v) * 0.5;
@@ -75,19 +541,356 @@ impl Camera {
             defocous_angle,
             disk_u,
             disk_v,
+            aperture: Aperture::default(),
             w,
             u,
             v,
+            projection: Projection::default(),
             background,
+            filter: FilterSampler::new(Filter::default()),
+            seed: 0,
+            frame: 0,
+            delta_lights: Vec::new(),
+            mode: RenderMode::default(),
+            clay: None,
+            max_radiance: None,
+            indirect_clamp: None,
+            exposure: None,
+            photon_mapping: None,
+            path_guiding: None,
+            light_resampling: None,
+            fog: None,
+            shutter_open: 0.0,
+            shutter_close: 1.0,
+            progress: None,
+            #[cfg(feature = "parallel")]
+            thread_pool: None,
         }
     }
 
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = FilterSampler::new(filter);
+        self
+    }
+
+    /// Adds a point, directional or spot light to the scene. Such lights are
+    /// invisible to camera rays and only reach surfaces through shadow rays.
+    pub fn with_light(mut self, light: Light) -> Self {
+        self.delta_lights.push(light);
+        self
+    }
+
+    pub fn with_projection(mut self, projection: Projection) -> Self {
+        self.projection = projection;
+        self
+    }
+
+    pub fn with_mode(mut self, mode: RenderMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Shades every non-emissive surface as a Lambertian of `albedo`, to judge
+    /// geometry and lighting independently of the scene materials.
+    pub fn with_clay(mut self, albedo: Vec3) -> Self {
+        self.clay = Some(albedo);
+        self
+    }
+
+    /// Sets the seed every sample stream is derived from. Rendering the same
+    /// scene with the same seed produces the same image.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Sets the frame of an animation being rendered, which together with
+    /// the seed decides the noise pattern, so consecutive frames don't share
+    /// it. Defaults to `0`.
+    pub fn with_frame(mut self, frame: u32) -> Self {
+        self.frame = frame;
+        self
+    }
+
+    /// Clamps the radiance of every camera sample so that no channel exceeds
+    /// `max_radiance`, trading a little energy for the removal of fireflies.
+    pub fn with_max_radiance(mut self, max_radiance: Float) -> Self {
+        self.max_radiance = Some(max_radiance);
+        self
+    }
+
+    /// Clamps every contribution arriving after the first bounce, leaving
+    /// directly visible lights untouched.
+    pub fn with_indirect_clamp(mut self, indirect_clamp: Float) -> Self {
+        self.indirect_clamp = Some(indirect_clamp);
+        self
+    }
+
+    /// Scales the beauty pass by a physical exposure instead of writing
+    /// scene radiance directly. Clamping options still apply to radiance.
+    pub fn with_exposure(mut self, exposure: Exposure) -> Self {
+        self.exposure = Some(exposure);
+        self
+    }
+
+    /// Renders caustics from a photon map traced before rendering, see
+    /// [`PhotonMapping`]. Light reaching a diffuse surface through mirrors
+    /// or glass is then gathered from the map instead of path traced, which
+    /// makes caustics from small lights converge.
+    pub fn with_photon_mapping(mut self, photon_mapping: PhotonMapping) -> Self {
+        self.photon_mapping = Some(photon_mapping);
+        self
+    }
+
+    /// Samples diffuse bounces of the beauty pass from a guide trained on
+    /// the scene before rendering, see [`PathGuiding`]. Helps scenes lit
+    /// indirectly through small openings, where most bounces miss the
+    /// light. Progressive renders train a new guide for every pass. Ignored
+    /// with photon mapping.
+    pub fn with_path_guiding(mut self, path_guiding: PathGuiding) -> Self {
+        self.path_guiding = Some(path_guiding);
+        self
+    }
+
+    /// Picks the light each shadow ray goes to among several candidates,
+    /// see [`LightResampling`]. Cuts the noise of direct light in scenes
+    /// with many emitters, of which only a few light any given point.
+    pub fn with_light_resampling(mut self, light_resampling: LightResampling) -> Self {
+        self.light_resampling = Some(light_resampling);
+        self
+    }
+
+    /// Fills the scene with homogeneous fog, see [`Fog`]. Rendered by the
+    /// path tracing modes; bidirectional path tracing, photon maps and the
+    /// GPU backend leave it out.
+    pub fn with_fog(mut self, fog: Fog) -> Self {
+        self.fog = Some(fog);
+        self
+    }
+
+    /// Sets the interval over which the shutter stays open, within the
+    /// motion of objects from time 0 to 1. Ray times are spread uniformly
+    /// over it, so moving objects blur over the corresponding fraction of
+    /// their motion. Equal values freeze the scene at that instant.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the shutter closes before it opens or the interval leaves
+    /// `[0, 1]`, see [`Camera::try_with_shutter`].
+    pub fn with_shutter(self, shutter_open: Float, shutter_close: Float) -> Self {
+        self.try_with_shutter(shutter_open, shutter_close)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Sets the shutter interval like [`Camera::with_shutter`], failing if
+    /// the shutter closes before it opens or the interval leaves `[0, 1]`.
+    pub fn try_with_shutter(mut self, shutter_open: Float, shutter_close: Float) -> Result<Self> {
+        if shutter_open.is_nan() || shutter_close.is_nan() || shutter_open > shutter_close {
+            return Err(Error::InvalidParameter(
+                "the shutter must open before it closes",
+            ));
+        }
+        if shutter_open < 0.0 || shutter_close > 1.0 {
+            return Err(Error::InvalidParameter(
+                "the shutter must stay open within time 0 to 1",
+            ));
+        }
+        self.shutter_open = shutter_open;
+        self.shutter_close = shutter_close;
+        Ok(self)
+    }
+
+    /// Reports rendering progress to `callback`, which may be called from
+    /// several threads. Nothing is reported by default; use
+    /// [`log_to_stderr`](crate::progress::log_to_stderr) for a console log.
+    pub fn with_progress(mut self, callback: impl Fn(RenderEvent) + Send + Sync + 'static) -> Self {
+        self.progress = Some(ProgressCallback::new(callback));
+        self
+    }
+
+    /// Renders on `pool` instead of the global rayon pool, for example to
+    /// share threads with other work or to lower their priority through the
+    /// pool's spawn handler.
+    #[cfg(feature = "parallel")]
+    pub fn with_thread_pool(mut self, pool: Arc<ThreadPool>) -> Self {
+        self.thread_pool = Some(pool);
+        self
+    }
+
+    /// Renders on a dedicated pool of `threads` threads, or as many as the
+    /// machine has cores for `0`.
+    #[cfg(feature = "parallel")]
+    pub fn with_threads(self, threads: usize) -> Result<Self> {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|index| format!("render-{index}"))
+            .build()?;
+        Ok(self.with_thread_pool(Arc::new(pool)))
+    }
+
+    /// Number of threads renders run on.
+    #[cfg(feature = "parallel")]
+    pub fn thread_count(&self) -> usize {
+        match &self.thread_pool {
+            Some(pool) => pool.current_num_threads(),
+            None => rayon::current_num_threads(),
+        }
+    }
+
+    /// Number of threads renders run on, always one without the `parallel`
+    /// feature.
+    #[cfg(not(feature = "parallel"))]
+    pub fn thread_count(&self) -> usize {
+        1
+    }
+
+    pub const fn aspect_ratio(&self) -> Float {
+        self.aspect_ratio
+    }
+
+    pub const fn img_width(&self) -> u32 {
+        self.img_width
+    }
+
+    pub const fn img_height(&self) -> u32 {
+        self.img_height
+    }
+
+    pub const fn background(&self) -> &Background {
+        &self.background
+    }
+
+    pub fn delta_lights(&self) -> &[Light] {
+        &self.delta_lights
+    }
+
+    pub const fn clay(&self) -> Option<Vec3> {
+        self.clay
+    }
+
+    pub const fn aperture(&self) -> &Aperture {
+        &self.aperture
+    }
+
+    pub const fn projection(&self) -> Projection {
+        self.projection
+    }
+
+    pub const fn mode(&self) -> RenderMode {
+        self.mode
+    }
+
+    pub const fn filter(&self) -> Filter {
+        self.filter.filter()
+    }
+
+    pub const fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub const fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    pub const fn max_radiance(&self) -> Option<Float> {
+        self.max_radiance
+    }
+
+    pub const fn indirect_clamp(&self) -> Option<Float> {
+        self.indirect_clamp
+    }
+
+    pub const fn exposure(&self) -> Option<Exposure> {
+        self.exposure
+    }
+
+    pub const fn photon_mapping(&self) -> Option<PhotonMapping> {
+        self.photon_mapping
+    }
+
+    pub const fn path_guiding(&self) -> Option<PathGuiding> {
+        self.path_guiding
+    }
+
+    pub const fn light_resampling(&self) -> Option<LightResampling> {
+        self.light_resampling
+    }
+
+    pub const fn fog(&self) -> Option<Fog> {
+        self.fog
+    }
+
+    pub const fn shutter_open(&self) -> Float {
+        self.shutter_open
+    }
+
+    pub const fn shutter_close(&self) -> Float {
+        self.shutter_close
+    }
+
+    /// Eye position, center of the top-left pixel and the steps to the next
+    /// pixel right and down, for the perspective projection.
+    #[cfg_attr(not(feature = "gpu"), allow(dead_code))]
+    pub(crate) const fn image_plane(&self) -> (Vec3, Vec3, Vec3, Vec3) {
+        (
+            self.center,
+            self.pixel00_loc,
+            self.pixel_delta_u,
+            self.pixel_delta_v,
+        )
+    }
+
+    /// Half axes of the lens disk, zero without defocus blur.
+    #[cfg_attr(not(feature = "gpu"), allow(dead_code))]
+    pub(crate) fn lens_axes(&self) -> (Vec3, Vec3) {
+        if self.defocous_angle <= 0.0 {
+            (Vec3::default(), Vec3::default())
+        } else {
+            (self.disk_u, self.disk_v)
+        }
+    }
+
+    /// Seed of the random stream used by sample `s` of pixel `(i, j)`.
+    ///
+    /// Hashing decorrelates the streams of neighboring pixels and samples,
+    /// and depending only on the pixel and sample index makes every pixel
+    /// the same however the image is split into passes or tiles.
+    fn sample_seed(&self, i: u32, j: u32, s: u32) -> u64 {
+        hash_seed(&[self.seed, self.frame as u64, i as u64, j as u64, s as u64])
+    }
+
+    /// Casts a ray through pixel `(i, j)` displaced by `offset` from its
+    /// center. Returns `None` for points the projection does not cover.
     #[inline(always)]
-    fn get_ray(&self, i: u32, j: u32) -> Ray {
-        let i = i as f64;
-        let j = j as f64;
+    pub(crate) fn get_ray(
+        &self,
+        i: u32,
+        j: u32,
+        offset: Vec3,
+        sampler: &mut dyn Sampler,
+    ) -> Option<Ray> {
+        let i = i as Float;
+        let j = j as Float;
+
+        match self.projection {
+            Projection::Perspective => (),
+            Projection::Equirectangular => {
+                let x = (i + 0.5 + offset.x()) / self.img_width as Float;
+                let y = (j + 0.5 + offset.y()) / self.img_height as Float;
+                let dir = self.panorama_direction(x, y);
+                return Some(Ray::new(self.center, dir, self.sample_time(sampler)));
+            }
+            Projection::Fisheye { fov, mapping } => {
+                let dir = self.fisheye_direction(
+                    i + 0.5 + offset.x(),
+                    j + 0.5 + offset.y(),
+                    fov,
+                    mapping,
+                )?;
+                return Some(Ray::new(self.center, dir, self.sample_time(sampler)));
+            }
+        }
 
-        let offset = Self::sample_square();
         let pixel_sample = self.pixel00_loc
             + (self.pixel_delta_u * (i + offset.x()))
             + (self.pixel_delta_v * (j + offset.y()));
@@ -95,114 +898,1020 @@ impl Camera {
         let origin = if self.defocous_angle <= 0.0 {
             self.center
         } else {
-            self.disk_sample()
+            self.disk_sample(sampler)
         };
         let dir = pixel_sample - origin;
-        let time = random_0_1();
+        let time = self.sample_time(sampler);
+
+        // The direction reaches the image plane at `t = 1`, where the ray
+        // stands for a pixel.
+        let spread = self.pixel_delta_u.length();
+        Some(Ray::new(origin, dir, time).with_spread(spread))
+    }
+
+    /// Direction of the point `(x, y)` of an equirectangular image, both in
+    /// `[0, 1]`, in the camera frame.
+    fn panorama_direction(&self, x: Float, y: Float) -> Vec3 {
+        let phi = (x - 0.5) * 2.0 * PI;
+        let theta = y * PI;
+        let sin_theta = theta.sin();
+        (self.u * (sin_theta * phi.sin())) + (self.v * theta.cos())
+            - (self.w * (sin_theta * phi.cos()))
+    }
+
+    /// Direction of the image point `(x, y)`, in pixels, of a fisheye
+    /// projection.
+    fn fisheye_direction(
+        &self,
+        x: Float,
+        y: Float,
+        fov: Float,
+        mapping: FisheyeMapping,
+    ) -> Option<Vec3> {
+        let radius = self.img_width.min(self.img_height) as Float / 2.0;
+        let dx = (x - self.img_width as Float / 2.0) / radius;
+        let dy = (y - self.img_height as Float / 2.0) / radius;
+        let r = (dx * dx + dy * dy).sqrt();
+        if r > 1.0 {
+            return None;
+        }
+
+        let theta_max = fov.to_radians() / 2.0;
+        let theta = match mapping {
+            FisheyeMapping::Equidistant => r * theta_max,
+            FisheyeMapping::Equisolid => {
+                2.0 * (r * (theta_max / 2.0).sin()).clamp(-1.0, 1.0).asin()
+            }
+        };
+
+        let (sin_phi, cos_phi) = if r > 0.0 {
+            (dy / r, dx / r)
+        } else {
+            (0.0, 0.0)
+        };
+        let radial = (self.u * cos_phi) - (self.v * sin_phi);
+        Some((radial * theta.sin()) - (self.w * theta.cos()))
+    }
 
-        Ray::new(origin, dir, time)
+    pub(crate) fn sample_time(&self, sampler: &mut dyn Sampler) -> Float {
+        self.shutter_open + sampler.next_1d() * (self.shutter_close - self.shutter_open)
     }
 
-    fn disk_sample(&self) -> Vec3 {
-        let p = Vec3::random_in_disk();
+    fn disk_sample(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        let p = self.aperture.sample(sampler);
         self.center + (self.disk_u * p.x()) + (self.disk_v * p.y())
     }
 
-    fn sample_square() -> Vec3 {
-        Vec3::new(random_0_1() - 0.5, random_0_1() - 0.5, 0.0)
+    /// Samples an offset from the pixel center with its filter weight, see
+    /// [`Filter`].
+    pub(crate) fn sample_filter(&self, sampler: &mut dyn Sampler) -> (Vec3, Float) {
+        self.filter.sample(sampler)
     }
 
-    fn sample_par<T: Hit + ?Sized + Sync>(
+    /// Returns the filter weighted radiance and first-hit data of sample `s`
+    /// of pixel `(i, j)` together with its weight.
+    #[inline(always)]
+    #[allow(clippy::too_many_arguments)]
+    fn sample<I: Integrator, T: Hit + ?Sized, L: Hit + ?Sized>(
         &self,
         i: u32,
         j: u32,
-        sample_count: u32,
+        s: u32,
         world: &T,
+        lights: &L,
         max_depth: u32,
-    ) -> Vec3
+        integrator: &I,
+    ) -> PixelSum
+    where
+        T::Material: Copy,
+    {
+        let mut sampler = RandomSampler::new(self.sample_seed(i, j, s));
+
+        // Every sample counts once towards the average, the filter weight
+        // scales what it adds.
+        let (offset, weight) = self.sample_filter(&mut sampler);
+        let Some(ray) = self.get_ray(i, j, offset, &mut sampler) else {
+            return PixelSum {
+                weight: 1.0,
+                weight_squared: 1.0,
+                ..PixelSum::default()
+            };
+        };
+        stats::count(Counter::PrimaryRays);
+        let (color, first_hit) =
+            integrator.radiance(self, &ray, world, lights, max_depth, &mut sampler);
+        // The light path passes are clamped and exposed like the whole.
+        let clamp = self
+            .max_radiance
+            .map_or(1.0, |max| clamp_factor(color, max));
+        let exposure = match (self.mode, self.exposure) {
+            (
+                RenderMode::Beauty
+                | RenderMode::Bidirectional
+                | RenderMode::DirectLighting
+                | RenderMode::Spectral,
+                Some(exposure),
+            ) => exposure.scale(),
+            _ => 1.0,
+        };
+        let color = color * clamp * exposure * weight;
+
+        PixelSum {
+            color,
+            color_squared: color.scale(color),
+            albedo: first_hit.albedo() * weight,
+            normal: first_hit.normal() * weight,
+            light_paths: first_hit
+                .light_paths()
+                .map(|radiance| radiance * (clamp * exposure * weight)),
+            depth: first_hit.depth(),
+            id: first_hit.object_id(),
+            weight: 1.0,
+            weight_squared: 1.0,
+        }
+    }
+
+    #[cfg(feature = "parallel")]
+    #[allow(clippy::too_many_arguments)]
+    fn sample_par<I: Integrator + Sync, T: Hit + ?Sized + Sync, L: Hit + ?Sized + Sync>(
+        &self,
+        i: u32,
+        j: u32,
+        samples: Range<u32>,
+        world: &T,
+        lights: &L,
+        max_depth: u32,
+        integrator: &I,
+    ) -> PixelSum
     where
         T::Material: Copy,
     {
-        (0..sample_count)
+        samples
             .into_par_iter()
-            .map(|_| {
-                let ray = self.get_ray(i, j);
-                Self::ray_color(&ray, world, max_depth, self.background)
-            })
-            .sum()
+            .map(|s| self.sample(i, j, s, world, lights, max_depth, integrator))
+            .reduce(PixelSum::default, PixelSum::add)
     }
 
-    fn sample_seq<T: Hit + ?Sized>(
+    #[cfg(not(feature = "parallel"))]
+    #[allow(clippy::too_many_arguments)]
+    fn sample_par<I: Integrator, T: Hit + ?Sized, L: Hit + ?Sized>(
         &self,
         i: u32,
         j: u32,
-        sample_count: u32,
+        samples: Range<u32>,
         world: &T,
+        lights: &L,
         max_depth: u32,
-    ) -> Vec3
+        integrator: &I,
+    ) -> PixelSum
     where
         T::Material: Copy,
     {
-        (0..sample_count)
-            .into_iter()
-            .map(|_| {
-                let ray = self.get_ray(i, j);
-                Self::ray_color(&ray, world, max_depth, self.background)
-            })
-            .sum()
+        self.sample_seq(i, j, samples, world, lights, max_depth, integrator)
     }
 
-    pub fn render<T: Hit + ?Sized + Sync, W: Write>(
+    #[allow(clippy::too_many_arguments)]
+    fn sample_seq<I: Integrator, T: Hit + ?Sized, L: Hit + ?Sized>(
         &self,
+        i: u32,
+        j: u32,
+        samples: Range<u32>,
         world: &T,
+        lights: &L,
+        max_depth: u32,
+        integrator: &I,
+    ) -> PixelSum
+    where
+        T::Material: Copy,
+    {
+        samples
+            .map(|s| self.sample(i, j, s, world, lights, max_depth, integrator))
+            .fold(PixelSum::default(), PixelSum::add)
+    }
+
+    /// Renders `world` as a PPM image into `writer`, failing if writing
+    /// fails.
+    ///
+    /// Emissive objects in `lights` are sampled explicitly at every diffuse
+    /// bounce. Every emitter of `world` should be part of `lights`, since
+    /// light reaching diffuse surfaces from an unregistered emitter is only
+    /// found by chance. An empty `lights` disables light sampling.
+    pub fn render<T: Hit + ?Sized + Sync, L: Hit + ?Sized + Sync, W: Write>(
+        &self,
+        world: &T,
+        lights: &L,
         sample_count: u32,
         max_depth: u32,
         writer: W,
-    ) where
+    ) -> Result<()>
+    where
+        T::Material: Copy,
+    {
+        let frame = self.render_frame(world, lights, sample_count, max_depth);
+        frame.beauty().write_ppm(writer)?;
+        Ok(())
+    }
+
+    /// Renders `world` into a [`Frame`] holding the beauty image and the
+    /// albedo, normal, depth and object id passes. See [`Camera::render`].
+    pub fn render_frame<T: Hit + ?Sized + Sync, L: Hit + ?Sized + Sync>(
+        &self,
+        world: &T,
+        lights: &L,
+        sample_count: u32,
+        max_depth: u32,
+    ) -> Frame
+    where
         T::Material: Copy,
     {
-        let mut w = BufWriter::new(writer);
+        self.render_region(
+            0..self.img_width,
+            0..self.img_height,
+            world,
+            lights,
+            sample_count,
+            max_depth,
+        )
+    }
+
+    /// Renders `world` like [`Camera::render_frame`], but with `integrator`
+    /// instead of the one of the camera's [`RenderMode`].
+    pub fn render_frame_with<I, T, L>(
+        &self,
+        integrator: &I,
+        world: &T,
+        lights: &L,
+        sample_count: u32,
+        max_depth: u32,
+    ) -> Frame
+    where
+        I: Integrator + Sync,
+        T: Hit + ?Sized + Sync,
+        L: Hit + ?Sized + Sync,
+        T::Material: Copy,
+    {
+        self.render_region_with(
+            integrator,
+            0..self.img_width,
+            0..self.img_height,
+            world,
+            lights,
+            sample_count,
+            max_depth,
+        )
+    }
 
-        let count = world.count() as f64;
+    /// Renders only the pixels in columns `x` and rows `y` of the full image,
+    /// returning a [`Frame`] of the size of the region. Every pixel gets the
+    /// same samples as in a full render, so the result matches the
+    /// corresponding crop of [`Camera::render_frame`].
+    pub fn render_region<T: Hit + ?Sized + Sync, L: Hit + ?Sized + Sync>(
+        &self,
+        x: Range<u32>,
+        y: Range<u32>,
+        world: &T,
+        lights: &L,
+        sample_count: u32,
+        max_depth: u32,
+    ) -> Frame
+    where
+        T::Material: Copy,
+    {
+        let integrator = self.install(|| self.mode_integrator(world, lights, max_depth, 0));
+        self.render_region_with(&integrator, x, y, world, lights, sample_count, max_depth)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn render_region_with<I, T, L>(
+        &self,
+        integrator: &I,
+        x: Range<u32>,
+        y: Range<u32>,
+        world: &T,
+        lights: &L,
+        sample_count: u32,
+        max_depth: u32,
+    ) -> Frame
+    where
+        I: Integrator + Sync,
+        T: Hit + ?Sized + Sync,
+        L: Hit + ?Sized + Sync,
+        T::Material: Copy,
+    {
+        assert!(
+            x.end <= self.img_width && y.end <= self.img_height,
+            "render region out of the image"
+        );
+
+        let count = world.count() as Float;
         let count_log2 = (count).log2() as u32;
 
-        writeln!(w, "P3\n{} {}\n255", self.img_width, self.img_height).unwrap();
+        let width = x.len() as u32;
+        let height = y.len() as u32;
+        let mut sums = Vec::with_capacity(width as usize * height as usize);
 
-        let sample_scale = 1.0 / sample_count as f64;
-        for j in 0..self.img_height {
-            eprintln!("\nScanlines remaining {}", self.img_height - j);
-            for i in 0..self.img_width {
-                let color = if (sample_count * count_log2) < 1000 {
-                    self.sample_seq(i, j, sample_count, world, max_depth)
-                } else {
-                    self.sample_par(i, j, sample_count, world, max_depth)
-                };
-                let color = color * sample_scale;
-                let (r, g, b) = color.to_color();
-                writeln!(w, "{} {} {}", r, g, b).unwrap();
+        let start = Stopwatch::start();
+        self.install(|| {
+            for j in y.clone() {
+                let row_start = Stopwatch::start();
+                for i in x.clone() {
+                    let samples = 0..sample_count;
+                    let sum = if (sample_count * count_log2) < 1000 {
+                        self.sample_seq(i, j, samples, world, lights, max_depth, integrator)
+                    } else {
+                        self.sample_par(i, j, samples, world, lights, max_depth, integrator)
+                    };
+                    sums.push(sum);
+                }
+                stats::record_row(row_start.elapsed());
+                self.report(RenderEvent::RowDone {
+                    done:    j - y.start + 1,
+                    total:   height,
+                    elapsed: start.elapsed(),
+                });
             }
+        });
+        self.report(RenderEvent::Finished {
+            elapsed: start.elapsed(),
+        });
+
+        Self::frame_from_sums(width, height, &sums)
+    }
+
+    /// Renders progressive passes of one sample per pixel until `budget` has
+    /// elapsed, then returns whatever has been accumulated. At least one pass
+    /// is always rendered, and a pass in progress when the budget expires is
+    /// completed. Targets without a clock, such as `wasm32-unknown-unknown`,
+    /// render only that first pass.
+    pub fn render_for<T: Hit + ?Sized + Sync, L: Hit + ?Sized + Sync>(
+        &self,
+        world: &T,
+        lights: &L,
+        budget: Duration,
+        max_depth: u32,
+    ) -> Frame
+    where
+        T::Material: Copy,
+    {
+        let start = Stopwatch::start();
+        let mut sums = self.pass_sums(0..1, world, lights, max_depth);
+        let mut pass = 1;
+        while clock::HAS_CLOCK && start.elapsed() < budget {
+            let pass_sums = self.pass_sums(pass..pass + 1, world, lights, max_depth);
+            for (sum, pass_sum) in sums.iter_mut().zip(pass_sums) {
+                *sum = *sum + pass_sum;
+            }
+            pass += 1;
         }
-        w.flush().unwrap();
+        self.report(RenderEvent::Finished {
+            elapsed: start.elapsed(),
+        });
 
-        eprintln!("\nDone.\n");
+        Self::frame_from_sums(self.img_width, self.img_height, &sums)
     }
 
-    #[inline(always)]
-    fn ray_color<T: Hit + ?Sized>(ray: &Ray, world: &T, depth: u32, background: Vec3) -> Vec3
+    /// Resolves row-major pixel sums into a `width` by `height` frame.
+    fn frame_from_sums(width: u32, height: u32, sums: &[PixelSum]) -> Frame {
+        let mut beauty = Image::new(width, height);
+        let mut albedo = Image::new(width, height);
+        let mut normal = Image::new(width, height);
+        let mut depth = Image::new(width, height);
+        let mut light_paths = LightPath::ALL.map(|_| Image::new(width, height));
+        let mut variance = Image::new(width, height);
+        let mut sample_count = Image::new(width, height);
+
+        for (index, sum) in sums.iter().enumerate() {
+            let (i, j) = (index as u32 % width, index as u32 / width);
+            // Kish's effective sample size, the number of equally weighted
+            // samples giving the mean the same variance.
+            let samples = if sum.weight_squared > 0.0 {
+                sum.weight * sum.weight / sum.weight_squared
+            } else {
+                0.0
+            };
+            sample_count.set(i, j, Vec3::new(samples, samples, samples));
+            if sum.weight > 0.0 {
+                let mean = sum.color / sum.weight;
+                if samples > 1.0 {
+                    let spread =
+                        (sum.color_squared / sum.weight - mean.scale(mean)).max(Vec3::default());
+                    variance.set(i, j, spread / (samples - 1.0));
+                }
+                beauty.set(i, j, mean);
+                albedo.set(i, j, sum.albedo / sum.weight);
+                normal.set(i, j, sum.normal / sum.weight);
+                for (image, radiance) in light_paths.iter_mut().zip(sum.light_paths) {
+                    image.set(i, j, radiance / sum.weight);
+                }
+            }
+            depth.set(i, j, Vec3::new(sum.depth, sum.depth, sum.depth));
+        }
+        let ids = sums.iter().map(|sum| sum.id).collect();
+
+        Frame::new(beauty, albedo, normal, depth, ids)
+            .with_light_paths(light_paths)
+            .with_sample_stats(variance, sample_count)
+    }
+
+    /// Renders samples `samples` of every pixel, scanlines in parallel, and
+    /// returns the filter weighted radiance sums and weights in row-major
+    /// order. Summing the passes over disjoint ranges gives the same result
+    /// as rendering all of their samples at once.
+    #[cfg_attr(not(feature = "preview"), allow(dead_code))]
+    pub(crate) fn render_pass<T: Hit + ?Sized + Sync, L: Hit + ?Sized + Sync>(
+        &self,
+        samples: Range<u32>,
+        world: &T,
+        lights: &L,
+        max_depth: u32,
+    ) -> Vec<(Vec3, Float)>
     where
         T::Material: Copy,
     {
-        if depth == 0 {
-            return background;
+        self.pass_sums(samples, world, lights, max_depth)
+            .into_iter()
+            .map(|sum| (sum.color, sum.weight))
+            .collect()
+    }
+
+    fn pass_sums<T: Hit + ?Sized + Sync, L: Hit + ?Sized + Sync>(
+        &self,
+        samples: Range<u32>,
+        world: &T,
+        lights: &L,
+        max_depth: u32,
+    ) -> Vec<PixelSum>
+    where
+        T::Material: Copy,
+    {
+        let start = Stopwatch::start();
+        let integrator =
+            self.install(|| self.mode_integrator(world, lights, max_depth, samples.start));
+        let integrator = &integrator;
+        let render_row = |j| {
+            let row_start = Stopwatch::start();
+            let row: Vec<_> = (0..self.img_width)
+                .map(|i| {
+                    let samples = samples.clone();
+                    self.sample_seq(i, j, samples, world, lights, max_depth, integrator)
+                })
+                .collect();
+            stats::record_row(row_start.elapsed());
+            row
+        };
+
+        #[cfg(feature = "parallel")]
+        let sums = self.install(|| {
+            (0..self.img_height)
+                .into_par_iter()
+                .flat_map_iter(render_row)
+                .collect()
+        });
+        #[cfg(not(feature = "parallel"))]
+        let sums = (0..self.img_height).flat_map(render_row).collect();
+        self.report(RenderEvent::PassDone {
+            samples: samples.end,
+            elapsed: start.elapsed(),
+        });
+        sums
+    }
+
+    /// Runs `work` on the camera's thread pool, if it has one.
+    #[cfg(feature = "parallel")]
+    fn install<R: Send>(&self, work: impl FnOnce() -> R + Send) -> R {
+        match &self.thread_pool {
+            Some(pool) => pool.install(work),
+            None => work(),
         }
-        match world.hit(ray, Interval::new(0.001, f64::INFINITY)) {
-            Some(hit) => match hit.scatter(ray) {
-                Some((attenuation, scattered)) => {
-                    Self::ray_color(&scattered, world, depth - 1, background).scale(attenuation)
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    fn install<R>(&self, work: impl FnOnce() -> R) -> R {
+        work()
+    }
+
+    /// Integrator of the render mode, tracing the photon map or training
+    /// the path guide of pass `pass` when enabled.
+    fn mode_integrator<T: Hit + ?Sized + Sync, L: Hit + ?Sized + Sync>(
+        &self,
+        world: &T,
+        lights: &L,
+        max_depth: u32,
+        pass: u32,
+    ) -> ModeIntegrator
+    where
+        T::Material: Copy,
+    {
+        match (self.mode, self.photon_mapping) {
+            (RenderMode::Beauty, Some(settings)) => {
+                let photons = PhotonMap::trace(self, world, lights, settings, max_depth, pass);
+                ModeIntegrator::Photons(PhotonMapper::new(photons))
+            }
+            (RenderMode::Beauty, None) => match self.path_guiding {
+                Some(settings) => {
+                    let guide = PathGuide::train(self, world, lights, settings, max_depth, pass);
+                    ModeIntegrator::Guided(GuidedPathTracer::new(guide))
                 }
-                None => hit.emit().unwrap_or(background),
+                None => ModeIntegrator::Path(PathTracer),
             },
-            None => background,
+            (RenderMode::Bidirectional, _) => {
+                ModeIntegrator::Bidirectional(BidirectionalPathTracer)
+            }
+            (RenderMode::DirectLighting, _) => ModeIntegrator::Direct(DirectLighting),
+            (RenderMode::Spectral, _) => ModeIntegrator::Spectral(SpectralPathTracer),
+            (RenderMode::Normals, _) => ModeIntegrator::Normals(DebugNormals),
+            (RenderMode::AmbientOcclusion { max_distance }, _) => {
+                ModeIntegrator::AmbientOcclusion(AmbientOcclusion::new(max_distance))
+            }
+            (mode @ (RenderMode::Depth { .. } | RenderMode::BvhCost { .. }), _) => {
+                ModeIntegrator::Debug(mode)
+            }
         }
     }
+
+    pub(crate) fn report(&self, event: RenderEvent) {
+        if let Some(progress) = &self.progress {
+            progress.call(event);
+        }
+    }
+
+    /// Traces sample `s` of pixel `(i, j)` the way [`RenderMode::Beauty`]
+    /// renders it, with the same random numbers, and returns every surface
+    /// the path hits. Useful to find out why a pixel is black or a material
+    /// misbehaves. With photon mapping or path guiding, the photon map or
+    /// path guide of the render is built first.
+    pub fn debug_ray<T: Hit + ?Sized + Sync, L: Hit + ?Sized + Sync>(
+        &self,
+        i: u32,
+        j: u32,
+        s: u32,
+        world: &T,
+        lights: &L,
+        max_depth: u32,
+    ) -> DebugPath<T::Material>
+    where
+        T::Material: Copy,
+    {
+        let mut sampler = RandomSampler::new(self.sample_seed(i, j, s));
+        let (offset, _) = self.sample_filter(&mut sampler);
+        let ray = self.get_ray(i, j, offset, &mut sampler);
+        let photons = self
+            .photon_mapping
+            .map(|settings| PhotonMap::trace(self, world, lights, settings, max_depth, 0));
+        let guide = self
+            .path_guiding
+            .filter(|_| photons.is_none())
+            .map(|settings| PathGuide::train(self, world, lights, settings, max_depth, 0));
+        let transport = match (&photons, &guide) {
+            (Some(photons), _) => Transport::Photons(photons),
+            (None, Some(guide)) => Transport::Guided {
+                guide,
+                vertices: None,
+            },
+            (None, None) => Transport::Full,
+        };
+        let mut vertices = Vec::new();
+        let radiance = ray.map_or(Vec3::default(), |ray| {
+            let path = Some(&mut vertices);
+            integrator::trace_path(
+                self,
+                &ray,
+                world,
+                lights,
+                max_depth,
+                transport,
+                &mut sampler,
+                path,
+            )
+            .0
+        });
+        DebugPath {
+            ray,
+            vertices,
+            radiance,
+        }
+    }
+
+    /// Shades the camera `ray` according to the depth or BVH cost render
+    /// modes.
+    fn debug_color<T: Hit + ?Sized>(
+        mode: RenderMode,
+        ray: &Ray,
+        world: &T,
+        sampler: &mut dyn Sampler,
+    ) -> (Vec3, FirstHit)
+    where
+        T::Material: Copy,
+    {
+        reset_nodes_visited();
+        let (_, first_hit) = integrator::probe(ray, world, sampler);
+        let nodes = nodes_visited();
+
+        let color = match mode {
+            RenderMode::Depth { far } => {
+                let v = 1.0 - (first_hit.depth() / far).min(1.0);
+                Vec3::new(v, v, v)
+            }
+            RenderMode::BvhCost { max_nodes } => heat_map(nodes as Float / max_nodes as Float),
+            _ => unreachable!("{mode:?} has its own integrator"),
+        };
+
+        (color, first_hit)
+    }
+
+    /// Estimates the light arriving at `hit` directly from `lights` with a
+    /// single shadow ray, weighted by the material's scattering density and
+    /// the MIS weight of light sampling against bounces sampled from the
+    /// material, or its mixture with `guide` if given, and dimmed by `fog`.
+    /// The result still has to be multiplied by the material attenuation.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn sample_light<M: Scatter, T: Hit + ?Sized, L: Hit + ?Sized>(
+        &self,
+        ray: &Ray,
+        hit: &HitRecord<M>,
+        world: &T,
+        lights: &L,
+        guide: Option<&DirectionTree>,
+        fog: Option<Fog>,
+        sampler: &mut dyn Sampler,
+    ) -> Vec3
+    where
+        T::Material: Copy,
+    {
+        if let Some(resampling) = self.light_resampling {
+            return self.resample_light(ray, hit, world, lights, resampling, guide, fog, sampler);
+        }
+
+        let origin = hit.point();
+        let light = HittablePdf::new(lights, origin, ray.time());
+        let direction = light.generate(sampler);
+        let light_pdf = light.value(direction);
+        if light_pdf <= 0.0 {
+            return Vec3::default();
+        }
+
+        let shadow = hit
+            .spawn_ray(direction, ray.time())
+            .with_kind(RayKind::Shadow);
+        let scattering_pdf = self.scattering_pdf(ray, hit, &shadow);
+        if scattering_pdf <= 0.0 {
+            return Vec3::default();
+        }
+        stats::count(Counter::ShadowRays);
+
+        world
+            .hit(&shadow, Interval::new(0.0, Float::INFINITY))
+            .filter(HitRecord::is_emitter)
+            .and_then(|light| Some((light.emit()?, light.t())))
+            .map(|(emitted, t)| {
+                let weight =
+                    power_heuristic(light_pdf, mixed_pdf(guide, scattering_pdf, direction));
+                let transmittance = transmittance(fog, world, &shadow, Interval::new(0.0, t));
+                emitted * (weight * transmittance * scattering_pdf / light_pdf)
+            })
+            .unwrap_or_default()
+    }
+
+    /// Estimates the light arriving at `hit` directly from `lights` like
+    /// [`Camera::sample_light`], tracing the shadow ray to the best of
+    /// several candidate points on the lights. Candidates are kept with a
+    /// probability proportional to the light they would bring if visible.
+    /// The density of the kept point is unknown, so the MIS weight uses the
+    /// one of [`Camera::light_pdf`] instead.
+    #[allow(clippy::too_many_arguments)]
+    fn resample_light<M: Scatter, T: Hit + ?Sized, L: Hit + ?Sized>(
+        &self,
+        ray: &Ray,
+        hit: &HitRecord<M>,
+        world: &T,
+        lights: &L,
+        resampling: LightResampling,
+        guide: Option<&DirectionTree>,
+        fog: Option<Fog>,
+        sampler: &mut dyn Sampler,
+    ) -> Vec3
+    where
+        T::Material: Copy,
+    {
+        let origin = hit.point();
+        let mut reservoir = Reservoir::new();
+        for _ in 0..resampling.candidates() {
+            let candidate =
+                lights
+                    .sample_surface(ray.time(), sampler)
+                    .and_then(|(light, area_pdf)| {
+                        let candidate = LightCandidate::new(self, ray, hit, &light)?;
+                        Some((candidate, candidate.target() / area_pdf))
+                    });
+            match candidate {
+                Some((candidate, weight)) => reservoir.update(candidate, weight, sampler),
+                None => reservoir.update(LightCandidate::default(), 0.0, sampler),
+            }
+        }
+
+        let Some(candidate) = reservoir.sample() else {
+            return Vec3::default();
+        };
+        let contribution_weight = reservoir.contribution_weight(candidate.target());
+        stats::count(Counter::ShadowRays);
+        let shadow = hit
+            .spawn_ray(candidate.direction, ray.time())
+            .with_kind(RayKind::Shadow);
+        let shadow_t = Interval::new(0.0, candidate.distance * SHADOW_END);
+        if world.hit_any(&shadow, shadow_t) {
+            return Vec3::default();
+        }
+
+        let light_pdf = self.light_pdf(lights, origin, candidate.direction, ray.time());
+        let scattering_pdf = mixed_pdf(guide, candidate.scattering_pdf, candidate.direction);
+        let weight = power_heuristic(light_pdf, scattering_pdf);
+        let transmittance = transmittance(fog, world, &shadow, shadow_t);
+        candidate.radiance() * (weight * transmittance * contribution_weight)
+    }
+
+    /// Density of light sampling `direction` from `origin` at `time` in the
+    /// MIS weights of direct light. With light resampling, the density of
+    /// sampling the lights directly times the number of candidates, as
+    /// resampling makes the lights that matter that much more likely. Any
+    /// density works as long as both strategies agree on it.
+    pub(crate) fn light_pdf<L: Hit + ?Sized>(
+        &self,
+        lights: &L,
+        origin: Vec3,
+        direction: Vec3,
+        time: Float,
+    ) -> Float {
+        let pdf = HittablePdf::new(lights, origin, time).value(direction);
+        match self.light_resampling {
+            Some(resampling) => pdf * resampling.candidates() as Float,
+            None => pdf,
+        }
+    }
+
+    /// Estimates the light arriving at `hit` directly from the background
+    /// with a single shadow ray, in the same way as [`Camera::sample_light`].
+    pub(crate) fn sample_background<M: Scatter, T: Hit + ?Sized>(
+        &self,
+        ray: &Ray,
+        hit: &HitRecord<M>,
+        world: &T,
+        guide: Option<&DirectionTree>,
+        fog: Option<Fog>,
+        sampler: &mut dyn Sampler,
+    ) -> Vec3
+    where
+        T::Material: Copy,
+    {
+        let direction = self.background.random_direction(sampler);
+        let background_pdf = self.background.pdf_value(direction);
+        if background_pdf <= 0.0 {
+            return Vec3::default();
+        }
+
+        let shadow = hit
+            .spawn_ray(direction, ray.time())
+            .with_kind(RayKind::Shadow);
+        let scattering_pdf = self.scattering_pdf(ray, hit, &shadow);
+        if scattering_pdf <= 0.0 {
+            return Vec3::default();
+        }
+        stats::count(Counter::ShadowRays);
+
+        let shadow_t = Interval::new(0.0, Float::INFINITY);
+        if world.hit_any(&shadow, shadow_t) {
+            return Vec3::default();
+        }
+
+        let weight = power_heuristic(background_pdf, mixed_pdf(guide, scattering_pdf, direction));
+        let transmittance = transmittance(fog, world, &shadow, shadow_t);
+        self.background.value(direction)
+            * (weight * transmittance * scattering_pdf / background_pdf)
+    }
+
+    /// Sums the light arriving at `hit` from every delta light, tracing one
+    /// shadow ray towards each of them, dimmed by `fog`.
+    pub(crate) fn sample_delta_lights<M: Scatter, T: Hit + ?Sized>(
+        &self,
+        ray: &Ray,
+        hit: &HitRecord<M>,
+        world: &T,
+        fog: Option<Fog>,
+        sampler: &mut dyn Sampler,
+    ) -> Vec3
+    where
+        T::Material: Copy,
+    {
+        self.delta_lights
+            .iter()
+            .filter_map(|light| light.sample(hit.point(), sampler))
+            .filter_map(|sample| {
+                let shadow = hit
+                    .spawn_ray(sample.direction(), ray.time())
+                    .with_kind(RayKind::Shadow);
+                let scattering_pdf = self.scattering_pdf(ray, hit, &shadow);
+                if scattering_pdf <= 0.0 {
+                    return None;
+                }
+
+                stats::count(Counter::ShadowRays);
+                let shadow_t = Interval::new(0.0, sample.distance() * SHADOW_END);
+                (!world.hit_any(&shadow, shadow_t)).then(|| {
+                    let transmittance = transmittance(fog, world, &shadow, shadow_t);
+                    sample.radiance() * (transmittance * scattering_pdf)
+                })
+            })
+            .sum()
+    }
+
+    /// Material override applied to `hit`, if any. Emitters and media always
+    /// keep their own material.
+    fn clay_override<M: Scatter>(&self, hit: &HitRecord<M>) -> Option<Lambertian> {
+        self.clay
+            .filter(|_| !hit.is_emitter() && !hit.material().is_volumetric())
+            .map(Lambertian::new)
+    }
+
+    pub(crate) fn scatter<M: Scatter>(
+        &self,
+        ray: &Ray,
+        hit: &HitRecord<M>,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Vec3, Ray)> {
+        match self.clay_override(hit) {
+            Some(clay) => clay.scatter(ray, hit, sampler),
+            None => hit.scatter(ray, sampler),
+        }
+    }
+
+    pub(crate) fn scattering_pdf<M: Scatter>(
+        &self,
+        ray: &Ray,
+        hit: &HitRecord<M>,
+        scattered: &Ray,
+    ) -> Float {
+        match self.clay_override(hit) {
+            Some(clay) => clay.scattering_pdf(ray, hit, scattered),
+            None => hit.scattering_pdf(ray, scattered),
+        }
+    }
+
+    /// Applies the indirect clamp to radiance reaching the camera after
+    /// `bounce` bounces.
+    pub(crate) fn contribution(&self, bounce: u32, radiance: Vec3) -> Vec3 {
+        match self.indirect_clamp {
+            Some(max) if bounce > 0 => clamp_radiance(radiance, max),
+            _ => radiance,
+        }
+    }
+}
+
+/// Integrator of one of the [`RenderMode`]s.
+#[derive(Debug)]
+enum ModeIntegrator {
+    Path(PathTracer),
+    Photons(PhotonMapper),
+    Guided(GuidedPathTracer),
+    Bidirectional(BidirectionalPathTracer),
+    Direct(DirectLighting),
+    Spectral(SpectralPathTracer),
+    Normals(DebugNormals),
+    AmbientOcclusion(AmbientOcclusion),
+    /// Depth and BVH cost, shaded by [`Camera::debug_color`].
+    Debug(RenderMode),
+}
+
+impl Integrator for ModeIntegrator {
+    fn radiance<T: Hit + ?Sized, L: Hit + ?Sized>(
+        &self,
+        camera: &Camera,
+        ray: &Ray,
+        world: &T,
+        lights: &L,
+        max_depth: u32,
+        sampler: &mut dyn Sampler,
+    ) -> (Vec3, FirstHit)
+    where
+        T::Material: Copy,
+    {
+        match self {
+            Self::Path(path) => path.radiance(camera, ray, world, lights, max_depth, sampler),
+            Self::Photons(photons) => {
+                photons.radiance(camera, ray, world, lights, max_depth, sampler)
+            }
+            Self::Guided(guided) => guided.radiance(camera, ray, world, lights, max_depth, sampler),
+            Self::Bidirectional(bidirectional) => {
+                bidirectional.radiance(camera, ray, world, lights, max_depth, sampler)
+            }
+            Self::Direct(direct) => direct.radiance(camera, ray, world, lights, max_depth, sampler),
+            Self::Spectral(spectral) => {
+                spectral.radiance(camera, ray, world, lights, max_depth, sampler)
+            }
+            Self::Normals(normals) => {
+                normals.radiance(camera, ray, world, lights, max_depth, sampler)
+            }
+            Self::AmbientOcclusion(occlusion) => {
+                occlusion.radiance(camera, ray, world, lights, max_depth, sampler)
+            }
+            Self::Debug(mode) => Camera::debug_color(*mode, ray, world, sampler),
+        }
+    }
+}
+
+/// Point on a light considered by [`Camera::resample_light`].
+#[derive(Debug, Default, Clone, Copy)]
+struct LightCandidate {
+    /// Unit direction from the shaded point to the light.
+    direction:      Vec3,
+    distance:       Float,
+    emitted:        Vec3,
+    scattering_pdf: Float,
+    /// Cosine at the light over the squared distance, converting densities
+    /// per area of the light to densities per solid angle.
+    geometry:       Float,
+}
+
+impl LightCandidate {
+    /// Candidate for the point `light` on a light, `None` if it sends no
+    /// light that `hit` scatters along `ray`.
+    fn new<M: Scatter, E: Scatter>(
+        camera: &Camera,
+        ray: &Ray,
+        hit: &HitRecord<M>,
+        light: &HitRecord<E>,
+    ) -> Option<Self> {
+        let to_light = light.point() - hit.point();
+        let distance = to_light.length();
+        if distance <= 0.0 {
+            return None;
+        }
+        let direction = to_light / distance;
+        let cosine = light.normal().dot(-direction);
+        let emitted = light
+            .material()
+            .emit()
+            .filter(|_| light.material().emission_sides().includes(cosine > 0.0))?;
+
+        let scattered = hit.spawn_ray(direction, ray.time());
+        let scattering_pdf = camera.scattering_pdf(ray, hit, &scattered);
+        let geometry = cosine.abs() / (distance * distance);
+        (scattering_pdf > 0.0 && geometry > 0.0).then_some(Self {
+            direction,
+            distance,
+            emitted,
+            scattering_pdf,
+            geometry,
+        })
+    }
+
+    /// Light the candidate brings if visible, per area of the light.
+    fn radiance(&self) -> Vec3 {
+        self.emitted * (self.scattering_pdf * self.geometry)
+    }
+
+    /// Function candidates are resampled proportionally to.
+    fn target(&self) -> Float {
+        self.radiance().luminance()
+    }
+}
+
+/// Maps `t` in `[0, 1]` to a blue, green, red color ramp.
+fn heat_map(t: Float) -> Vec3 {
+    let t = t.clamp(0.0, 1.0);
+    if t < 0.5 {
+        let t = t * 2.0;
+        Vec3::new(0.0, t, 1.0 - t)
+    } else {
+        let t = (t - 0.5) * 2.0;
+        Vec3::new(t, 1.0 - t, 0.0)
+    }
+}
+
+/// Multiple importance sampling weight of a sample drawn with density `pdf`
+/// against the alternative strategy with density `other_pdf`.
+pub(crate) fn power_heuristic(pdf: Float, other_pdf: Float) -> Float {
+    let pdf_squared = pdf * pdf;
+    pdf_squared / (pdf_squared + other_pdf * other_pdf)
+}
+
+/// Fraction of the light along `ray` over `ray_t` crossing `fog`, which
+/// fills the bounds of `world` unless it has bounds of its own.
+fn transmittance<T: Hit + ?Sized>(
+    fog: Option<Fog>,
+    world: &T,
+    ray: &Ray,
+    ray_t: Interval,
+) -> Float {
+    fog.map_or(1.0, |fog| fog.transmittance(ray, ray_t, world.aabb()))
+}
+
+/// Scales `radiance` down so that its largest channel is at most `max`,
+/// preserving its hue.
+fn clamp_radiance(radiance: Vec3, max: Float) -> Vec3 {
+    radiance * clamp_factor(radiance, max)
+}
+
+/// Factor scaling `radiance` down so that no channel exceeds `max`.
+fn clamp_factor(radiance: Vec3, max: Float) -> Float {
+    let largest = radiance.x().max(radiance.y()).max(radiance.z());
+    if largest > max { max / largest } else { 1.0 }
 }
@@ -1,6 +1,6 @@
-use crate::{interval::Interval, vec3::Vec3};
+use crate::{Float, interval::Interval, vec3::Vec3};
 
-fn linear_to_gamma(linear_component: f64) -> f64 {
+fn linear_to_gamma(linear_component: Float) -> Float {
     if linear_component > 0.0 {
         linear_component.sqrt()
     } else {
@@ -9,6 +9,11 @@ fn linear_to_gamma(linear_component: f64) -> f64 {
 }
 
 impl Vec3 {
+    /// Relative luminance of a linear sRGB color.
+    pub const fn luminance(self) -> Float {
+        0.2126 * self.x() + 0.7152 * self.y() + 0.0722 * self.z()
+    }
+
     pub fn to_color(self) -> (u8, u8, u8) {
         let r = linear_to_gamma(self.x());
         let g = linear_to_gamma(self.y());
@@ -0,0 +1,61 @@
+use std::{fmt, io};
+
+#[cfg(feature = "parallel")]
+use rayon::ThreadPoolBuildError;
+
+/// Errors of the fallible rendering and scene building APIs.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing a file or image failed.
+    Io(io::Error),
+    /// A parameter is out of its valid range, described by the message.
+    InvalidParameter(&'static str),
+    /// The rendering threads could not be started.
+    #[cfg(feature = "parallel")]
+    ThreadPool(ThreadPoolBuildError),
+    /// No GPU is available or it failed to run the renderer, described by
+    /// the message.
+    #[cfg(feature = "gpu")]
+    Gpu(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{err}"),
+            Self::InvalidParameter(msg) => write!(f, "invalid parameter: {msg}"),
+            #[cfg(feature = "parallel")]
+            Self::ThreadPool(err) => write!(f, "{err}"),
+            #[cfg(feature = "gpu")]
+            Self::Gpu(msg) => write!(f, "gpu: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::InvalidParameter(_) => None,
+            #[cfg(feature = "parallel")]
+            Self::ThreadPool(err) => Some(err),
+            #[cfg(feature = "gpu")]
+            Self::Gpu(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+#[cfg(feature = "parallel")]
+impl From<ThreadPoolBuildError> for Error {
+    fn from(err: ThreadPoolBuildError) -> Self {
+        Self::ThreadPool(err)
+    }
+}
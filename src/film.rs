@@ -0,0 +1,250 @@
+use std::io::{self, BufWriter, Write};
+
+use crate::{hit::ObjectId, integrator::LightPath, vec3::Vec3};
+
+/// Linear RGB image, stored row by row starting at the top.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    width:  u32,
+    height: u32,
+    pixels: Vec<Vec3>,
+}
+
+/// Output of a render: the beauty image together with first-hit auxiliary
+/// passes, as used by denoisers and compositing, the variance and sample
+/// count of each pixel, and the beauty image split by [`LightPath`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    beauty:       Image,
+    albedo:       Image,
+    normal:       Image,
+    depth:        Image,
+    ids:          Vec<Option<ObjectId>>,
+    variance:     Image,
+    sample_count: Image,
+    light_paths:  [Image; LightPath::ALL.len()],
+}
+
+impl Image {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Vec3::default(); width as usize * height as usize],
+        }
+    }
+
+    pub const fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub const fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[Vec3] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [Vec3] {
+        &mut self.pixels
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> Vec3 {
+        self.pixels[self.index(x, y)]
+    }
+
+    pub fn set(&mut self, x: u32, y: u32, color: Vec3) {
+        let index = self.index(x, y);
+        self.pixels[index] = color;
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        y as usize * self.width as usize + x as usize
+    }
+
+    /// Returns a new image with `f` applied to every pixel.
+    pub fn map(&self, f: impl Fn(Vec3) -> Vec3) -> Self {
+        Self {
+            width:  self.width,
+            height: self.height,
+            pixels: self.pixels.iter().map(|&p| f(p)).collect(),
+        }
+    }
+
+    /// Gamma encoded 8-bit RGBA bytes with opaque alpha, row by row from the
+    /// top, the layout of a canvas `ImageData`.
+    pub fn to_rgba8(&self) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|pixel| {
+                let (r, g, b) = pixel.to_color();
+                [r, g, b, 255]
+            })
+            .collect()
+    }
+
+    /// Writes the image as a gamma encoded ASCII PPM.
+    pub fn write_ppm<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut w = BufWriter::new(writer);
+        writeln!(w, "P3\n{} {}\n255", self.width, self.height)?;
+        for pixel in &self.pixels {
+            let (r, g, b) = pixel.to_color();
+            writeln!(w, "{} {} {}", r, g, b)?;
+        }
+        w.flush()
+    }
+
+    /// Writes the image as a linear, little endian PFM, preserving values
+    /// outside `[0, 1]`.
+    pub fn write_pfm<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut w = BufWriter::new(writer);
+        write!(w, "PF\n{} {}\n-1.0\n", self.width, self.height)?;
+        for row in self.pixels.chunks(self.width as usize).rev() {
+            for pixel in row {
+                for c in [pixel.x(), pixel.y(), pixel.z()] {
+                    #[allow(clippy::unnecessary_cast, reason = "`Float` may be `f32`")]
+                    w.write_all(&(c as f32).to_le_bytes())?;
+                }
+            }
+        }
+        w.flush()
+    }
+}
+
+impl Frame {
+    pub fn new(
+        beauty: Image,
+        albedo: Image,
+        normal: Image,
+        depth: Image,
+        ids: Vec<Option<ObjectId>>,
+    ) -> Self {
+        assert_eq!(ids.len(), beauty.pixels().len());
+        let blank = || Image::new(beauty.width(), beauty.height());
+        Self {
+            variance: blank(),
+            sample_count: blank(),
+            light_paths: LightPath::ALL.map(|_| blank()),
+            beauty,
+            albedo,
+            normal,
+            depth,
+            ids,
+        }
+    }
+
+    /// Sets the variance and sample count passes, black by default.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a pass is not the size of the beauty image.
+    pub fn with_sample_stats(mut self, variance: Image, sample_count: Image) -> Self {
+        self.assert_size(&variance);
+        self.assert_size(&sample_count);
+        self.variance = variance;
+        self.sample_count = sample_count;
+        self
+    }
+
+    /// Sets the passes of each of [`LightPath::ALL`], black by default.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a pass is not the size of the beauty image.
+    pub fn with_light_paths(mut self, light_paths: [Image; LightPath::ALL.len()]) -> Self {
+        for image in &light_paths {
+            self.assert_size(image);
+        }
+        self.light_paths = light_paths;
+        self
+    }
+
+    fn assert_size(&self, image: &Image) {
+        assert_eq!(
+            (image.width(), image.height()),
+            (self.beauty.width(), self.beauty.height()),
+            "pass size differs from the beauty image"
+        );
+    }
+
+    pub const fn beauty(&self) -> &Image {
+        &self.beauty
+    }
+
+    /// Reflectance of the first surface seen through each pixel.
+    pub const fn albedo(&self) -> &Image {
+        &self.albedo
+    }
+
+    /// Shading normal, in world space, of the first surface seen through
+    /// each pixel.
+    pub const fn normal(&self) -> &Image {
+        &self.normal
+    }
+
+    /// Distance from the camera to the closest surface seen through each
+    /// pixel, stored in every channel. Pixels that only see the background
+    /// are infinitely far.
+    pub const fn depth(&self) -> &Image {
+        &self.depth
+    }
+
+    /// Variance of the value of each pixel in every channel, how far it may
+    /// be off its converged value: the spread of its samples over one less
+    /// than [`Frame::sample_count`]. Zero for pixels with less than two
+    /// effective samples, whose variance can't be estimated; their sample
+    /// count tells them apart.
+    pub const fn variance(&self) -> &Image {
+        &self.variance
+    }
+
+    /// Effective number of samples of each pixel, stored in every channel:
+    /// the squared sum of their weights over the sum of the squared weights.
+    /// Camera samples all weigh the same, so it is the number of samples
+    /// taken.
+    pub const fn sample_count(&self) -> &Image {
+        &self.sample_count
+    }
+
+    /// Part of the beauty image that reached the camera along `path`. Only
+    /// the render modes that path trace, all but the bidirectional and
+    /// debug ones, split the light, the others leaving every pass black.
+    pub const fn light_path(&self, path: LightPath) -> &Image {
+        &self.light_paths[path as usize]
+    }
+
+    /// Id of the closest object seen through each pixel, row by row.
+    pub fn ids(&self) -> &[Option<ObjectId>] {
+        &self.ids
+    }
+
+    /// Color coded ID pass, each object drawn with its [`ObjectId::color`].
+    pub fn id_image(&self) -> Image {
+        self.id_pass(|id| id.map_or(Vec3::default(), ObjectId::color))
+    }
+
+    /// Selection mask that is white where `id` is the closest object.
+    pub fn id_matte(&self, id: ObjectId) -> Image {
+        let white = Vec3::new(1.0, 1.0, 1.0);
+        self.id_pass(|pixel| {
+            if pixel == Some(id) {
+                white
+            } else {
+                Vec3::default()
+            }
+        })
+    }
+
+    fn id_pass(&self, f: impl Fn(Option<ObjectId>) -> Vec3) -> Image {
+        let mut image = Image::new(self.beauty.width(), self.beauty.height());
+        for (pixel, &id) in image.pixels_mut().iter_mut().zip(&self.ids) {
+            *pixel = f(id);
+        }
+        image
+    }
+
+    pub fn into_beauty(self) -> Image {
+        self.beauty
+    }
+}
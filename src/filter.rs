@@ -0,0 +1,208 @@
+use crate::{
+    Float, consts::PI, interval::Interval, pdf::Distribution1D, sampler::Sampler, vec3::Vec3,
+};
+
+/// Number of pieces of the table offsets along each axis are sampled from.
+const TABLE_SIZE: usize = 256;
+
+/// Pixel reconstruction filter.
+///
+/// Camera samples are placed around the pixel center with a density
+/// proportional to the magnitude of the filter, so that every sample counts
+/// the same. Samples in the negative lobes of a [`Filter::Mitchell`] count
+/// negatively instead, and the final pixel is the average of its samples.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Filter {
+    Box {
+        radius: Float,
+    },
+    Tent {
+        radius: Float,
+    },
+    Gaussian {
+        radius: Float,
+        alpha:  Float,
+    },
+    Mitchell {
+        radius: Float,
+        b:      Float,
+        c:      Float,
+    },
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Self::box_filter(0.5)
+    }
+}
+
+impl Filter {
+    pub const fn box_filter(radius: Float) -> Self {
+        Self::Box { radius }
+    }
+
+    pub const fn tent(radius: Float) -> Self {
+        Self::Tent { radius }
+    }
+
+    pub const fn gaussian(radius: Float, alpha: Float) -> Self {
+        Self::Gaussian { radius, alpha }
+    }
+
+    /// Mitchell-Netravali filter with the recommended `B = C = 1/3`.
+    pub const fn mitchell(radius: Float) -> Self {
+        Self::Mitchell {
+            radius,
+            b: 1.0 / 3.0,
+            c: 1.0 / 3.0,
+        }
+    }
+
+    pub const fn radius(&self) -> Float {
+        match *self {
+            Self::Box { radius }
+            | Self::Tent { radius }
+            | Self::Gaussian { radius, .. }
+            | Self::Mitchell { radius, .. } => radius,
+        }
+    }
+
+    /// Filter weight for a sample at offset `(x, y)` from the pixel center.
+    pub fn evaluate(&self, x: Float, y: Float) -> Float {
+        self.evaluate_1d(x) * self.evaluate_1d(y)
+    }
+
+    fn evaluate_1d(&self, x: Float) -> Float {
+        let x = x.abs();
+        match *self {
+            Self::Box { radius } => {
+                if x <= radius {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Self::Tent { radius } => (radius - x).max(0.0),
+            Self::Gaussian { radius, alpha } => {
+                let gaussian = |x: Float| (-alpha * x * x).exp();
+                (gaussian(x) - gaussian(radius)).max(0.0) * (alpha / PI).sqrt()
+            }
+            Self::Mitchell { radius, b, c } => mitchell_1d(2.0 * x / radius, b, c),
+        }
+    }
+}
+
+fn mitchell_1d(x: Float, b: Float, c: Float) -> Float {
+    let x = x.abs();
+    if x > 2.0 {
+        0.0
+    } else if x > 1.0 {
+        ((-b - 6.0 * c) * x * x * x
+            + (6.0 * b + 30.0 * c) * x * x
+            + (-12.0 * b - 48.0 * c) * x
+            + (8.0 * b + 24.0 * c))
+            / 6.0
+    } else {
+        ((12.0 - 9.0 * b - 6.0 * c) * x * x * x
+            + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+            + (6.0 - 2.0 * b))
+            / 6.0
+    }
+}
+
+/// [`Filter`] together with the tables its offsets are sampled from. Only the
+/// filter is serialized.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "Filter", into = "Filter")
+)]
+pub(crate) struct FilterSampler {
+    filter: Filter,
+    /// Density over the support along one axis, proportional to the
+    /// magnitude of the filter. `None` for box filters, sampled uniformly.
+    table:  Option<Distribution1D>,
+    /// Weight of a sample along one axis where the filter is positive, the
+    /// integral of its magnitude over its integral.
+    weight: Float,
+}
+
+impl FilterSampler {
+    pub(crate) fn new(filter: Filter) -> Self {
+        if let Filter::Box { .. } = filter {
+            return Self {
+                filter,
+                table: None,
+                weight: 1.0,
+            };
+        }
+
+        let radius = filter.radius();
+        let func: Vec<_> = (0..TABLE_SIZE)
+            .map(|piece| filter.evaluate_1d(piece_center(piece, radius)))
+            .collect();
+        let integral: Float = func.iter().sum();
+        let table = Distribution1D::new(func);
+        // Filters integrating to zero or less can't be normalized, their
+        // samples count for nothing.
+        let weight = if integral > 0.0 {
+            table.integral() * TABLE_SIZE as Float / integral
+        } else {
+            0.0
+        };
+        Self {
+            filter,
+            table: Some(table),
+            weight,
+        }
+    }
+
+    pub(crate) const fn filter(&self) -> Filter {
+        self.filter
+    }
+
+    /// Samples an offset from the pixel center, returning it with its weight.
+    /// The average of sample values times their weights converges to the
+    /// filtered pixel.
+    pub(crate) fn sample(&self, sampler: &mut dyn Sampler) -> (Vec3, Float) {
+        let radius = self.filter.radius();
+        let Some(table) = &self.table else {
+            let range = Interval::new(-radius, radius);
+            let offset = Vec3::new(sampler.next_range(range), sampler.next_range(range), 0.0);
+            return (offset, 1.0);
+        };
+
+        let mut axis = || {
+            let (x, _, piece) = table.sample(sampler.next_1d());
+            // The table holds the filter at the center of each piece.
+            let sign = if self.filter.evaluate_1d(piece_center(piece, radius)) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+            ((2.0 * x - 1.0) * radius, sign * self.weight)
+        };
+        let (x, weight_x) = axis();
+        let (y, weight_y) = axis();
+        (Vec3::new(x, y, 0.0), weight_x * weight_y)
+    }
+}
+
+/// Offset of the center of table piece `piece` over a support of `radius`.
+fn piece_center(piece: usize, radius: Float) -> Float {
+    ((piece as Float + 0.5) / TABLE_SIZE as Float * 2.0 - 1.0) * radius
+}
+
+impl From<Filter> for FilterSampler {
+    fn from(filter: Filter) -> Self {
+        Self::new(filter)
+    }
+}
+
+impl From<FilterSampler> for Filter {
+    fn from(sampler: FilterSampler) -> Self {
+        sampler.filter
+    }
+}
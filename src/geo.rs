@@ -1,33 +1,50 @@
 use crate::{
+    Float,
     aabb::Aabb,
-    hit::{Hit, HitRecord},
+    consts::PI,
+    error::{Error, Result},
+    hit::{Hit, HitList, HitRecord, Sides},
     interval::Interval,
-    material::Scatter,
+    material::{Material, Scatter},
     ray::Ray,
-    vec3::Vec3,
+    sampler::Sampler,
+    stats::{self, Counter},
+    vec2::Vec2,
+    vec3::{Onb, Vec3},
 };
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Sphere<T> {
     center: Vec3,
-    radius: f64,
+    motion: Vec3,
+    radius: Float,
 
     bbox:     Aabb,
     material: T,
+    #[cfg_attr(feature = "serde", serde(default))]
+    sides:    Sides,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Triangle<T> {
     a: Vec3,
     b: Vec3,
     c: Vec3,
 
     normal:   Vec3,
+    area:     Float,
     bbox:     Aabb,
     material: T,
+    #[cfg_attr(feature = "serde", serde(default))]
+    sides:    Sides,
+    #[cfg_attr(feature = "serde", serde(default = "default_triangle_uvs"))]
+    uvs:      [Vec2; 3],
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Quad<T> {
     origin: Vec3,
     u:      Vec3,
@@ -35,8 +52,209 @@ pub struct Quad<T> {
     w:      Vec3,
 
     normal:   Vec3,
+    area:     Float,
     bbox:     Aabb,
     material: T,
+    #[cfg_attr(feature = "serde", serde(default))]
+    sides:    Sides,
+}
+
+/// Flat disk of `radius` around `center`, facing along `normal`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Disk<T> {
+    center: Vec3,
+    normal: Vec3,
+    radius: Float,
+
+    bbox:     Aabb,
+    material: T,
+    #[cfg_attr(feature = "serde", serde(default))]
+    sides:    Sides,
+}
+
+/// Solid angles, in steradians, between which rectangles are sampled by
+/// solid angle. Smaller ones lose precision, and larger ones only occur on
+/// the plane of the rectangle, so both are sampled by area instead.
+const SPHERICAL_SAMPLING_RANGE: [Float; 2] = [3e-4, 6.22];
+
+/// Smallest `1 - cos` of the half angle of a cone sampled uniformly. The
+/// edges of narrower ones are lost to rounding, so the spheres seen under
+/// them are sampled by area instead.
+const MIN_CONE_SIZE: Float = 16.0 * Float::EPSILON;
+
+/// Largest cosine between the edges of a quad treated as a rectangle.
+const RECTANGLE_TOLERANCE: Float = 1e-4;
+
+/// Segments of the fan triangulating a disk.
+const DISK_SEGMENTS: usize = 32;
+
+/// Axis aligned box with opposite corners `a` and `b`, made of six quads.
+pub fn cuboid(a: Vec3, b: Vec3, material: Material) -> HitList {
+    let mut sides = HitList::new();
+
+    let min = a.min(b);
+    let max = a.max(b);
+
+    let dx = Vec3::new(max.x() - min.x(), 0.0, 0.0);
+    let dy = Vec3::new(0.0, max.y() - min.y(), 0.0);
+    let dz = Vec3::new(0.0, 0.0, max.z() - min.z());
+
+    sides.push(Quad::new(
+        Vec3::new(min.x(), min.y(), max.z()),
+        dx,
+        dy,
+        material,
+    ));
+    sides.push(Quad::new(
+        Vec3::new(max.x(), min.y(), max.z()),
+        -dz,
+        dy,
+        material,
+    ));
+    sides.push(Quad::new(
+        Vec3::new(max.x(), min.y(), min.z()),
+        -dx,
+        dy,
+        material,
+    ));
+    sides.push(Quad::new(
+        Vec3::new(min.x(), min.y(), min.z()),
+        dz,
+        dy,
+        material,
+    ));
+    sides.push(Quad::new(
+        Vec3::new(min.x(), max.y(), max.z()),
+        dx,
+        -dz,
+        material,
+    ));
+    sides.push(Quad::new(
+        Vec3::new(min.x(), min.y(), min.z()),
+        dx,
+        dz,
+        material,
+    ));
+
+    sides
+}
+
+/// Texture coordinates of the vertices of triangles without their own.
+const TRIANGLE_UVS: [Vec2; 3] = [
+    Vec2::new(0.0, 0.0),
+    Vec2::new(1.0, 0.0),
+    Vec2::new(0.0, 1.0),
+];
+
+#[cfg(feature = "serde")]
+const fn default_triangle_uvs() -> [Vec2; 3] {
+    TRIANGLE_UVS
+}
+
+/// Power of a diffuse emitter of `area` with `material`.
+fn emitted_power<T: Scatter>(material: &T, area: Float) -> Float {
+    material
+        .emit()
+        .map_or(0.0, |emitted| PI * emitted.luminance() * area)
+}
+
+/// Texture coordinates of the point of a sphere with outward `normal`, `u`
+/// following the longitude around `+y` and `v` going from the bottom to the
+/// top.
+fn sphere_uv(normal: Vec3) -> Vec2 {
+    let theta = (-normal.y()).clamp(-1.0, 1.0).acos();
+    let phi = Float::atan2(-normal.z(), normal.x()) + PI;
+    Vec2::new(phi / (2.0 * PI), theta / PI)
+}
+
+/// Converts the area density of sampling the point hit by `direction` at
+/// distance `t` into a solid angle density as seen from the ray origin.
+fn area_to_solid_angle(direction: Vec3, t: Float, normal: Vec3, area: Float) -> Float {
+    let distance_squared = t * t * direction.length_squared();
+    let cosine = (direction.dot(normal) / direction.length()).abs();
+    distance_squared / (cosine * area)
+}
+
+/// Rectangle as seen from a point, the spherical rectangle of Ureña et al.,
+/// "An Area-Preserving Parametrization for Spherical Rectangles" (2013),
+/// sampled uniformly over its solid angle.
+struct SphericalRectangle {
+    origin:      Vec3,
+    frame:       [Vec3; 3],
+    x:           [Float; 2],
+    y:           [Float; 2],
+    z0:          Float,
+    b:           [Float; 2],
+    k:           Float,
+    solid_angle: Float,
+}
+
+impl SphericalRectangle {
+    /// Rectangle with `corner` and perpendicular edges `u` and `v` seen from
+    /// `origin`, if its solid angle is in [`SPHERICAL_SAMPLING_RANGE`].
+    fn new(origin: Vec3, corner: Vec3, u: Vec3, v: Vec3) -> Option<Self> {
+        let (width, height) = (u.length(), v.length());
+        let (x, y) = (u / width, v / height);
+        let mut z = x.cross(y);
+
+        let d = corner - origin;
+        let mut z0 = d.dot(z);
+        if z0 > 0.0 {
+            z = -z;
+            z0 = -z0;
+        }
+        let (x0, y0) = (d.dot(x), d.dot(y));
+        let (x1, y1) = (x0 + width, y0 + height);
+
+        // Normals of the planes through the origin and each edge.
+        let n0 = Vec3::new(0.0, z0, -y0).unit();
+        let n1 = Vec3::new(-z0, 0.0, x1).unit();
+        let n2 = Vec3::new(0.0, -z0, y1).unit();
+        let n3 = Vec3::new(z0, 0.0, -x0).unit();
+        let angle = |a: Vec3, b: Vec3| (-a.dot(b)).clamp(-1.0, 1.0).acos();
+        let g = [angle(n0, n1), angle(n1, n2), angle(n2, n3), angle(n3, n0)];
+        let k = 2.0 * PI - g[2] - g[3];
+        let solid_angle = g[0] + g[1] - k;
+
+        let [min, max] = SPHERICAL_SAMPLING_RANGE;
+        (solid_angle.is_finite() && solid_angle > min && solid_angle < max).then_some(Self {
+            origin,
+            frame: [x, y, z],
+            x: [x0, x1],
+            y: [y0, y1],
+            z0,
+            b: [n0.z(), n2.z()],
+            k,
+            solid_angle,
+        })
+    }
+
+    /// Point of the rectangle at `(s, t)` in `[0, 1)²` of its solid angle.
+    fn sample(&self, s: Float, t: Float) -> Vec3 {
+        let [x0, x1] = self.x;
+        let [y0, y1] = self.y;
+        let [b0, b1] = self.b;
+        let z0 = self.z0;
+
+        let au = s * self.solid_angle + self.k;
+        let fu = (au.cos() * b0 - b1) / au.sin();
+        let cu = ((fu * fu + b0 * b0).sqrt().recip() * fu.signum()).clamp(-1.0, 1.0);
+        let xu = (-(cu * z0) / (1.0 - cu * cu).sqrt()).clamp(x0, x1);
+
+        let d = (xu * xu + z0 * z0).sqrt();
+        let h0 = y0 / (d * d + y0 * y0).sqrt();
+        let h1 = y1 / (d * d + y1 * y1).sqrt();
+        let hv = h0 + t * (h1 - h0);
+        let yv = if hv * hv < 1.0 - Float::EPSILON {
+            (hv * d / (1.0 - hv * hv).sqrt()).clamp(y0, y1)
+        } else {
+            y1
+        };
+
+        let [x, y, z] = self.frame;
+        self.origin + x * xu + y * yv + z * z0
+    }
 }
 
 impl<T> Quad<T> {
@@ -48,6 +266,7 @@ impl<T> Quad<T> {
         let n = u.cross(v);
         let normal = n.unit();
         let w = n / n.dot(n);
+        let area = n.length();
 
         Self {
             origin,
@@ -56,22 +275,206 @@ impl<T> Quad<T> {
             w,
             bbox,
             normal,
+            area,
+            material,
+            sides: Sides::Both,
+        }
+    }
+
+    /// Sets which sides of the quad rays can hit, [`Sides::Front`] making it
+    /// invisible from behind. Defaults to both.
+    pub const fn with_sides(mut self, sides: Sides) -> Self {
+        self.sides = sides;
+        self
+    }
+
+    /// Corner the edges `u` and `v` start from.
+    pub const fn origin(&self) -> Vec3 {
+        self.origin
+    }
+
+    pub const fn u(&self) -> Vec3 {
+        self.u
+    }
+
+    pub const fn v(&self) -> Vec3 {
+        self.v
+    }
+
+    pub const fn sides(&self) -> Sides {
+        self.sides
+    }
+
+    pub const fn material(&self) -> T
+    where
+        T: Copy,
+    {
+        self.material
+    }
+
+    /// Whether the edges are perpendicular, making the quad a rectangle that
+    /// light sampling picks points of by solid angle.
+    pub fn is_rectangle(&self) -> bool {
+        self.u.dot(self.v).abs() <= RECTANGLE_TOLERANCE * self.u.length() * self.v.length()
+    }
+
+    /// Spherical rectangle of the quad seen from `origin`, if it is a
+    /// rectangle that can be sampled by solid angle from there.
+    fn spherical(&self, origin: Vec3) -> Option<SphericalRectangle> {
+        self.is_rectangle()
+            .then(|| SphericalRectangle::new(origin, self.origin, self.u, self.v))
+            .flatten()
+    }
+}
+
+impl<T> Disk<T> {
+    /// # Panics
+    ///
+    /// Panics if the disk is degenerate, see [`Disk::try_new`].
+    pub fn new(center: Vec3, normal: Vec3, radius: Float, material: T) -> Self {
+        Self::try_new(center, normal, radius, material).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Disk of `radius` around `center`, its front facing along `normal`.
+    /// Fails unless `radius` is positive and `normal` nonzero, both finite.
+    pub fn try_new(center: Vec3, normal: Vec3, radius: Float, material: T) -> Result<Self> {
+        if !(radius > 0.0 && radius.is_finite()) {
+            return Err(Error::InvalidParameter("disk radius must be positive"));
+        }
+        let length = normal.length();
+        if !(length > 0.0 && length.is_finite()) {
+            return Err(Error::InvalidParameter("disk normal must be nonzero"));
+        }
+        let normal = normal / length;
+
+        // Extent along each axis of a circle in the plane of `normal`.
+        let extent = |n: Float| radius * (1.0 - n * n).max(0.0).sqrt();
+        let extent = Vec3::new(extent(normal.x()), extent(normal.y()), extent(normal.z()));
+        let bbox = Aabb::from_points(center - extent, center + extent);
+
+        Ok(Self {
+            center,
+            normal,
+            radius,
+            bbox,
             material,
+            sides: Sides::Both,
+        })
+    }
+
+    /// Sets which sides of the disk rays can hit, [`Sides::Front`] making it
+    /// invisible from behind. Defaults to both.
+    pub const fn with_sides(mut self, sides: Sides) -> Self {
+        self.sides = sides;
+        self
+    }
+
+    pub const fn center(&self) -> Vec3 {
+        self.center
+    }
+
+    pub const fn normal(&self) -> Vec3 {
+        self.normal
+    }
+
+    pub const fn radius(&self) -> Float {
+        self.radius
+    }
+
+    pub const fn sides(&self) -> Sides {
+        self.sides
+    }
+
+    pub const fn material(&self) -> T
+    where
+        T: Copy,
+    {
+        self.material
+    }
+
+    pub fn area(&self) -> Float {
+        PI * self.radius * self.radius
+    }
+
+    /// In-plane axes the angle of the texture coordinates starts from and
+    /// turns towards, with the normal.
+    fn frame(&self) -> Onb {
+        Onb::new(self.normal)
+    }
+
+    /// Corner and edges of the square circumscribing the disk, which light
+    /// sampling picks points of by solid angle.
+    fn bounding_square(&self) -> (Vec3, Vec3, Vec3) {
+        let frame = self.frame();
+        let (u, v) = (frame.u() * self.radius, frame.v() * self.radius);
+        (self.center - u - v, u * 2.0, v * 2.0)
+    }
+
+    /// Point at `(s, t)` in `[0, 1)²` of the disk, `s` turning around the
+    /// center and `t` spreading out from it so that points are uniform over
+    /// the area.
+    fn point_at(&self, s: Float, t: Float) -> Vec3 {
+        let frame = self.frame();
+        let (phi, r) = (2.0 * PI * s, self.radius * t.sqrt());
+        self.center + (frame.u() * phi.cos() + frame.v() * phi.sin()) * r
+    }
+
+    /// Point where the line from `origin` along `direction` crosses the
+    /// plane of the disk, if ahead of `origin`.
+    fn plane_crossing(&self, origin: Vec3, direction: Vec3) -> Option<Vec3> {
+        let denom = self.normal.dot(direction);
+        if denom.abs() < Float::EPSILON {
+            return None;
         }
+        let t = self.normal.dot(self.center - origin) / denom;
+        (t > 0.001).then(|| origin + direction * t)
     }
 }
 
 impl<T: Scatter> Sphere<T> {
-    pub fn new(center: Vec3, radius: f64, material: T) -> Self {
-        assert!(radius.is_sign_positive());
+    /// # Panics
+    ///
+    /// Panics if `radius` is negative or NaN, see [`Sphere::try_new`].
+    pub fn new(center: Vec3, radius: Float, material: T) -> Self {
+        Self::try_new(center, radius, material).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Sphere of `radius` around `center`, failing if `radius` is negative
+    /// or NaN.
+    pub fn try_new(center: Vec3, radius: Float, material: T) -> Result<Self> {
+        if !radius.is_sign_positive() || radius.is_nan() {
+            return Err(Error::InvalidParameter("sphere radius must be positive"));
+        }
         let rvec = Vec3::new(radius, radius, radius);
         let bbox = Aabb::from_points(center - rvec, center + rvec);
 
-        Self {
+        Ok(Self {
             center,
-            radius: radius.max(0.0),
+            motion: Vec3::default(),
+            radius,
             material,
             bbox,
+            sides: Sides::Both,
+        })
+    }
+
+    /// Sets which sides of the sphere rays can hit, [`Sides::Front`] making
+    /// it invisible from inside. Defaults to both.
+    pub const fn with_sides(mut self, sides: Sides) -> Self {
+        self.sides = sides;
+        self
+    }
+
+    /// Sphere moving linearly from `center0` at time 0 to `center1` at time
+    /// 1.
+    pub fn moving(center0: Vec3, center1: Vec3, radius: Float, material: T) -> Self {
+        let rvec = Vec3::new(radius, radius, radius);
+        let end = Aabb::from_points(center1 - rvec, center1 + rvec);
+        let sphere = Self::new(center0, radius, material);
+        Self {
+            motion: center1 - center0,
+            bbox: sphere.bbox.merge(end),
+            ..sphere
         }
     }
 
@@ -79,7 +482,12 @@ impl<T: Scatter> Sphere<T> {
         self.center
     }
 
-    pub const fn radius(&self) -> f64 {
+    /// Center of the sphere at `time`.
+    pub fn center_at(&self, time: Float) -> Vec3 {
+        self.center + self.motion * time
+    }
+
+    pub const fn radius(&self) -> Float {
         self.radius
     }
 
@@ -87,6 +495,28 @@ impl<T: Scatter> Sphere<T> {
         self.bbox
     }
 
+    /// Axis, cosine of the half angle and solid angle of the cone of
+    /// directions from `origin` that hit the sphere at `time`, if `origin`
+    /// lies outside it. Light sampling picks directions uniformly over that
+    /// cone, unless it is too narrow to do so precisely, see
+    /// [`MIN_CONE_SIZE`].
+    fn visible_cone(&self, origin: Vec3, time: Float) -> Option<(Vec3, Float, Float)> {
+        let oc = self.center_at(time) - origin;
+        let distance_squared = oc.length_squared();
+        let sin_squared = self.radius * self.radius / distance_squared;
+        if sin_squared >= 1.0 {
+            return None;
+        }
+        let cos_max = (1.0 - sin_squared).sqrt();
+        // `1 - cos_max` without the cancellation of small cones.
+        let size = sin_squared / (1.0 + cos_max);
+        (size > MIN_CONE_SIZE).then(|| (oc / distance_squared.sqrt(), cos_max, 2.0 * PI * size))
+    }
+
+    pub const fn sides(&self) -> Sides {
+        self.sides
+    }
+
     pub const fn material(&self) -> T
     where
         T: Copy,
@@ -105,7 +535,9 @@ impl<T> Triangle<T> {
 
         let e1 = b - a;
         let e2 = c - a;
-        let normal = e1.cross(e2).unit();
+        let n = e1.cross(e2);
+        let normal = n.unit();
+        let area = n.length() / 2.0;
 
         Self {
             material,
@@ -113,18 +545,58 @@ impl<T> Triangle<T> {
             b,
             c,
             normal,
+            area,
             bbox,
+            sides: Sides::Both,
+            uvs: TRIANGLE_UVS,
         }
     }
+
+    /// Sets which sides of the triangle rays can hit, the front being the
+    /// one its vertices wind counterclockwise around. [`Sides::Front`] culls
+    /// back faces. Defaults to both.
+    pub const fn with_sides(mut self, sides: Sides) -> Self {
+        self.sides = sides;
+        self
+    }
+
+    /// Sets the texture coordinates of the vertices `a`, `b` and `c`, which
+    /// are interpolated over the triangle. Defaults to `(0, 0)`, `(1, 0)`
+    /// and `(0, 1)`.
+    pub const fn with_uvs(mut self, uvs: [Vec2; 3]) -> Self {
+        self.uvs = uvs;
+        self
+    }
+
+    pub const fn uvs(&self) -> [Vec2; 3] {
+        self.uvs
+    }
+
+    /// Vertices `a`, `b` and `c`.
+    pub const fn vertices(&self) -> [Vec3; 3] {
+        [self.a, self.b, self.c]
+    }
+
+    pub const fn sides(&self) -> Sides {
+        self.sides
+    }
+
+    pub const fn material(&self) -> T
+    where
+        T: Copy,
+    {
+        self.material
+    }
 }
 
 impl<T: Copy + Scatter> Hit for Quad<T> {
     type Material = T;
 
     fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<Self::Material>> {
+        stats::count(Counter::IntersectionTests);
         let denom = self.normal.dot(ray.direction());
 
-        if denom > -f64::EPSILON && denom < f64::EPSILON {
+        if denom > -Float::EPSILON && denom < Float::EPSILON {
             return None;
         }
 
@@ -147,13 +619,99 @@ impl<T: Copy + Scatter> Hit for Quad<T> {
             return None;
         }
 
-        Some(HitRecord::new(
-            intersection_point,
-            self.normal,
-            t,
-            ray,
-            self.material,
-        ))
+        let hit = HitRecord::new(intersection_point, self.normal, t, ray, self.material)
+            .with_uv(Vec2::new(alpha, beta))
+            .with_uv_density(self.area.sqrt().recip());
+        (self.sides.includes(hit.front_face()) && hit.is_opaque(ray)).then_some(hit)
+    }
+
+    fn aabb(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn count(&self) -> usize {
+        1
+    }
+
+    // Rectangles are sampled uniformly over their solid angle when it is
+    // large enough, other quads over their area.
+    fn pdf_value(&self, origin: Vec3, direction: Vec3, _time: Float) -> Float {
+        let ray = Ray::new(origin, direction, 0.0);
+        let Some(hit) = self.hit(&ray, Interval::new(0.001, Float::INFINITY)) else {
+            return 0.0;
+        };
+        match self.spherical(origin) {
+            Some(rectangle) => rectangle.solid_angle.recip(),
+            None => area_to_solid_angle(direction, hit.t(), self.normal, self.area),
+        }
+    }
+
+    fn random_to(&self, origin: Vec3, _time: Float, sampler: &mut dyn Sampler) -> Vec3 {
+        let (s, t) = (sampler.next_1d(), sampler.next_1d());
+        let p = match self.spherical(origin) {
+            Some(rectangle) => rectangle.sample(s, t),
+            None => self.origin + (self.u * s) + (self.v * t),
+        };
+        p - origin
+    }
+
+    fn power(&self) -> Float {
+        emitted_power(&self.material, self.area)
+    }
+
+    fn sample_surface(
+        &self,
+        time: Float,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(HitRecord<Self::Material>, Float)> {
+        let uv = Vec2::new(sampler.next_1d(), sampler.next_1d());
+        let point = self.origin + (self.u * uv.x()) + (self.v * uv.y());
+        let rec = HitRecord::facing(point, self.normal, time, self.material).with_uv(uv);
+        Some((rec, 1.0 / self.area))
+    }
+
+    fn triangulate(&self, triangles: &mut Vec<[Vec3; 3]>) {
+        let (a, b, c, d) = (
+            self.origin,
+            self.origin + self.u,
+            self.origin + self.u + self.v,
+            self.origin + self.v,
+        );
+        triangles.extend([[a, b, c], [a, c, d]]);
+    }
+}
+
+impl<T: Copy + Scatter> Hit for Disk<T> {
+    type Material = T;
+
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<Self::Material>> {
+        stats::count(Counter::IntersectionTests);
+        let denom = self.normal.dot(ray.direction());
+        if denom > -Float::EPSILON && denom < Float::EPSILON {
+            return None;
+        }
+
+        let t = self.normal.dot(self.center - ray.origin()) / denom;
+        if !ray_t.contains(t) {
+            return None;
+        }
+
+        let point = ray.at(t);
+        let offset = point - self.center;
+        let distance_squared = offset.length_squared();
+        if distance_squared > self.radius * self.radius {
+            return None;
+        }
+
+        // `u` follows the angle around the center and `v` the distance out
+        // from it.
+        let frame = self.frame();
+        let phi = Float::atan2(offset.dot(frame.v()), offset.dot(frame.u())).rem_euclid(2.0 * PI);
+        let uv = Vec2::new(phi / (2.0 * PI), distance_squared.sqrt() / self.radius);
+        let hit = HitRecord::new(point, self.normal, t, ray, self.material)
+            .with_uv(uv)
+            .with_uv_density(self.area().sqrt().recip());
+        (self.sides.includes(hit.front_face()) && hit.is_opaque(ray)).then_some(hit)
     }
 
     fn aabb(&self) -> Aabb {
@@ -163,13 +721,77 @@ impl<T: Copy + Scatter> Hit for Quad<T> {
     fn count(&self) -> usize {
         1
     }
+
+    // Directions are sampled uniformly over the solid angle of the bounding
+    // square when it is large enough, the ones missing the disk bringing no
+    // light, and otherwise by area of the disk.
+    fn pdf_value(&self, origin: Vec3, direction: Vec3, _time: Float) -> Float {
+        let (corner, u, v) = self.bounding_square();
+        if let Some(square) = SphericalRectangle::new(origin, corner, u, v) {
+            let inside = self.plane_crossing(origin, direction).is_some_and(|point| {
+                let offset = point - corner;
+                let (s, t) = (offset.dot(u), offset.dot(v));
+                (0.0..=u.length_squared()).contains(&s) && (0.0..=v.length_squared()).contains(&t)
+            });
+            return if inside {
+                square.solid_angle.recip()
+            } else {
+                0.0
+            };
+        }
+
+        let ray = Ray::new(origin, direction, 0.0);
+        match self.hit(&ray, Interval::new(0.001, Float::INFINITY)) {
+            Some(hit) => area_to_solid_angle(direction, hit.t(), self.normal, self.area()),
+            None => 0.0,
+        }
+    }
+
+    fn random_to(&self, origin: Vec3, _time: Float, sampler: &mut dyn Sampler) -> Vec3 {
+        let (s, t) = (sampler.next_1d(), sampler.next_1d());
+        let (corner, u, v) = self.bounding_square();
+        let p = match SphericalRectangle::new(origin, corner, u, v) {
+            Some(square) => square.sample(s, t),
+            None => self.point_at(s, t),
+        };
+        p - origin
+    }
+
+    fn power(&self) -> Float {
+        emitted_power(&self.material, self.area())
+    }
+
+    fn sample_surface(
+        &self,
+        time: Float,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(HitRecord<Self::Material>, Float)> {
+        let (s, t) = (sampler.next_1d(), sampler.next_1d());
+        let point = self.point_at(s, t);
+        let rec = HitRecord::facing(point, self.normal, time, self.material)
+            .with_uv(Vec2::new(s, t.sqrt()));
+        Some((rec, 1.0 / self.area()))
+    }
+
+    fn triangulate(&self, triangles: &mut Vec<[Vec3; 3]>) {
+        let frame = self.frame();
+        let point = |segment: usize| {
+            let phi = 2.0 * PI * segment as Float / DISK_SEGMENTS as Float;
+            self.center + (frame.u() * phi.cos() + frame.v() * phi.sin()) * self.radius
+        };
+        // The frame turns clockwise around the normal.
+        triangles.extend(
+            (0..DISK_SEGMENTS).map(|segment| [self.center, point(segment + 1), point(segment)]),
+        );
+    }
 }
 
 impl<T: Copy + Scatter> Hit for Sphere<T> {
     type Material = T;
 
     fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<Self::Material>> {
-        let cur_center = self.center;
+        stats::count(Counter::IntersectionTests);
+        let cur_center = self.center_at(ray.time());
         let oc = cur_center - ray.origin();
         let a = ray.direction().length_squared();
         let h = ray.direction().dot(oc);
@@ -194,7 +816,11 @@ impl<T: Copy + Scatter> Hit for Sphere<T> {
         let point = ray.at(root);
         let normal = (point - cur_center) / self.radius;
 
-        Some(HitRecord::new(point, normal, root, ray, self.material))
+        let area = 4.0 * PI * self.radius * self.radius;
+        let hit = HitRecord::new(point, normal, root, ray, self.material)
+            .with_uv(sphere_uv(normal))
+            .with_uv_density(area.sqrt().recip());
+        (self.sides.includes(hit.front_face()) && hit.is_opaque(ray)).then_some(hit)
     }
 
     fn aabb(&self) -> Aabb {
@@ -204,53 +830,162 @@ impl<T: Copy + Scatter> Hit for Sphere<T> {
     fn count(&self) -> usize {
         1
     }
+
+    // From outside, directions are sampled uniformly over the cone of the
+    // visible cap. From inside or far away, points are sampled uniformly over
+    // the whole surface, so both points where the line crosses the sphere
+    // could have produced `direction`.
+    fn pdf_value(&self, origin: Vec3, direction: Vec3, time: Float) -> Float {
+        if let Some((axis, cos_max, solid_angle)) = self.visible_cone(origin, time) {
+            return if direction.unit().dot(axis) >= cos_max {
+                1.0 / solid_angle
+            } else {
+                0.0
+            };
+        }
+
+        let center = self.center_at(time);
+        let oc = center - origin;
+        let a = direction.length_squared();
+        let h = direction.dot(oc);
+        let c = oc.length_squared() - self.radius * self.radius;
+        let discriminant = h * h - a * c;
+
+        if discriminant < 0.0 {
+            return 0.0;
+        }
+
+        let dsqrt = discriminant.sqrt();
+        let area = 4.0 * PI * self.radius * self.radius;
+
+        [(h - dsqrt) / a, (h + dsqrt) / a]
+            .into_iter()
+            .filter(|&t| t > 0.001)
+            .map(|t| {
+                let normal = (origin + direction * t - center) / self.radius;
+                area_to_solid_angle(direction, t, normal, area)
+            })
+            .sum()
+    }
+
+    fn random_to(&self, origin: Vec3, time: Float, sampler: &mut dyn Sampler) -> Vec3 {
+        match self.visible_cone(origin, time) {
+            Some((axis, cos_max, _)) => Vec3::random_in_cone(axis, cos_max, sampler),
+            None => self.center_at(time) + Vec3::random_unit(sampler) * self.radius - origin,
+        }
+    }
+
+    fn power(&self) -> Float {
+        let area = 4.0 * PI * self.radius * self.radius;
+        emitted_power(&self.material, area)
+    }
+
+    fn sample_surface(
+        &self,
+        time: Float,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(HitRecord<Self::Material>, Float)> {
+        let normal = Vec3::random_unit(sampler);
+        let point = self.center_at(time) + normal * self.radius;
+        let rec = HitRecord::facing(point, normal, time, self.material).with_uv(sphere_uv(normal));
+        Some((rec, 1.0 / (4.0 * PI * self.radius * self.radius)))
+    }
+
+    /// Latitude-longitude mesh of the sphere at time 0.
+    fn triangulate(&self, triangles: &mut Vec<[Vec3; 3]>) {
+        const RINGS: usize = 8;
+        const SEGMENTS: usize = 16;
+
+        let point = |ring: usize, segment: usize| {
+            let theta = PI * ring as Float / RINGS as Float;
+            let phi = 2.0 * PI * segment as Float / SEGMENTS as Float;
+            let direction = Vec3::new(
+                theta.sin() * phi.cos(),
+                theta.cos(),
+                theta.sin() * phi.sin(),
+            );
+            self.center + direction * self.radius
+        };
+        for ring in 0..RINGS {
+            for segment in 0..SEGMENTS {
+                let a = point(ring, segment);
+                let b = point(ring, segment + 1);
+                let c = point(ring + 1, segment + 1);
+                let d = point(ring + 1, segment);
+                if ring > 0 {
+                    triangles.push([a, b, c]);
+                }
+                if ring + 1 < RINGS {
+                    triangles.push([a, c, d]);
+                }
+            }
+        }
+    }
 }
 
 impl<T: Copy + Scatter> Hit for Triangle<T> {
     type Material = T;
 
-    // https://en.wikipedia.org/wiki/M%C3%B6ller%E2%80%93Trumbore_intersection_algorithm
+    // Watertight intersection, https://jcgt.org/published/0002/01/05/
+    //
+    // The vertices are moved to a space where the ray starts at the origin
+    // and points along `+z`, and the hit is found with 2D edge functions.
+    // Edges shared by two triangles give the same edge function values in
+    // both, so rays along an edge can't slip between them.
     fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<Self::Material>> {
+        stats::count(Counter::IntersectionTests);
         let origin = ray.origin();
         let direction = ray.direction();
+        let to_array = |v: Vec3| [v.x(), v.y(), v.z()];
 
-        let e1 = self.b - self.a;
-        let e2 = self.c - self.a;
+        // Largest component of the direction becomes `z`, keeping the
+        // winding of the other two.
+        let d = to_array(direction);
+        let kz = (0..3)
+            .max_by(|&i, &j| d[i].abs().total_cmp(&d[j].abs()))
+            .unwrap_or(2);
+        let (mut kx, mut ky) = ((kz + 1) % 3, (kz + 2) % 3);
+        if d[kz] < 0.0 {
+            (kx, ky) = (ky, kx);
+        }
 
-        let ray_cross_e2 = direction.cross(e2);
-        let det = e1.dot(ray_cross_e2);
+        let sx = d[kx] / d[kz];
+        let sy = d[ky] / d[kz];
+        let sz = 1.0 / d[kz];
 
-        if det > -f64::EPSILON && det < f64::EPSILON {
-            return None; // This ray is parallel to this self.
-        }
+        let [a, b, c] = [self.a, self.b, self.c].map(|p| to_array(p - origin));
+        let shear = |p: [Float; 3]| (p[kx] - sx * p[kz], p[ky] - sy * p[kz]);
+        let (ax, ay) = shear(a);
+        let (bx, by) = shear(b);
+        let (cx, cy) = shear(c);
 
-        let inv_det = 1.0 / det;
-        let s = origin - self.a;
-        let u = inv_det * s.dot(ray_cross_e2);
-        if u < 0.0 || u > 1.0 {
+        let u = cx * by - cy * bx;
+        let v = ax * cy - ay * cx;
+        let w = bx * ay - by * ax;
+        if (u < 0.0 || v < 0.0 || w < 0.0) && (u > 0.0 || v > 0.0 || w > 0.0) {
             return None;
         }
 
-        let s_cross_e1 = s.cross(e1);
-        let v = inv_det * direction.dot(s_cross_e1);
-        if v < 0.0 || u + v > 1.0 {
-            return None;
+        let det = u + v + w;
+        if det == 0.0 {
+            return None; // This ray is parallel to this triangle.
         }
-        let t = inv_det * e2.dot(s_cross_e1);
 
+        let t = (u * a[kz] + v * b[kz] + w * c[kz]) * sz / det;
         if !ray_t.surrounds(t) {
             return None;
         }
 
         let intersection_point = origin + direction * t;
+        let [uv_a, uv_b, uv_c] = self.uvs;
+        let uv = (uv_a * u + uv_b * v + uv_c * w) / det;
 
-        Some(HitRecord::new(
-            intersection_point,
-            self.normal,
-            t,
-            ray,
-            self.material,
-        ))
+        let (uv_ab, uv_ac) = (uv_b - uv_a, uv_c - uv_a);
+        let uv_area = (uv_ab.x() * uv_ac.y() - uv_ab.y() * uv_ac.x()).abs() / 2.0;
+        let hit = HitRecord::new(intersection_point, self.normal, t, ray, self.material)
+            .with_uv(uv)
+            .with_uv_density((uv_area / self.area).sqrt());
+        (self.sides.includes(hit.front_face()) && hit.is_opaque(ray)).then_some(hit)
     }
 
     fn aabb(&self) -> Aabb {
@@ -260,4 +995,49 @@ impl<T: Copy + Scatter> Hit for Triangle<T> {
     fn count(&self) -> usize {
         1
     }
+
+    fn pdf_value(&self, origin: Vec3, direction: Vec3, _time: Float) -> Float {
+        let ray = Ray::new(origin, direction, 0.0);
+        match self.hit(&ray, Interval::new(0.001, Float::INFINITY)) {
+            Some(hit) => area_to_solid_angle(direction, hit.t(), self.normal, self.area),
+            None => 0.0,
+        }
+    }
+
+    fn random_to(&self, origin: Vec3, _time: Float, sampler: &mut dyn Sampler) -> Vec3 {
+        let mut r1 = sampler.next_1d();
+        let mut r2 = sampler.next_1d();
+        if r1 + r2 > 1.0 {
+            r1 = 1.0 - r1;
+            r2 = 1.0 - r2;
+        }
+        let p = self.a + (self.b - self.a) * r1 + (self.c - self.a) * r2;
+        p - origin
+    }
+
+    fn power(&self) -> Float {
+        emitted_power(&self.material, self.area)
+    }
+
+    fn sample_surface(
+        &self,
+        time: Float,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(HitRecord<Self::Material>, Float)> {
+        let mut r1 = sampler.next_1d();
+        let mut r2 = sampler.next_1d();
+        if r1 + r2 > 1.0 {
+            r1 = 1.0 - r1;
+            r2 = 1.0 - r2;
+        }
+        let point = self.a + (self.b - self.a) * r1 + (self.c - self.a) * r2;
+        let [uv_a, uv_b, uv_c] = self.uvs;
+        let uv = uv_a * (1.0 - r1 - r2) + uv_b * r1 + uv_c * r2;
+        let rec = HitRecord::facing(point, self.normal, time, self.material).with_uv(uv);
+        Some((rec, 1.0 / self.area))
+    }
+
+    fn triangulate(&self, triangles: &mut Vec<[Vec3; 3]>) {
+        triangles.push([self.a, self.b, self.c]);
+    }
 }
@@ -0,0 +1,654 @@
+//! GPU path tracer, available with the `gpu` feature.
+//!
+//! A [`GpuScene`] holds spheres, quads and triangles with their materials.
+//! [`GpuRenderer::render`] uploads them together with a bounding volume
+//! hierarchy built over them and traces every pixel in a WGSL compute shader.
+//! The shader covers the common subset of the CPU renderer: perspective
+//! cameras with a circular lens, box filters, the built-in materials but
+//! isotropic, without textures, cutouts, dielectric priorities or dispersion,
+//! and any background, baked into a latitude/longitude table. Lights are
+//! found by scattered rays only, so small emitters are noisier than on the
+//! CPU.
+//!
+//! [`render_or_cpu`] picks the GPU when there is one and the camera and
+//! scene are supported, and renders on the CPU otherwise.
+
+#![allow(clippy::unnecessary_cast, reason = "`Float` may be `f32`")]
+
+use std::sync::mpsc;
+
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+
+use crate::{
+    Float,
+    aabb::Aabb,
+    aperture::Aperture,
+    background::Background,
+    camera::{Camera, Projection, RenderMode},
+    clock::Stopwatch,
+    consts::PI,
+    error::{Error, Result},
+    film::Image,
+    filter::Filter,
+    geo::{Quad, Sphere, Triangle},
+    hit::{Hit, Sides},
+    material::Material,
+    progress::RenderEvent,
+    sampler::hash_seed,
+    vec3::Vec3,
+};
+
+/// Most primitives in a BVH leaf.
+const LEAF_SIZE: usize = 4;
+/// Resolution of the table backgrounds other than a plain color are baked
+/// into.
+const BACKGROUND_SIZE: [u32; 2] = [512, 256];
+/// Samples per pixel traced by one dispatch, keeping each short enough not
+/// to trip the driver's watchdog.
+const SAMPLES_PER_DISPATCH: u32 = 8;
+/// Side of the square workgroups of the shader.
+const WORKGROUP_SIZE: u32 = 8;
+
+const SPHERE: u32 = 0;
+const QUAD: u32 = 1;
+const TRIANGLE: u32 = 2;
+
+const LAMBERTIAN: u32 = 0;
+const METAL: u32 = 1;
+const DIELECTRIC: u32 = 2;
+const DIFFUSE_LIGHT: u32 = 3;
+
+/// Connection to a GPU with the path tracer compiled for it.
+#[derive(Debug)]
+pub struct GpuRenderer {
+    device:   wgpu::Device,
+    queue:    wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+    adapter:  String,
+}
+
+/// Spheres, quads and triangles laid out for [`GpuRenderer::render`].
+#[derive(Debug, Default, Clone)]
+pub struct GpuScene {
+    primitives:  Vec<GpuPrimitive>,
+    bounds:      Vec<Aabb>,
+    materials:   Vec<GpuMaterial>,
+    /// Whether a material uses an option the shader leaves out.
+    unsupported: bool,
+}
+
+/// Sphere (center and radius in `a`, motion in `b`), quad (origin and edges)
+/// or triangle (vertices).
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, Pod, Zeroable)]
+struct GpuPrimitive {
+    a:        [f32; 4],
+    b:        [f32; 4],
+    c:        [f32; 4],
+    kind:     u32,
+    material: u32,
+    sides:    u32,
+    _pad:     u32,
+}
+
+/// Albedo or emitted color, with the fuzz of metals or the index of
+/// refraction of dielectrics in the last component.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, Pod, Zeroable)]
+struct GpuMaterial {
+    color: [f32; 4],
+    kind:  u32,
+    sides: u32,
+    _pad:  [u32; 2],
+}
+
+/// BVH node in depth-first order. Leaves hold `count` primitives from
+/// `left_first`; interior nodes have a `count` of zero, their left child
+/// right after them and their right child at `left_first`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, Pod, Zeroable)]
+struct GpuNode {
+    min:        [f32; 3],
+    left_first: u32,
+    max:        [f32; 3],
+    count:      u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, Pod, Zeroable)]
+struct Params {
+    center:          [f32; 4],
+    pixel00:         [f32; 4],
+    delta_u:         [f32; 4],
+    delta_v:         [f32; 4],
+    disk_u:          [f32; 4],
+    disk_v:          [f32; 4],
+    width:           u32,
+    height:          u32,
+    sample_start:    u32,
+    sample_count:    u32,
+    max_depth:       u32,
+    seed:            u32,
+    background_size: [u32; 2],
+    filter_radius:   f32,
+    shutter_open:    f32,
+    shutter_close:   f32,
+    max_radiance:    f32,
+    indirect_clamp:  f32,
+    _pad:            [u32; 3],
+}
+
+fn gpu_vec(v: Vec3, w: Float) -> [f32; 4] {
+    [v.x() as f32, v.y() as f32, v.z() as f32, w as f32]
+}
+
+fn gpu_error(err: impl ToString) -> Error {
+    Error::Gpu(err.to_string())
+}
+
+/// Whether the shader renders `material` like the CPU does, which it
+/// can't with a texture or an opacity texture, nor for dielectrics nested
+/// by priority, as it doesn't track which ones a path is inside, or
+/// dispersive ones, as it traces a single wavelength. It has no isotropic
+/// phase function either.
+fn gpu_supports(material: Material) -> bool {
+    match material {
+        Material::Lambertian(lambertian) => {
+            lambertian.texture().is_none() && lambertian.opacity().is_none()
+        }
+        Material::Dielectric(dielectric) => {
+            dielectric.priority() == 0 && dielectric.dispersion().is_none()
+        }
+        Material::Isotropic(_) => false,
+        Material::Metal(_) | Material::DiffuseLight(_) => true,
+    }
+}
+
+const fn gpu_sides(sides: Sides) -> u32 {
+    match sides {
+        Sides::Front => 0,
+        Sides::Both => 1,
+    }
+}
+
+impl GpuMaterial {
+    fn new(material: Material) -> Self {
+        let (color, kind, sides) = match material {
+            Material::Lambertian(lambertian) => {
+                (gpu_vec(lambertian.albedo(), 0.0), LAMBERTIAN, Sides::Both)
+            }
+            Material::Metal(metal) => (gpu_vec(metal.albedo(), metal.fuzz()), METAL, Sides::Both),
+            Material::Dielectric(dielectric) => (
+                gpu_vec(Vec3::new(1.0, 1.0, 1.0), dielectric.refraction()),
+                DIELECTRIC,
+                Sides::Both,
+            ),
+            Material::DiffuseLight(light) => {
+                (gpu_vec(light.color(), 0.0), DIFFUSE_LIGHT, light.sides())
+            }
+            // Never rendered, see `gpu_supports`.
+            Material::Isotropic(isotropic) => {
+                (gpu_vec(isotropic.albedo(), 0.0), LAMBERTIAN, Sides::Both)
+            }
+        };
+        Self {
+            color,
+            kind,
+            sides: gpu_sides(sides),
+            _pad: [0; 2],
+        }
+    }
+}
+
+impl GpuNode {
+    fn new(bbox: Aabb, left_first: u32, count: u32) -> Self {
+        Self {
+            min: [bbox.x().min(), bbox.y().min(), bbox.z().min()].map(|x| x as f32),
+            left_first,
+            max: [bbox.x().max(), bbox.y().max(), bbox.z().max()].map(|x| x as f32),
+            count,
+        }
+    }
+}
+
+impl GpuScene {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Scene made of `spheres`.
+    pub fn from_spheres(spheres: &[Sphere<Material>]) -> Self {
+        let mut scene = Self::new();
+        for sphere in spheres {
+            scene.push_sphere(sphere);
+        }
+        scene
+    }
+
+    pub fn push_sphere(&mut self, sphere: &Sphere<Material>) {
+        let motion = sphere.center_at(1.0) - sphere.center();
+        self.push(
+            SPHERE,
+            [
+                gpu_vec(sphere.center(), sphere.radius()),
+                gpu_vec(motion, 0.0),
+                [0.0; 4],
+            ],
+            sphere.bbox(),
+            sphere.material(),
+            sphere.sides(),
+        );
+    }
+
+    pub fn push_quad(&mut self, quad: &Quad<Material>) {
+        self.push(
+            QUAD,
+            [quad.origin(), quad.u(), quad.v()].map(|v| gpu_vec(v, 0.0)),
+            quad.aabb(),
+            quad.material(),
+            quad.sides(),
+        );
+    }
+
+    pub fn push_triangle(&mut self, triangle: &Triangle<Material>) {
+        self.push(
+            TRIANGLE,
+            triangle.vertices().map(|v| gpu_vec(v, 0.0)),
+            triangle.aabb(),
+            triangle.material(),
+            triangle.sides(),
+        );
+    }
+
+    fn push(
+        &mut self,
+        kind: u32,
+        [a, b, c]: [[f32; 4]; 3],
+        bbox: Aabb,
+        material: Material,
+        sides: Sides,
+    ) {
+        self.primitives.push(GpuPrimitive {
+            a,
+            b,
+            c,
+            kind,
+            material: self.materials.len() as u32,
+            sides: gpu_sides(sides),
+            _pad: 0,
+        });
+        self.bounds.push(bbox);
+        self.materials.push(GpuMaterial::new(material));
+        self.unsupported |= !gpu_supports(material);
+    }
+
+    /// Whether the shader implements every material of the scene, which
+    /// rules out isotropic materials, textures, cutouts and nested or
+    /// dispersive dielectrics.
+    pub const fn is_supported(&self) -> bool {
+        !self.unsupported
+    }
+
+    /// Number of primitives.
+    pub fn len(&self) -> usize {
+        self.primitives.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.primitives.is_empty()
+    }
+
+    /// BVH nodes and the primitives reordered to match their leaves.
+    fn flatten(&self) -> (Vec<GpuNode>, Vec<GpuPrimitive>) {
+        if self.is_empty() {
+            // Leaf whose inverted box no ray enters.
+            let root = GpuNode {
+                min:        [1.0; 3],
+                left_first: 0,
+                max:        [-1.0; 3],
+                count:      1,
+            };
+            return (vec![root], vec![GpuPrimitive::default()]);
+        }
+
+        let mut order: Vec<_> = (0..self.len()).collect();
+        let mut nodes = Vec::with_capacity(2 * self.len().div_ceil(LEAF_SIZE));
+        self.build(&mut order, 0, &mut nodes);
+        let primitives = order.iter().map(|&i| self.primitives[i]).collect();
+        (nodes, primitives)
+    }
+
+    /// Appends the subtree over the primitives `order`, which start at
+    /// `first` in leaf order, splitting at the median centroid along the
+    /// longest axis.
+    fn build(&self, order: &mut [usize], first: usize, nodes: &mut Vec<GpuNode>) {
+        let bbox = order
+            .iter()
+            .fold(Aabb::empty(), |bbox, &i| bbox.merge(self.bounds[i]));
+        let index = nodes.len();
+        nodes.push(GpuNode::new(bbox, first as u32, order.len() as u32));
+        if order.len() <= LEAF_SIZE {
+            return;
+        }
+
+        let centroid = |i: usize| self.bounds[i].centroid();
+        let centroids = order.iter().fold(Aabb::empty(), |bbox, &i| {
+            bbox.merge(Aabb::from_points(centroid(i), centroid(i)))
+        });
+        let axis = centroids.longest_axis();
+        let mid = order.len() / 2;
+        order.select_nth_unstable_by(mid, |&a, &b| {
+            centroid(a).get(axis).total_cmp(&centroid(b).get(axis))
+        });
+
+        let (left, right) = order.split_at_mut(mid);
+        self.build(left, first, nodes);
+        nodes[index].left_first = nodes.len() as u32;
+        nodes[index].count = 0;
+        self.build(right, first + mid, nodes);
+    }
+}
+
+/// `background` as a row-major latitude/longitude table, the first row
+/// looking straight up, and its size.
+fn background_table(background: &Background) -> (Vec<[f32; 4]>, [u32; 2]) {
+    if let Background::Color(color) = background {
+        return (vec![gpu_vec(*color, 0.0)], [1, 1]);
+    }
+
+    let [width, height] = BACKGROUND_SIZE;
+    let table = (0..height)
+        .flat_map(|y| {
+            (0..width).map(move |x| {
+                let phi = ((x as Float + 0.5) / width as Float - 0.5) * 2.0 * PI;
+                let theta = (y as Float + 0.5) / height as Float * PI;
+                let direction = Vec3::new(
+                    theta.sin() * phi.cos(),
+                    theta.cos(),
+                    theta.sin() * phi.sin(),
+                );
+                gpu_vec(background.value(direction), 0.0)
+            })
+        })
+        .collect();
+    (table, BACKGROUND_SIZE)
+}
+
+impl GpuRenderer {
+    /// Connects to the default GPU and compiles the path tracer for it,
+    /// failing if there is no GPU.
+    pub fn new() -> Result<Self> {
+        pollster::block_on(Self::request())
+    }
+
+    async fn request() -> Result<Self> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                ..Default::default()
+            })
+            .await
+            .ok_or_else(|| gpu_error("no adapter found"))?;
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: Some("ray"),
+                    required_limits: wgpu::Limits::default().using_resolution(adapter.limits()),
+                    ..Default::default()
+                },
+                None,
+            )
+            .await
+            .map_err(gpu_error)?;
+
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let module = device.create_shader_module(wgpu::include_wgsl!("gpu.wgsl"));
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label:               Some("path tracer"),
+            layout:              None,
+            module:              &module,
+            entry_point:         Some("main"),
+            compilation_options: Default::default(),
+            cache:               None,
+        });
+        if let Some(err) = device.pop_error_scope().await {
+            return Err(gpu_error(err));
+        }
+
+        Ok(Self {
+            device,
+            queue,
+            pipeline,
+            adapter: adapter.get_info().name,
+        })
+    }
+
+    /// Name of the GPU.
+    pub fn adapter(&self) -> &str {
+        &self.adapter
+    }
+
+    /// Whether the shader implements every option of `camera`: the
+    /// perspective projection of the beauty pass through a circular lens and
+    /// a box filter, without delta lights, clay override, photon mapping,
+    /// path guiding, light resampling or fog.
+    pub fn supports(camera: &Camera) -> bool {
+        camera.projection() == Projection::Perspective
+            && camera.mode() == RenderMode::Beauty
+            && *camera.aperture() == Aperture::Circle
+            && matches!(camera.filter(), Filter::Box { .. })
+            && camera.delta_lights().is_empty()
+            && camera.clay().is_none()
+            && camera.photon_mapping().is_none()
+            && camera.path_guiding().is_none()
+            && camera.light_resampling().is_none()
+            && camera.fog().is_none()
+    }
+
+    /// Renders `scene` as seen by `camera` with `sample_count` samples per
+    /// pixel, failing if the camera or scene is not supported, see
+    /// [`GpuRenderer::supports`] and [`GpuScene::is_supported`], or the GPU
+    /// fails.
+    pub fn render(
+        &self,
+        camera: &Camera,
+        scene: &GpuScene,
+        sample_count: u32,
+        max_depth: u32,
+    ) -> Result<Image> {
+        if !Self::supports(camera) {
+            return Err(Error::InvalidParameter(
+                "camera options not supported on the GPU",
+            ));
+        }
+        if !scene.is_supported() {
+            return Err(Error::InvalidParameter(
+                "scene materials not supported on the GPU",
+            ));
+        }
+
+        let width = camera.img_width();
+        let height = camera.img_height();
+        let accumulated_size = width as u64 * height as u64 * size_of::<[f32; 4]>() as u64;
+
+        let (nodes, primitives) = scene.flatten();
+        let materials = match scene.materials.as_slice() {
+            [] => vec![GpuMaterial::default()],
+            materials => materials.to_vec(),
+        };
+        let (background, background_size) = background_table(camera.background());
+
+        self.device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+        let storage = |label, contents: &[u8]| {
+            self.device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(label),
+                    contents,
+                    usage: wgpu::BufferUsages::STORAGE,
+                })
+        };
+        let params_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label:              Some("params"),
+            size:               size_of::<Params>() as u64,
+            usage:              wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let nodes_buffer = storage("nodes", bytemuck::cast_slice(&nodes));
+        let primitives_buffer = storage("primitives", bytemuck::cast_slice(&primitives));
+        let materials_buffer = storage("materials", bytemuck::cast_slice(&materials));
+        let background_buffer = storage("background", bytemuck::cast_slice(&background));
+        let accumulated_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label:              Some("accumulated"),
+            size:               accumulated_size,
+            usage:              wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label:              Some("readback"),
+            size:               accumulated_size,
+            usage:              wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let buffers = [
+            &params_buffer,
+            &nodes_buffer,
+            &primitives_buffer,
+            &materials_buffer,
+            &background_buffer,
+            &accumulated_buffer,
+        ];
+        let entries: Vec<_> = (0..)
+            .zip(buffers)
+            .map(|(binding, buffer)| wgpu::BindGroupEntry {
+                binding,
+                resource: buffer.as_entire_binding(),
+            })
+            .collect();
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label:   Some("scene"),
+            layout:  &self.pipeline.get_bind_group_layout(0),
+            entries: &entries,
+        });
+
+        if let Some(err) = pollster::block_on(self.device.pop_error_scope()) {
+            return Err(gpu_error(err));
+        }
+        if let Some(err) = pollster::block_on(self.device.pop_error_scope()) {
+            return Err(gpu_error(err));
+        }
+
+        let (center, pixel00, delta_u, delta_v) = camera.image_plane();
+        let (disk_u, disk_v) = camera.lens_axes();
+        let mut params = Params {
+            center: gpu_vec(center, 0.0),
+            pixel00: gpu_vec(pixel00, 0.0),
+            delta_u: gpu_vec(delta_u, 0.0),
+            delta_v: gpu_vec(delta_v, 0.0),
+            disk_u: gpu_vec(disk_u, 0.0),
+            disk_v: gpu_vec(disk_v, 0.0),
+            width,
+            height,
+            max_depth,
+            seed: hash_seed(&[camera.seed(), camera.frame() as u64]) as u32,
+            background_size,
+            filter_radius: camera.filter().radius() as f32,
+            shutter_open: camera.shutter_open() as f32,
+            shutter_close: camera.shutter_close() as f32,
+            max_radiance: camera.max_radiance().unwrap_or(0.0) as f32,
+            indirect_clamp: camera.indirect_clamp().unwrap_or(0.0) as f32,
+            ..Params::default()
+        };
+
+        let start = Stopwatch::start();
+        while params.sample_start < sample_count {
+            params.sample_count = SAMPLES_PER_DISPATCH.min(sample_count - params.sample_start);
+            self.queue
+                .write_buffer(&params_buffer, 0, bytemuck::bytes_of(&params));
+
+            let mut encoder = self.device.create_command_encoder(&Default::default());
+            {
+                let mut pass = encoder.begin_compute_pass(&Default::default());
+                pass.set_pipeline(&self.pipeline);
+                pass.set_bind_group(0, &bind_group, &[]);
+                pass.dispatch_workgroups(
+                    width.div_ceil(WORKGROUP_SIZE),
+                    height.div_ceil(WORKGROUP_SIZE),
+                    1,
+                );
+            }
+            self.queue.submit([encoder.finish()]);
+            self.device.poll(wgpu::Maintain::Wait);
+
+            params.sample_start += params.sample_count;
+            camera.report(RenderEvent::PassDone {
+                samples: params.sample_start,
+                elapsed: start.elapsed(),
+            });
+        }
+
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+        encoder.copy_buffer_to_buffer(
+            &accumulated_buffer,
+            0,
+            &readback_buffer,
+            0,
+            accumulated_size,
+        );
+        self.queue.submit([encoder.finish()]);
+
+        let slice = readback_buffer.slice(..);
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver.recv().map_err(gpu_error)?.map_err(gpu_error)?;
+
+        let scale = camera.exposure().map_or(1.0, |exposure| exposure.scale())
+            / sample_count.max(1) as Float;
+        let mut image = Image::new(width, height);
+        let mapped = slice.get_mapped_range();
+        let sums: &[[f32; 4]] = bytemuck::cast_slice(&mapped);
+        for (pixel, sum) in image.pixels_mut().iter_mut().zip(sums) {
+            *pixel = Vec3::new(sum[0] as Float, sum[1] as Float, sum[2] as Float) * scale;
+        }
+
+        camera.report(RenderEvent::Finished {
+            elapsed: start.elapsed(),
+        });
+        Ok(image)
+    }
+}
+
+/// Renders on the GPU when `gpu` is given, a GPU is available and it
+/// supports `camera` and the scene, and on the CPU from `world` and `lights`
+/// otherwise.
+/// Either way the beauty image is returned.
+pub fn render_or_cpu<T: Hit + ?Sized + Sync, L: Hit + ?Sized + Sync>(
+    camera: &Camera,
+    gpu: Option<&GpuScene>,
+    world: &T,
+    lights: &L,
+    sample_count: u32,
+    max_depth: u32,
+) -> Image
+where
+    T::Material: Copy,
+{
+    let rendered = gpu
+        .filter(|scene| GpuRenderer::supports(camera) && scene.is_supported())
+        .and_then(|scene| {
+            let renderer = GpuRenderer::new().ok()?;
+            renderer.render(camera, scene, sample_count, max_depth).ok()
+        });
+    rendered.unwrap_or_else(|| {
+        camera
+            .render_frame(world, lights, sample_count, max_depth)
+            .into_beauty()
+    })
+}
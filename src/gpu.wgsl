@@ -0,0 +1,392 @@
+// Path tracer run by `gpu::GpuRenderer`, one invocation per pixel.
+//
+// Mirrors the CPU integrator without light sampling: emitters and the
+// background are only found by scattered rays, which converges to the same
+// image with more noise around small lights.
+
+struct Params {
+    center:          vec4<f32>,
+    pixel00:         vec4<f32>,
+    delta_u:         vec4<f32>,
+    delta_v:         vec4<f32>,
+    disk_u:          vec4<f32>,
+    disk_v:          vec4<f32>,
+    width:           u32,
+    height:          u32,
+    sample_start:    u32,
+    sample_count:    u32,
+    max_depth:       u32,
+    seed:            u32,
+    background_size: vec2<u32>,
+    filter_radius:   f32,
+    shutter_open:    f32,
+    shutter_close:   f32,
+    max_radiance:    f32,
+    indirect_clamp:  f32,
+}
+
+struct Primitive {
+    a:        vec4<f32>,
+    b:        vec4<f32>,
+    c:        vec4<f32>,
+    kind:     u32,
+    material: u32,
+    sides:    u32,
+    pad:      u32,
+}
+
+struct Material {
+    color: vec4<f32>,
+    kind:  u32,
+    sides: u32,
+    pad0:  u32,
+    pad1:  u32,
+}
+
+struct Node {
+    min:        vec3<f32>,
+    left_first: u32,
+    max:        vec3<f32>,
+    count:      u32,
+}
+
+struct Ray {
+    origin:    vec3<f32>,
+    direction: vec3<f32>,
+    time:      f32,
+}
+
+struct Hit {
+    found:      bool,
+    t:          f32,
+    point:      vec3<f32>,
+    normal:     vec3<f32>,
+    front_face: bool,
+    material:   u32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> nodes: array<Node>;
+@group(0) @binding(2) var<storage, read> primitives: array<Primitive>;
+@group(0) @binding(3) var<storage, read> materials: array<Material>;
+@group(0) @binding(4) var<storage, read> background: array<vec4<f32>>;
+@group(0) @binding(5) var<storage, read_write> accumulated: array<vec4<f32>>;
+
+const PI: f32 = 3.14159265358979;
+const T_MIN: f32 = 0.001;
+const T_MAX: f32 = 3.0e38;
+const STACK_SIZE: u32 = 64u;
+
+const SPHERE: u32 = 0u;
+const QUAD: u32 = 1u;
+const TRIANGLE: u32 = 2u;
+
+const LAMBERTIAN: u32 = 0u;
+const METAL: u32 = 1u;
+const DIELECTRIC: u32 = 2u;
+const DIFFUSE_LIGHT: u32 = 3u;
+
+const SIDES_FRONT: u32 = 0u;
+
+var<private> rng_state: u32;
+
+// PCG hash, https://www.jcgt.org/published/0009/03/02/
+fn hash(x: u32) -> u32 {
+    let state = x * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+fn next_1d() -> f32 {
+    let state = rng_state;
+    rng_state = state * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return f32(((word >> 22u) ^ word) >> 8u) * (1.0 / 16777216.0);
+}
+
+fn random_unit() -> vec3<f32> {
+    let z = 1.0 - 2.0 * next_1d();
+    let r = sqrt(max(0.0, 1.0 - z * z));
+    let phi = 2.0 * PI * next_1d();
+    return vec3<f32>(r * cos(phi), r * sin(phi), z);
+}
+
+fn random_in_disk() -> vec2<f32> {
+    let r = sqrt(next_1d());
+    let phi = 2.0 * PI * next_1d();
+    return vec2<f32>(r * cos(phi), r * sin(phi));
+}
+
+fn hit_sphere(p: Primitive, ray: Ray, t_max: f32) -> f32 {
+    let center = p.a.xyz + p.b.xyz * ray.time;
+    let oc = center - ray.origin;
+    let a = dot(ray.direction, ray.direction);
+    let h = dot(ray.direction, oc);
+    let c = dot(oc, oc) - p.a.w * p.a.w;
+    let discriminant = h * h - a * c;
+    if discriminant < 0.0 {
+        return -1.0;
+    }
+    let dsqrt = sqrt(discriminant);
+    var root = (h - dsqrt) / a;
+    if root <= T_MIN || root >= t_max {
+        root = (h + dsqrt) / a;
+        if root <= T_MIN || root >= t_max {
+            return -1.0;
+        }
+    }
+    return root;
+}
+
+fn hit_quad(p: Primitive, ray: Ray, t_max: f32) -> f32 {
+    let n = cross(p.b.xyz, p.c.xyz);
+    let normal = normalize(n);
+    let denom = dot(normal, ray.direction);
+    if abs(denom) < 1e-8 {
+        return -1.0;
+    }
+    let t = (dot(normal, p.a.xyz) - dot(normal, ray.origin)) / denom;
+    if t <= T_MIN || t >= t_max {
+        return -1.0;
+    }
+    let w = n / dot(n, n);
+    let planar = ray.origin + ray.direction * t - p.a.xyz;
+    let alpha = dot(w, cross(planar, p.c.xyz));
+    let beta = dot(w, cross(p.b.xyz, planar));
+    if alpha < 0.0 || alpha > 1.0 || beta < 0.0 || beta > 1.0 {
+        return -1.0;
+    }
+    return t;
+}
+
+// Möller-Trumbore.
+fn hit_triangle(p: Primitive, ray: Ray, t_max: f32) -> f32 {
+    let e1 = p.b.xyz - p.a.xyz;
+    let e2 = p.c.xyz - p.a.xyz;
+    let pvec = cross(ray.direction, e2);
+    let det = dot(e1, pvec);
+    if abs(det) < 1e-12 {
+        return -1.0;
+    }
+    let inv_det = 1.0 / det;
+    let tvec = ray.origin - p.a.xyz;
+    let u = dot(tvec, pvec) * inv_det;
+    if u < 0.0 || u > 1.0 {
+        return -1.0;
+    }
+    let qvec = cross(tvec, e1);
+    let v = dot(ray.direction, qvec) * inv_det;
+    if v < 0.0 || u + v > 1.0 {
+        return -1.0;
+    }
+    let t = dot(e2, qvec) * inv_det;
+    if t <= T_MIN || t >= t_max {
+        return -1.0;
+    }
+    return t;
+}
+
+// Outward normal of `p` at `point`.
+fn outward_normal(p: Primitive, point: vec3<f32>, time: f32) -> vec3<f32> {
+    switch p.kind {
+        case SPHERE: {
+            return (point - (p.a.xyz + p.b.xyz * time)) / p.a.w;
+        }
+        case QUAD: {
+            return normalize(cross(p.b.xyz, p.c.xyz));
+        }
+        default: {
+            return normalize(cross(p.b.xyz - p.a.xyz, p.c.xyz - p.a.xyz));
+        }
+    }
+}
+
+// Distance to `p` along `ray` if hit before `t_max`, negative otherwise.
+fn intersect(p: Primitive, ray: Ray, t_max: f32) -> f32 {
+    var t: f32;
+    switch p.kind {
+        case SPHERE: {
+            t = hit_sphere(p, ray, t_max);
+        }
+        case QUAD: {
+            t = hit_quad(p, ray, t_max);
+        }
+        default: {
+            t = hit_triangle(p, ray, t_max);
+        }
+    }
+    if t > 0.0 && p.sides == SIDES_FRONT {
+        let normal = outward_normal(p, ray.origin + ray.direction * t, ray.time);
+        if dot(ray.direction, normal) >= 0.0 {
+            return -1.0;
+        }
+    }
+    return t;
+}
+
+fn hit_node(node: Node, origin: vec3<f32>, inv_direction: vec3<f32>, t_max: f32) -> bool {
+    let t0 = (node.min - origin) * inv_direction;
+    let t1 = (node.max - origin) * inv_direction;
+    let near = min(t0, t1);
+    let far = max(t0, t1);
+    let enter = max(max(near.x, near.y), max(near.z, T_MIN));
+    let exit = min(min(far.x, far.y), min(far.z, t_max));
+    return enter <= exit;
+}
+
+fn trace(ray: Ray) -> Hit {
+    var hit: Hit;
+    hit.found = false;
+    var closest = T_MAX;
+    var index = 0u;
+
+    let inv_direction = 1.0 / ray.direction;
+    var stack: array<u32, STACK_SIZE>;
+    var top = 1u;
+    stack[0] = 0u;
+
+    while top > 0u {
+        top -= 1u;
+        let node_index = stack[top];
+        let node = nodes[node_index];
+        if !hit_node(node, ray.origin, inv_direction, closest) {
+            continue;
+        }
+        if node.count > 0u {
+            for (var k = 0u; k < node.count; k++) {
+                let t = intersect(primitives[node.left_first + k], ray, closest);
+                if t > 0.0 {
+                    closest = t;
+                    index = node.left_first + k;
+                    hit.found = true;
+                }
+            }
+        } else if top + 2u <= STACK_SIZE {
+            stack[top] = node.left_first;
+            stack[top + 1u] = node_index + 1u;
+            top += 2u;
+        }
+    }
+
+    if hit.found {
+        let p = primitives[index];
+        hit.t = closest;
+        hit.point = ray.origin + ray.direction * closest;
+        let normal = outward_normal(p, hit.point, ray.time);
+        hit.front_face = dot(ray.direction, normal) < 0.0;
+        hit.normal = select(-normal, normal, hit.front_face);
+        hit.material = p.material;
+    }
+    return hit;
+}
+
+fn background_color(direction: vec3<f32>) -> vec3<f32> {
+    let d = normalize(direction);
+    let u = atan2(d.z, d.x) / (2.0 * PI) + 0.5;
+    let v = acos(clamp(d.y, -1.0, 1.0)) / PI;
+    let size = params.background_size;
+    let x = min(u32(u * f32(size.x)), size.x - 1u);
+    let y = min(u32(v * f32(size.y)), size.y - 1u);
+    return background[y * size.x + x].rgb;
+}
+
+fn schlick(refraction: f32, cosine: f32) -> f32 {
+    let r0 = (1.0 - refraction) / (1.0 + refraction);
+    let r0_squared = r0 * r0;
+    return r0_squared * (1.0 - r0_squared) * pow(1.0 - cosine, 5.0);
+}
+
+fn clamp_radiance(radiance: vec3<f32>, max_radiance: f32) -> vec3<f32> {
+    let largest = max(max(radiance.x, radiance.y), radiance.z);
+    if max_radiance > 0.0 && largest > max_radiance {
+        return radiance * (max_radiance / largest);
+    }
+    return radiance;
+}
+
+fn contribution(bounce: u32, radiance: vec3<f32>) -> vec3<f32> {
+    if bounce > 0u {
+        return clamp_radiance(radiance, params.indirect_clamp);
+    }
+    return radiance;
+}
+
+fn ray_color(camera_ray: Ray) -> vec3<f32> {
+    var ray = camera_ray;
+    var throughput = vec3<f32>(1.0);
+    var radiance = vec3<f32>(0.0);
+
+    for (var bounce = 0u; bounce < params.max_depth; bounce++) {
+        let hit = trace(ray);
+        if !hit.found {
+            radiance += contribution(bounce, throughput * background_color(ray.direction));
+            break;
+        }
+
+        let material = materials[hit.material];
+        if material.kind == DIFFUSE_LIGHT {
+            if material.sides != SIDES_FRONT || hit.front_face {
+                radiance += contribution(bounce, throughput * material.color.rgb);
+            }
+            break;
+        }
+
+        let unit_direction = normalize(ray.direction);
+        var direction: vec3<f32>;
+        if material.kind == LAMBERTIAN {
+            direction = hit.normal + random_unit();
+            if all(abs(direction) < vec3<f32>(1e-8)) {
+                direction = hit.normal;
+            }
+            throughput *= material.color.rgb;
+        } else if material.kind == METAL {
+            direction = reflect(unit_direction, hit.normal) + random_unit() * material.color.w;
+            if dot(direction, hit.normal) <= 0.0 {
+                break;
+            }
+            throughput *= material.color.rgb;
+        } else {
+            let refraction = material.color.w;
+            let eta_from = select(refraction, 1.0, hit.front_face);
+            let eta_to = select(1.0, refraction, hit.front_face);
+            let ratio = eta_from / eta_to;
+            let cos_theta = min(dot(-unit_direction, hit.normal), 1.0);
+            let sin_theta = sqrt(max(0.0, 1.0 - cos_theta * cos_theta));
+            if ratio * sin_theta > 1.0 || schlick(eta_to / eta_from, cos_theta) > next_1d() {
+                direction = reflect(unit_direction, hit.normal);
+            } else {
+                direction = refract(unit_direction, hit.normal, ratio);
+            }
+        }
+        ray = Ray(hit.point, direction, ray.time);
+    }
+    return radiance;
+}
+
+@compute @workgroup_size(8, 8)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= params.width || id.y >= params.height {
+        return;
+    }
+
+    var sum = vec3<f32>(0.0);
+    for (var s = params.sample_start; s < params.sample_start + params.sample_count; s++) {
+        rng_state = hash(params.seed ^ hash(id.x ^ hash(id.y ^ hash(s))));
+
+        let offset = (vec2<f32>(next_1d(), next_1d()) * 2.0 - 1.0) * params.filter_radius;
+        let pixel = params.pixel00.xyz
+            + params.delta_u.xyz * (f32(id.x) + offset.x)
+            + params.delta_v.xyz * (f32(id.y) + offset.y);
+        let lens = random_in_disk();
+        let origin = params.center.xyz + params.disk_u.xyz * lens.x + params.disk_v.xyz * lens.y;
+        let time = mix(params.shutter_open, params.shutter_close, next_1d());
+
+        let color = clamp_radiance(ray_color(Ray(origin, pixel - origin, time)), params.max_radiance);
+        if all(color == color) {
+            sum += color;
+        }
+    }
+
+    let index = id.y * params.width + id.x;
+    accumulated[index] += vec4<f32>(sum, 0.0);
+}
@@ -0,0 +1,216 @@
+//! Uniform grid acceleration structure.
+//!
+//! Cheaper to build than a [`Bvh`](crate::bvh::Bvh) and fast when primitives
+//! are spread evenly, but a poor fit for scenes mixing very large and very
+//! small objects.
+
+use std::sync::Arc;
+
+use crate::{
+    Float,
+    aabb::{Aabb, Axis},
+    bvh::visit_node,
+    hit::{DynHit, Hit, HitRecord},
+    interval::Interval,
+    material::Material,
+    ray::Ray,
+    vec3::Vec3,
+};
+
+/// Average number of primitives per cell aimed for when sizing the grid.
+const DENSITY: Float = 3.0;
+/// Largest number of cells along an axis.
+const MAX_RESOLUTION: usize = 128;
+
+/// Grid of equally sized cells over the bounds of the scene, each listing
+/// the primitives overlapping it. Rays walk the cells they cross in order,
+/// so the walk stops at the first cell holding a hit.
+#[derive(Debug, Clone)]
+pub struct Grid {
+    bbox:       Aabb,
+    resolution: [usize; 3],
+    cell_size:  [Float; 3],
+    /// Start of the primitives of every cell in `indices`, plus the end of
+    /// the last cell.
+    offsets:    Vec<u32>,
+    indices:    Vec<u32>,
+    primitives: Vec<Arc<dyn DynHit>>,
+    count:      usize,
+}
+
+impl Grid {
+    pub fn from_list(hit_list: &[Arc<dyn DynHit>]) -> Self {
+        let bbox = hit_list
+            .iter()
+            .fold(Aabb::empty(), |bbox, obj| bbox.merge(obj.aabb()));
+        let extent = Axis::all().map(|axis| bbox.get(axis).size());
+
+        // Cube root of the cells wanted per unit volume.
+        let volume = extent.iter().product::<Float>();
+        let scale = (DENSITY * hit_list.len() as Float / volume).cbrt();
+        let resolution =
+            extent.map(|size| ((size * scale).round() as usize).clamp(1, MAX_RESOLUTION));
+        let cell_size = [0, 1, 2].map(|i| extent[i] / resolution[i] as Float);
+
+        let cell_count = resolution.iter().product::<usize>();
+        let mut cells = vec![Vec::new(); cell_count];
+        for (index, obj) in hit_list.iter().enumerate() {
+            let obj_box = obj.aabb();
+            let range = Axis::all().map(|axis| {
+                let i = axis as usize;
+                let interval = obj_box.get(axis);
+                let origin = bbox.get(axis).min();
+                let cell = |x: Float| {
+                    (((x - origin) / cell_size[i]).floor().max(0.0) as usize).min(resolution[i] - 1)
+                };
+                cell(interval.min())..=cell(interval.max())
+            });
+            for z in range[2].clone() {
+                for y in range[1].clone() {
+                    for x in range[0].clone() {
+                        cells[x + resolution[0] * (y + resolution[1] * z)].push(index as u32);
+                    }
+                }
+            }
+        }
+
+        let mut offsets = Vec::with_capacity(cell_count + 1);
+        let mut indices = Vec::new();
+        for cell in cells {
+            offsets.push(indices.len() as u32);
+            indices.extend(cell);
+        }
+        offsets.push(indices.len() as u32);
+
+        Self {
+            bbox,
+            resolution,
+            cell_size,
+            offsets,
+            indices,
+            primitives: hit_list.to_vec(),
+            count: hit_list.iter().map(|obj| obj.count()).sum(),
+        }
+    }
+
+    /// Number of cells along `x`, `y` and `z`.
+    pub const fn resolution(&self) -> [usize; 3] {
+        self.resolution
+    }
+
+    fn cell(&self, [x, y, z]: [usize; 3]) -> impl Iterator<Item = &Arc<dyn DynHit>> {
+        let index = x + self.resolution[0] * (y + self.resolution[1] * z);
+        let range = self.offsets[index] as usize..self.offsets[index + 1] as usize;
+        self.indices[range]
+            .iter()
+            .map(|&i| &self.primitives[i as usize])
+    }
+
+    /// Walks the cells crossed by `ray` within `ray_t`, front to back,
+    /// calling `visit` with each cell and the parameter at which the ray
+    /// leaves it until it returns `true`.
+    fn walk(&self, ray: &Ray, ray_t: Interval, mut visit: impl FnMut([usize; 3], Float) -> bool) {
+        if self.primitives.is_empty() {
+            return;
+        }
+        let Some(clipped) = self.bbox.hit(ray, ray_t) else {
+            return;
+        };
+
+        let origin = ray.origin();
+        let dir = ray.direction();
+        let entry = clipped.min();
+
+        let mut cell = [0; 3];
+        let mut step = [0isize; 3];
+        let mut t_next = [Float::INFINITY; 3];
+        let mut t_delta = [Float::INFINITY; 3];
+        for axis in Axis::all() {
+            let i = axis as usize;
+            let min = self.bbox.get(axis).min();
+            let o = origin.get(axis);
+            let d = dir.get(axis);
+            let inv = ray.inv_direction().get(axis);
+            let position = (o + d * entry - min) / self.cell_size[i];
+            cell[i] = (position.floor().max(0.0) as usize).min(self.resolution[i] - 1);
+
+            if d > 0.0 {
+                step[i] = 1;
+                t_next[i] = (min + (cell[i] + 1) as Float * self.cell_size[i] - o) * inv;
+                t_delta[i] = self.cell_size[i] * inv;
+            } else if d < 0.0 {
+                step[i] = -1;
+                t_next[i] = (min + cell[i] as Float * self.cell_size[i] - o) * inv;
+                t_delta[i] = -self.cell_size[i] * inv;
+            }
+        }
+
+        loop {
+            visit_node();
+            let axis = if t_next[0] < t_next[1] && t_next[0] < t_next[2] {
+                0
+            } else if t_next[1] < t_next[2] {
+                1
+            } else {
+                2
+            };
+            let exit = t_next[axis];
+            if visit(cell, exit) || exit > clipped.max() {
+                return;
+            }
+
+            let next = cell[axis] as isize + step[axis];
+            if next < 0 || next as usize >= self.resolution[axis] {
+                return;
+            }
+            cell[axis] = next as usize;
+            t_next[axis] += t_delta[axis];
+        }
+    }
+}
+
+impl Hit for Grid {
+    type Material = Material;
+
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<Self::Material>> {
+        let mut closest = None;
+        let mut max = ray_t.max();
+
+        self.walk(ray, ray_t, |cell, exit| {
+            for obj in self.cell(cell) {
+                if let Some(hit) = obj.hit(ray, ray_t.with_max(max)) {
+                    max = hit.t();
+                    closest = Some(hit);
+                }
+            }
+            // Primitives spanning several cells may have been hit beyond
+            // this one, in which case a closer hit can still come.
+            max <= exit
+        });
+
+        closest
+    }
+
+    fn hit_any(&self, ray: &Ray, ray_t: Interval) -> bool {
+        let mut found = false;
+        self.walk(ray, ray_t, |cell, _| {
+            found = self.cell(cell).any(|obj| obj.hit_any(ray, ray_t));
+            found
+        });
+        found
+    }
+
+    fn aabb(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn count(&self) -> usize {
+        self.count
+    }
+
+    fn triangulate(&self, triangles: &mut Vec<[Vec3; 3]>) {
+        for obj in &self.primitives {
+            obj.triangulate(triangles);
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! Named groups of objects, mirroring the hierarchy of scene files.
+//!
+//! A loader builds a [`Group`] per node of the file, pushing its objects and
+//! child groups under their names. A group is placed by one transform, can
+//! be hidden as a whole and is itself an object, pushed into its parent or
+//! used as the world. Hits inside it are tagged with the id of the group in
+//! its parent, so an id pass tells groups apart rather than their members.
+
+use std::sync::{Arc, OnceLock};
+
+use crate::{
+    aabb::Aabb,
+    bvh::Bvh,
+    hit::{DynHit, Hit, HitList, HitRecord, ObjectId},
+    interval::Interval,
+    material::Material,
+    ray::Ray,
+    transform::{Transform, Transformed},
+    vec3::Vec3,
+};
+
+/// Named objects and child groups sharing a transform and visibility.
+#[derive(Debug)]
+pub struct Group {
+    name:      String,
+    objects:   HitList,
+    groups:    Vec<(ObjectId, Arc<Group>)>,
+    transform: Transform,
+    visible:   bool,
+    /// Objects in a BVH placed by the transform, built on the first hit
+    /// after a change, `None` while the group is empty.
+    placed:    OnceLock<Option<Transformed<Bvh>>>,
+}
+
+impl Group {
+    /// Empty, visible group without a transform.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name:      name.into(),
+            objects:   HitList::new(),
+            groups:    Vec::new(),
+            transform: Transform::default(),
+            visible:   true,
+            placed:    OnceLock::new(),
+        }
+    }
+
+    /// Adds an unnamed object.
+    pub fn push<T: DynHit + 'static>(&mut self, v: T) -> ObjectId {
+        self.placed = OnceLock::new();
+        self.objects.push(v)
+    }
+
+    /// Adds an object under `name`, see [`HitList::push_named`].
+    pub fn push_named<T: DynHit + 'static>(&mut self, name: impl Into<String>, v: T) -> ObjectId {
+        self.placed = OnceLock::new();
+        self.objects.push_named(name, v)
+    }
+
+    /// Adds a child group under its name.
+    pub fn push_group(&mut self, group: Group) -> ObjectId {
+        let group = Arc::new(group);
+        let id = self.push_named(group.name.clone(), Arc::clone(&group));
+        self.groups.push((id, group));
+        id
+    }
+
+    /// Places the objects of the group, relative to its parent.
+    pub fn with_transform(mut self, transform: Transform) -> Self {
+        self.transform = transform;
+        self.placed = OnceLock::new();
+        self
+    }
+
+    /// Hides or shows the group and everything in it.
+    pub fn with_visible(mut self, visible: bool) -> Self {
+        self.visible = visible;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Objects of the group, child groups included, with their names.
+    pub const fn objects(&self) -> &HitList {
+        &self.objects
+    }
+
+    /// Child groups with their ids in [`Group::objects`].
+    pub fn groups(&self) -> impl Iterator<Item = (ObjectId, &Group)> {
+        self.groups.iter().map(|(id, group)| (*id, &**group))
+    }
+
+    /// Child group named `name`.
+    pub fn group(&self, name: &str) -> Option<&Group> {
+        self.groups()
+            .map(|(_, group)| group)
+            .find(|group| group.name == name)
+    }
+
+    pub const fn transform(&self) -> Transform {
+        self.transform
+    }
+
+    pub const fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    fn placed(&self) -> Option<&Transformed<Bvh>> {
+        if !self.visible {
+            return None;
+        }
+        self.placed
+            .get_or_init(|| {
+                let mut list = self.objects.list().to_vec();
+                (!list.is_empty())
+                    .then(|| Transformed::new(Bvh::from_list(&mut list), self.transform))
+            })
+            .as_ref()
+    }
+}
+
+impl Hit for Group {
+    type Material = Material;
+
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<Self::Material>> {
+        self.placed()?.hit(ray, ray_t)
+    }
+
+    fn hit_any(&self, ray: &Ray, ray_t: Interval) -> bool {
+        self.placed()
+            .is_some_and(|placed| placed.hit_any(ray, ray_t))
+    }
+
+    fn aabb(&self) -> Aabb {
+        if self.objects.is_empty() {
+            Aabb::empty()
+        } else {
+            self.transform.aabb(self.objects.aabb())
+        }
+    }
+
+    fn count(&self) -> usize {
+        self.objects.iter().map(|obj| obj.count()).sum()
+    }
+
+    fn triangulate(&self, triangles: &mut Vec<[Vec3; 3]>) {
+        if let Some(placed) = self.placed() {
+            placed.triangulate(triangles);
+        }
+    }
+}
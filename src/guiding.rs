@@ -0,0 +1,587 @@
+//! Path guiding: importance sampling indirect light with a learned
+//! distribution of where it comes from.
+//!
+//! Sampling diffuse bounces from the material alone ignores where light
+//! actually comes from, which in indoor scenes lit through a door or a
+//! window is a small part of the hemisphere. A [`PathGuide`] learns the
+//! incident light of the scene in a few training passes before rendering,
+//! following "Practical Path Guiding for Efficient Light-Transport
+//! Simulation" (Müller et al. 2017): a binary tree over space, refined where
+//! many paths pass, holds in each leaf a quadtree over directions, refined
+//! where much light arrives from. The [`GuidedPathTracer`] then samples
+//! half of its diffuse bounces from the quadtree of the leaf around the
+//! hit, combining both densities so that the estimate stays unbiased.
+
+#[cfg(feature = "parallel")]
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::{
+    Float,
+    aabb::{Aabb, Axis},
+    camera::Camera,
+    consts::PI,
+    hit::{Hit, HitRecord},
+    integrator::{FirstHit, Integrator, Transport, trace_path},
+    interval::Interval,
+    material::Scatter,
+    ray::Ray,
+    sampler::{RandomSampler, Sampler, hash_seed},
+    vec3::Vec3,
+};
+
+/// Seed value telling training paths apart from camera paths and photons.
+const GUIDE_STREAM: u64 = u64::MAX - 1;
+
+/// Fraction of the guided bounces sampled from the material rather than the
+/// guide, which keeps directions the guide missed reachable.
+const MATERIAL_FRACTION: Float = 0.5;
+
+/// Fraction of the light of a quadtree above which a quadrant is split.
+const SUBDIVISION_FRACTION: Float = 0.01;
+
+/// Maximum depth of the quadtrees.
+const MAX_DIRECTION_DEPTH: u32 = 20;
+
+/// Number of samples per pixel of the image, times the square root of the
+/// paths per pixel of the training pass, above which a spatial leaf is
+/// split, so that the number of regions does not depend on the resolution.
+const SPATIAL_THRESHOLD: Float = 0.02;
+
+/// Maximum depth of the spatial tree.
+const MAX_SPATIAL_DEPTH: u32 = 32;
+
+/// Settings of the path guiding enabled by [`Camera::with_path_guiding`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PathGuiding {
+    training_passes: u32,
+}
+
+/// Incident light of a scene learned by training passes, for guiding the
+/// bounces of camera paths.
+#[derive(Debug, Clone)]
+pub struct PathGuide {
+    bounds: Aabb,
+    nodes:  Vec<SpatialNode>,
+}
+
+/// [`PathTracer`](crate::integrator::PathTracer) sampling diffuse bounces
+/// from a [`PathGuide`] as well as from the material. Renders the beauty
+/// mode of cameras with [`PathGuiding`].
+#[derive(Debug, Clone)]
+pub struct GuidedPathTracer {
+    guide: PathGuide,
+}
+
+/// Node of the binary tree over space.
+#[derive(Debug, Clone)]
+enum SpatialNode {
+    /// Halves of the node on either side of `position` along `axis`.
+    Split {
+        axis:     Axis,
+        position: Float,
+        children: [usize; 2],
+    },
+    Leaf(GuideLeaf),
+}
+
+/// Directions light comes from in a region of space.
+#[derive(Debug, Clone)]
+struct GuideLeaf {
+    /// Light learned by the previous training passes, sampled by paths.
+    sampling:  DirectionTree,
+    /// Light recorded by the current training pass.
+    recording: DirectionTree,
+    /// Number of samples recorded by the current training pass.
+    samples:   u32,
+}
+
+/// Quadtree over the square the sphere of directions maps to with equal
+/// areas, holding the light arriving from each quadrant.
+#[derive(Debug, Clone)]
+pub(crate) struct DirectionTree {
+    nodes: Vec<QuadNode>,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct QuadNode {
+    energy:   [Float; 4],
+    /// Node subdividing each quadrant, zero for quadrants that are leaves.
+    children: [usize; 4],
+}
+
+/// Diffuse bounce of a training path, recorded once the light its path
+/// gathered after it is known.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct GuideVertex {
+    point:      Vec3,
+    direction:  Vec3,
+    pdf:        Float,
+    /// Throughput of the path after the bounce.
+    throughput: Vec3,
+    /// Light the path gathered before the bounce.
+    radiance:   Vec3,
+}
+
+/// Light arriving at `point` from `direction` over the density of
+/// sampling it.
+#[derive(Debug, Clone, Copy)]
+struct GuideSample {
+    point:     Vec3,
+    direction: Vec3,
+    value:     Float,
+}
+
+impl PathGuiding {
+    /// Trains the guide for `training_passes` passes before rendering, pass
+    /// `k` tracing `2^k` paths per pixel. Later passes learn from the
+    /// guide of the earlier ones, so the light gets easier to find.
+    pub const fn new(training_passes: u32) -> Self {
+        Self { training_passes }
+    }
+
+    pub const fn training_passes(&self) -> u32 {
+        self.training_passes
+    }
+}
+
+impl PathGuide {
+    /// Trains a guide for `world` seen by `camera`, tracing the training
+    /// passes of `settings` with paths of up to `max_depth` bounces. Paths
+    /// are sampled from the camera's seed and frame, and `pass` gives
+    /// progressive renders a new guide for every pass.
+    pub fn train<T: Hit + ?Sized + Sync, L: Hit + ?Sized + Sync>(
+        camera: &Camera,
+        world: &T,
+        lights: &L,
+        settings: PathGuiding,
+        max_depth: u32,
+        pass: u32,
+    ) -> Self
+    where
+        T::Material: Copy,
+    {
+        let mut guide = Self {
+            bounds: world.aabb(),
+            nodes:  vec![SpatialNode::Leaf(GuideLeaf::new(DirectionTree::new()))],
+        };
+
+        for iteration in 0..settings.training_passes {
+            let samples = 1 << iteration.min(16);
+            let trace_row = |j: u32| {
+                let mut row = Vec::new();
+                let mut vertices = Vec::new();
+                for i in 0..camera.img_width() {
+                    for s in 0..samples {
+                        let seed = hash_seed(&[
+                            camera.seed(),
+                            camera.frame() as u64,
+                            GUIDE_STREAM,
+                            pass as u64,
+                            iteration as u64,
+                            i as u64,
+                            j as u64,
+                            s as u64,
+                        ]);
+                        let mut sampler = RandomSampler::new(seed);
+                        let (offset, _) = camera.sample_filter(&mut sampler);
+                        let Some(ray) = camera.get_ray(i, j, offset, &mut sampler) else {
+                            continue;
+                        };
+
+                        vertices.clear();
+                        let transport = Transport::Guided {
+                            guide:    &guide,
+                            vertices: Some(&mut vertices),
+                        };
+                        let (radiance, _) = trace_path(
+                            camera,
+                            &ray,
+                            world,
+                            lights,
+                            max_depth,
+                            transport,
+                            &mut sampler,
+                            None,
+                        );
+                        row.extend(vertices.iter().filter_map(|vertex| vertex.sample(radiance)));
+                    }
+                }
+                row
+            };
+
+            #[cfg(feature = "parallel")]
+            let samples_of_pass: Vec<_> = (0..camera.img_height())
+                .into_par_iter()
+                .flat_map_iter(trace_row)
+                .collect();
+            #[cfg(not(feature = "parallel"))]
+            let samples_of_pass: Vec<_> = (0..camera.img_height()).flat_map(trace_row).collect();
+
+            for sample in samples_of_pass {
+                guide.record(sample);
+            }
+            let pixels = (camera.img_width() * camera.img_height()) as Float;
+            guide.refine(SPATIAL_THRESHOLD * pixels * (samples as Float).sqrt());
+        }
+        guide
+    }
+
+    /// Number of regions of space the guide learned the light of
+    /// separately.
+    pub fn regions(&self) -> usize {
+        self.nodes
+            .iter()
+            .filter(|node| matches!(node, SpatialNode::Leaf(_)))
+            .count()
+    }
+
+    fn leaf(&self, point: Vec3) -> usize {
+        let mut index = 0;
+        while let SpatialNode::Split {
+            axis,
+            position,
+            children,
+        } = self.nodes[index]
+        {
+            index = children[usize::from(point.get(axis) >= position)];
+        }
+        index
+    }
+
+    /// Learned light around `point`, `None` before it has learned any.
+    pub(crate) fn directions(&self, point: Vec3) -> Option<&DirectionTree> {
+        match &self.nodes[self.leaf(point)] {
+            SpatialNode::Leaf(leaf) => Some(&leaf.sampling).filter(|tree| tree.total() > 0.0),
+            SpatialNode::Split { .. } => unreachable!("lookups end at leaves"),
+        }
+    }
+
+    fn record(&mut self, sample: GuideSample) {
+        let index = self.leaf(sample.point);
+        if let SpatialNode::Leaf(leaf) = &mut self.nodes[index] {
+            leaf.recording.record(sample.direction, sample.value);
+            leaf.samples += 1;
+        }
+    }
+
+    /// Splits the leaves that recorded more than `threshold` samples, then
+    /// turns the recorded light into the light sampled by the next pass.
+    fn refine(&mut self, threshold: Float) {
+        let mut stack = vec![(0, self.bounds, 0)];
+        while let Some((index, bounds, depth)) = stack.pop() {
+            match &self.nodes[index] {
+                SpatialNode::Split {
+                    axis,
+                    position,
+                    children,
+                } => {
+                    let [below, above] = split_bounds(bounds, *axis, *position);
+                    stack.push((children[0], below, depth + 1));
+                    stack.push((children[1], above, depth + 1));
+                }
+                SpatialNode::Leaf(leaf)
+                    if leaf.samples as Float > threshold && depth < MAX_SPATIAL_DEPTH =>
+                {
+                    let mut half = leaf.clone();
+                    half.samples /= 2;
+                    let axis = bounds.longest_axis();
+                    let position = bounds.get(axis).midpoint();
+                    let children = [self.nodes.len(), self.nodes.len() + 1];
+                    self.nodes.push(SpatialNode::Leaf(half.clone()));
+                    self.nodes.push(SpatialNode::Leaf(half));
+                    self.nodes[index] = SpatialNode::Split {
+                        axis,
+                        position,
+                        children,
+                    };
+                    // Revisit the node to split the halves further if needed.
+                    stack.push((index, bounds, depth));
+                }
+                SpatialNode::Leaf(_) => (),
+            }
+        }
+
+        for node in &mut self.nodes {
+            if let SpatialNode::Leaf(leaf) = node {
+                *leaf = GuideLeaf::new(leaf.recording.clone());
+            }
+        }
+    }
+}
+
+impl GuideLeaf {
+    /// Leaf sampling `learned`, recording into a tree refined from it.
+    fn new(learned: DirectionTree) -> Self {
+        Self {
+            recording: learned.refined(),
+            sampling:  learned,
+            samples:   0,
+        }
+    }
+}
+
+impl DirectionTree {
+    fn new() -> Self {
+        Self {
+            nodes: vec![QuadNode::default()],
+        }
+    }
+
+    fn total(&self) -> Float {
+        self.nodes[0].energy.iter().sum()
+    }
+
+    fn record(&mut self, direction: Vec3, value: Float) {
+        let mut point = direction_to_square(direction);
+        let mut index = 0;
+        loop {
+            let quadrant = descend(&mut point);
+            let node = &mut self.nodes[index];
+            node.energy[quadrant] += value;
+            match node.children[quadrant] {
+                0 => break,
+                child => index = child,
+            }
+        }
+    }
+
+    /// Tree of the same light with quadrants holding more than
+    /// [`SUBDIVISION_FRACTION`] of it split and the others merged, its
+    /// energies reset for recording.
+    fn refined(&self) -> Self {
+        let mut tree = Self::new();
+        let threshold = self.total() * SUBDIVISION_FRACTION;
+        if threshold <= 0.0 {
+            return tree;
+        }
+
+        // New node, matching node of this tree if any, its energies, depth.
+        let mut stack = vec![(0, Some(0), self.nodes[0].energy, 1)];
+        while let Some((index, old, energy, depth)) = stack.pop() {
+            for (quadrant, &energy) in energy.iter().enumerate() {
+                if energy <= threshold || depth >= MAX_DIRECTION_DEPTH {
+                    continue;
+                }
+                let child = tree.nodes.len();
+                tree.nodes.push(QuadNode::default());
+                tree.nodes[index].children[quadrant] = child;
+
+                let old_child = old
+                    .map(|old| self.nodes[old].children[quadrant])
+                    .filter(|&child| child != 0);
+                let child_energy = match old_child {
+                    Some(old_child) => self.nodes[old_child].energy,
+                    None => [energy / 4.0; 4],
+                };
+                stack.push((child, old_child, child_energy, depth + 1));
+            }
+        }
+        tree
+    }
+
+    /// Density per solid angle of [`DirectionTree::sample`] generating
+    /// `direction`.
+    pub(crate) fn pdf(&self, direction: Vec3) -> Float {
+        let mut point = direction_to_square(direction);
+        let mut pdf = 1.0 / (4.0 * PI);
+        let mut index = 0;
+        loop {
+            let node = &self.nodes[index];
+            let total: Float = node.energy.iter().sum();
+            if total <= 0.0 {
+                return 0.0;
+            }
+            let quadrant = descend(&mut point);
+            pdf *= 4.0 * node.energy[quadrant] / total;
+            match node.children[quadrant] {
+                0 => return pdf,
+                child => index = child,
+            }
+        }
+    }
+
+    /// Samples a direction with a density proportional to the light the
+    /// tree holds.
+    pub(crate) fn sample(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        let (mut x, mut y, mut size) = (0.0, 0.0, 1.0);
+        let mut index = 0;
+        loop {
+            let node = &self.nodes[index];
+            let total: Float = node.energy.iter().sum();
+            let mut target = sampler.next_1d() * total;
+            let mut quadrant = 3;
+            for (candidate, &energy) in node.energy.iter().enumerate() {
+                if target < energy {
+                    quadrant = candidate;
+                    break;
+                }
+                target -= energy;
+            }
+
+            size *= 0.5;
+            x += size * (quadrant % 2) as Float;
+            y += size * (quadrant / 2) as Float;
+            match node.children[quadrant] {
+                0 => break,
+                child => index = child,
+            }
+        }
+        let x = x + size * sampler.next_1d();
+        let y = y + size * sampler.next_1d();
+        square_to_direction(x, y)
+    }
+}
+
+impl GuideVertex {
+    pub(crate) fn new(
+        point: Vec3,
+        direction: Vec3,
+        pdf: Float,
+        throughput: Vec3,
+        radiance: Vec3,
+    ) -> Self {
+        Self {
+            point,
+            direction,
+            pdf,
+            throughput,
+            radiance,
+        }
+    }
+
+    /// Sample of the light arriving from the direction of the bounce, given
+    /// the light its whole path gathered.
+    fn sample(&self, radiance: Vec3) -> Option<GuideSample> {
+        let gathered = radiance - self.radiance;
+        let incident = |gathered: Float, throughput: Float| {
+            if throughput > 0.0 {
+                gathered / throughput
+            } else {
+                0.0
+            }
+        };
+        let incident = Vec3::new(
+            incident(gathered.x(), self.throughput.x()),
+            incident(gathered.y(), self.throughput.y()),
+            incident(gathered.z(), self.throughput.z()),
+        );
+        let value = incident.luminance() / self.pdf;
+        (value.is_finite() && value > 0.0).then_some(GuideSample {
+            point: self.point,
+            direction: self.direction,
+            value,
+        })
+    }
+}
+
+impl GuidedPathTracer {
+    pub const fn new(guide: PathGuide) -> Self {
+        Self { guide }
+    }
+
+    pub const fn guide(&self) -> &PathGuide {
+        &self.guide
+    }
+}
+
+impl Integrator for GuidedPathTracer {
+    fn radiance<T: Hit + ?Sized, L: Hit + ?Sized>(
+        &self,
+        camera: &Camera,
+        ray: &Ray,
+        world: &T,
+        lights: &L,
+        max_depth: u32,
+        sampler: &mut dyn Sampler,
+    ) -> (Vec3, FirstHit)
+    where
+        T::Material: Copy,
+    {
+        let transport = Transport::Guided {
+            guide:    &self.guide,
+            vertices: None,
+        };
+        trace_path(
+            camera, ray, world, lights, max_depth, transport, sampler, None,
+        )
+    }
+}
+
+/// Density per solid angle of the guided strategy sampling `direction`,
+/// whose material density is `scattering_pdf`, or `scattering_pdf` itself
+/// without a guide.
+pub(crate) fn mixed_pdf(
+    guide: Option<&DirectionTree>,
+    scattering_pdf: Float,
+    direction: Vec3,
+) -> Float {
+    guide.map_or(scattering_pdf, |guide| {
+        MATERIAL_FRACTION * scattering_pdf + (1.0 - MATERIAL_FRACTION) * guide.pdf(direction)
+    })
+}
+
+/// Resamples the diffuse bounce `scattered` off `hit`, sampled by the
+/// material, from the mixture of the material and `guide`. Returns the
+/// ratio of the material density of the new bounce to the mixture one,
+/// which scales its attenuation, the bounce and the mixture density.
+pub(crate) fn guided_scatter<M: Scatter>(
+    camera: &Camera,
+    ray: &Ray,
+    hit: &HitRecord<M>,
+    guide: &DirectionTree,
+    scattered: Ray,
+    sampler: &mut dyn Sampler,
+) -> (Float, Ray, Float) {
+    let scattered = if sampler.next_1d() < MATERIAL_FRACTION {
+        scattered
+    } else {
+        hit.spawn_ray(guide.sample(sampler), ray.time())
+    };
+    let scattering_pdf = camera.scattering_pdf(ray, hit, &scattered);
+    let pdf = mixed_pdf(Some(guide), scattering_pdf, scattered.direction());
+    if pdf <= 0.0 {
+        return (0.0, scattered, 0.0);
+    }
+    (scattering_pdf / pdf, scattered, pdf)
+}
+
+/// Halves of `bounds` on either side of `position` along `axis`.
+fn split_bounds(bounds: Aabb, axis: Axis, position: Float) -> [Aabb; 2] {
+    let (x, y, z) = (bounds.x(), bounds.y(), bounds.z());
+    let below = |interval: Interval| interval.with_max(position);
+    let above = |interval: Interval| interval.with_min(position);
+    match axis {
+        Axis::X => [Aabb::new(below(x), y, z), Aabb::new(above(x), y, z)],
+        Axis::Y => [Aabb::new(x, below(y), z), Aabb::new(x, above(y), z)],
+        Axis::Z => [Aabb::new(x, y, below(z)), Aabb::new(x, y, above(z))],
+    }
+}
+
+/// Quadrant of the unit square `point` lies in, moving `point` into the
+/// unit square of that quadrant.
+fn descend(point: &mut (Float, Float)) -> usize {
+    let right = point.0 >= 0.5;
+    let top = point.1 >= 0.5;
+    point.0 = 2.0 * point.0 - Float::from(u8::from(right));
+    point.1 = 2.0 * point.1 - Float::from(u8::from(top));
+    usize::from(right) + 2 * usize::from(top)
+}
+
+/// Maps `direction` to the unit square by its height and angle around the
+/// z axis, which preserves areas.
+fn direction_to_square(direction: Vec3) -> (Float, Float) {
+    let direction = direction.unit();
+    let cos_theta = direction.z().clamp(-1.0, 1.0);
+    let phi = direction.y().atan2(direction.x());
+    let x = (cos_theta + 1.0) * 0.5;
+    let y = ((phi + PI) / (2.0 * PI)).clamp(0.0, 1.0);
+    (x.min(1.0 - Float::EPSILON), y.min(1.0 - Float::EPSILON))
+}
+
+fn square_to_direction(x: Float, y: Float) -> Vec3 {
+    let cos_theta = 2.0 * x - 1.0;
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * y - PI;
+    Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+}
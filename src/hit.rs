@@ -1,40 +1,173 @@
-use std::{fmt::Debug, sync::Arc};
+use std::{collections::HashMap, fmt::Debug, mem, slice, sync::Arc, vec};
 
 use crate::{
+    Float,
     aabb::Aabb,
     interval::Interval,
-    material::{Material, Scatter},
-    ray::Ray,
+    material::{Dielectric, Material, Scatter},
+    ray::{Ray, RayKind},
+    sampler::{Sampler, hash_seed},
+    vec2::Vec2,
     vec3::Vec3,
 };
 
+/// Distance rays spawned at a hit start off the surface, relative to the
+/// magnitude of the hit point.
+const RAY_OFFSET: Float = if cfg!(feature = "f32") { 1e-4 } else { 1e-9 };
+
+/// Smallest cosine between a ray and the surface it hits used to stretch the
+/// ray's footprint, so grazing hits get a long but finite one.
+const MIN_FOOTPRINT_COS: Float = 1e-3;
+
+/// Stable identifier of an object pushed into a [`HitList`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ObjectId(u32);
+
+/// Sides of a surface that count, the front being the side its geometric
+/// normal points to, or the outside of closed surfaces.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Sides {
+    Front,
+    #[default]
+    Both,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct HitRecord<T> {
     point:      Vec3,
     normal:     Vec3,
-    t:          f64,
+    t:          Float,
+    uv:         Vec2,
+    uv_density: Float,
+    footprint:  Float,
     material:   T,
     front_face: bool,
+    object_id:  Option<ObjectId>,
+    emission:   Option<Vec3>,
+}
+
+impl ObjectId {
+    pub const fn new(index: u32) -> Self {
+        Self(index)
+    }
+
+    pub const fn index(self) -> u32 {
+        self.0
+    }
+
+    /// Pseudo-random color derived from the id, for visualizing ID passes.
+    pub fn color(self) -> Vec3 {
+        let mut h = self.0.wrapping_mul(0x9e37_79b9) ^ 0x85eb_ca6b;
+        h ^= h >> 16;
+        h = h.wrapping_mul(0x7feb_352d);
+        h ^= h >> 15;
+        h = h.wrapping_mul(0x846c_a68b);
+        h ^= h >> 16;
+
+        let channel = |shift: u32| ((h >> shift) & 0xff) as Float / 255.0;
+        Vec3::new(channel(0), channel(8), channel(16))
+    }
+}
+
+impl Sides {
+    /// Whether the front side, or the back side if `!front_face`, counts.
+    pub const fn includes(self, front_face: bool) -> bool {
+        front_face || matches!(self, Self::Both)
+    }
 }
 
 impl<T: Scatter> HitRecord<T> {
-    pub fn new(point: Vec3, normal: Vec3, t: f64, ray: &Ray, material: T) -> Self {
+    pub fn new(point: Vec3, normal: Vec3, t: Float, ray: &Ray, material: T) -> Self {
         let front_face = ray.direction().dot(normal) < 0.0;
         let normal = if front_face { normal } else { -normal };
+        let footprint = if ray.spread() > 0.0 {
+            let cos = ray.direction().unit().dot(normal).abs();
+            ray.spread() * t / cos.max(MIN_FOOTPRINT_COS)
+        } else {
+            0.0
+        };
 
         Self {
             point,
             normal,
             t,
+            uv: Vec2::default(),
+            uv_density: 0.0,
+            footprint,
             front_face,
             material,
+            object_id: None,
+            emission: None,
         }
     }
 
-    pub fn t(&self) -> f64 {
+    /// Record of `point` on a surface with outward `normal`, seen from the
+    /// front at `time`, for points sampled on emitters.
+    pub(crate) fn facing(point: Vec3, normal: Vec3, time: Float, material: T) -> Self {
+        let ray = Ray::new(point + normal, -normal, time);
+        Self::new(point, normal, 1.0, &ray, material)
+    }
+
+    /// Tags the record with the id of the object that was hit.
+    pub fn with_object_id(mut self, id: ObjectId) -> Self {
+        self.object_id = Some(id);
+        self
+    }
+
+    /// Sets the texture coordinates of the hit point, `(0, 0)` by default.
+    pub fn with_uv(mut self, uv: Vec2) -> Self {
+        self.uv = uv;
+        self
+    }
+
+    /// Sets how many units of texture coordinates a unit of length on the
+    /// surface around the hit point spans, zero by default.
+    pub fn with_uv_density(mut self, density: Float) -> Self {
+        self.uv_density = density;
+        self
+    }
+
+    /// Sets the light emitted at the hit point in place of the material's,
+    /// for media whose emission varies over their volume. Unlike emitting
+    /// materials, it is never sampled as a light.
+    pub fn with_emission(mut self, emitted: Vec3) -> Self {
+        self.emission = Some(emitted);
+        self
+    }
+
+    /// Moves the record to another `point` and `normal`, keeping which side
+    /// of the surface was hit. Used by wrappers hitting objects in their own
+    /// space.
+    pub fn with_geometry(mut self, point: Vec3, normal: Vec3) -> Self {
+        self.point = point;
+        self.normal = normal;
+        self
+    }
+
+    pub fn object_id(&self) -> Option<ObjectId> {
+        self.object_id
+    }
+
+    pub fn t(&self) -> Float {
         self.t
     }
 
+    pub fn uv(&self) -> Vec2 {
+        self.uv
+    }
+
+    /// Width in texture coordinates of the footprint of the ray on the
+    /// surface, zero for rays without spread.
+    pub fn uv_footprint(&self) -> Float {
+        self.footprint * self.uv_density
+    }
+
+    pub fn material(&self) -> &T {
+        &self.material
+    }
+
     pub fn front_face(&self) -> bool {
         self.front_face
     }
@@ -47,12 +180,77 @@ impl<T: Scatter> HitRecord<T> {
         self.normal
     }
 
-    pub fn scatter(&self, ray: &Ray) -> Option<(Vec3, Ray)> {
-        self.material.scatter(ray, self)
+    /// Ray leaving the hit point towards `direction`.
+    ///
+    /// The origin is moved off the surface along the normal, to the side the
+    /// ray leaves through, so rounding errors in the hit point can't make the
+    /// ray hit the same surface again. The offset grows with the distance
+    /// from the world origin, as the rounding errors do.
+    ///
+    /// The ray is a [`RayKind::Reflection`] one; shadow rays are marked as
+    /// such by the caller.
+    pub fn spawn_ray(&self, direction: Vec3, time: Float) -> Ray {
+        let magnitude = self.point.abs();
+        let scale = magnitude.x().max(magnitude.y()).max(magnitude.z()).max(1.0);
+        let offset = self.normal * (RAY_OFFSET * scale);
+        let origin = if direction.dot(self.normal) >= 0.0 {
+            self.point + offset
+        } else {
+            self.point - offset
+        };
+        Ray::new(origin, direction, time).with_kind(RayKind::Reflection)
+    }
+
+    pub fn scatter(&self, ray: &Ray, sampler: &mut dyn Sampler) -> Option<(Vec3, Ray)> {
+        self.material.scatter(ray, self, sampler)
     }
 
+    /// Light emitted towards the ray, `None` when hitting the back of an
+    /// emitter that only emits from its front.
     pub fn emit(&self) -> Option<Vec3> {
-        self.material.emit()
+        self.emission.or_else(|| {
+            self.material
+                .emit()
+                .filter(|_| self.material.emission_sides().includes(self.front_face))
+        })
+    }
+
+    /// Whether `ray` stops at the hit rather than passing through a cutout,
+    /// drawn with the opacity of the material as probability. The draw is
+    /// hashed from the ray and the hit distance, so that every test of the
+    /// same ray against the same surface agrees.
+    #[allow(clippy::unnecessary_cast, reason = "`Float` may be `f32`")]
+    pub fn is_opaque(&self, ray: &Ray) -> bool {
+        let opacity = self.material.opacity_at(self.uv, self.uv_footprint());
+        if opacity >= 1.0 {
+            return true;
+        }
+        let (origin, direction) = (ray.origin(), ray.direction());
+        let bits = [
+            origin.x(),
+            origin.y(),
+            origin.z(),
+            direction.x(),
+            direction.y(),
+            direction.z(),
+            self.t,
+        ]
+        .map(|value| (value as f64).to_bits());
+        let u = (hash_seed(&bits) >> 11) as Float / (1u64 << 53) as Float;
+        u < opacity
+    }
+
+    /// Whether the material emits light, from either side.
+    pub fn is_emitter(&self) -> bool {
+        self.material.emit().is_some()
+    }
+
+    pub fn scattering_pdf(&self, ray: &Ray, scattered: &Ray) -> Float {
+        self.material.scattering_pdf(ray, self, scattered)
+    }
+
+    pub fn dielectric(&self) -> Option<Dielectric> {
+        self.material.dielectric()
     }
 }
 
@@ -62,28 +260,206 @@ pub trait Hit {
     fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<Self::Material>>;
     fn aabb(&self) -> Aabb;
     fn count(&self) -> usize;
+
+    /// Whether anything is hit within `ray_t`. Unlike [`Hit::hit`], may stop
+    /// at the first intersection found, which is all shadow rays need.
+    fn hit_any(&self, ray: &Ray, ray_t: Interval) -> bool {
+        self.hit(ray, ray_t).is_some()
+    }
+
+    /// Solid angle density, as seen from `origin` at `time`, of sampling
+    /// `direction` with [`Hit::random_to`].
+    fn pdf_value(&self, _origin: Vec3, _direction: Vec3, _time: Float) -> Float {
+        0.0
+    }
+
+    /// Samples a direction from `origin` towards a point of the object where
+    /// it is at `time`.
+    fn random_to(&self, _origin: Vec3, _time: Float, _sampler: &mut dyn Sampler) -> Vec3 {
+        Vec3::new(1.0, 0.0, 0.0)
+    }
+
+    /// Total power emitted by the object, used to balance light sampling.
+    fn power(&self) -> Float {
+        0.0
+    }
+
+    /// Samples a point of the surface at `time`, for emitting light from
+    /// it. Returns the point seen from the front together with its density
+    /// over the surface area, or `None` for objects that can't be sampled.
+    fn sample_surface(
+        &self,
+        _time: Float,
+        _sampler: &mut dyn Sampler,
+    ) -> Option<(HitRecord<Self::Material>, Float)> {
+        None
+    }
+
+    /// Id of the object in the [`HitList`] it was pushed into, if it is an
+    /// entry of one.
+    fn object_id(&self) -> Option<ObjectId> {
+        None
+    }
+
+    /// Appends triangles approximating the surface to `triangles`, for
+    /// exporting the scene with [`obj`](crate::obj). Objects that can't be
+    /// triangulated add nothing.
+    fn triangulate(&self, _triangles: &mut Vec<[Vec3; 3]>) {}
 }
 
-pub trait DynHit: Hit<Material = Material> + Send + Sync + Debug {}
-impl<T: Hit<Material = Material> + Send + Sync + Debug> DynHit for T {
+impl<T: Hit + ?Sized> Hit for Arc<T> {
+    type Material = T::Material;
+
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<Self::Material>> {
+        (**self).hit(ray, ray_t)
+    }
+
+    fn aabb(&self) -> Aabb {
+        (**self).aabb()
+    }
+
+    fn hit_any(&self, ray: &Ray, ray_t: Interval) -> bool {
+        (**self).hit_any(ray, ray_t)
+    }
+
+    fn count(&self) -> usize {
+        (**self).count()
+    }
+
+    fn pdf_value(&self, origin: Vec3, direction: Vec3, time: Float) -> Float {
+        (**self).pdf_value(origin, direction, time)
+    }
+
+    fn random_to(&self, origin: Vec3, time: Float, sampler: &mut dyn Sampler) -> Vec3 {
+        (**self).random_to(origin, time, sampler)
+    }
+
+    fn power(&self) -> Float {
+        (**self).power()
+    }
+
+    fn sample_surface(
+        &self,
+        time: Float,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(HitRecord<Self::Material>, Float)> {
+        (**self).sample_surface(time, sampler)
+    }
+
+    fn object_id(&self) -> Option<ObjectId> {
+        (**self).object_id()
+    }
+
+    fn triangulate(&self, triangles: &mut Vec<[Vec3; 3]>) {
+        (**self).triangulate(triangles);
+    }
 }
 
+pub trait DynHit: Hit<Material = Material> + Send + Sync + Debug {}
+impl<T: Hit<Material = Material> + Send + Sync + Debug> DynHit for T {}
+
+#[derive(Debug)]
 pub struct HitList {
-    list: Vec<Arc<dyn DynHit>>,
-    bbox: Aabb,
+    list:    Vec<Arc<dyn DynHit>>,
+    bbox:    Aabb,
+    next_id: u32,
+    names:   HashMap<ObjectId, String>,
+}
+
+/// Object pushed into a [`HitList`], tagging its hits with its id.
+#[derive(Debug)]
+struct Identified<T> {
+    id:     ObjectId,
+    object: T,
+}
+
+impl Default for HitList {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl HitList {
     pub fn new() -> Self {
         Self {
-            list: Vec::new(),
-            bbox: Aabb::default(),
+            list:    Vec::new(),
+            bbox:    Aabb::empty(),
+            next_id: 0,
+            names:   HashMap::new(),
         }
     }
 
-    pub fn push<T: DynHit + 'static>(&mut self, v: T) {
+    /// Adds an object to the list, returning the id its hits are tagged
+    /// with.
+    pub fn push<T: DynHit + 'static>(&mut self, v: T) -> ObjectId {
+        let id = ObjectId::new(self.next_id);
+        self.next_id += 1;
+
         self.bbox = self.bbox.merge(v.aabb());
-        self.list.push(Arc::new(v));
+        self.list.push(Arc::new(Identified { id, object: v }));
+        id
+    }
+
+    /// Adds an object under `name`, by which debug output and loaders can
+    /// refer to it. Names need not be unique.
+    pub fn push_named<T: DynHit + 'static>(&mut self, name: impl Into<String>, v: T) -> ObjectId {
+        let id = self.push(v);
+        self.names.insert(id, name.into());
+        id
+    }
+
+    /// Name the object with `id` was pushed under.
+    pub fn name(&self, id: ObjectId) -> Option<&str> {
+        self.names.get(&id).map(String::as_str)
+    }
+
+    /// Id of the first object pushed under `name`.
+    pub fn find(&self, name: &str) -> Option<ObjectId> {
+        self.names
+            .iter()
+            .filter(|(_, n)| *n == name)
+            .map(|(&id, _)| id)
+            .min()
+    }
+
+    /// Entry of the object pushed with `id`, as stored in acceleration
+    /// structures built over the list.
+    pub fn get(&self, id: ObjectId) -> Option<&Arc<dyn DynHit>> {
+        self.list.iter().find(|obj| obj.object_id() == Some(id))
+    }
+
+    /// Removes the object pushed with `id`, keeping the order of the others.
+    /// Ids are not reused.
+    pub fn remove(&mut self, id: ObjectId) -> Option<Arc<dyn DynHit>> {
+        let index = self.position(id)?;
+        let removed = self.list.remove(index);
+        self.names.remove(&id);
+        self.update_bbox();
+        Some(removed)
+    }
+
+    /// Replaces the object pushed with `id` by `v`, which keeps the id.
+    /// Returns the previous entry, or `None` without adding `v` if there is
+    /// no object with `id`.
+    pub fn replace<T: DynHit + 'static>(&mut self, id: ObjectId, v: T) -> Option<Arc<dyn DynHit>> {
+        let index = self.position(id)?;
+        let replaced = mem::replace(
+            &mut self.list[index],
+            Arc::new(Identified { id, object: v }),
+        );
+        self.update_bbox();
+        Some(replaced)
+    }
+
+    fn position(&self, id: ObjectId) -> Option<usize> {
+        self.list.iter().position(|obj| obj.object_id() == Some(id))
+    }
+
+    fn update_bbox(&mut self) {
+        self.bbox = self
+            .list
+            .iter()
+            .fold(Aabb::empty(), |bbox, obj| bbox.merge(obj.aabb()));
     }
 
     pub fn list(&self) -> &[Arc<dyn DynHit>] {
@@ -93,6 +469,106 @@ impl HitList {
     pub fn list_mut(&mut self) -> &mut [Arc<dyn DynHit>] {
         &mut self.list
     }
+
+    pub fn iter(&self) -> slice::Iter<'_, Arc<dyn DynHit>> {
+        self.list.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+}
+
+impl<T: DynHit + 'static> FromIterator<T> for HitList {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut list = Self::new();
+        list.extend(iter);
+        list
+    }
+}
+
+/// Pushes every object, as with [`HitList::push`].
+impl<T: DynHit + 'static> Extend<T> for HitList {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.list.reserve(iter.size_hint().0);
+        for object in iter {
+            self.push(object);
+        }
+    }
+}
+
+impl IntoIterator for HitList {
+    type Item = Arc<dyn DynHit>;
+    type IntoIter = vec::IntoIter<Arc<dyn DynHit>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.list.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a HitList {
+    type Item = &'a Arc<dyn DynHit>;
+    type IntoIter = slice::Iter<'a, Arc<dyn DynHit>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.list.iter()
+    }
+}
+
+impl<T: Hit> Hit for Identified<T> {
+    type Material = T::Material;
+
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<Self::Material>> {
+        self.object
+            .hit(ray, ray_t)
+            .map(|rec| rec.with_object_id(self.id))
+    }
+
+    fn aabb(&self) -> Aabb {
+        self.object.aabb()
+    }
+
+    fn hit_any(&self, ray: &Ray, ray_t: Interval) -> bool {
+        self.object.hit_any(ray, ray_t)
+    }
+
+    fn count(&self) -> usize {
+        self.object.count()
+    }
+
+    fn pdf_value(&self, origin: Vec3, direction: Vec3, time: Float) -> Float {
+        self.object.pdf_value(origin, direction, time)
+    }
+
+    fn random_to(&self, origin: Vec3, time: Float, sampler: &mut dyn Sampler) -> Vec3 {
+        self.object.random_to(origin, time, sampler)
+    }
+
+    fn power(&self) -> Float {
+        self.object.power()
+    }
+
+    fn sample_surface(
+        &self,
+        time: Float,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(HitRecord<Self::Material>, Float)> {
+        let (rec, pdf) = self.object.sample_surface(time, sampler)?;
+        Some((rec.with_object_id(self.id), pdf))
+    }
+
+    fn object_id(&self) -> Option<ObjectId> {
+        Some(self.id)
+    }
+
+    fn triangulate(&self, triangles: &mut Vec<[Vec3; 3]>) {
+        self.object.triangulate(triangles);
+    }
 }
 
 impl Hit for HitList {
@@ -102,7 +578,7 @@ impl Hit for HitList {
         let mut closest = ray_t.max();
 
         for obj in self.list.iter() {
-            if let Some(hit) = obj.hit(ray, Interval::new(ray_t.min(), closest)) {
+            if let Some(hit) = obj.hit(ray, ray_t.with_max(closest)) {
                 closest = hit.t;
                 rec = Some(hit);
             }
@@ -111,6 +587,10 @@ impl Hit for HitList {
         rec
     }
 
+    fn hit_any(&self, ray: &Ray, ray_t: Interval) -> bool {
+        self.list.iter().any(|obj| obj.hit_any(ray, ray_t))
+    }
+
     fn aabb(&self) -> Aabb {
         self.bbox
     }
@@ -118,4 +598,44 @@ impl Hit for HitList {
     fn count(&self) -> usize {
         self.list.len()
     }
+
+    fn pdf_value(&self, origin: Vec3, direction: Vec3, time: Float) -> Float {
+        if self.list.is_empty() {
+            return 0.0;
+        }
+        let sum: Float = self
+            .list
+            .iter()
+            .map(|obj| obj.pdf_value(origin, direction, time))
+            .sum();
+        sum / self.list.len() as Float
+    }
+
+    fn random_to(&self, origin: Vec3, time: Float, sampler: &mut dyn Sampler) -> Vec3 {
+        let index = (sampler.next_1d() * self.list.len() as Float) as usize;
+        self.list[index.min(self.list.len() - 1)].random_to(origin, time, sampler)
+    }
+
+    fn power(&self) -> Float {
+        self.list.iter().map(|obj| obj.power()).sum()
+    }
+
+    fn sample_surface(
+        &self,
+        time: Float,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(HitRecord<Self::Material>, Float)> {
+        if self.list.is_empty() {
+            return None;
+        }
+        let index = (sampler.next_1d() * self.list.len() as Float) as usize;
+        let (rec, pdf) = self.list[index.min(self.list.len() - 1)].sample_surface(time, sampler)?;
+        Some((rec, pdf / self.list.len() as Float))
+    }
+
+    fn triangulate(&self, triangles: &mut Vec<[Vec3; 3]>) {
+        for obj in &self.list {
+            obj.triangulate(triangles);
+        }
+    }
 }
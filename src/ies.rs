@@ -0,0 +1,300 @@
+//! IES LM-63 photometric profiles: the measured intensity of real light
+//! fixtures in every direction, attached to point and spot lights with
+//! [`Light::with_profile`](crate::light::Light::with_profile).
+//!
+//! Only type C photometry is read, the kind of nearly all interior and
+//! street fixtures. Vertical angles go from 0 degrees straight down the
+//! fixture's axis to 180 straight up, and horizontal angles turn around it.
+//! Tilt data, for lamps whose output changes with their inclination, is
+//! skipped.
+
+use std::{
+    fs::File,
+    io::{self, BufReader, Read},
+    path::Path,
+};
+
+use crate::{
+    Float,
+    error::{Error, Result},
+    vec3::Vec3,
+};
+
+/// Steps of the polar and azimuthal angles over which the average intensity
+/// of a profile is integrated.
+const AVERAGE_STEPS: [usize; 2] = [90, 180];
+
+/// Most angles or tilt entries a file may list along one axis, far more than
+/// any measurement but few enough that counts can be multiplied safely.
+const MAX_COUNT: usize = 1 << 16;
+
+/// Candela values of a fixture over a grid of vertical and horizontal
+/// angles, normalized so that the brightest is one.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IesProfile {
+    vertical:    Vec<Float>,
+    horizontal:  Vec<Float>,
+    candela:     Vec<Float>,
+    max_candela: Float,
+    average:     Float,
+}
+
+impl IesProfile {
+    /// # Panics
+    ///
+    /// Panics if the angles or values are invalid, see
+    /// [`IesProfile::try_new`].
+    pub fn new(vertical: Vec<Float>, horizontal: Vec<Float>, candela: Vec<Float>) -> Self {
+        Self::try_new(vertical, horizontal, candela).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Profile measured at the `vertical` and `horizontal` angles, in
+    /// degrees, with the `candela` values of each horizontal angle in turn
+    /// listing every vertical angle. Fails unless both angle lists are
+    /// non-empty and increasing, vertical angles lie in `[0, 180]`,
+    /// horizontal ones in `[0, 360]`, and there are as many finite,
+    /// non-negative values as angle pairs, not all zero.
+    ///
+    /// Horizontal angles ending at 0, 90 or 180 degrees stand for fixtures
+    /// symmetric around their axis, in each quadrant or about a plane.
+    pub fn try_new(
+        vertical: Vec<Float>,
+        horizontal: Vec<Float>,
+        mut candela: Vec<Float>,
+    ) -> Result<Self> {
+        let increasing = |angles: &[Float], max: Float| {
+            !angles.is_empty()
+                && angles.windows(2).all(|pair| pair[0] < pair[1])
+                && angles.iter().all(|&angle| (0.0..=max).contains(&angle))
+        };
+        if !increasing(&vertical, 180.0) || !increasing(&horizontal, 360.0) {
+            return Err(Error::InvalidParameter(
+                "profile angles must be increasing and within range",
+            ));
+        }
+        if candela.len() != vertical.len() * horizontal.len() {
+            return Err(Error::InvalidParameter(
+                "profile needs one candela value per pair of angles",
+            ));
+        }
+        if !candela
+            .iter()
+            .all(|&value| value.is_finite() && value >= 0.0)
+        {
+            return Err(Error::InvalidParameter(
+                "profile candela values must be finite and non-negative",
+            ));
+        }
+        let max_candela = candela.iter().copied().fold(0.0, Float::max);
+        if max_candela <= 0.0 {
+            return Err(Error::InvalidParameter("profile emits no light"));
+        }
+        for value in &mut candela {
+            *value /= max_candela;
+        }
+
+        let mut profile = Self {
+            vertical,
+            horizontal,
+            candela,
+            max_candela,
+            average: 0.0,
+        };
+        profile.average = profile.integrate_average();
+        Ok(profile)
+    }
+
+    /// Loads an IES LM-63 file.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        Self::from_ies(BufReader::new(file))
+    }
+
+    /// Reads an IES LM-63 profile, of any of its 1986 to 2002 revisions.
+    pub fn from_ies<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+
+        let mut lines = text.lines();
+        let tilt = lines
+            .by_ref()
+            .find_map(|line| line.trim().strip_prefix("TILT="))
+            .ok_or_else(|| invalid_data("missing IES TILT line"))?;
+        let mut numbers = lines
+            .flat_map(|line| line.split([' ', '\t', ',']))
+            .filter(|token| !token.is_empty())
+            .map(|token| {
+                token
+                    .parse::<Float>()
+                    .map_err(|_| invalid_data("invalid number in IES data"))
+            });
+        let mut next = || {
+            numbers
+                .next()
+                .unwrap_or_else(|| Err(invalid_data("truncated IES data")))
+        };
+
+        if tilt.trim() == "INCLUDE" {
+            // Lamp to luminaire geometry, then the tilt angles and factors.
+            next()?;
+            let count = to_count(next()?)?;
+            for _ in 0..2 * count {
+                next()?;
+            }
+        }
+
+        let _lamps = next()?;
+        let _lumens = next()?;
+        let multiplier = next()?;
+        let vertical_count = to_count(next()?)?;
+        let horizontal_count = to_count(next()?)?;
+        let photometric_type = next()?;
+        // Units and the sizes of the luminous opening, then the ballast
+        // factor, a reserved value and the input watts.
+        for _ in 0..7 {
+            next()?;
+        }
+        if photometric_type != 1.0 {
+            return Err(invalid_data("only type C IES photometry is supported"));
+        }
+
+        let vertical = (0..vertical_count)
+            .map(|_| next())
+            .collect::<io::Result<Vec<_>>>()?;
+        let horizontal = (0..horizontal_count)
+            .map(|_| next())
+            .collect::<io::Result<Vec<_>>>()?;
+        let candela_count = vertical_count
+            .checked_mul(horizontal_count)
+            .ok_or_else(|| invalid_data("too many IES candela values"))?;
+        let candela = (0..candela_count)
+            .map(|_| next().map(|value| value * multiplier))
+            .collect::<io::Result<Vec<_>>>()?;
+        Self::try_new(vertical, horizontal, candela).map_err(|err| invalid_data(&err.to_string()))
+    }
+
+    /// Vertical angles of the measurements, in degrees from straight down.
+    pub fn vertical_angles(&self) -> &[Float] {
+        &self.vertical
+    }
+
+    /// Horizontal angles of the measurements, in degrees around the axis.
+    pub fn horizontal_angles(&self) -> &[Float] {
+        &self.horizontal
+    }
+
+    /// Brightest measured intensity, in candela, which the profile is
+    /// normalized to.
+    pub const fn max_candela(&self) -> Float {
+        self.max_candela
+    }
+
+    /// Average of [`IesProfile::value`] over all directions, the fraction of
+    /// the power of a uniform light of the peak intensity emitted.
+    pub const fn average(&self) -> Float {
+        self.average
+    }
+
+    /// Intensity, relative to the brightest, towards `direction` of a
+    /// fixture pointing down `axis`, both unit vectors. Horizontal angles
+    /// turn around the axis from the direction across it closest to `+x`
+    /// towards `axis × +x`, or from `+z` for axes along `x`.
+    pub fn value(&self, direction: Vec3, axis: Vec3) -> Float {
+        let across = if axis.x().abs() > 0.9 {
+            Vec3::new(0.0, 0.0, 1.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let reference = (across - axis * axis.dot(across)).unit();
+        let side = axis.cross(reference);
+
+        let vertical = direction.dot(axis).clamp(-1.0, 1.0).acos().to_degrees();
+        let horizontal = direction
+            .dot(side)
+            .atan2(direction.dot(reference))
+            .to_degrees()
+            .rem_euclid(360.0);
+        self.value_at(vertical, horizontal)
+    }
+
+    /// Intensity, relative to the brightest, at `vertical` degrees from
+    /// straight down and `horizontal` degrees around the axis, interpolated
+    /// bilinearly. Directions beyond the measured vertical angles are dark.
+    pub fn value_at(&self, vertical: Float, horizontal: Float) -> Float {
+        let (first, last) = (self.vertical[0], self.vertical[self.vertical.len() - 1]);
+        if vertical < first || vertical > last {
+            return 0.0;
+        }
+        let (v, tv) = bracket(&self.vertical, vertical);
+        let (h, th) = bracket(&self.horizontal, self.fold(horizontal));
+
+        let rows = self.vertical.len();
+        let at = |h: usize, v: usize| self.candela[h * rows + v];
+        let (v1, h1) = (
+            (v + 1).min(rows - 1),
+            (h + 1).min(self.horizontal.len() - 1),
+        );
+        let low = at(h, v) * (1.0 - tv) + at(h, v1) * tv;
+        let high = at(h1, v) * (1.0 - tv) + at(h1, v1) * tv;
+        low * (1.0 - th) + high * th
+    }
+
+    /// Horizontal angle in `[0, 360)` moved into the measured range, using
+    /// the symmetry the last measured angle implies.
+    fn fold(&self, horizontal: Float) -> Float {
+        let last = self.horizontal[self.horizontal.len() - 1];
+        let mut angle = horizontal;
+        if last <= 180.0 && angle > 180.0 {
+            angle = 360.0 - angle;
+        }
+        if last <= 90.0 && angle > 90.0 {
+            angle = 180.0 - angle;
+        }
+        angle.clamp(self.horizontal[0], last)
+    }
+
+    /// Average intensity over the sphere, summed over cells of equal solid
+    /// angle.
+    fn integrate_average(&self) -> Float {
+        let [polar, azimuthal] = AVERAGE_STEPS;
+        let mut sum = 0.0;
+        for i in 0..polar {
+            let cos = 1.0 - 2.0 * (i as Float + 0.5) / polar as Float;
+            let vertical = cos.acos().to_degrees();
+            for j in 0..azimuthal {
+                let horizontal = 360.0 * (j as Float + 0.5) / azimuthal as Float;
+                sum += self.value_at(vertical, horizontal);
+            }
+        }
+        sum / (polar * azimuthal) as Float
+    }
+}
+
+/// Index of the last of the increasing `angles` not above `angle`, and how
+/// far `angle` is towards the next one.
+fn bracket(angles: &[Float], angle: Float) -> (usize, Float) {
+    let upper = angles.partition_point(|&a| a <= angle);
+    if upper == 0 {
+        return (0, 0.0);
+    }
+    let lower = upper - 1;
+    if upper == angles.len() {
+        return (lower, 0.0);
+    }
+    let t = (angle - angles[lower]) / (angles[upper] - angles[lower]);
+    (lower, t)
+}
+
+/// Number of entries `value` read from a file gives, which must be a whole
+/// number up to [`MAX_COUNT`].
+fn to_count(value: Float) -> io::Result<usize> {
+    if value.fract() != 0.0 || !(0.0..=MAX_COUNT as Float).contains(&value) {
+        return Err(invalid_data("invalid count in IES data"));
+    }
+    Ok(value as usize)
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
@@ -0,0 +1,87 @@
+//! Two-level acceleration structure for instancing.
+//!
+//! Geometry repeated across the scene is built once into a bottom-level
+//! [`Bvh`] (BLAS) and shared behind an [`Arc`]. Each copy is an [`Instance`]
+//! placing it with a [`Transform`], and a [`Tlas`] is a BVH over the
+//! instances, so a thousand copies of a mesh cost one mesh BVH plus a
+//! thousand small nodes.
+
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    bvh::Bvh,
+    hit::{Hit, HitList, HitRecord, ObjectId},
+    interval::Interval,
+    material::Material,
+    ray::Ray,
+    transform::{Transform, Transformed},
+    vec3::Vec3,
+};
+
+/// Shared bottom-level BVH placed in the scene by a transform.
+pub type Instance = Transformed<Arc<Bvh>>;
+
+/// Top-level BVH over instances.
+#[derive(Debug, Clone)]
+pub struct Tlas {
+    bvh:   Bvh,
+    count: usize,
+}
+
+/// Places `blas` in the scene with `transform`, sharing its BVH.
+pub fn instance(blas: &Arc<Bvh>, transform: Transform) -> Instance {
+    Transformed::new(Arc::clone(blas), transform)
+}
+
+impl Tlas {
+    /// Builds the top level over `instances`, the hits of each being tagged
+    /// with an [`ObjectId`] following the iteration order.
+    pub fn new(instances: impl IntoIterator<Item = Instance>) -> Self {
+        let mut list = HitList::new();
+        let mut count = 0;
+        for instance in instances {
+            list.push(instance);
+            count += 1;
+        }
+        Self {
+            bvh: Bvh::from_list(list.list_mut()),
+            count,
+        }
+    }
+
+    /// Number of instances.
+    pub const fn instance_count(&self) -> usize {
+        self.count
+    }
+
+    /// Id the hits of instance `index` are tagged with.
+    pub fn instance_id(&self, index: usize) -> ObjectId {
+        assert!(index < self.count);
+        ObjectId::new(index as u32)
+    }
+}
+
+impl Hit for Tlas {
+    type Material = Material;
+
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<Self::Material>> {
+        self.bvh.hit(ray, ray_t)
+    }
+
+    fn hit_any(&self, ray: &Ray, ray_t: Interval) -> bool {
+        self.bvh.hit_any(ray, ray_t)
+    }
+
+    fn aabb(&self) -> Aabb {
+        self.bvh.aabb()
+    }
+
+    fn count(&self) -> usize {
+        self.bvh.count()
+    }
+
+    fn triangulate(&self, triangles: &mut Vec<[Vec3; 3]>) {
+        self.bvh.triangulate(triangles);
+    }
+}
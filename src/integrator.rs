@@ -0,0 +1,1015 @@
+//! Light transport algorithms turning camera rays into radiance.
+//!
+//! Each [`RenderMode`](crate::camera::RenderMode) is rendered by an
+//! [`Integrator`], and [`Camera::render_frame_with`] renders with any other
+//! one, so new algorithms need no changes to the camera. The
+//! [`PathTracer`] of the beauty pass handles everything but caustics from
+//! small lights, which [`PhotonMapping`] adds: photons are traced from the
+//! lights before rendering, and those reaching a diffuse surface after
+//! bouncing off mirrors or passing through glass are stored in a
+//! [`PhotonMap`]. Camera paths then read these caustics from the map instead
+//! of having to find the lights through the specular surfaces by chance,
+//! which for small lights they almost never do.
+
+use std::{collections::HashMap, ops::Range};
+
+#[cfg(feature = "parallel")]
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::{
+    Float,
+    camera::{Camera, PathVertex, power_heuristic},
+    consts::PI,
+    error::{Error, Result},
+    guiding::{self, GuideVertex, PathGuide},
+    hit::{Hit, HitRecord, ObjectId},
+    interior::Interior,
+    interval::Interval,
+    material::{Isotropic, Scatter},
+    pdf::{CosinePdf, Distribution1D, Pdf},
+    ray::{Ray, RayKind},
+    sampler::{RandomSampler, Sampler, hash_seed},
+    spectrum::{self, SampledWavelengths},
+    stats::{self, Counter},
+    vec3::Vec3,
+};
+
+/// Light transport algorithm estimating the radiance carried by camera rays.
+pub trait Integrator {
+    /// Estimates the radiance arriving at `camera` along `ray` from `world`,
+    /// whose emitters are sampled through `lights`, following paths of up
+    /// to `max_depth` bounces. Returns it together with the first hit of
+    /// `ray`, which fills the auxiliary passes.
+    fn radiance<T: Hit + ?Sized, L: Hit + ?Sized>(
+        &self,
+        camera: &Camera,
+        ray: &Ray,
+        world: &T,
+        lights: &L,
+        max_depth: u32,
+        sampler: &mut dyn Sampler,
+    ) -> (Vec3, FirstHit)
+    where
+        T::Material: Copy;
+}
+
+/// First hit of a camera ray, recorded for the auxiliary passes, together
+/// with the radiance split by [`LightPath`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FirstHit {
+    albedo:      Vec3,
+    normal:      Vec3,
+    depth:       Float,
+    id:          Option<ObjectId>,
+    light_paths: [Vec3; LightPath::ALL.len()],
+}
+
+/// Kind of path light reaches the camera along, told apart by the first
+/// bounce, so that compositors can rebalance the lighting of a render. The
+/// light path passes of a path traced [`Frame`](crate::film::Frame) add up
+/// to its beauty image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LightPath {
+    /// Emitters and the background seen directly.
+    Emission,
+    /// Light of the emitters, the background and the delta lights reflected
+    /// once off a diffuse surface.
+    DirectDiffuse,
+    /// Light bouncing more than once after a diffuse surface, caustics
+    /// included.
+    IndirectDiffuse,
+    /// Everything seen in mirrors and through glass, after a first specular
+    /// bounce.
+    Specular,
+}
+
+/// Unidirectional path tracing with multiple importance sampling of the
+/// lights and the background. Renders the beauty mode.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PathTracer;
+
+/// [`PathTracer`] gathering caustics from a [`PhotonMap`]. Renders the
+/// beauty mode of cameras with [`PhotonMapping`].
+#[derive(Debug, Clone)]
+pub struct PhotonMapper {
+    photons: PhotonMap,
+}
+
+/// Emission and direct light at the first diffuse surface seen, directly or
+/// through mirrors and glass, ignoring indirect light. Quick and nearly
+/// noise free, for checking the placement of lights.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DirectLighting;
+
+/// White unless something lies within `max_distance` of the first hit in a
+/// cosine distributed direction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AmbientOcclusion {
+    max_distance: Float,
+}
+
+/// Shading normal of the first hit, mapped from `[-1, 1]` to `[0, 1]`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DebugNormals;
+
+/// Radiance found along a path by [`trace_path`], split by [`LightPath`] as
+/// it is added.
+#[derive(Debug, Default)]
+struct PathRadiance {
+    total:         Vec3,
+    paths:         [Vec3; LightPath::ALL.len()],
+    /// Whether the first bounce was diffuse, `None` before it.
+    first_diffuse: Option<bool>,
+    bounces:       u32,
+}
+
+/// Light transport followed by [`trace_path`].
+#[derive(Debug)]
+pub(crate) enum Transport<'a> {
+    /// Every path, as the [`PathTracer`] does.
+    Full,
+    /// Caustics gathered from a photon map instead of path traced.
+    Photons(&'a PhotonMap),
+    /// Direct light only.
+    Direct,
+    /// Diffuse bounces sampled from a path guide as well as the material,
+    /// recording each of them into `vertices`, if given, to train the guide.
+    Guided {
+        guide:    &'a PathGuide,
+        vertices: Option<&'a mut Vec<GuideVertex>>,
+    },
+    /// Every path, carrying light at the sampled wavelengths instead of as
+    /// linear sRGB.
+    Spectral(&'a SampledWavelengths),
+}
+
+impl FirstHit {
+    pub const fn new(albedo: Vec3, normal: Vec3, depth: Float, id: Option<ObjectId>) -> Self {
+        Self {
+            albedo,
+            normal,
+            depth,
+            id,
+            light_paths: [Vec3::new(0.0, 0.0, 0.0); LightPath::ALL.len()],
+        }
+    }
+
+    /// Sets the radiance that reached the camera along each of
+    /// [`LightPath::ALL`], black by default.
+    pub const fn with_light_paths(mut self, light_paths: [Vec3; LightPath::ALL.len()]) -> Self {
+        self.light_paths = light_paths;
+        self
+    }
+
+    /// Color of the surface, or of the background for rays hitting nothing.
+    pub const fn albedo(&self) -> Vec3 {
+        self.albedo
+    }
+
+    /// Shading normal, zero for rays hitting nothing.
+    pub const fn normal(&self) -> Vec3 {
+        self.normal
+    }
+
+    /// Distance along the ray, infinite for rays hitting nothing.
+    pub const fn depth(&self) -> Float {
+        self.depth
+    }
+
+    pub const fn object_id(&self) -> Option<ObjectId> {
+        self.id
+    }
+
+    /// Part of the radiance that reached the camera along `path`.
+    pub const fn light_path(&self, path: LightPath) -> Vec3 {
+        self.light_paths[path as usize]
+    }
+
+    pub const fn light_paths(&self) -> [Vec3; LightPath::ALL.len()] {
+        self.light_paths
+    }
+}
+
+impl LightPath {
+    /// Every kind of path, in the order of [`FirstHit::light_paths`].
+    pub const ALL: [Self; 4] = [
+        Self::Emission,
+        Self::DirectDiffuse,
+        Self::IndirectDiffuse,
+        Self::Specular,
+    ];
+
+    /// Short name of the pass, as used in file names.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Emission => "emission",
+            Self::DirectDiffuse => "direct_diffuse",
+            Self::IndirectDiffuse => "indirect_diffuse",
+            Self::Specular => "specular",
+        }
+    }
+}
+
+impl PathRadiance {
+    /// Kind of path light found at the current vertex took.
+    fn light_path(&self) -> LightPath {
+        match self.first_diffuse {
+            None => LightPath::Emission,
+            Some(false) => LightPath::Specular,
+            Some(true) if self.bounces <= 1 => LightPath::DirectDiffuse,
+            Some(true) => LightPath::IndirectDiffuse,
+        }
+    }
+
+    fn add(&mut self, radiance: Vec3) {
+        let path = self.light_path();
+        self.add_along(path, radiance);
+    }
+
+    /// Adds caustics gathered from a photon map, which are indirect light
+    /// even at the first diffuse bounce.
+    fn add_caustics(&mut self, radiance: Vec3) {
+        let path = match self.light_path() {
+            LightPath::DirectDiffuse => LightPath::IndirectDiffuse,
+            path => path,
+        };
+        self.add_along(path, radiance);
+    }
+
+    fn add_along(&mut self, path: LightPath, radiance: Vec3) {
+        self.total += radiance;
+        self.paths[path as usize] += radiance;
+    }
+
+    /// Records a bounce, `diffuse` or specular, before the light found
+    /// after it is added.
+    fn scatter(&mut self, diffuse: bool) {
+        self.first_diffuse.get_or_insert(diffuse);
+        self.bounces += 1;
+    }
+
+    fn finish(self, first_hit: FirstHit) -> (Vec3, FirstHit) {
+        (self.total, first_hit.with_light_paths(self.paths))
+    }
+}
+
+/// Ray hitting nothing.
+impl Default for FirstHit {
+    fn default() -> Self {
+        Self::new(Vec3::default(), Vec3::default(), Float::INFINITY, None)
+    }
+}
+
+impl PhotonMapper {
+    pub const fn new(photons: PhotonMap) -> Self {
+        Self { photons }
+    }
+
+    pub const fn photons(&self) -> &PhotonMap {
+        &self.photons
+    }
+}
+
+impl AmbientOcclusion {
+    pub const fn new(max_distance: Float) -> Self {
+        Self { max_distance }
+    }
+
+    pub const fn max_distance(&self) -> Float {
+        self.max_distance
+    }
+}
+
+impl Integrator for PathTracer {
+    fn radiance<T: Hit + ?Sized, L: Hit + ?Sized>(
+        &self,
+        camera: &Camera,
+        ray: &Ray,
+        world: &T,
+        lights: &L,
+        max_depth: u32,
+        sampler: &mut dyn Sampler,
+    ) -> (Vec3, FirstHit)
+    where
+        T::Material: Copy,
+    {
+        let transport = Transport::Full;
+        trace_path(
+            camera, ray, world, lights, max_depth, transport, sampler, None,
+        )
+    }
+}
+
+impl Integrator for PhotonMapper {
+    fn radiance<T: Hit + ?Sized, L: Hit + ?Sized>(
+        &self,
+        camera: &Camera,
+        ray: &Ray,
+        world: &T,
+        lights: &L,
+        max_depth: u32,
+        sampler: &mut dyn Sampler,
+    ) -> (Vec3, FirstHit)
+    where
+        T::Material: Copy,
+    {
+        let transport = Transport::Photons(&self.photons);
+        trace_path(
+            camera, ray, world, lights, max_depth, transport, sampler, None,
+        )
+    }
+}
+
+impl Integrator for DirectLighting {
+    fn radiance<T: Hit + ?Sized, L: Hit + ?Sized>(
+        &self,
+        camera: &Camera,
+        ray: &Ray,
+        world: &T,
+        lights: &L,
+        max_depth: u32,
+        sampler: &mut dyn Sampler,
+    ) -> (Vec3, FirstHit)
+    where
+        T::Material: Copy,
+    {
+        let transport = Transport::Direct;
+        trace_path(
+            camera, ray, world, lights, max_depth, transport, sampler, None,
+        )
+    }
+}
+
+impl Integrator for AmbientOcclusion {
+    fn radiance<T: Hit + ?Sized, L: Hit + ?Sized>(
+        &self,
+        _camera: &Camera,
+        ray: &Ray,
+        world: &T,
+        _lights: &L,
+        _max_depth: u32,
+        sampler: &mut dyn Sampler,
+    ) -> (Vec3, FirstHit)
+    where
+        T::Material: Copy,
+    {
+        let (hit, first_hit) = probe(ray, world, sampler);
+        let Some(hit) = hit else {
+            return (Vec3::new(1.0, 1.0, 1.0), first_hit);
+        };
+
+        let direction = CosinePdf::new(hit.normal()).generate(sampler);
+        let occlusion = hit
+            .spawn_ray(direction, ray.time())
+            .with_kind(RayKind::Shadow);
+        stats::count(Counter::ShadowRays);
+        let occlusion_t = Interval::new(0.0, self.max_distance);
+        let color = if world.hit_any(&occlusion, occlusion_t) {
+            Vec3::default()
+        } else {
+            Vec3::new(1.0, 1.0, 1.0)
+        };
+        (color, first_hit)
+    }
+}
+
+impl Integrator for DebugNormals {
+    fn radiance<T: Hit + ?Sized, L: Hit + ?Sized>(
+        &self,
+        _camera: &Camera,
+        ray: &Ray,
+        world: &T,
+        _lights: &L,
+        _max_depth: u32,
+        sampler: &mut dyn Sampler,
+    ) -> (Vec3, FirstHit)
+    where
+        T::Material: Copy,
+    {
+        let (hit, first_hit) = probe(ray, world, sampler);
+        let color = match hit {
+            Some(_) => (first_hit.normal + Vec3::new(1.0, 1.0, 1.0)) * 0.5,
+            None => Vec3::default(),
+        };
+        (color, first_hit)
+    }
+}
+
+/// Closest hit of `ray` and its first-hit data, the albedo sampled from the
+/// material, for the integrators looking no further than that.
+pub(crate) fn probe<T: Hit + ?Sized>(
+    ray: &Ray,
+    world: &T,
+    sampler: &mut dyn Sampler,
+) -> (Option<HitRecord<T::Material>>, FirstHit)
+where
+    T::Material: Copy,
+{
+    let hit = world.hit(ray, Interval::new(0.0, Float::INFINITY));
+    let first_hit = match hit {
+        Some(ref hit) => FirstHit::new(
+            hit.scatter(ray, sampler)
+                .map_or(Vec3::default(), |(albedo, _)| albedo),
+            hit.normal(),
+            hit.t() * ray.direction().length(),
+            hit.object_id(),
+        ),
+        None => FirstHit::default(),
+    };
+    (hit, first_hit)
+}
+
+/// Seed value telling photon streams apart from the camera's pixel streams.
+const PHOTON_STREAM: u64 = u64::MAX;
+
+/// Settings of the photon mapping enabled by
+/// [`Camera::with_photon_mapping`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PhotonMapping {
+    photons: u32,
+    radius:  Float,
+}
+
+/// Light arriving at a diffuse surface through specular bounces.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Photon {
+    point:     Vec3,
+    direction: Vec3,
+    power:     Vec3,
+}
+
+/// Caustic photons of a render, indexed by a grid of cells as wide as the
+/// gathering diameter, so that gathering visits at most eight of them.
+#[derive(Debug, Clone, Default)]
+pub struct PhotonMap {
+    photons: Vec<Photon>,
+    cells:   HashMap<[i64; 3], Range<usize>>,
+    radius:  Float,
+}
+
+impl PhotonMapping {
+    /// Emits `photons` photons from the lights for every render, camera
+    /// paths gathering those stored within `radius` of their diffuse hits.
+    /// A larger radius blurs the caustics, a smaller one leaves them noisy.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `radius` is not positive, see [`PhotonMapping::try_new`].
+    pub fn new(photons: u32, radius: Float) -> Self {
+        Self::try_new(photons, radius).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Creates the settings like [`PhotonMapping::new`], failing if `radius`
+    /// is not positive.
+    pub fn try_new(photons: u32, radius: Float) -> Result<Self> {
+        if radius.is_nan() || radius <= 0.0 {
+            return Err(Error::InvalidParameter(
+                "the photon gathering radius must be positive",
+            ));
+        }
+        Ok(Self { photons, radius })
+    }
+
+    pub const fn photons(&self) -> u32 {
+        self.photons
+    }
+
+    pub const fn radius(&self) -> Float {
+        self.radius
+    }
+}
+
+impl Photon {
+    pub const fn point(&self) -> Vec3 {
+        self.point
+    }
+
+    /// Unit direction the photon arrived from.
+    pub const fn direction(&self) -> Vec3 {
+        self.direction
+    }
+
+    /// Flux carried by the photon.
+    pub const fn power(&self) -> Vec3 {
+        self.power
+    }
+}
+
+impl PhotonMap {
+    /// Traces the photons of `settings` from `lights` and the delta lights
+    /// of `camera` through `world`, following up to `max_depth` specular
+    /// bounces. Photons are sampled from the camera's seed and frame, and
+    /// `pass` gives progressive renders a new set for every pass.
+    ///
+    /// Every emitter of `world` should be part of `lights`, as photons
+    /// only leave from those, and their surfaces must support
+    /// [`Hit::sample_surface`].
+    pub fn trace<T: Hit + ?Sized + Sync, L: Hit + ?Sized + Sync>(
+        camera: &Camera,
+        world: &T,
+        lights: &L,
+        settings: PhotonMapping,
+        max_depth: u32,
+        pass: u32,
+    ) -> Self
+    where
+        T::Material: Copy,
+    {
+        let bounds = world.aabb();
+        // The area lights come first, followed by the delta lights.
+        let powers = [lights.power()]
+            .into_iter()
+            .chain(
+                camera
+                    .delta_lights()
+                    .iter()
+                    .map(|light| light.power(&bounds)),
+            )
+            .collect();
+        let sources = Distribution1D::new(powers);
+        if sources.integral() <= 0.0 {
+            return Self::from_photons(Vec::new(), settings.radius);
+        }
+
+        let emit = |index: u32| {
+            let seed = hash_seed(&[
+                camera.seed(),
+                camera.frame() as u64,
+                PHOTON_STREAM,
+                pass as u64,
+                index as u64,
+            ]);
+            let mut sampler = RandomSampler::new(seed);
+            let time = camera.sample_time(&mut sampler);
+            let (source, probability) = sources.sample_discrete(sampler.next_1d());
+            let (ray, flux) = match source {
+                0 => emit_from_surface(lights, time, &mut sampler)?,
+                light => {
+                    let light = &camera.delta_lights()[light - 1];
+                    light.sample_emission(&bounds, time, &mut sampler)
+                }
+            };
+            let power = flux / (probability * settings.photons as Float);
+            trace_photon(camera, world, ray, power, max_depth, &mut sampler)
+        };
+
+        #[cfg(feature = "parallel")]
+        let photons = (0..settings.photons)
+            .into_par_iter()
+            .filter_map(emit)
+            .collect();
+        #[cfg(not(feature = "parallel"))]
+        let photons = (0..settings.photons).filter_map(emit).collect();
+        Self::from_photons(photons, settings.radius)
+    }
+
+    fn from_photons(mut photons: Vec<Photon>, radius: Float) -> Self {
+        let cell = |photon: &Photon| cell_of(photon.point, 2.0 * radius);
+        photons.sort_by_key(cell);
+
+        let mut cells = HashMap::new();
+        let mut start = 0;
+        for end in 1..=photons.len() {
+            if end == photons.len() || cell(&photons[end]) != cell(&photons[start]) {
+                cells.insert(cell(&photons[start]), start..end);
+                start = end;
+            }
+        }
+
+        Self {
+            photons,
+            cells,
+            radius,
+        }
+    }
+
+    pub fn photons(&self) -> &[Photon] {
+        &self.photons
+    }
+
+    pub fn len(&self) -> usize {
+        self.photons.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.photons.is_empty()
+    }
+
+    /// Radius photons are gathered within.
+    pub const fn radius(&self) -> Float {
+        self.radius
+    }
+
+    /// Estimates the light the photons around `hit` scatter along `ray`
+    /// back towards the camera. Like the direct light estimates of the path
+    /// tracer, the result still has to be multiplied by the attenuation.
+    fn gather<M: Scatter>(&self, camera: &Camera, ray: &Ray, hit: &HitRecord<M>) -> Vec3 {
+        let area = PI * self.radius * self.radius;
+        let sum: Vec3 = self
+            .near(hit.point())
+            .filter_map(|photon| {
+                let cosine = photon.direction().dot(hit.normal());
+                if cosine <= 0.0 {
+                    return None;
+                }
+                let incoming = hit.spawn_ray(photon.direction(), ray.time());
+                let scattering_pdf = camera.scattering_pdf(ray, hit, &incoming);
+                Some(photon.power() * (scattering_pdf / cosine))
+            })
+            .sum();
+        sum / area
+    }
+
+    /// Photons within the gathering radius of `point`.
+    pub fn near(&self, point: Vec3) -> impl Iterator<Item = &Photon> {
+        let offset = Vec3::new(self.radius, self.radius, self.radius);
+        let [x0, y0, z0] = cell_of(point - offset, 2.0 * self.radius);
+        let [x1, y1, z1] = cell_of(point + offset, 2.0 * self.radius);
+        let radius_squared = self.radius * self.radius;
+
+        (x0..=x1)
+            .flat_map(move |x| (y0..=y1).flat_map(move |y| (z0..=z1).map(move |z| [x, y, z])))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flat_map(|range| &self.photons[range.clone()])
+            .filter(move |photon| (photon.point - point).length_squared() <= radius_squared)
+    }
+}
+
+/// Grid cell of `point` for cells of size `size`.
+fn cell_of(point: Vec3, size: Float) -> [i64; 3] {
+    [point.x(), point.y(), point.z()].map(|c| (c / size).floor() as i64)
+}
+
+/// Samples a photon leaving a point of `lights`, cosine distributed around
+/// the side it is emitted from, returned with its flux over its density.
+fn emit_from_surface<L: Hit + ?Sized>(
+    lights: &L,
+    time: Float,
+    sampler: &mut dyn Sampler,
+) -> Option<(Ray, Vec3)> {
+    let (rec, pdf) = lights.sample_surface(time, sampler)?;
+    let emitted = rec.emit()?;
+    if pdf <= 0.0 {
+        return None;
+    }
+
+    // Two sided emitters pick a side at random.
+    let two_sided = rec.material().emission_sides().includes(false);
+    let normal = if two_sided && sampler.next_1d() < 0.5 {
+        -rec.normal()
+    } else {
+        rec.normal()
+    };
+    let sides = if two_sided { 2.0 } else { 1.0 };
+
+    // The cosine of the emitted radiance cancels with the density of the
+    // direction, leaving `emitted * PI / pdf`.
+    let direction = CosinePdf::new(normal).generate(sampler);
+    let flux = emitted * (sides * PI / pdf);
+    Some((rec.spawn_ray(direction, time), flux))
+}
+
+/// Follows a photon through specular bounces, returning it where it lands
+/// on a diffuse surface if it bounced at least once on the way.
+fn trace_photon<T: Hit + ?Sized>(
+    camera: &Camera,
+    world: &T,
+    mut ray: Ray,
+    mut power: Vec3,
+    max_depth: u32,
+    sampler: &mut dyn Sampler,
+) -> Option<Photon>
+where
+    T::Material: Copy,
+{
+    let mut interior = Interior::new();
+    let mut specular = false;
+
+    for _ in 0..max_depth {
+        let hit = world.hit(&ray, Interval::new(0.0, Float::INFINITY))?;
+
+        let dielectric = hit.dielectric().filter(|_| camera.clay().is_none());
+        let (attenuation, scattered) = match dielectric {
+            Some(dielectric) => {
+                let entering = hit.front_face();
+                let Some((from, to)) = interior.crossing(dielectric, entering) else {
+                    // Surface hidden inside a higher priority dielectric.
+                    interior.cross(dielectric, entering);
+                    ray = hit.spawn_ray(ray.direction(), ray.time());
+                    continue;
+                };
+                let (attenuation, scattered) =
+                    dielectric.scatter_between(&ray, &hit, from, to, sampler);
+                if scattered.direction().dot(hit.normal()) < 0.0 {
+                    interior.cross(dielectric, entering);
+                }
+                (attenuation, scattered)
+            }
+            None => camera.scatter(&ray, &hit, sampler)?,
+        };
+
+        if camera.scattering_pdf(&ray, &hit, &scattered) > 0.0 {
+            return specular.then(|| Photon {
+                point: hit.point(),
+                direction: -ray.direction().unit(),
+                power,
+            });
+        }
+        specular = true;
+        power *= attenuation;
+        ray = scattered;
+    }
+    None
+}
+
+/// Traces a path starting at `ray`, carrying the product of the
+/// attenuations seen so far as `throughput` and accumulating emission
+/// into `radiance` until the path escapes, is absorbed or reaches
+/// `max_depth` bounces. Data about the first surface hit is returned
+/// alongside the radiance.
+///
+/// When `lights` is not empty, direct light at diffuse bounces is estimated
+/// both by [`Camera::sample_light`] and by the scattered ray hitting an
+/// emitter. The two estimates are combined with the power heuristic.
+/// Environment map backgrounds are sampled in the same way through
+/// [`Camera::sample_background`].
+///
+/// With [`Transport::Photons`], caustics are gathered from the photon map
+/// at diffuse bounces, and emitters reached through specular bounces after
+/// a diffuse one are skipped, since the map already holds their light.
+/// [`Transport::Direct`] stops at the first hit after a diffuse bounce.
+/// [`Transport::Guided`] samples diffuse bounces from the guide as well,
+/// weighting every estimate by the density of the mixture.
+/// [`Transport::Spectral`] upsamples every color the path meets to its
+/// values at the wavelengths, which the returned radiance then holds.
+///
+/// Dispersive dielectrics refract each wavelength differently. The first
+/// one a spectral path hits drops the wavelengths other than the hero one,
+/// while other paths pick a single wavelength there and carry its color.
+///
+/// With [`Camera::fog`], every segment of the path may end at a particle of
+/// the fog before reaching a surface. The path scatters there in a uniformly
+/// random direction, estimating direct light like at a diffuse bounce, with
+/// shadow rays dimmed by the fog they cross.
+///
+/// The returned first hit holds the radiance split by [`LightPath`].
+///
+/// Every surface hit is appended to `path`, if given.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn trace_path<T: Hit + ?Sized, L: Hit + ?Sized>(
+    camera: &Camera,
+    ray: &Ray,
+    world: &T,
+    lights: &L,
+    max_depth: u32,
+    transport: Transport<'_>,
+    sampler: &mut dyn Sampler,
+    mut path: Option<&mut Vec<PathVertex<T::Material>>>,
+) -> (Vec3, FirstHit)
+where
+    T::Material: Copy,
+{
+    let direct_only = matches!(transport, Transport::Direct);
+    let (photons, guide, mut vertices, wavelengths) = match transport {
+        Transport::Photons(photons) => (Some(photons), None, None, None),
+        Transport::Guided { guide, vertices } => (None, Some(guide), vertices, None),
+        Transport::Spectral(wavelengths) => (None, None, None, Some(wavelengths)),
+        Transport::Full | Transport::Direct => (None, None, None, None),
+    };
+    let spectrum = |rgb: Vec3| wavelengths.map_or(rgb, |wavelengths| wavelengths.from_rgb(rgb));
+    let sample_lights = lights.count() > 0;
+    let sample_background = camera.background().is_sampled();
+    let fog = camera.fog();
+    let world_bounds = world.aabb();
+
+    let mut ray = *ray;
+    let mut throughput = Vec3::new(1.0, 1.0, 1.0);
+    let mut radiance = PathRadiance::default();
+    // Origin and density of the last diffuse scatter, `None` after the
+    // camera ray or a specular bounce.
+    let mut diffuse_scatter: Option<(Vec3, Float)> = None;
+    // Whether specular bounces followed a diffuse one, making the light
+    // the path finds a caustic.
+    let mut caustic = false;
+    let mut interior = Interior::new();
+    // Wavelength dispersive dielectrics refract at, the hero one for
+    // spectral paths and otherwise picked when the path first hits one.
+    let mut wavelength = wavelengths.map(SampledWavelengths::hero);
+    let mut dispersed = false;
+    let mut first_hit = FirstHit::default();
+
+    for bounce in 0..max_depth {
+        let hit = world.hit(&ray, Interval::new(0.0, Float::INFINITY));
+        let ray_t = Interval::new(0.0, hit.as_ref().map_or(Float::INFINITY, HitRecord::t));
+        let in_fog = fog.and_then(|fog| {
+            let t = fog.sample_distance(&ray, ray_t, world_bounds, sampler)?;
+            Some((fog, t))
+        });
+        if let Some((fog, t)) = in_fog {
+            if direct_only && diffuse_scatter.is_some() {
+                return radiance.finish(first_hit);
+            }
+            let particle = HitRecord::facing(
+                ray.at(t),
+                -ray.direction().unit(),
+                ray.time(),
+                Isotropic::new(fog.albedo()),
+            );
+            let Some((attenuation, scattered)) = particle.scatter(&ray, sampler) else {
+                return radiance.finish(first_hit);
+            };
+            let scattering_pdf = particle.scattering_pdf(&ray, &scattered);
+            diffuse_scatter = Some((particle.point(), scattering_pdf));
+            caustic = false;
+            radiance.scatter(true);
+            throughput *= spectrum(attenuation);
+
+            if sample_lights {
+                let direct =
+                    camera.sample_light(&ray, &particle, world, lights, None, Some(fog), sampler);
+                radiance.add(camera.contribution(bounce + 1, throughput * spectrum(direct)));
+            }
+            if sample_background {
+                let direct =
+                    camera.sample_background(&ray, &particle, world, None, Some(fog), sampler);
+                radiance.add(camera.contribution(bounce + 1, throughput * spectrum(direct)));
+            }
+            if !camera.delta_lights().is_empty() {
+                let direct = camera.sample_delta_lights(&ray, &particle, world, Some(fog), sampler);
+                radiance.add(camera.contribution(bounce + 1, throughput * spectrum(direct)));
+            }
+
+            ray = scattered;
+            stats::count(Counter::SecondaryRays);
+            continue;
+        }
+
+        let Some(hit) = hit else {
+            let weight = match diffuse_scatter {
+                Some((_, scattering_pdf)) if sample_background => {
+                    let background_pdf = camera.background().pdf_value(ray.direction());
+                    power_heuristic(scattering_pdf, background_pdf)
+                }
+                _ => 1.0,
+            };
+            let background = camera.background().value(ray.direction());
+            if bounce == 0 {
+                first_hit.albedo = background;
+            }
+            radiance
+                .add(camera.contribution(bounce, throughput.scale(spectrum(background)) * weight));
+            return radiance.finish(first_hit);
+        };
+
+        if bounce == 0 {
+            first_hit.normal = hit.normal();
+            first_hit.depth = hit.t() * ray.direction().length();
+            first_hit.id = hit.object_id();
+        }
+
+        let emitted = hit.emit();
+        if let Some(emitted) = emitted.filter(|_| !(caustic && photons.is_some())) {
+            let weight = match diffuse_scatter {
+                Some((origin, scattering_pdf)) if sample_lights && hit.is_emitter() => {
+                    let light_pdf = camera.light_pdf(lights, origin, ray.direction(), ray.time());
+                    power_heuristic(scattering_pdf, light_pdf)
+                }
+                _ => 1.0,
+            };
+            radiance.add(camera.contribution(bounce, throughput * spectrum(emitted) * weight));
+        }
+        if direct_only && diffuse_scatter.is_some() {
+            return radiance.finish(first_hit);
+        }
+
+        let dielectric = hit.dielectric().filter(|_| camera.clay().is_none());
+        let scatter = match dielectric {
+            Some(dielectric) => {
+                let entering = hit.front_face();
+                if dielectric.dispersion().is_some() && !dispersed {
+                    dispersed = true;
+                    throughput = match wavelengths {
+                        Some(wavelengths) => wavelengths.hero_only(throughput),
+                        None => {
+                            let lambda = spectrum::sample_wavelength(sampler.next_1d());
+                            wavelength = Some(lambda);
+                            throughput.scale(spectrum::wavelength_to_rgb(lambda))
+                        }
+                    };
+                }
+                let crossing = match wavelength {
+                    Some(lambda) => interior.crossing_at(dielectric, entering, lambda),
+                    None => interior.crossing(dielectric, entering),
+                };
+                let Some((from, to)) = crossing else {
+                    // Surface hidden inside a higher priority dielectric.
+                    interior.cross(dielectric, entering);
+                    let continued = hit
+                        .spawn_ray(ray.direction(), ray.time())
+                        .with_kind(ray.kind());
+                    if let Some(path) = path.as_deref_mut() {
+                        path.push(PathVertex::new(
+                            &hit,
+                            throughput,
+                            emitted,
+                            Some((Vec3::new(1.0, 1.0, 1.0), continued)),
+                        ));
+                    }
+                    ray = continued;
+                    stats::count(Counter::SecondaryRays);
+                    continue;
+                };
+                let (attenuation, scattered) =
+                    dielectric.scatter_between(&ray, &hit, from, to, sampler);
+                if scattered.direction().dot(hit.normal()) < 0.0 {
+                    interior.cross(dielectric, entering);
+                }
+                Some((attenuation, scattered))
+            }
+            None => camera.scatter(&ray, &hit, sampler),
+        };
+        if let Some(path) = path.as_deref_mut() {
+            path.push(PathVertex::new(&hit, throughput, emitted, scatter));
+        }
+        if bounce == 0 {
+            first_hit.albedo = match (scatter, emitted) {
+                (Some((attenuation, _)), _) => attenuation,
+                (None, Some(emitted)) => emitted,
+                (None, None) => Vec3::default(),
+            };
+        }
+
+        match scatter {
+            Some((attenuation, scattered)) => {
+                let scattering_pdf = camera.scattering_pdf(&ray, &hit, &scattered);
+                let directions = guide
+                    .filter(|_| scattering_pdf > 0.0)
+                    .and_then(|guide| guide.directions(hit.point()));
+                let (guide_weight, scattered, pdf) = match directions {
+                    Some(directions) => {
+                        guiding::guided_scatter(camera, &ray, &hit, directions, scattered, sampler)
+                    }
+                    None => (1.0, scattered, scattering_pdf),
+                };
+                let after_diffuse = diffuse_scatter.is_some();
+                diffuse_scatter = (scattering_pdf > 0.0).then_some((hit.point(), pdf));
+                caustic = diffuse_scatter.is_none() && (caustic || after_diffuse);
+                radiance.scatter(diffuse_scatter.is_some());
+                throughput *= spectrum(attenuation);
+
+                if let Some(photons) = photons.filter(|_| diffuse_scatter.is_some()) {
+                    let caustics = photons.gather(camera, &ray, &hit);
+                    radiance.add_caustics(camera.contribution(bounce + 1, throughput * caustics));
+                }
+
+                if sample_lights && diffuse_scatter.is_some() {
+                    let direct =
+                        camera.sample_light(&ray, &hit, world, lights, directions, fog, sampler);
+                    radiance.add(camera.contribution(bounce + 1, throughput * spectrum(direct)));
+                }
+
+                if sample_background && diffuse_scatter.is_some() {
+                    let direct =
+                        camera.sample_background(&ray, &hit, world, directions, fog, sampler);
+                    radiance.add(camera.contribution(bounce + 1, throughput * spectrum(direct)));
+                }
+
+                if !camera.delta_lights().is_empty() && diffuse_scatter.is_some() {
+                    let direct = camera.sample_delta_lights(&ray, &hit, world, fog, sampler);
+                    radiance.add(camera.contribution(bounce + 1, throughput * spectrum(direct)));
+                }
+
+                if guide_weight <= 0.0 {
+                    return radiance.finish(first_hit);
+                }
+                throughput *= guide_weight;
+                if let Some(vertices) = vertices
+                    .as_deref_mut()
+                    .filter(|_| diffuse_scatter.is_some())
+                {
+                    vertices.push(GuideVertex::new(
+                        hit.point(),
+                        scattered.direction(),
+                        pdf,
+                        throughput,
+                        radiance.total,
+                    ));
+                }
+
+                ray = scattered;
+                stats::count(Counter::SecondaryRays);
+            }
+            None if hit.is_emitter() => return radiance.finish(first_hit),
+            None => {
+                let background = camera.background().value(ray.direction());
+                radiance.add(camera.contribution(bounce, throughput.scale(spectrum(background))));
+                return radiance.finish(first_hit);
+            }
+        }
+    }
+
+    let background = camera.background().value(ray.direction());
+    radiance.add(camera.contribution(max_depth, throughput.scale(spectrum(background))));
+    radiance.finish(first_hit)
+}
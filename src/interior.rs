@@ -0,0 +1,98 @@
+//! Tracking of the dielectrics a path is inside of, so that overlapping
+//! volumes refract with the right relative indices of refraction.
+
+use crate::{Float, material::Dielectric};
+
+/// Dielectrics a ray is inside of, in the order they were entered.
+///
+/// Where dielectrics overlap, the one of highest [priority] fills the
+/// overlap and the surfaces of the others are ignored there.
+///
+/// [priority]: Dielectric::with_priority
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Interior {
+    media: Vec<Dielectric>,
+}
+
+impl Interior {
+    /// Interior of a ray outside of any dielectric.
+    pub const fn new() -> Self {
+        Self { media: Vec::new() }
+    }
+
+    /// Dielectric filling the space the ray is in, if any.
+    pub fn current(&self) -> Option<Dielectric> {
+        highest(self.media.iter().copied())
+    }
+
+    /// Index of refraction of the space the ray is in, `1.0` outside of any
+    /// dielectric.
+    pub fn refraction(&self) -> Float {
+        self.current().map_or(1.0, |medium| medium.refraction())
+    }
+
+    /// Indices of refraction on both sides of the surface of `dielectric`
+    /// hit from outside if `entering`, in the order the ray crosses them, or
+    /// `None` if the surface lies inside a dielectric of higher priority and
+    /// must be passed through.
+    pub fn crossing(&self, dielectric: Dielectric, entering: bool) -> Option<(Float, Float)> {
+        self.crossing_with(dielectric, entering, |medium| medium.refraction())
+    }
+
+    /// Indices of refraction on both sides of the surface of `dielectric`
+    /// at `lambda` nanometers, like [`Interior::crossing`].
+    pub fn crossing_at(
+        &self,
+        dielectric: Dielectric,
+        entering: bool,
+        lambda: Float,
+    ) -> Option<(Float, Float)> {
+        self.crossing_with(dielectric, entering, |medium| medium.refraction_at(lambda))
+    }
+
+    fn crossing_with(
+        &self,
+        dielectric: Dielectric,
+        entering: bool,
+        refraction: impl Fn(Dielectric) -> Float,
+    ) -> Option<(Float, Float)> {
+        if entering {
+            match self.current() {
+                Some(current) if current.priority() > dielectric.priority() => None,
+                current => Some((current.map_or(1.0, &refraction), refraction(dielectric))),
+            }
+        } else {
+            let skip = self.position(dielectric);
+            let rest = self
+                .media
+                .iter()
+                .enumerate()
+                .filter(|&(i, _)| Some(i) != skip)
+                .map(|(_, &medium)| medium);
+            match highest(rest) {
+                Some(outside) if outside.priority() > dielectric.priority() => None,
+                outside => Some((refraction(dielectric), outside.map_or(1.0, &refraction))),
+            }
+        }
+    }
+
+    /// Records the ray crossing the surface of `dielectric`, from outside if
+    /// `entering`.
+    pub fn cross(&mut self, dielectric: Dielectric, entering: bool) {
+        if entering {
+            self.media.push(dielectric);
+        } else if let Some(i) = self.position(dielectric) {
+            self.media.remove(i);
+        }
+    }
+
+    /// Position of the last entered instance of `dielectric`.
+    fn position(&self, dielectric: Dielectric) -> Option<usize> {
+        self.media.iter().rposition(|&medium| medium == dielectric)
+    }
+}
+
+/// Dielectric of highest priority, the last one on ties.
+fn highest(media: impl Iterator<Item = Dielectric>) -> Option<Dielectric> {
+    media.max_by_key(|medium| medium.priority())
+}
@@ -0,0 +1,118 @@
+//! Conversions to and from the vector, matrix and quaternion types of `glam` and `nalgebra`,
+//! available with the features of the same names.
+
+#[cfg(feature = "glam")]
+mod glam_impls {
+    #[cfg(not(feature = "f32"))]
+    use glam::{DMat4 as GlamMat4, DQuat as GlamQuat};
+    #[cfg(feature = "f32")]
+    use glam::{Mat4 as GlamMat4, Quat as GlamQuat};
+
+    use crate::vec3::{Mat4, Quat, Vec3};
+
+    impl From<glam::DVec3> for Vec3<f64> {
+        fn from(v: glam::DVec3) -> Self {
+            Vec3::new(v.x, v.y, v.z)
+        }
+    }
+
+    impl From<Vec3<f64>> for glam::DVec3 {
+        fn from(v: Vec3<f64>) -> Self {
+            glam::DVec3::new(v.x(), v.y(), v.z())
+        }
+    }
+
+    impl From<glam::Vec3> for Vec3<f32> {
+        fn from(v: glam::Vec3) -> Self {
+            Vec3::new(v.x, v.y, v.z)
+        }
+    }
+
+    impl From<Vec3<f32>> for glam::Vec3 {
+        fn from(v: Vec3<f32>) -> Self {
+            glam::Vec3::new(v.x(), v.y(), v.z())
+        }
+    }
+
+    impl From<GlamMat4> for Mat4 {
+        fn from(m: GlamMat4) -> Self {
+            Mat4::new(m.transpose().to_cols_array_2d())
+        }
+    }
+
+    impl From<Mat4> for GlamMat4 {
+        fn from(m: Mat4) -> Self {
+            GlamMat4::from_cols_array_2d(&m.transpose().rows())
+        }
+    }
+
+    impl From<GlamQuat> for Quat {
+        fn from(q: GlamQuat) -> Self {
+            Quat::new(q.w, Vec3::new(q.x, q.y, q.z))
+        }
+    }
+
+    impl From<Quat> for GlamQuat {
+        fn from(q: Quat) -> Self {
+            GlamQuat::from_xyzw(q.v().x(), q.v().y(), q.v().z(), q.w())
+        }
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+mod nalgebra_impls {
+    use nalgebra::{Matrix4, Point3, Quaternion, Scalar, Vector3};
+
+    use crate::{
+        Float,
+        vec3::{Mat4, Quat, Vec3},
+    };
+
+    impl<T: Scalar + Copy> From<Vector3<T>> for Vec3<T> {
+        fn from(v: Vector3<T>) -> Self {
+            Vec3::new(v.x, v.y, v.z)
+        }
+    }
+
+    impl<T: Scalar + Copy> From<Vec3<T>> for Vector3<T> {
+        fn from(v: Vec3<T>) -> Self {
+            Vector3::new(v.x(), v.y(), v.z())
+        }
+    }
+
+    impl<T: Scalar + Copy> From<Point3<T>> for Vec3<T> {
+        fn from(p: Point3<T>) -> Self {
+            Vec3::new(p.x, p.y, p.z)
+        }
+    }
+
+    impl<T: Scalar + Copy> From<Vec3<T>> for Point3<T> {
+        fn from(v: Vec3<T>) -> Self {
+            Point3::new(v.x(), v.y(), v.z())
+        }
+    }
+
+    impl From<Matrix4<Float>> for Mat4 {
+        fn from(m: Matrix4<Float>) -> Self {
+            Mat4::new(std::array::from_fn(|i| std::array::from_fn(|j| m[(i, j)])))
+        }
+    }
+
+    impl From<Mat4> for Matrix4<Float> {
+        fn from(m: Mat4) -> Self {
+            Matrix4::from_fn(|i, j| m.get(i, j))
+        }
+    }
+
+    impl From<Quaternion<Float>> for Quat {
+        fn from(q: Quaternion<Float>) -> Self {
+            Quat::new(q.w, Vec3::new(q.i, q.j, q.k))
+        }
+    }
+
+    impl From<Quat> for Quaternion<Float> {
+        fn from(q: Quat) -> Self {
+            Quaternion::new(q.w(), q.v().x(), q.v().y(), q.v().z())
+        }
+    }
+}
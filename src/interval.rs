@@ -1,5 +1,10 @@
+use std::ops::{Add, Sub};
+
+use crate::Float;
+
 #[derive(Debug, Default, Clone, Copy, PartialEq)]
-pub struct Interval<T = f64> {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Interval<T = Float> {
     min: T,
     max: T,
 }
@@ -26,16 +31,35 @@ impl<T: Copy> Interval<T> {
     }
 }
 
-impl Interval<f64> {
+impl Interval<Float> {
     pub const fn inf() -> Self {
-        Self::new(f64::NEG_INFINITY, f64::INFINITY)
+        Self::new(Float::NEG_INFINITY, Float::INFINITY)
     }
 
     pub const fn empty() -> Self {
-        Self::new(f64::INFINITY, f64::NEG_INFINITY)
+        Self::new(Float::INFINITY, Float::NEG_INFINITY)
+    }
+
+    /// Interval between `a` and `b`, in either order.
+    pub const fn spanning(a: Float, b: Float) -> Self {
+        Self::new(a.min(b), a.max(b))
+    }
+
+    pub const fn with_min(self, min: Float) -> Self {
+        Self::new(min, self.max)
+    }
+
+    pub const fn with_max(self, max: Float) -> Self {
+        Self::new(self.min, max)
     }
 
-    pub const fn expand(&self, delta: f64) -> Self {
+    /// Whether no value lies in the interval. Intervals of a single value
+    /// are not empty.
+    pub const fn is_empty(&self) -> bool {
+        self.min > self.max
+    }
+
+    pub const fn expand(&self, delta: Float) -> Self {
         let padding = delta / 2.0;
         Self::new(self.min - padding, self.max + padding)
     }
@@ -46,19 +70,36 @@ impl Interval<f64> {
         Self::new(min, max)
     }
 
-    pub const fn size(&self) -> f64 {
+    /// Values in both intervals, [empty](Interval::is_empty) if they don't
+    /// overlap.
+    pub const fn intersect(&self, other: Self) -> Self {
+        let min = self.min().max(other.min());
+        let max = self.max().min(other.max());
+        Self::new(min, max)
+    }
+
+    pub const fn size(&self) -> Float {
         self.max - self.min
     }
 
-    pub const fn contains(&self, x: f64) -> bool {
+    pub const fn midpoint(&self) -> Float {
+        (self.min + self.max) / 2.0
+    }
+
+    pub const fn contains(&self, x: Float) -> bool {
         x >= self.min && x <= self.max
     }
 
-    pub const fn surrounds(&self, x: f64) -> bool {
+    /// Whether `other` lies entirely inside the interval.
+    pub const fn contains_interval(&self, other: Self) -> bool {
+        self.min <= other.min && other.max <= self.max
+    }
+
+    pub const fn surrounds(&self, x: Float) -> bool {
         x > self.min && x < self.max
     }
 
-    pub const fn clamp(&self, x: f64) -> f64 {
+    pub const fn clamp(&self, x: Float) -> Float {
         if x < self.min {
             self.min
         } else if x > self.max {
@@ -68,3 +109,29 @@ impl Interval<f64> {
         }
     }
 }
+
+/// Interval displaced by `rhs`.
+impl Add<Float> for Interval<Float> {
+    type Output = Self;
+
+    fn add(self, rhs: Float) -> Self::Output {
+        Interval::new(self.min + rhs, self.max + rhs)
+    }
+}
+
+impl Add<Interval<Float>> for Float {
+    type Output = Interval<Float>;
+
+    fn add(self, rhs: Interval<Float>) -> Self::Output {
+        rhs + self
+    }
+}
+
+/// Interval displaced by `-rhs`.
+impl Sub<Float> for Interval<Float> {
+    type Output = Self;
+
+    fn sub(self, rhs: Float) -> Self::Output {
+        Interval::new(self.min - rhs, self.max - rhs)
+    }
+}
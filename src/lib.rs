@@ -1,23 +1,73 @@
 pub mod aabb;
+pub mod accel;
+pub mod anim;
+pub mod aperture;
+pub mod background;
+pub mod bdpt;
 pub mod bvh;
 pub mod camera;
+mod clock;
 pub mod color;
+pub mod error;
+pub mod film;
+pub mod filter;
 pub mod geo;
+#[cfg(feature = "gpu")]
+pub mod gpu;
+pub mod grid;
+pub mod group;
+pub mod guiding;
 pub mod hit;
+pub mod ies;
+pub mod instance;
+pub mod integrator;
+pub mod interior;
+#[cfg(any(feature = "glam", feature = "nalgebra"))]
+mod interop;
 pub mod interval;
+pub mod light;
 pub mod material;
+pub mod medium;
+pub mod metadata;
+pub mod obj;
+pub mod pdf;
+pub mod post;
+#[cfg(feature = "preview")]
+pub mod preview;
+pub mod primitive;
+pub mod progress;
+pub mod qbvh;
 pub mod ray;
+pub mod reservoir;
+pub mod sampler;
+pub mod scenes;
+pub mod sequence;
+pub mod sky;
+pub mod spectrum;
+pub mod stats;
+pub mod texture;
+pub mod transform;
+pub mod vec2;
 pub mod vec3;
+pub mod visibility;
+pub mod vox;
 
-use interval::Interval;
-use rand::{Rng, distributions::Uniform};
+pub use error::{Error, Result};
 
-pub fn random_0_1() -> f64 {
-    let distr = Uniform::new(0.0, 1.0);
-    rand::thread_rng().sample(distr)
-}
+/// Floating point type of geometry, radiance and sampling, `f32` with the
+/// `f32` feature. Single precision halves the memory traffic and doubles the
+/// SIMD width at a small cost in quality, mostly for very large scenes.
+#[cfg(not(feature = "f32"))]
+pub type Float = f64;
+/// Floating point type of geometry, radiance and sampling, `f32` with the
+/// `f32` feature. Single precision halves the memory traffic and doubles the
+/// SIMD width at a small cost in quality, mostly for very large scenes.
+#[cfg(feature = "f32")]
+pub type Float = f32;
 
-pub fn random_range(interval: Interval) -> f64 {
-    let distr = Uniform::new(interval.min(), interval.max());
-    rand::thread_rng().sample(distr)
-}
+/// Mathematical constants of [`Float`].
+#[cfg(feature = "f32")]
+pub use std::f32::consts;
+/// Mathematical constants of [`Float`].
+#[cfg(not(feature = "f32"))]
+pub use std::f64::consts;
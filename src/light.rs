@@ -0,0 +1,408 @@
+use std::sync::Arc;
+
+use crate::{
+    Float,
+    aabb::Aabb,
+    consts::PI,
+    error::{Error, Result},
+    hit::{DynHit, Hit, HitList, HitRecord},
+    ies::IesProfile,
+    interval::Interval,
+    material::Material,
+    pdf::Distribution1D,
+    ray::Ray,
+    sampler::Sampler,
+    vec3::{Onb, Vec3},
+};
+
+/// Axis of the photometric profiles of point lights, straight down.
+const DOWN: Vec3 = Vec3::new(0.0, -1.0, 0.0);
+
+/// Light source without area, reachable only by explicit shadow rays.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Light {
+    /// Emits `intensity` from `position`, in every direction alike or
+    /// scaled by a `profile` pointing straight down.
+    Point {
+        position:  Vec3,
+        intensity: Vec3,
+        #[cfg_attr(feature = "serde", serde(default))]
+        profile:   Option<Arc<IesProfile>>,
+    },
+    /// Infinitely far light whose rays travel along `direction`, delivering
+    /// `irradiance` to surfaces facing it. Rays come from a disk
+    /// `angular_diameter` degrees across, softening shadows like the sun's,
+    /// or from a single direction if it is zero.
+    Directional {
+        direction:        Vec3,
+        irradiance:       Vec3,
+        #[cfg_attr(feature = "serde", serde(default))]
+        angular_diameter: Float,
+    },
+    /// Point light restricted to a cone around `direction`, at full
+    /// intensity inside `cos_inner` and fading out towards `cos_outer`,
+    /// further scaled by a `profile` pointing along `direction`.
+    Spot {
+        position:  Vec3,
+        direction: Vec3,
+        intensity: Vec3,
+        cos_inner: Float,
+        cos_outer: Float,
+        #[cfg_attr(feature = "serde", serde(default))]
+        profile:   Option<Arc<IesProfile>>,
+    },
+}
+
+/// Emissive objects sampled proportionally to the power they emit, so that
+/// dim emitters do not take shadow rays away from the ones lighting the
+/// scene.
+#[derive(Debug, Clone)]
+pub struct LightList {
+    list:         Vec<Arc<dyn DynHit>>,
+    distribution: Option<Distribution1D>,
+    bbox:         Aabb,
+}
+
+/// Light arriving at a point from a [`Light`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LightSample {
+    direction: Vec3,
+    distance:  Float,
+    radiance:  Vec3,
+}
+
+impl LightSample {
+    /// Unit direction from the shaded point towards the light.
+    pub const fn direction(&self) -> Vec3 {
+        self.direction
+    }
+
+    /// Distance to the light, infinite for directional lights.
+    pub const fn distance(&self) -> Float {
+        self.distance
+    }
+
+    /// Incident radiance, already divided by the squared distance.
+    pub const fn radiance(&self) -> Vec3 {
+        self.radiance
+    }
+}
+
+impl Light {
+    pub const fn point(position: Vec3, intensity: Vec3) -> Self {
+        Self::Point {
+            position,
+            intensity,
+            profile: None,
+        }
+    }
+
+    pub fn directional(direction: Vec3, irradiance: Vec3) -> Self {
+        Self::sun(direction, irradiance, 0.0)
+    }
+
+    /// Directional light from a disk `angular_diameter` degrees across, about
+    /// 0.53 for the sun.
+    pub fn sun(direction: Vec3, irradiance: Vec3, angular_diameter: Float) -> Self {
+        Self::Directional {
+            direction: direction.unit(),
+            irradiance,
+            angular_diameter,
+        }
+    }
+
+    /// Spot light with a full-intensity cone of `inner_angle` and a falloff
+    /// ending at `outer_angle`, both given in degrees from the axis.
+    pub fn spot(
+        position: Vec3,
+        direction: Vec3,
+        intensity: Vec3,
+        inner_angle: Float,
+        outer_angle: Float,
+    ) -> Self {
+        Self::Spot {
+            position,
+            direction: direction.unit(),
+            intensity,
+            cos_inner: inner_angle.to_radians().cos(),
+            cos_outer: outer_angle.max(inner_angle).to_radians().cos(),
+            profile: None,
+        }
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the light is directional, see [`Light::try_with_profile`].
+    pub fn with_profile(self, profile: Arc<IesProfile>) -> Self {
+        self.try_with_profile(profile)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Shapes the light like the fixture `profile` was measured from, its
+    /// intensity becoming the one of the brightest direction, see
+    /// [`IesProfile::max_candela`]. Point lights point the profile straight
+    /// down and spot lights along their direction. Fails for directional
+    /// lights.
+    pub fn try_with_profile(mut self, profile: Arc<IesProfile>) -> Result<Self> {
+        match &mut self {
+            Self::Point { profile: slot, .. } | Self::Spot { profile: slot, .. } => {
+                *slot = Some(profile);
+                Ok(self)
+            }
+            Self::Directional { .. } => Err(Error::InvalidParameter(
+                "directional lights take no photometric profile",
+            )),
+        }
+    }
+
+    /// Photometric profile shaping the light, if any.
+    pub fn profile(&self) -> Option<&IesProfile> {
+        match self {
+            Self::Point { profile, .. } | Self::Spot { profile, .. } => profile.as_deref(),
+            Self::Directional { .. } => None,
+        }
+    }
+    /// Light arriving at `point`, ignoring occlusion, from a direction
+    /// drawn with `sampler` for lights of some angular size. Returns `None`
+    /// if the light does not reach `point` at all.
+    pub fn sample(&self, point: Vec3, sampler: &mut dyn Sampler) -> Option<LightSample> {
+        match *self {
+            Self::Point {
+                position,
+                intensity,
+                ref profile,
+            } => {
+                let to_light = position - point;
+                let distance_squared = to_light.length_squared();
+                let distance = distance_squared.sqrt();
+                let to_light = to_light / distance;
+                let shape = profile
+                    .as_ref()
+                    .map_or(1.0, |profile| profile.value(-to_light, DOWN));
+                Some(LightSample {
+                    direction: to_light,
+                    distance,
+                    radiance: intensity * (shape / distance_squared),
+                })
+            }
+            // Over a cone this narrow the cosine barely changes, so each
+            // direction of it delivers the full irradiance.
+            Self::Directional {
+                direction,
+                irradiance,
+                angular_diameter,
+            } => Some(LightSample {
+                direction: if angular_diameter > 0.0 {
+                    Vec3::random_in_cone(-direction, cos_radius(angular_diameter), sampler)
+                } else {
+                    -direction
+                },
+                distance:  Float::INFINITY,
+                radiance:  irradiance,
+            }),
+            Self::Spot {
+                position,
+                direction,
+                intensity,
+                cos_inner,
+                cos_outer,
+                ref profile,
+            } => {
+                let to_light = position - point;
+                let distance_squared = to_light.length_squared();
+                let distance = distance_squared.sqrt();
+                let to_light = to_light / distance;
+
+                let cos_theta = -to_light.dot(direction);
+                if cos_theta <= cos_outer {
+                    return None;
+                }
+                let falloff = smoothstep(cos_outer, cos_inner, cos_theta)
+                    * profile
+                        .as_ref()
+                        .map_or(1.0, |profile| profile.value(-to_light, direction));
+
+                Some(LightSample {
+                    direction: to_light,
+                    distance,
+                    radiance: intensity * (falloff / distance_squared),
+                })
+            }
+        }
+    }
+
+    /// Approximate power of the light, to spread emitted photons over the
+    /// lights. Directional lights count what falls onto `bounds`.
+    pub(crate) fn power(&self, bounds: &Aabb) -> Float {
+        let shape = self.profile().map_or(1.0, IesProfile::average);
+        match *self {
+            Self::Point { intensity, .. } => 4.0 * PI * intensity.luminance() * shape,
+            Self::Directional { irradiance, .. } => {
+                let (_, radius) = bounding_sphere(bounds);
+                PI * radius * radius * irradiance.luminance()
+            }
+            Self::Spot {
+                intensity,
+                cos_inner,
+                cos_outer,
+                ..
+            } => PI * (2.0 - cos_inner - cos_outer) * intensity.luminance() * shape,
+        }
+    }
+
+    /// Samples a ray leaving the light at `time`, returned with the flux it
+    /// carries divided by the density of sampling it. Directional lights
+    /// emit from a disk covering `bounds`.
+    pub(crate) fn sample_emission(
+        &self,
+        bounds: &Aabb,
+        time: Float,
+        sampler: &mut dyn Sampler,
+    ) -> (Ray, Vec3) {
+        match *self {
+            Self::Point { position, .. } => {
+                let direction = Vec3::random_unit(sampler);
+                let intensity = self
+                    .sample(position + direction, sampler)
+                    .map_or(Vec3::default(), |sample| sample.radiance());
+                (Ray::new(position, direction, time), intensity * (4.0 * PI))
+            }
+            Self::Directional {
+                direction,
+                irradiance,
+                angular_diameter,
+            } => {
+                let (center, radius) = bounding_sphere(bounds);
+                let frame = Onb::new(direction);
+                let disk = Vec3::random_in_disk(sampler);
+                let origin = center + frame.transform(Vec3::new(disk.x(), disk.y(), -1.0)) * radius;
+                let flux = irradiance * (PI * radius * radius);
+                let emitted = if angular_diameter > 0.0 {
+                    Vec3::random_in_cone(direction, cos_radius(angular_diameter), sampler)
+                } else {
+                    direction
+                };
+                (Ray::new(origin, emitted, time), flux)
+            }
+            Self::Spot {
+                position,
+                direction,
+                cos_outer,
+                ..
+            } => {
+                let emitted = Vec3::random_in_cone(direction, cos_outer, sampler);
+                let solid_angle = 2.0 * PI * (1.0 - cos_outer);
+                let intensity = self
+                    .sample(position + emitted, sampler)
+                    .map_or(Vec3::default(), |sample| sample.radiance());
+                (Ray::new(position, emitted, time), intensity * solid_angle)
+            }
+        }
+    }
+}
+
+/// Cosine of the angular radius of a disk `angular_diameter` degrees across.
+fn cos_radius(angular_diameter: Float) -> Float {
+    (angular_diameter / 2.0).to_radians().cos()
+}
+
+/// Center and radius of a sphere enclosing `bounds`.
+fn bounding_sphere(bounds: &Aabb) -> (Vec3, Float) {
+    let (x, y, z) = (bounds.x(), bounds.y(), bounds.z());
+    let diagonal = Vec3::new(x.size(), y.size(), z.size());
+    (bounds.centroid(), diagonal.length() / 2.0)
+}
+
+fn smoothstep(edge0: Float, edge1: Float, x: Float) -> Float {
+    if edge1 <= edge0 {
+        return if x >= edge1 { 1.0 } else { 0.0 };
+    }
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+impl LightList {
+    pub fn from_list(hit_list: &HitList) -> Self {
+        let list = hit_list.list().to_vec();
+        let distribution = (!list.is_empty())
+            .then(|| Distribution1D::new(list.iter().map(|obj| obj.power()).collect()));
+        let bbox = hit_list.aabb();
+
+        Self {
+            list,
+            distribution,
+            bbox,
+        }
+    }
+
+    pub fn list(&self) -> &[Arc<dyn DynHit>] {
+        &self.list
+    }
+
+    /// Probability of sampling the light at `index`.
+    pub fn probability(&self, index: usize) -> Float {
+        self.distribution
+            .as_ref()
+            .map_or(0.0, |distribution| distribution.discrete_pdf(index))
+    }
+}
+
+impl Hit for LightList {
+    type Material = Material;
+
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<Self::Material>> {
+        let mut rec = None;
+        let mut closest = ray_t.max();
+
+        for obj in self.list.iter() {
+            if let Some(hit) = obj.hit(ray, ray_t.with_max(closest)) {
+                closest = hit.t();
+                rec = Some(hit);
+            }
+        }
+
+        rec
+    }
+
+    fn aabb(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn count(&self) -> usize {
+        self.list.len()
+    }
+
+    fn pdf_value(&self, origin: Vec3, direction: Vec3, time: Float) -> Float {
+        self.list
+            .iter()
+            .enumerate()
+            .map(|(i, obj)| self.probability(i) * obj.pdf_value(origin, direction, time))
+            .sum()
+    }
+
+    fn random_to(&self, origin: Vec3, time: Float, sampler: &mut dyn Sampler) -> Vec3 {
+        match self.distribution {
+            Some(ref distribution) => {
+                let (index, _) = distribution.sample_discrete(sampler.next_1d());
+                self.list[index].random_to(origin, time, sampler)
+            }
+            None => Vec3::new(1.0, 0.0, 0.0),
+        }
+    }
+
+    fn power(&self) -> Float {
+        self.list.iter().map(|obj| obj.power()).sum()
+    }
+
+    fn sample_surface(
+        &self,
+        time: Float,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(HitRecord<Self::Material>, Float)> {
+        let distribution = self.distribution.as_ref()?;
+        let (index, probability) = distribution.sample_discrete(sampler.next_1d());
+        let (rec, pdf) = self.list[index].sample_surface(time, sampler)?;
+        Some((rec, pdf * probability))
+    }
+}
@@ -1,180 +1,212 @@
-use std::fs::OpenOptions;
+use std::{ffi::OsStr, fs::File, path::PathBuf, process::ExitCode, time::Instant};
 
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use ray::{
-    bvh::Bvh,
-    camera::Camera,
-    geo::{Quad, Sphere, Triangle},
-    hit::{Hit, HitList},
-    interval::Interval,
-    material::Material,
-    random_0_1, random_range,
-    vec3::Vec3,
+    Float,
+    metadata::RenderInfo,
+    post::{Effect, PostPipeline},
+    progress::log_to_stderr,
+    scenes::Preset,
+    stats,
 };
 
-fn spheres() -> Bvh {
-    let mut world = HitList::new();
-
-    let material_ground = Material::metal(Vec3::new(0.7, 0.7, 0.7), 0.01);
-    let ground = Sphere::new(Vec3::new(0.0, -1000.0, 0.0), 1000.0, material_ground);
-
-    world.push(ground);
-
-    for a in -11..11 {
-        for b in -11..11 {
-            let mat = random_0_1();
-            let center = Vec3::new(
-                a as f64 + 0.8 * random_0_1(),
-                0.2,
-                b as f64 + 0.8 * random_0_1(),
-            );
-
-            if (center - Vec3::new(4.0, 1.0, 0.0)).length() > 1.2
-                && (center - Vec3::new(0.0, 1.0, 0.0)).length() > 1.2
-                && (center - Vec3::new(-4.0, 1.0, 0.0)).length() > 1.2
-            {
-                let material = if mat < 0.4 {
-                    let albedo = Vec3::random().scale(Vec3::random());
-                    Material::lambertian(albedo)
-                } else if mat < 0.7 {
-                    let color_interval = Interval::new(0.6, 1.0);
-                    let fuzz_interval = Interval::new(0.0, 0.5);
-                    let albedo = Vec3::random_range(color_interval);
-                    let fuzz = random_range(fuzz_interval);
-                    Material::metal(albedo, fuzz)
-                } else if mat < 0.9 {
-                    Material::dialectric(1.5)
-                } else {
-                    let interval = Interval::new(0.2, 1.0);
-                    let color = Vec3::random_range(interval);
-                    Material::diffuse_light(color)
-                };
-                let sphere = Sphere::new(center, 0.2, material);
-                world.push(sphere);
-                if material.is_dielectric() {
-                    let material = Material::dialectric(1.0 / 1.5);
-                    let sphere = Sphere::new(center, 0.2 * 0.6, material);
-                    world.push(sphere);
-                }
-            }
-        }
-    }
-
-    Bvh::from_list(world.list_mut())
-}
+/// Standard deviation of the bloom blur, as a fraction of the image width.
+const BLOOM_RADIUS: Float = 0.01;
 
-fn triangles() -> Bvh {
-    let mut world = HitList::new();
+/// Weight of the bloom glow added to the image.
+const BLOOM_INTENSITY: Float = 0.25;
 
-    let material_ground = Material::lambertian(Vec3::new(0.5, 0.5, 0.5));
-    let ground = Sphere::new(Vec3::new(0.0, -1000.0, 0.0), 1000.0, material_ground);
-    world.push(ground);
-
-    let metal = Vec3::new(0.9, 0.9, 0.9);
-    let material = Material::metal(metal, 0.0);
-
-    let a = Vec3::new(0.0, 0.0, 0.0);
-    let b = Vec3::new(-8.0, 0.0, 0.0);
-    let c = Vec3::new(-8.0, 10.0, 0.0);
-
-    let triangle = Triangle::new(a, b, c, material);
-    world.push(triangle);
+/// Command line interface of the renderer.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
 
-    let a = Vec3::new(0.0, 0.0, 0.0);
-    let b = Vec3::new(0.0, 10.0, 0.0);
-    let c = Vec3::new(-8.0, 10.0, 0.0);
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Renders one of the built-in scenes to an image file.
+    Render(RenderArgs),
+}
 
-    let triangle = Triangle::new(a, b, c, material);
-    world.push(triangle);
+#[derive(Debug, Args)]
+struct RenderArgs {
+    /// Scene to render.
+    #[arg(long, value_enum, default_value_t = SceneName::Spheres)]
+    scene: SceneName,
+
+    /// Image width in pixels.
+    #[arg(long, default_value_t = 2560)]
+    width: u32,
+
+    /// Width over height of the image.
+    #[arg(long, default_value_t = 16.0 / 9.0)]
+    aspect_ratio: Float,
+
+    /// Samples per pixel.
+    #[arg(long, default_value_t = 700)]
+    samples: u32,
+
+    /// Maximum number of bounces per path.
+    #[arg(long, default_value_t = 50)]
+    max_depth: u32,
+
+    /// Rendering threads, all cores if 0.
+    #[arg(long, default_value_t = 0)]
+    threads: usize,
+
+    /// Render on the GPU when the scene and camera allow it.
+    #[cfg(feature = "gpu")]
+    #[arg(long)]
+    gpu: bool,
+
+    /// Seed for scene generation and sampling.
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// Output file, with a `.ppm` extension for an 8-bit PPM image or a
+    /// `.pfm` extension for a linear PFM image.
+    #[arg(long, default_value = "img/image5.ppm", value_parser = parse_out)]
+    out: PathBuf,
+
+    /// Also write the scene geometry and BVH node bounds to this OBJ file.
+    #[arg(long)]
+    obj: Option<PathBuf>,
+
+    /// Fringe the corners with chromatic aberration, red and blue spread
+    /// apart by this fraction of their distance to the center.
+    #[arg(long)]
+    chromatic_aberration: Option<Float>,
+
+    /// Add a glow around pixels brighter than this luminance.
+    #[arg(long)]
+    bloom: Option<Float>,
+
+    /// Darken the corners, seen at an angle whose squared tangent is this.
+    #[arg(long)]
+    vignette: Option<Float>,
+
+    /// Burn the scene, resolution, sample count and render time into the
+    /// bottom of the image.
+    #[arg(long)]
+    stamp: bool,
+
+    /// Also write the render settings and time to a JSON file named after
+    /// the output, with `.json` appended.
+    #[arg(long)]
+    metadata: bool,
+}
 
-    let material = Material::diffuse_light(Vec3::new(1.0, 1.0, 1.0));
-    let sphere = Sphere::new(Vec3::new(-4.0, 1.0, 2.5), 1.0, material);
-    world.push(sphere);
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum SceneName {
+    Spheres,
+    Triangles,
+    Quads,
+    CornellBox,
+}
 
-    Bvh::from_list(world.list_mut())
+impl From<SceneName> for Preset {
+    fn from(name: SceneName) -> Self {
+        match name {
+            SceneName::Spheres => Self::Spheres,
+            SceneName::Triangles => Self::Triangles,
+            SceneName::Quads => Self::Quads,
+            SceneName::CornellBox => Self::CornellBox,
+        }
+    }
 }
 
-fn quads() -> Bvh {
-    let mut world = HitList::new();
+/// Accepts output paths whose extension names a format the renderer writes.
+fn parse_out(arg: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(arg);
+    match path.extension().and_then(OsStr::to_str) {
+        Some("ppm" | "pfm") => Ok(path),
+        _ => Err("expected a .ppm or .pfm extension".to_owned()),
+    }
+}
 
-    let left_red = Material::lambertian(Vec3::new(1.0, 0.2, 0.2));
-    let back_green = Material::lambertian(Vec3::new(0.2, 1.0, 0.2));
-    let right_blue = Material::lambertian(Vec3::new(0.2, 0.2, 1.0));
-    let upper_orange = Material::lambertian(Vec3::new(1.0, 0.5, 0.0));
-    let lower_teal = Material::lambertian(Vec3::new(0.2, 0.8, 0.8));
+fn render(args: RenderArgs) -> ray::Result<()> {
+    let scene = Preset::from(args.scene).build(args.seed);
+    let camera = scene
+        .camera()
+        .aspect_ratio(args.aspect_ratio)
+        .img_width(args.width)
+        .build()
+        .with_seed(args.seed)
+        .with_progress(log_to_stderr)
+        .with_threads(args.threads)?;
+    if let Some(path) = &args.obj {
+        ray::obj::write_bvh(File::create(path)?, scene.world())?;
+    }
 
-    let left = Quad::new(
-        Vec3::new(-3.0, -2.0, 5.0),
-        Vec3::new(0.0, 0.0, -4.0),
-        Vec3::new(0.0, 4.0, 0.0),
-        left_red,
+    let file = File::create(&args.out)?;
+    let start = Instant::now();
+    #[cfg(feature = "gpu")]
+    let image = ray::gpu::render_or_cpu(
+        &camera,
+        scene.gpu().filter(|_| args.gpu),
+        scene.world(),
+        scene.lights(),
+        args.samples,
+        args.max_depth,
     );
-    let back = Quad::new(
-        Vec3::new(-2.0, -2.0, 0.0),
-        Vec3::new(4.0, 0.0, 0.0),
-        Vec3::new(0.0, 4.0, 0.0),
-        back_green,
-    );
-    let right = Quad::new(
-        Vec3::new(3.0, -2.0, 1.0),
-        Vec3::new(0.0, 0.0, 4.0),
-        Vec3::new(0.0, 4.0, 0.0),
-        right_blue,
-    );
-    let upper = Quad::new(
-        Vec3::new(-2.0, 3.0, 1.0),
-        Vec3::new(4.0, 0.0, 0.0),
-        Vec3::new(0.0, 0.0, 4.0),
-        upper_orange,
-    );
-    let lower = Quad::new(
-        Vec3::new(-2.0, -3.0, 5.0),
-        Vec3::new(4.0, 0.0, 0.0),
-        Vec3::new(0.0, 0.0, -4.0),
-        lower_teal,
+    #[cfg(not(feature = "gpu"))]
+    let image = camera
+        .render_frame(scene.world(), scene.lights(), args.samples, args.max_depth)
+        .into_beauty();
+    if cfg!(feature = "stats") {
+        eprintln!("{}", stats::snapshot());
+    }
+    let info = RenderInfo::new(
+        Preset::from(args.scene).name(),
+        &camera,
+        args.samples,
+        args.max_depth,
+        start.elapsed(),
     );
-
-    world.push(left);
-    world.push(right);
-    world.push(back);
-    world.push(upper);
-    world.push(lower);
-
-    Bvh::from_list(world.list_mut())
+    let mut image = post_pipeline(&args).apply(&image);
+    if args.stamp {
+        image = info.burn_in(&image);
+    }
+    if args.metadata {
+        let mut path = args.out.clone().into_os_string();
+        path.push(".json");
+        info.write_json(File::create(path)?)?;
+    }
+    match args.out.extension().and_then(OsStr::to_str) {
+        Some("pfm") => image.write_pfm(file)?,
+        _ => image.write_ppm(file)?,
+    }
+    Ok(())
 }
 
-fn main() {
-    let img_width = 2560u32;
-    let aspect_ratio = 16.0 / 9.0;
-    let sample_count = 700;
-    let max_depth = 50;
-    let fov = 20.0;
-    let lookfrom = Vec3::new(10.0, 20.0, 20.0);
-    let lookat = Vec3::new(0.0, 0.0, 0.0);
-    let vup = Vec3::new(0.0, 1.0, 0.0);
-    let defocus_angle = 0.0;
-    let focus_dist = 30.0;
-    let background = Vec3::default();
-
-    let camera = Camera::new(
-        aspect_ratio,
-        img_width,
-        fov,
-        lookfrom,
-        lookat,
-        vup,
-        defocus_angle,
-        focus_dist,
-        background,
-    );
-
-    let world = spheres();
-
-    let file = OpenOptions::new()
-        .write(true)
-        .truncate(true)
-        .create(true)
-        .open("img/image5.ppm")
-        .unwrap();
+/// Effects requested on the command line, lens ones before film ones.
+fn post_pipeline(args: &RenderArgs) -> PostPipeline {
+    let effects = [
+        args.chromatic_aberration.map(Effect::chromatic_aberration),
+        args.bloom.map(|threshold| {
+            let radius = BLOOM_RADIUS * args.width as Float;
+            Effect::bloom(threshold, radius, BLOOM_INTENSITY)
+        }),
+        args.vignette.map(Effect::vignette),
+    ];
+    effects
+        .into_iter()
+        .flatten()
+        .fold(PostPipeline::new(), PostPipeline::with_effect)
+}
 
-    camera.render(&world, sample_count, max_depth, file);
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Command::Render(args) => render(args),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
 }
@@ -1,73 +1,346 @@
 use std::ops::Neg;
 
-use crate::{hit::HitRecord, random_0_1, ray::Ray, vec3::Vec3};
+use crate::{
+    Float,
+    hit::{HitRecord, Sides},
+    pdf::{CosinePdf, Pdf, SpherePdf},
+    ray::Ray,
+    sampler::Sampler,
+    texture::Texture,
+    vec2::Vec2,
+    vec3::Vec3,
+};
+
+/// Wavelength of the yellow helium line in nanometers, at which the index of
+/// refraction of glasses is usually given.
+const D_LINE: Float = 587.6;
 
 pub trait Scatter: Sized {
-    fn scatter<T: Scatter>(&self, ray: &Ray, hit: &HitRecord<T>) -> Option<(Vec3, Ray)>;
+    fn scatter<T: Scatter>(
+        &self,
+        ray: &Ray,
+        hit: &HitRecord<T>,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Vec3, Ray)>;
     fn emit(&self) -> Option<Vec3> {
         None
     }
+
+    /// Density of scattering `ray` into `scattered` at `hit`. Materials that
+    /// scatter into a single direction (mirrors, glass) return zero, which
+    /// excludes them from light sampling.
+    fn scattering_pdf<T: Scatter>(
+        &self,
+        _ray: &Ray,
+        _hit: &HitRecord<T>,
+        _scattered: &Ray,
+    ) -> Float {
+        0.0
+    }
+
+    /// Sides of a surface [`Scatter::emit`] applies to.
+    fn emission_sides(&self) -> Sides {
+        Sides::Both
+    }
+
+    /// The dielectric the material is made of, if any, used to track the
+    /// nested dielectrics a path is inside of.
+    fn dielectric(&self) -> Option<Dielectric> {
+        None
+    }
+
+    /// Whether the material scatters light inside a medium rather than at a
+    /// surface, which keeps it out of the clay override.
+    fn is_volumetric(&self) -> bool {
+        false
+    }
+
+    /// Probability that a ray stops at a surface point with texture
+    /// coordinates `uv` rather than passing through it, for cutouts. The
+    /// ray's footprint is `footprint` wide in texture coordinates.
+    fn opacity_at(&self, _uv: Vec2, _footprint: Float) -> Float {
+        1.0
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Lambertian {
-    albedo: Vec3,
+    albedo:  Vec3,
+    #[cfg_attr(feature = "serde", serde(default))]
+    texture: Option<Texture>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    opacity: Option<Texture>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Metal {
     albedo: Vec3,
-    fuzz:   f64,
+    fuzz:   Float,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Dielectric {
-    refraction: f64,
+    refraction: Float,
+    #[cfg_attr(feature = "serde", serde(default))]
+    priority:   u32,
+    #[cfg_attr(feature = "serde", serde(default))]
+    dispersion: Option<Dispersion>,
 }
 
+/// Index of refraction varying with the wavelength `λ`, in micrometers,
+/// which splits white light into its colors.
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Dispersion {
+    /// Cauchy's equation, `n = a + b / λ²`.
+    Cauchy { a: Float, b: Float },
+    /// Sellmeier's equation, `n² = 1 + Σ bᵢ λ² / (λ² - cᵢ)`.
+    Sellmeier { b: [Float; 3], c: [Float; 3] },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DiffuseLight {
     color: Vec3,
+    #[cfg_attr(feature = "serde", serde(default))]
+    sides: Sides,
 }
 
+/// Phase function of a medium scattering light equally in every direction,
+/// keeping `albedo` of it.
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Isotropic {
+    albedo: Vec3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Material {
     Metal(Metal),
     Lambertian(Lambertian),
     Dielectric(Dielectric),
     DiffuseLight(DiffuseLight),
+    Isotropic(Isotropic),
 }
 
 impl Metal {
-    pub const fn new(albedo: Vec3, fuzz: f64) -> Self {
+    pub const fn new(albedo: Vec3, fuzz: Float) -> Self {
         Self {
             albedo,
             fuzz: fuzz.min(1.0),
         }
     }
+
+    pub const fn albedo(&self) -> Vec3 {
+        self.albedo
+    }
+
+    pub const fn fuzz(&self) -> Float {
+        self.fuzz
+    }
 }
 
 impl Lambertian {
     pub const fn new(albedo: Vec3) -> Self {
-        Self { albedo }
+        Self {
+            albedo,
+            texture: None,
+            opacity: None,
+        }
+    }
+
+    /// Multiplies the albedo by `texture` at the texture coordinates of
+    /// each hit.
+    pub const fn with_texture(mut self, texture: Texture) -> Self {
+        self.texture = Some(texture);
+        self
+    }
+
+    /// Cuts the surface out where the luminance of `opacity` is below one,
+    /// letting rays through with the missing probability, for leaves and
+    /// fences made of textured quads.
+    pub const fn with_opacity(mut self, opacity: Texture) -> Self {
+        self.opacity = Some(opacity);
+        self
+    }
+
+    pub const fn albedo(&self) -> Vec3 {
+        self.albedo
+    }
+
+    pub const fn texture(&self) -> Option<Texture> {
+        self.texture
+    }
+
+    pub const fn opacity(&self) -> Option<Texture> {
+        self.opacity
+    }
+
+    /// Albedo at `hit`, textured if the material has a texture.
+    pub fn albedo_at<T: Scatter>(&self, hit: &HitRecord<T>) -> Vec3 {
+        self.texture.map_or(self.albedo, |texture| {
+            self.albedo
+                .scale(texture.value(hit.uv(), hit.uv_footprint()))
+        })
     }
 }
 
 impl Dielectric {
-    pub const fn new(refraction: f64) -> Self {
-        Self { refraction }
+    pub const fn new(refraction: Float) -> Self {
+        Self {
+            refraction,
+            priority: 0,
+            dispersion: None,
+        }
+    }
+
+    /// Dielectric whose index of refraction follows `dispersion`. Paths
+    /// of the path tracers pick a wavelength when they first hit it and
+    /// refract at that wavelength, while other renderers use the index at
+    /// the yellow helium line, 587.6 nm.
+    pub fn dispersive(dispersion: Dispersion) -> Self {
+        Self {
+            refraction: dispersion.refraction(D_LINE),
+            priority:   0,
+            dispersion: Some(dispersion),
+        }
+    }
+
+    /// Sets which of overlapping dielectrics fills their overlap, the
+    /// highest priority winning; surfaces of lower priority dielectrics
+    /// inside it are ignored. For a glass of water with ice, modelling the
+    /// water slightly larger than the inside of the glass, the glass should
+    /// have the highest priority, then the ice, then the water. Defaults to
+    /// `0`.
+    pub const fn with_priority(mut self, priority: u32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub const fn refraction(&self) -> Float {
+        self.refraction
+    }
+
+    pub const fn priority(&self) -> u32 {
+        self.priority
+    }
+
+    pub const fn dispersion(&self) -> Option<Dispersion> {
+        self.dispersion
+    }
+
+    /// Index of refraction at `lambda` nanometers.
+    pub fn refraction_at(&self, lambda: Float) -> Float {
+        self.dispersion
+            .map_or(self.refraction, |dispersion| dispersion.refraction(lambda))
+    }
+
+    pub fn reflectance(self, cos: Float) -> Float {
+        schlick(self.refraction, cos)
+    }
+
+    /// Reflects or refracts `ray` at a surface of the dielectric, crossing
+    /// from a medium of index of refraction `from` into one of index `to`.
+    pub fn scatter_between<T: Scatter>(
+        &self,
+        ray: &Ray,
+        hit: &HitRecord<T>,
+        from: Float,
+        to: Float,
+        sampler: &mut dyn Sampler,
+    ) -> (Vec3, Ray) {
+        let attenuation = Vec3::new(1.0, 1.0, 1.0);
+        let ri = from / to;
+        let unit_dir = ray.direction().unit();
+        let cos_theta = unit_dir.neg().dot(hit.normal()).min(1.0);
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+
+        let direction = if ri * sin_theta > 1.0 || schlick(to / from, cos_theta) > sampler.next_1d()
+        {
+            unit_dir.reflect(hit.normal())
+        } else {
+            unit_dir.refract(hit.normal(), ri)
+        };
+
+        (attenuation, hit.spawn_ray(direction, ray.time()))
     }
+}
+
+impl Dispersion {
+    /// Borosilicate crown glass, BK7, the most common optical glass.
+    #[allow(clippy::excessive_precision)]
+    pub const BK7: Self = Self::Sellmeier {
+        b: [1.03961212, 0.231792344, 1.01046945],
+        c: [0.00600069867, 0.0200179144, 103.560653],
+    };
 
-    pub fn reflectance(self, cos: f64) -> f64 {
-        let r0 = (1.0 - self.refraction) / (1.0 + self.refraction);
-        let r0 = r0 * r0;
-        r0 * (1.0 - r0) * (1.0 - cos).powi(5)
+    /// Dense flint glass, SF11, dispersing about three times as much as
+    /// BK7.
+    #[allow(clippy::excessive_precision)]
+    pub const SF11: Self = Self::Sellmeier {
+        b: [1.73759695, 0.313747346, 1.89878101],
+        c: [0.013188707, 0.0623068142, 155.23629],
+    };
+
+    /// Index of refraction at `lambda` nanometers.
+    pub fn refraction(&self, lambda: Float) -> Float {
+        let micrometers = lambda / 1000.0;
+        let squared = micrometers * micrometers;
+        match *self {
+            Self::Cauchy { a, b } => a + b / squared,
+            Self::Sellmeier { b, c } => {
+                let sum: Float = b
+                    .iter()
+                    .zip(c)
+                    .map(|(b, c)| b * squared / (squared - c))
+                    .sum();
+                (1.0 + sum).sqrt()
+            }
+        }
     }
 }
 
+fn schlick(refraction: Float, cos: Float) -> Float {
+    let r0 = (1.0 - refraction) / (1.0 + refraction);
+    let r0 = r0 * r0;
+    r0 * (1.0 - r0) * (1.0 - cos).powi(5)
+}
+
 impl DiffuseLight {
     pub const fn new(color: Vec3) -> Self {
-        Self { color }
+        Self {
+            color,
+            sides: Sides::Both,
+        }
+    }
+
+    /// Sets which sides of the surfaces emit, [`Sides::Front`] making one
+    /// way light panels. Defaults to both.
+    pub const fn with_sides(mut self, sides: Sides) -> Self {
+        self.sides = sides;
+        self
+    }
+
+    pub const fn color(&self) -> Vec3 {
+        self.color
+    }
+
+    pub const fn sides(&self) -> Sides {
+        self.sides
+    }
+}
+
+impl Isotropic {
+    pub const fn new(albedo: Vec3) -> Self {
+        Self { albedo }
+    }
+
+    pub const fn albedo(&self) -> Vec3 {
+        self.albedo
     }
 }
 
@@ -76,11 +349,11 @@ impl Material {
         Material::Lambertian(Lambertian::new(albedo))
     }
 
-    pub const fn metal(albedo: Vec3, fuzz: f64) -> Self {
+    pub const fn metal(albedo: Vec3, fuzz: Float) -> Self {
         Material::Metal(Metal::new(albedo, fuzz))
     }
 
-    pub const fn dialectric(refraction: f64) -> Self {
+    pub const fn dialectric(refraction: Float) -> Self {
         Material::Dielectric(Dielectric::new(refraction))
     }
 
@@ -88,6 +361,10 @@ impl Material {
         Material::DiffuseLight(DiffuseLight::new(color))
     }
 
+    pub const fn isotropic(albedo: Vec3) -> Self {
+        Material::Isotropic(Isotropic::new(albedo))
+    }
+
     /// Returns `true` if the material is [`Dielectric`].
     ///
     /// [`Dielectric`]: Material::Dielectric
@@ -98,21 +375,38 @@ impl Material {
 }
 
 impl Scatter for Lambertian {
-    fn scatter<T: Scatter>(&self, ray: &Ray, hit: &HitRecord<T>) -> Option<(Vec3, Ray)> {
-        let mut dir = hit.normal() + Vec3::random_unit();
-        if dir.near_zero() {
-            dir = hit.normal();
-        }
-        let scattered = Ray::new(hit.point(), dir, ray.time());
-        Some((self.albedo, scattered))
+    fn scatter<T: Scatter>(
+        &self,
+        ray: &Ray,
+        hit: &HitRecord<T>,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Vec3, Ray)> {
+        let dir = CosinePdf::new(hit.normal()).generate(sampler);
+        let scattered = hit.spawn_ray(dir, ray.time());
+        Some((self.albedo_at(hit), scattered))
+    }
+
+    fn scattering_pdf<T: Scatter>(&self, _: &Ray, hit: &HitRecord<T>, scattered: &Ray) -> Float {
+        CosinePdf::new(hit.normal()).value(scattered.direction())
+    }
+
+    fn opacity_at(&self, uv: Vec2, footprint: Float) -> Float {
+        self.opacity.map_or(1.0, |opacity| {
+            opacity.value(uv, footprint).luminance().clamp(0.0, 1.0)
+        })
     }
 }
 
 impl Scatter for Metal {
-    fn scatter<T: Scatter>(&self, ray: &Ray, hit: &HitRecord<T>) -> Option<(Vec3, Ray)> {
+    fn scatter<T: Scatter>(
+        &self,
+        ray: &Ray,
+        hit: &HitRecord<T>,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Vec3, Ray)> {
         let reflected = ray.direction().reflect(hit.normal());
-        let reflected = reflected.unit() + (Vec3::random_unit() * self.fuzz);
-        let scattered = Ray::new(hit.point(), reflected, ray.time());
+        let reflected = reflected.unit() + (Vec3::random_unit(sampler) * self.fuzz);
+        let scattered = hit.spawn_ray(reflected, ray.time());
         if scattered.direction().dot(hit.normal()) > 0.0 {
             Some((self.albedo, scattered))
         } else {
@@ -122,46 +416,77 @@ impl Scatter for Metal {
 }
 
 impl Scatter for Dielectric {
-    fn scatter<T: Scatter>(&self, ray: &Ray, hit: &HitRecord<T>) -> Option<(Vec3, Ray)> {
-        let attenuation = Vec3::new(1.0, 1.0, 1.0);
-        let ri = if hit.front_face() {
-            1.0 / self.refraction
+    fn scatter<T: Scatter>(
+        &self,
+        ray: &Ray,
+        hit: &HitRecord<T>,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Vec3, Ray)> {
+        let (from, to) = if hit.front_face() {
+            (1.0, self.refraction)
         } else {
-            self.refraction
+            (self.refraction, 1.0)
         };
-        let unit_dir = ray.direction().unit();
-        let cos_theta = unit_dir.neg().dot(hit.normal()).min(1.0);
-        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
-
-        let direction = if ri * sin_theta > 1.0 || self.reflectance(cos_theta) > random_0_1() {
-            unit_dir.reflect(hit.normal())
-        } else {
-            unit_dir.refract(hit.normal(), ri)
-        };
-
-        let scattered = Ray::new(hit.point(), direction, ray.time());
+        Some(self.scatter_between(ray, hit, from, to, sampler))
+    }
 
-        Some((attenuation, scattered))
+    fn dielectric(&self) -> Option<Dielectric> {
+        Some(*self)
     }
 }
 
 impl Scatter for DiffuseLight {
-    fn scatter<T: Scatter>(&self, _: &Ray, _: &HitRecord<T>) -> Option<(Vec3, Ray)> {
+    fn scatter<T: Scatter>(
+        &self,
+        _: &Ray,
+        _: &HitRecord<T>,
+        _: &mut dyn Sampler,
+    ) -> Option<(Vec3, Ray)> {
         None
     }
 
     fn emit(&self) -> Option<Vec3> {
         Some(self.color)
     }
+
+    fn emission_sides(&self) -> Sides {
+        self.sides
+    }
+}
+
+impl Scatter for Isotropic {
+    fn scatter<T: Scatter>(
+        &self,
+        ray: &Ray,
+        hit: &HitRecord<T>,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Vec3, Ray)> {
+        let scattered = hit.spawn_ray(SpherePdf.generate(sampler), ray.time());
+        Some((self.albedo, scattered))
+    }
+
+    fn scattering_pdf<T: Scatter>(&self, _: &Ray, _: &HitRecord<T>, scattered: &Ray) -> Float {
+        SpherePdf.value(scattered.direction())
+    }
+
+    fn is_volumetric(&self) -> bool {
+        true
+    }
 }
 
 impl Scatter for Material {
-    fn scatter<T: Scatter>(&self, ray: &Ray, hit: &HitRecord<T>) -> Option<(Vec3, Ray)> {
+    fn scatter<T: Scatter>(
+        &self,
+        ray: &Ray,
+        hit: &HitRecord<T>,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Vec3, Ray)> {
         match self {
-            Material::Metal(metal) => metal.scatter(ray, hit),
-            Material::Lambertian(lambertian) => lambertian.scatter(ray, hit),
-            Material::Dielectric(dielectric) => dielectric.scatter(ray, hit),
-            Material::DiffuseLight(light) => light.scatter(ray, hit),
+            Material::Metal(metal) => metal.scatter(ray, hit, sampler),
+            Material::Lambertian(lambertian) => lambertian.scatter(ray, hit, sampler),
+            Material::Dielectric(dielectric) => dielectric.scatter(ray, hit, sampler),
+            Material::DiffuseLight(light) => light.scatter(ray, hit, sampler),
+            Material::Isotropic(isotropic) => isotropic.scatter(ray, hit, sampler),
         }
     }
 
@@ -171,6 +496,42 @@ impl Scatter for Material {
             Material::Lambertian(lambertian) => lambertian.emit(),
             Material::Dielectric(dielectric) => dielectric.emit(),
             Material::DiffuseLight(diffuse_light) => diffuse_light.emit(),
+            Material::Isotropic(isotropic) => isotropic.emit(),
+        }
+    }
+
+    fn scattering_pdf<T: Scatter>(&self, ray: &Ray, hit: &HitRecord<T>, scattered: &Ray) -> Float {
+        match self {
+            Material::Metal(metal) => metal.scattering_pdf(ray, hit, scattered),
+            Material::Lambertian(lambertian) => lambertian.scattering_pdf(ray, hit, scattered),
+            Material::Dielectric(dielectric) => dielectric.scattering_pdf(ray, hit, scattered),
+            Material::DiffuseLight(light) => light.scattering_pdf(ray, hit, scattered),
+            Material::Isotropic(isotropic) => isotropic.scattering_pdf(ray, hit, scattered),
+        }
+    }
+
+    fn emission_sides(&self) -> Sides {
+        match self {
+            Material::DiffuseLight(light) => light.emission_sides(),
+            _ => Sides::Both,
+        }
+    }
+
+    fn dielectric(&self) -> Option<Dielectric> {
+        match self {
+            Material::Dielectric(dielectric) => Some(*dielectric),
+            _ => None,
+        }
+    }
+
+    fn is_volumetric(&self) -> bool {
+        matches!(self, Material::Isotropic(..))
+    }
+
+    fn opacity_at(&self, uv: Vec2, footprint: Float) -> Float {
+        match self {
+            Material::Lambertian(lambertian) => lambertian.opacity_at(uv, footprint),
+            _ => 1.0,
         }
     }
 }
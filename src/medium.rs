@@ -0,0 +1,468 @@
+//! Participating media, which scatter and absorb light between surfaces.
+//!
+//! [`Fog`] fills the scene with a homogeneous medium the path tracer
+//! follows every ray through. Rays travel a random distance through it,
+//! exponentially distributed with its density, before meeting a particle
+//! that scatters them in a new direction or absorbs them. Shadow rays are
+//! dimmed by the fog they cross instead, so light sampled from points in the
+//! fog shows the beams of light shafts.
+//!
+//! Smoke and clouds are [`Volume`]s, objects of the scene whose density
+//! varies over a [`DensityGrid`]. Their [`Hit::hit`] finds where a ray meets
+//! a particle with delta tracking ("Monte Carlo Methods for Volumetric Light
+//! Transport Simulation", Novák et al. 2018), testing tentative collisions
+//! drawn against the densest cell and keeping each with the probability of
+//! the density found there. Shadow rays estimate the transmittance with
+//! ratio tracking and are blocked with the remaining probability. Hits have
+//! no sampler to draw from, so both seed their random numbers from the ray.
+//! OpenVDB and NanoVDB files are not read; their grids have to be exported
+//! as raw values, see [`DensityGrid::open`].
+//!
+//! Fire is a volume with a grid of temperatures as well, whose particles
+//! glow with the color of a blackbody, see [`Volume::with_temperature`].
+//! Their light is only found by rays meeting them, not sampled like the
+//! lights of the scene.
+
+use std::{
+    fs::File,
+    io::{self, BufReader, Read},
+    path::Path,
+    sync::Arc,
+};
+
+use crate::{
+    Float,
+    aabb::Aabb,
+    error::{Error, Result},
+    hit::{Hit, HitRecord},
+    interval::Interval,
+    material::Scatter,
+    ray::Ray,
+    sampler::{RandomSampler, Sampler, hash_seed},
+    spectrum,
+    vec3::Vec3,
+};
+
+/// Temperature, in kelvin, at which the particles of a volume glow with the
+/// intensity given to [`Volume::with_temperature`].
+pub const REFERENCE_TEMPERATURE: Float = 1000.0;
+
+/// Homogeneous fog enabled by
+/// [`Camera::with_fog`](crate::camera::Camera::with_fog).
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Fog {
+    density: Float,
+    albedo:  Vec3,
+    #[cfg_attr(feature = "serde", serde(default))]
+    bounds:  Option<Aabb>,
+}
+
+/// Densities, or other values such as temperatures, sampled on a regular 3D
+/// grid, trilinearly interpolated between the centers of the cells.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DensityGrid {
+    size:   [usize; 3],
+    /// Value of every cell, indexed by `x + size_x * (y + size_y * z)`.
+    values: Vec<Float>,
+    max:    Float,
+}
+
+/// Heterogeneous medium, a [`DensityGrid`] stretched over a box, whose
+/// particles scatter light with a phase function material such as
+/// [`Material::isotropic`](crate::material::Material::isotropic).
+#[derive(Debug, Clone)]
+pub struct Volume<M> {
+    grid:        Arc<DensityGrid>,
+    bounds:      Aabb,
+    scale:       Float,
+    phase:       M,
+    temperature: Option<Arc<DensityGrid>>,
+    intensity:   Float,
+}
+
+impl Fog {
+    /// Fog of `density`, the chance per unit distance for light to meet a
+    /// particle, which scatters `albedo` of the light and absorbs the rest.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `density` is negative or not finite, see
+    /// [`Fog::try_new`].
+    pub fn new(density: Float, albedo: Vec3) -> Self {
+        Self::try_new(density, albedo).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Creates the fog like [`Fog::new`], failing if `density` is negative
+    /// or not finite.
+    pub fn try_new(density: Float, albedo: Vec3) -> Result<Self> {
+        if !(density.is_finite() && density >= 0.0) {
+            return Err(Error::InvalidParameter(
+                "the fog density must be finite and non-negative",
+            ));
+        }
+        Ok(Self {
+            density,
+            albedo,
+            bounds: None,
+        })
+    }
+
+    /// Confines the fog to `bounds`. Defaults to the bounds of the world,
+    /// so rays leaving the scene reach the background unattenuated.
+    pub const fn with_bounds(mut self, bounds: Aabb) -> Self {
+        self.bounds = Some(bounds);
+        self
+    }
+
+    pub const fn density(&self) -> Float {
+        self.density
+    }
+
+    pub const fn albedo(&self) -> Vec3 {
+        self.albedo
+    }
+
+    pub const fn bounds(&self) -> Option<Aabb> {
+        self.bounds
+    }
+
+    /// Fraction of the light carried along `ray` over `ray_t` that crosses
+    /// the fog, which fills `world_bounds` unless it has bounds of its own.
+    pub fn transmittance(&self, ray: &Ray, ray_t: Interval, world_bounds: Aabb) -> Float {
+        self.extent(ray, ray_t, world_bounds)
+            .map_or(1.0, |extent| (-self.density * length(ray, extent)).exp())
+    }
+
+    /// Samples the parameter along `ray` within `ray_t` at which it meets a
+    /// particle of the fog, which fills `world_bounds` unless it has bounds
+    /// of its own. Returns `None` for rays crossing the fog, with a
+    /// probability equal to their transmittance.
+    pub fn sample_distance(
+        &self,
+        ray: &Ray,
+        ray_t: Interval,
+        world_bounds: Aabb,
+        sampler: &mut dyn Sampler,
+    ) -> Option<Float> {
+        let extent = self.extent(ray, ray_t, world_bounds)?;
+        let distance = free_flight(self.density, sampler);
+        let speed = ray.direction().length();
+        (distance < length(ray, extent)).then(|| extent.min() + distance / speed)
+    }
+
+    /// Part of `ray_t` inside the fog.
+    fn extent(&self, ray: &Ray, ray_t: Interval, world_bounds: Aabb) -> Option<Interval> {
+        if self.density <= 0.0 {
+            return None;
+        }
+        self.bounds.unwrap_or(world_bounds).hit(ray, ray_t)
+    }
+}
+
+impl DensityGrid {
+    /// Grid of `size` cells along `x`, `y` and `z`, with `values` indexed by
+    /// `x + size_x * (y + size_y * z)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the grid is empty, `values` does not hold one value per
+    /// cell or a value is negative or not finite, see
+    /// [`DensityGrid::try_new`].
+    pub fn new(size: [usize; 3], values: Vec<Float>) -> Self {
+        Self::try_new(size, values).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Creates the grid like [`DensityGrid::new`], failing if the grid is
+    /// empty, `values` does not hold one value per cell or a value is
+    /// negative or not finite.
+    pub fn try_new(size: [usize; 3], values: Vec<Float>) -> Result<Self> {
+        if size.contains(&0) {
+            return Err(Error::InvalidParameter(
+                "the density grid must not be empty",
+            ));
+        }
+        if values.len() != size.iter().product::<usize>() {
+            return Err(Error::InvalidParameter(
+                "the density grid must hold one value per cell",
+            ));
+        }
+        if !values
+            .iter()
+            .all(|value| value.is_finite() && *value >= 0.0)
+        {
+            return Err(Error::InvalidParameter(
+                "grid densities must be finite and non-negative",
+            ));
+        }
+        let max = values.iter().copied().fold(0.0, Float::max);
+        Ok(Self { size, values, max })
+    }
+
+    /// Loads a grid of `size` cells from a raw file, see
+    /// [`DensityGrid::from_raw`].
+    pub fn open(path: impl AsRef<Path>, size: [usize; 3]) -> io::Result<Self> {
+        let file = File::open(path)?;
+        Self::from_raw(BufReader::new(file), size)
+    }
+
+    /// Reads a grid of `size` cells stored as little-endian 32-bit floats
+    /// in the order of [`DensityGrid::new`], with no header, the simplest
+    /// format for grids converted from OpenVDB or other tools.
+    pub fn from_raw<R: Read>(mut reader: R, size: [usize; 3]) -> io::Result<Self> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        if bytes.len() % 4 != 0 {
+            return Err(invalid_data("raw grid size is not a multiple of 4 bytes"));
+        }
+        let values = bytes
+            .chunks_exact(4)
+            .map(|value| f32::from_le_bytes([value[0], value[1], value[2], value[3]]) as Float)
+            .collect();
+        Self::try_new(size, values).map_err(|err| invalid_data(&err.to_string()))
+    }
+
+    /// Number of cells along `x`, `y` and `z`.
+    pub const fn size(&self) -> [usize; 3] {
+        self.size
+    }
+
+    pub fn values(&self) -> &[Float] {
+        &self.values
+    }
+
+    /// Largest value of the grid, which bounds the interpolated ones.
+    pub const fn max(&self) -> Float {
+        self.max
+    }
+
+    /// Value at `point`, with the grid spanning the unit cube. Cells at the
+    /// border extend to it.
+    pub fn value(&self, point: Vec3) -> Float {
+        let [sx, sy, sz] = self.size;
+        let cell = |coordinate: Float, size: usize| {
+            let x = (coordinate * size as Float - 0.5).clamp(0.0, (size - 1) as Float);
+            let i = (x as usize).min(size.saturating_sub(2));
+            (i, x - i as Float)
+        };
+        let (x, tx) = cell(point.x(), sx);
+        let (y, ty) = cell(point.y(), sy);
+        let (z, tz) = cell(point.z(), sz);
+
+        let at = |dx: usize, dy: usize, dz: usize| {
+            let index =
+                (x + dx).min(sx - 1) + sx * ((y + dy).min(sy - 1) + sy * (z + dz).min(sz - 1));
+            self.values[index]
+        };
+        let lerp = |a: Float, b: Float, t: Float| a + (b - a) * t;
+        let plane = |dz| {
+            lerp(
+                lerp(at(0, 0, dz), at(1, 0, dz), tx),
+                lerp(at(0, 1, dz), at(1, 1, dz), tx),
+                ty,
+            )
+        };
+        lerp(plane(0), plane(1), tz)
+    }
+}
+
+impl<M> Volume<M> {
+    /// Stretches `grid` over `bounds`, its particles scattering with
+    /// `phase`. Densities are per unit distance, scaled by one unless set
+    /// with [`Volume::with_density_scale`].
+    pub fn new(grid: Arc<DensityGrid>, bounds: Aabb, phase: M) -> Self {
+        Self {
+            grid,
+            bounds,
+            scale: 1.0,
+            phase,
+            temperature: None,
+            intensity: 0.0,
+        }
+    }
+
+    /// Multiplies every density of the grid by `scale`, making the medium
+    /// thinner or thicker without touching the grid.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `scale` is negative or not finite.
+    pub fn with_density_scale(mut self, scale: Float) -> Self {
+        assert!(
+            scale.is_finite() && scale >= 0.0,
+            "the density scale must be finite and non-negative"
+        );
+        self.scale = scale;
+        self
+    }
+
+    /// Makes the particles glow with the color of a blackbody at the
+    /// temperature, in kelvin, of the `temperature` grid stretched over the
+    /// bounds like the densities. The light they emit grows with the fourth
+    /// power of the temperature and is `intensity` at
+    /// [`REFERENCE_TEMPERATURE`]. Every particle a ray meets adds its light,
+    /// so only places with density glow, and `intensity` stands for the
+    /// fraction of the light meeting particles that they absorb as well.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `intensity` is negative or not finite.
+    pub fn with_temperature(mut self, temperature: Arc<DensityGrid>, intensity: Float) -> Self {
+        assert!(
+            intensity.is_finite() && intensity >= 0.0,
+            "the emission intensity must be finite and non-negative"
+        );
+        self.temperature = Some(temperature);
+        self.intensity = intensity;
+        self
+    }
+
+    pub fn grid(&self) -> &DensityGrid {
+        &self.grid
+    }
+
+    pub fn temperature(&self) -> Option<&DensityGrid> {
+        self.temperature.as_deref()
+    }
+
+    pub const fn emission_intensity(&self) -> Float {
+        self.intensity
+    }
+
+    pub const fn bounds(&self) -> Aabb {
+        self.bounds
+    }
+
+    pub const fn density_scale(&self) -> Float {
+        self.scale
+    }
+
+    pub const fn phase(&self) -> &M {
+        &self.phase
+    }
+
+    /// Position of `point` in the bounds, mapped to the unit cube.
+    fn local(&self, point: Vec3) -> Vec3 {
+        Vec3::new(
+            (point.x() - self.bounds.x().min()) / self.bounds.x().size(),
+            (point.y() - self.bounds.y().min()) / self.bounds.y().size(),
+            (point.z() - self.bounds.z().min()) / self.bounds.z().size(),
+        )
+    }
+
+    /// Density at `point` inside the bounds.
+    fn density(&self, point: Vec3) -> Float {
+        self.grid.value(self.local(point)) * self.scale
+    }
+
+    /// Light emitted by a particle at `point` inside the bounds, if the
+    /// volume glows.
+    fn emission(&self, point: Vec3) -> Option<Vec3> {
+        let kelvin = self.temperature.as_ref()?.value(self.local(point));
+        let relative = kelvin / REFERENCE_TEMPERATURE;
+        let power = relative * relative * relative * relative;
+        Some(spectrum::blackbody_rgb(kelvin) * (self.intensity * power))
+    }
+
+    /// Part of `ray_t` inside the bounds and the largest density of the
+    /// medium, which tentative collisions are drawn against. `None` if the
+    /// ray misses the medium or it is empty.
+    fn extent(&self, ray: &Ray, ray_t: Interval) -> Option<(Interval, Float)> {
+        let majorant = self.grid.max() * self.scale;
+        if majorant <= 0.0 {
+            return None;
+        }
+        Some((self.bounds.hit(ray, ray_t)?, majorant))
+    }
+}
+
+impl<M: Scatter + Copy> Hit for Volume<M> {
+    type Material = M;
+
+    /// Delta tracking: tentative collisions are real with the ratio of the
+    /// density to the majorant, and the ray stops at the first real one.
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<M>> {
+        let (extent, majorant) = self.extent(ray, ray_t)?;
+        let mut sampler = ray_sampler(ray);
+        let speed = ray.direction().length();
+        let mut t = extent.min();
+        loop {
+            t += free_flight(majorant, &mut sampler) / speed;
+            if t >= extent.max() {
+                return None;
+            }
+            if sampler.next_1d() * majorant < self.density(ray.at(t)) {
+                break;
+            }
+        }
+        let point = ray.at(t);
+        let normal = -ray.direction().unit();
+        let hit = HitRecord::new(point, normal, t, ray, self.phase);
+        Some(match self.emission(point) {
+            Some(emitted) => hit.with_emission(emitted),
+            None => hit,
+        })
+    }
+
+    /// Ratio tracking: the transmittance is the product of the chances of
+    /// the tentative collisions being null, and the ray is blocked with the
+    /// remaining probability.
+    fn hit_any(&self, ray: &Ray, ray_t: Interval) -> bool {
+        let Some((extent, majorant)) = self.extent(ray, ray_t) else {
+            return false;
+        };
+        let mut sampler = ray_sampler(ray);
+        let speed = ray.direction().length();
+        let mut transmittance = 1.0;
+        let mut t = extent.min();
+        loop {
+            t += free_flight(majorant, &mut sampler) / speed;
+            if t >= extent.max() {
+                break;
+            }
+            transmittance *= 1.0 - self.density(ray.at(t)) / majorant;
+        }
+        sampler.next_1d() >= transmittance
+    }
+
+    fn aabb(&self) -> Aabb {
+        self.bounds
+    }
+
+    fn count(&self) -> usize {
+        1
+    }
+}
+
+/// Distance to the next collision with particles of `density`, sampled
+/// from its exponential distribution.
+fn free_flight(density: Float, sampler: &mut dyn Sampler) -> Float {
+    -(1.0 - sampler.next_1d()).ln() / density
+}
+
+/// Random numbers for the tracking along `ray`, seeded from the ray so that
+/// every ray draws different ones.
+#[allow(clippy::unnecessary_cast, reason = "`Float` may be `f32`")]
+fn ray_sampler(ray: &Ray) -> RandomSampler {
+    let (origin, direction) = (ray.origin(), ray.direction());
+    let bits = [
+        origin.x(),
+        origin.y(),
+        origin.z(),
+        direction.x(),
+        direction.y(),
+        direction.z(),
+        ray.time(),
+    ]
+    .map(|value| (value as f64).to_bits());
+    RandomSampler::new(hash_seed(&bits))
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Distance travelled along `ray` over `extent`.
+fn length(ray: &Ray, extent: Interval) -> Float {
+    extent.size() * ray.direction().length()
+}
@@ -0,0 +1,343 @@
+//! Records of how an image was rendered, kept with it so that renders can
+//! be compared long after the settings are forgotten.
+//!
+//! A [`RenderInfo`] is either burnt into the image as a strip of text along
+//! its bottom edge, drawn with a built-in 5 by 7 pixel font, or written to a
+//! sidecar JSON file next to it.
+
+use std::{
+    io::{self, Write},
+    time::Duration,
+};
+
+use crate::{
+    Float,
+    camera::{Camera, RenderMode},
+    film::Image,
+    vec3::Vec3,
+};
+
+/// Width of a glyph of the font, in font pixels.
+const GLYPH_WIDTH: u32 = 5;
+
+/// Height of a glyph of the font, in font pixels.
+const GLYPH_HEIGHT: u32 = 7;
+
+/// Fraction of the light behind the text strip that shows through it.
+const STRIP_OPACITY: Float = 0.25;
+
+/// Settings and timing of a render.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderInfo {
+    scene:     String,
+    width:     u32,
+    height:    u32,
+    mode:      RenderMode,
+    seed:      u64,
+    samples:   u32,
+    max_depth: u32,
+    elapsed:   Duration,
+}
+
+impl RenderInfo {
+    /// Render of `scene` through `camera` with `samples` samples per pixel
+    /// and paths of up to `max_depth` bounces, which took `elapsed`.
+    pub fn new(
+        scene: impl Into<String>,
+        camera: &Camera,
+        samples: u32,
+        max_depth: u32,
+        elapsed: Duration,
+    ) -> Self {
+        Self {
+            scene: scene.into(),
+            width: camera.img_width(),
+            height: camera.img_height(),
+            mode: camera.mode(),
+            seed: camera.seed(),
+            samples,
+            max_depth,
+            elapsed,
+        }
+    }
+
+    pub fn scene(&self) -> &str {
+        &self.scene
+    }
+
+    pub const fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub const fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub const fn mode(&self) -> RenderMode {
+        self.mode
+    }
+
+    pub const fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub const fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    pub const fn max_depth(&self) -> u32 {
+        self.max_depth
+    }
+
+    pub const fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// One line summary of the render, as burnt into images.
+    pub fn summary(&self) -> String {
+        format!(
+            "{}  {}x{}  {} spp  depth {}  seed {}  {:.1} s",
+            self.scene,
+            self.width,
+            self.height,
+            self.samples,
+            self.max_depth,
+            self.seed,
+            self.elapsed.as_secs_f64(),
+        )
+    }
+
+    /// Writes the settings as a JSON object.
+    pub fn write_json<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let string = |value: &str| format!("\"{}\"", escape_json(value));
+        let fields = [
+            (
+                "renderer",
+                string(concat!("ray ", env!("CARGO_PKG_VERSION"))),
+            ),
+            ("scene", string(&self.scene)),
+            ("width", self.width.to_string()),
+            ("height", self.height.to_string()),
+            ("mode", string(&format!("{:?}", self.mode))),
+            ("samples", self.samples.to_string()),
+            ("max_depth", self.max_depth.to_string()),
+            ("seed", self.seed.to_string()),
+            ("seconds", self.elapsed.as_secs_f64().to_string()),
+        ];
+        writeln!(writer, "{{")?;
+        for (i, (name, value)) in fields.iter().enumerate() {
+            let separator = if i + 1 < fields.len() { "," } else { "" };
+            writeln!(writer, "  \"{name}\": {value}{separator}")?;
+        }
+        writeln!(writer, "}}")?;
+        writer.flush()
+    }
+
+    /// Returns `image` with the [`RenderInfo::summary`] written in white on
+    /// a dark strip along its bottom edge. The font is scaled up with the
+    /// image, and text beyond the right edge is cut off.
+    pub fn burn_in(&self, image: &Image) -> Image {
+        let scale = (image.width() / 640).max(1);
+        let margin = 2 * scale;
+        let strip_height = (GLYPH_HEIGHT * scale + 2 * margin).min(image.height());
+        let top = image.height() - strip_height;
+
+        let mut result = image.clone();
+        for y in top..image.height() {
+            for x in 0..image.width() {
+                result.set(x, y, image.get(x, y) * STRIP_OPACITY);
+            }
+        }
+
+        let white = Vec3::new(1.0, 1.0, 1.0);
+        let advance = (GLYPH_WIDTH + 1) * scale;
+        for (i, c) in self.summary().chars().enumerate() {
+            let left = margin + i as u32 * advance;
+            if left + GLYPH_WIDTH * scale > image.width() {
+                break;
+            }
+            for (row, bits) in glyph(c).into_iter().enumerate() {
+                for column in 0..GLYPH_WIDTH {
+                    if bits & (1 << (GLYPH_WIDTH - 1 - column)) == 0 {
+                        continue;
+                    }
+                    for dy in 0..scale {
+                        for dx in 0..scale {
+                            let x = left + column * scale + dx;
+                            let y = top + margin + row as u32 * scale + dy;
+                            if y < image.height() {
+                                result.set(x, y, white);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        result
+    }
+}
+
+/// Escapes `value` for a JSON string.
+fn escape_json(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Rows of the 5 by 7 glyph of `c`, top first, with the leftmost pixel in
+/// the highest of the five bits. Letters are drawn as capitals, and
+/// characters without a glyph as a question mark.
+fn glyph(c: char) -> [u8; 7] {
+    match c.to_ascii_uppercase() {
+        ' ' => [0; 7],
+        '0' => [
+            0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110,
+        ],
+        '1' => [
+            0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110,
+        ],
+        '2' => [
+            0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111,
+        ],
+        '3' => [
+            0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110,
+        ],
+        '4' => [
+            0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010,
+        ],
+        '5' => [
+            0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110,
+        ],
+        '6' => [
+            0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110,
+        ],
+        '7' => [
+            0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000,
+        ],
+        '8' => [
+            0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110,
+        ],
+        '9' => [
+            0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100,
+        ],
+        'A' => [
+            0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001,
+        ],
+        'B' => [
+            0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110,
+        ],
+        'C' => [
+            0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110,
+        ],
+        'D' => [
+            0b11100, 0b10010, 0b10001, 0b10001, 0b10001, 0b10010, 0b11100,
+        ],
+        'E' => [
+            0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111,
+        ],
+        'F' => [
+            0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000,
+        ],
+        'G' => [
+            0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111,
+        ],
+        'H' => [
+            0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001,
+        ],
+        'I' => [
+            0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110,
+        ],
+        'J' => [
+            0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100,
+        ],
+        'K' => [
+            0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001,
+        ],
+        'L' => [
+            0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111,
+        ],
+        'M' => [
+            0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001,
+        ],
+        'N' => [
+            0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001,
+        ],
+        'O' => [
+            0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110,
+        ],
+        'P' => [
+            0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000,
+        ],
+        'Q' => [
+            0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101,
+        ],
+        'R' => [
+            0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001,
+        ],
+        'S' => [
+            0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110,
+        ],
+        'T' => [
+            0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100,
+        ],
+        'U' => [
+            0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110,
+        ],
+        'V' => [
+            0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100,
+        ],
+        'W' => [
+            0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010,
+        ],
+        'X' => [
+            0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001,
+        ],
+        'Y' => [
+            0b10001, 0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100,
+        ],
+        'Z' => [
+            0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111,
+        ],
+        '.' => [
+            0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100,
+        ],
+        ',' => [
+            0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b00100, 0b01000,
+        ],
+        ':' => [
+            0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000,
+        ],
+        '-' => [
+            0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000,
+        ],
+        '_' => [
+            0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b11111,
+        ],
+        '=' => [
+            0b00000, 0b00000, 0b11111, 0b00000, 0b11111, 0b00000, 0b00000,
+        ],
+        '/' => [
+            0b00000, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b00000,
+        ],
+        '(' => [
+            0b00010, 0b00100, 0b01000, 0b01000, 0b01000, 0b00100, 0b00010,
+        ],
+        ')' => [
+            0b01000, 0b00100, 0b00010, 0b00010, 0b00010, 0b00100, 0b01000,
+        ],
+        '%' => [
+            0b11000, 0b11001, 0b00010, 0b00100, 0b01000, 0b10011, 0b00011,
+        ],
+        _ => [
+            0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b00000, 0b00100,
+        ],
+    }
+}
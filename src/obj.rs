@@ -0,0 +1,118 @@
+//! Wavefront OBJ export of scene geometry and BVH bounds, to inspect them
+//! in a 3D viewer such as Blender.
+//!
+//! Geometry is written as the triangles of [`Hit::triangulate`]. BVH nodes
+//! become wireframe boxes, one object per tree level, so degenerate bounds
+//! and bad splits show up by toggling the levels.
+
+use std::io::{self, BufWriter, Write};
+
+use crate::{
+    aabb::Aabb,
+    bvh::{Bvh, NodeKind},
+    hit::Hit,
+    interval::Interval,
+    vec3::Vec3,
+};
+
+/// Writes the triangulated surfaces of `world` as a single object.
+pub fn write_geometry<W: Write, T: Hit + ?Sized>(writer: W, world: &T) -> io::Result<()> {
+    let mut obj = ObjWriter::new(writer);
+    obj.geometry("geometry", world)?;
+    obj.finish()
+}
+
+/// Writes the primitives of `bvh` as the object `primitives`, then the
+/// bounds of its nodes as wireframe boxes, the nodes `depth` levels below
+/// the root in the object `bvh_<depth>`.
+pub fn write_bvh<W: Write, P: Hit + Clone>(writer: W, bvh: &Bvh<P>) -> io::Result<()> {
+    let mut obj = ObjWriter::new(writer);
+    obj.geometry("primitives", bvh)?;
+
+    let mut levels: Vec<Vec<Aabb>> = Vec::new();
+    let mut stack = vec![(0, 0)];
+    while let Some((index, depth)) = stack.pop() {
+        let node = bvh.nodes()[index];
+        if levels.len() <= depth {
+            levels.resize_with(depth + 1, Vec::new);
+        }
+        levels[depth].push(node.bbox());
+        if let NodeKind::Interior { second_child } = node.kind() {
+            stack.push((second_child as usize, depth + 1));
+            stack.push((index + 1, depth + 1));
+        }
+    }
+    for (depth, boxes) in levels.iter().enumerate() {
+        obj.object(&format!("bvh_{depth}"))?;
+        for bbox in boxes {
+            obj.wire_box(bbox)?;
+        }
+    }
+    obj.finish()
+}
+
+/// OBJ writer keeping count of the vertices, which faces and lines refer to
+/// by their one-based index in the whole file.
+struct ObjWriter<W: Write> {
+    w:        BufWriter<W>,
+    vertices: usize,
+}
+
+impl<W: Write> ObjWriter<W> {
+    fn new(writer: W) -> Self {
+        Self {
+            w:        BufWriter::new(writer),
+            vertices: 0,
+        }
+    }
+
+    fn object(&mut self, name: &str) -> io::Result<()> {
+        writeln!(self.w, "o {name}")
+    }
+
+    fn vertex(&mut self, p: Vec3) -> io::Result<usize> {
+        writeln!(self.w, "v {} {} {}", p.x(), p.y(), p.z())?;
+        self.vertices += 1;
+        Ok(self.vertices)
+    }
+
+    fn geometry<T: Hit + ?Sized>(&mut self, name: &str, world: &T) -> io::Result<()> {
+        let mut triangles = Vec::new();
+        world.triangulate(&mut triangles);
+        self.object(name)?;
+        for triangle in triangles {
+            let [a, b, c] = triangle.map(|p| self.vertex(p));
+            writeln!(self.w, "f {} {} {}", a?, b?, c?)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the twelve edges of `bbox`, nothing if it is empty.
+    fn wire_box(&mut self, bbox: &Aabb) -> io::Result<()> {
+        let (x, y, z) = (bbox.x(), bbox.y(), bbox.z());
+        if x.min() > x.max() || y.min() > y.max() || z.min() > z.max() {
+            return Ok(());
+        }
+
+        // Corner `i` takes the maximum along the axes whose bit is set.
+        let first = self.vertices + 1;
+        for i in 0..8 {
+            let pick = |bit: usize, axis: Interval| {
+                if i & bit == 0 { axis.min() } else { axis.max() }
+            };
+            self.vertex(Vec3::new(pick(1, x), pick(2, y), pick(4, z)))?;
+        }
+        for i in 0..8 {
+            for bit in [1, 2, 4] {
+                if i & bit == 0 {
+                    writeln!(self.w, "l {} {}", first + i, first + (i | bit))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> io::Result<()> {
+        self.w.flush()
+    }
+}
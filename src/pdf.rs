@@ -0,0 +1,245 @@
+use crate::{
+    Float,
+    consts::PI,
+    hit::Hit,
+    sampler::Sampler,
+    vec2::Vec2,
+    vec3::{Onb, Vec3},
+};
+
+/// Probability density over directions that can also be sampled.
+pub trait Pdf {
+    /// Solid angle density of `direction`.
+    fn value(&self, direction: Vec3) -> Float;
+    /// Samples a direction distributed according to [`Pdf::value`].
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vec3;
+}
+
+/// Uniform density over the whole sphere of directions.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct SpherePdf;
+
+/// Cosine weighted density over the hemisphere around a normal.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CosinePdf {
+    uvw: Onb,
+}
+
+/// Density of directions from `origin` towards the points of `objects` at
+/// `time`, as sampled by [`Hit::random_to`].
+#[derive(Debug, Clone, Copy)]
+pub struct HittablePdf<'a, T: ?Sized> {
+    objects: &'a T,
+    origin:  Vec3,
+    time:    Float,
+}
+
+/// Equal-weight mixture of two densities.
+#[derive(Clone, Copy)]
+pub struct MixturePdf<'a> {
+    pdfs: [&'a dyn Pdf; 2],
+}
+
+impl CosinePdf {
+    pub fn new(normal: Vec3) -> Self {
+        Self {
+            uvw: Onb::new(normal),
+        }
+    }
+}
+
+impl<'a, T: Hit + ?Sized> HittablePdf<'a, T> {
+    pub const fn new(objects: &'a T, origin: Vec3, time: Float) -> Self {
+        Self {
+            objects,
+            origin,
+            time,
+        }
+    }
+}
+
+impl<'a> MixturePdf<'a> {
+    pub const fn new(p0: &'a dyn Pdf, p1: &'a dyn Pdf) -> Self {
+        Self { pdfs: [p0, p1] }
+    }
+}
+
+impl Pdf for SpherePdf {
+    fn value(&self, _: Vec3) -> Float {
+        1.0 / (4.0 * PI)
+    }
+
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        Vec3::random_unit(sampler)
+    }
+}
+
+impl Pdf for CosinePdf {
+    fn value(&self, direction: Vec3) -> Float {
+        let cosine_theta = direction.unit().dot(self.uvw.w());
+        cosine_theta.max(0.0) / PI
+    }
+
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        self.uvw.transform(Vec3::random_cosine_direction(sampler))
+    }
+}
+
+impl<T: Hit + ?Sized> Pdf for HittablePdf<'_, T> {
+    fn value(&self, direction: Vec3) -> Float {
+        self.objects.pdf_value(self.origin, direction, self.time)
+    }
+
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        self.objects.random_to(self.origin, self.time, sampler)
+    }
+}
+
+impl Pdf for MixturePdf<'_> {
+    fn value(&self, direction: Vec3) -> Float {
+        0.5 * self.pdfs[0].value(direction) + 0.5 * self.pdfs[1].value(direction)
+    }
+
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        if sampler.next_1d() < 0.5 {
+            self.pdfs[0].generate(sampler)
+        } else {
+            self.pdfs[1].generate(sampler)
+        }
+    }
+}
+
+/// Piecewise-constant density over `[0, 1)` proportional to a tabulated
+/// function.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Distribution1D {
+    func:     Vec<Float>,
+    cdf:      Vec<Float>,
+    integral: Float,
+}
+
+/// Piecewise-constant density over `[0, 1)²`, sampled through the marginal
+/// density of rows and the conditional density of each row.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Distribution2D {
+    conditional: Vec<Distribution1D>,
+    marginal:    Distribution1D,
+}
+
+impl Distribution1D {
+    /// Builds the density from non-negative `func` values. A function that is
+    /// zero everywhere yields a uniform density.
+    pub fn new(func: Vec<Float>) -> Self {
+        assert!(!func.is_empty());
+        let n = func.len() as Float;
+
+        let mut cdf = Vec::with_capacity(func.len() + 1);
+        cdf.push(0.0);
+        for (i, f) in func.iter().enumerate() {
+            cdf.push(cdf[i] + f.abs() / n);
+        }
+
+        let integral = cdf[func.len()];
+        if integral > 0.0 {
+            cdf.iter_mut().for_each(|c| *c /= integral);
+        } else {
+            cdf.iter_mut()
+                .enumerate()
+                .for_each(|(i, c)| *c = i as Float / n);
+        }
+
+        Self {
+            func,
+            cdf,
+            integral,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.func.len()
+    }
+
+    pub const fn integral(&self) -> Float {
+        self.integral
+    }
+
+    /// Maps a uniform `u` to a sample in `[0, 1)`, returning it together with
+    /// its density and the index of the piece it falls in.
+    pub fn sample(&self, u: Float) -> (Float, Float, usize) {
+        let offset = self
+            .cdf
+            .partition_point(|&c| c <= u)
+            .saturating_sub(1)
+            .min(self.count() - 1);
+
+        let width = self.cdf[offset + 1] - self.cdf[offset];
+        let du = if width > 0.0 {
+            (u - self.cdf[offset]) / width
+        } else {
+            0.0
+        };
+
+        let x = (offset as Float + du) / self.count() as Float;
+        (x, self.piece_pdf(offset), offset)
+    }
+
+    /// Picks a piece with probability proportional to its function value,
+    /// returning its index and that probability.
+    pub fn sample_discrete(&self, u: Float) -> (usize, Float) {
+        let (_, _, offset) = self.sample(u);
+        (offset, self.discrete_pdf(offset))
+    }
+
+    /// Probability of [`Distribution1D::sample_discrete`] picking `offset`.
+    pub fn discrete_pdf(&self, offset: usize) -> Float {
+        self.piece_pdf(offset) / self.count() as Float
+    }
+
+    /// Density of `x` in `[0, 1)`.
+    pub fn pdf(&self, x: Float) -> Float {
+        let offset = ((x * self.count() as Float) as usize).min(self.count() - 1);
+        self.piece_pdf(offset)
+    }
+
+    fn piece_pdf(&self, offset: usize) -> Float {
+        if self.integral > 0.0 {
+            self.func[offset].abs() / self.integral
+        } else {
+            1.0
+        }
+    }
+}
+
+impl Distribution2D {
+    /// Builds the density from `width * height` row-major `func` values.
+    pub fn new(func: &[Float], width: usize, height: usize) -> Self {
+        assert_eq!(func.len(), width * height);
+        let conditional: Vec<_> = func
+            .chunks(width)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(conditional.iter().map(|c| c.integral()).collect());
+
+        Self {
+            conditional,
+            marginal,
+        }
+    }
+
+    /// Maps two uniform numbers to a point `(u, v)`, returning it with its
+    /// density.
+    pub fn sample(&self, u1: Float, u2: Float) -> (Vec2, Float) {
+        let (v, pdf_v, row) = self.marginal.sample(u2);
+        let (u, pdf_u, _) = self.conditional[row].sample(u1);
+        (Vec2::new(u, v), pdf_u * pdf_v)
+    }
+
+    /// Density of the point `uv`.
+    pub fn pdf(&self, uv: Vec2) -> Float {
+        let rows = self.conditional.len();
+        let row = ((uv.v() * rows as Float) as usize).min(rows - 1);
+        self.conditional[row].pdf(uv.u()) * self.marginal.pdf(uv.v())
+    }
+}
@@ -0,0 +1,205 @@
+//! Post-processing of rendered images: lens and film effects applied to the
+//! linear pixels before they are encoded.
+//!
+//! A [`PostPipeline`] chains [`Effect`]s, each turning an [`Image`] into a
+//! new one of the same size. They work on the whole image, so they are
+//! applied to finished renders rather than by the camera, whose regions
+//! must match crops of the full image.
+
+use crate::{Float, film::Image, vec3::Vec3};
+
+/// Image effect of a [`PostPipeline`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Effect {
+    /// Glow around bright pixels: the light of pixels with a luminance above
+    /// `threshold` is blurred with a Gaussian of standard deviation `radius`
+    /// pixels and added back, scaled by `intensity`.
+    Bloom {
+        threshold: Float,
+        radius:    Float,
+        intensity: Float,
+    },
+    /// Darkening towards the corners, following the cos⁴ falloff of a lens
+    /// seeing the corners at an angle whose squared tangent is `strength`.
+    Vignette { strength: Float },
+    /// Lateral chromatic aberration: red is magnified and blue shrunk about
+    /// the center by `strength`, a fraction of the distance to it, fringing
+    /// edges towards the corners.
+    ChromaticAberration { strength: Float },
+}
+
+/// Effects applied to an image one after the other, in the order they were
+/// added.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PostPipeline {
+    effects: Vec<Effect>,
+}
+
+impl Effect {
+    pub const fn bloom(threshold: Float, radius: Float, intensity: Float) -> Self {
+        Self::Bloom {
+            threshold,
+            radius,
+            intensity,
+        }
+    }
+
+    pub const fn vignette(strength: Float) -> Self {
+        Self::Vignette { strength }
+    }
+
+    pub const fn chromatic_aberration(strength: Float) -> Self {
+        Self::ChromaticAberration { strength }
+    }
+
+    /// Returns `image` with the effect applied.
+    pub fn apply(&self, image: &Image) -> Image {
+        match *self {
+            Self::Bloom {
+                threshold,
+                radius,
+                intensity,
+            } => bloom(image, threshold, radius, intensity),
+            Self::Vignette { strength } => {
+                let half_diagonal = center(image).length();
+                map_positions(image, |pixel, offset| {
+                    let distance = offset.length() / half_diagonal;
+                    let falloff = 1.0 / (1.0 + strength * distance * distance);
+                    pixel * (falloff * falloff)
+                })
+            }
+            Self::ChromaticAberration { strength } => {
+                let center = center(image);
+                map_positions(image, |pixel, offset| {
+                    let at = |scale: Float| {
+                        let position = center + offset * scale;
+                        sample_bilinear(image, position.x(), position.y())
+                    };
+                    Vec3::new(at(1.0 + strength).x(), pixel.y(), at(1.0 - strength).z())
+                })
+            }
+        }
+    }
+}
+
+impl PostPipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends `effect`, applied after the effects already added.
+    pub fn with_effect(mut self, effect: Effect) -> Self {
+        self.effects.push(effect);
+        self
+    }
+
+    pub fn effects(&self) -> &[Effect] {
+        &self.effects
+    }
+
+    /// Returns `image` with every effect applied in order.
+    pub fn apply(&self, image: &Image) -> Image {
+        self.effects
+            .iter()
+            .fold(image.clone(), |image, effect| effect.apply(&image))
+    }
+}
+
+/// Adds the blurred light of the pixels of `image` brighter than
+/// `threshold` back to it.
+fn bloom(image: &Image, threshold: Float, radius: Float, intensity: Float) -> Image {
+    let bright = image.map(|pixel| {
+        let luminance = pixel.luminance();
+        if luminance > threshold {
+            pixel * ((luminance - threshold) / luminance)
+        } else {
+            Vec3::default()
+        }
+    });
+    let kernel = gaussian_kernel(radius);
+    let blurred = blur(&blur(&bright, &kernel, true), &kernel, false);
+
+    let mut result = image.clone();
+    for (pixel, glow) in result.pixels_mut().iter_mut().zip(blurred.pixels()) {
+        *pixel += *glow * intensity;
+    }
+    result
+}
+
+/// Normalized weights of a Gaussian of standard deviation `sigma` pixels,
+/// from its center out to three deviations.
+fn gaussian_kernel(sigma: Float) -> Vec<Float> {
+    if sigma <= 0.0 {
+        return vec![1.0];
+    }
+    let half = (3.0 * sigma).ceil() as usize;
+    let weights: Vec<Float> = (0..=half)
+        .map(|i| (-0.5 * (i as Float / sigma).powi(2)).exp())
+        .collect();
+    let total = weights[0] + 2.0 * weights[1..].iter().sum::<Float>();
+    weights.into_iter().map(|weight| weight / total).collect()
+}
+
+/// Convolves `image` with the symmetric `kernel` along rows if
+/// `horizontal`, otherwise along columns, extending the border pixels.
+fn blur(image: &Image, kernel: &[Float], horizontal: bool) -> Image {
+    let (width, height) = (image.width() as i64, image.height() as i64);
+    let mut result = Image::new(image.width(), image.height());
+    for y in 0..height {
+        for x in 0..width {
+            let tap = |offset: i64| {
+                let (x, y) = if horizontal {
+                    ((x + offset).clamp(0, width - 1), y)
+                } else {
+                    (x, (y + offset).clamp(0, height - 1))
+                };
+                image.get(x as u32, y as u32)
+            };
+            let mut sum = tap(0) * kernel[0];
+            for (i, &weight) in kernel.iter().enumerate().skip(1) {
+                sum += (tap(i as i64) + tap(-(i as i64))) * weight;
+            }
+            result.set(x as u32, y as u32, sum);
+        }
+    }
+    result
+}
+
+/// Applies `f` to every pixel of `image` and its offset in pixels from the
+/// center of the image.
+fn map_positions(image: &Image, f: impl Fn(Vec3, Vec3) -> Vec3) -> Image {
+    let center = center(image);
+    let mut result = Image::new(image.width(), image.height());
+    for y in 0..image.height() {
+        for x in 0..image.width() {
+            let position = Vec3::new(x as Float + 0.5, y as Float + 0.5, 0.0);
+            result.set(x, y, f(image.get(x, y), position - center));
+        }
+    }
+    result
+}
+
+/// Center of `image`, in pixels from its top left corner.
+fn center(image: &Image) -> Vec3 {
+    Vec3::new(image.width() as Float, image.height() as Float, 0.0) / 2.0
+}
+
+/// Color of `image` at the continuous position `(x, y)`, pixel centers
+/// lying at half integers, interpolated bilinearly and clamped to the
+/// border.
+fn sample_bilinear(image: &Image, x: Float, y: Float) -> Vec3 {
+    let max_x = image.width() as Float - 1.0;
+    let max_y = image.height() as Float - 1.0;
+    let x = (x - 0.5).clamp(0.0, max_x);
+    let y = (y - 0.5).clamp(0.0, max_y);
+    let (x0, y0) = (x.floor(), y.floor());
+    let (tx, ty) = (x - x0, y - y0);
+    let (x1, y1) = ((x0 + 1.0).min(max_x), (y0 + 1.0).min(max_y));
+
+    let at = |x: Float, y: Float| image.get(x as u32, y as u32);
+    let top = at(x0, y0) * (1.0 - tx) + at(x1, y0) * tx;
+    let bottom = at(x0, y1) * (1.0 - tx) + at(x1, y1) * tx;
+    top * (1.0 - ty) + bottom * ty
+}
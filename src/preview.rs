@@ -0,0 +1,122 @@
+//! Interactive preview window, available with the `preview` feature.
+//!
+//! The image is refined progressively, one sample per pixel per pass, and
+//! every pass is displayed as soon as it completes. Moving the camera
+//! restarts the accumulation:
+//!
+//! - `W`/`S` move forward and backward, `A`/`D` left and right and `Q`/`E`
+//!   down and up.
+//! - The arrow keys turn the view.
+//! - `Escape` closes the window.
+
+use minifb::{Key, Window, WindowOptions};
+
+use crate::{
+    Float,
+    camera::{Camera, CameraBuilder},
+    hit::Hit,
+    vec3::Vec3,
+};
+
+/// Fraction of the distance to the look-at point moved per pass while a key
+/// is held.
+const MOVE_STEP: Float = 0.05;
+/// Degrees turned per pass while a key is held.
+const TURN_STEP: Float = 2.0;
+
+/// Opens a window previewing `world` as seen by the camera `builder` builds.
+///
+/// `configure` is applied to every camera built, both at the start and
+/// after each move, to set the options that are not part of the builder.
+/// Returns when the window is closed.
+pub fn run<T: Hit + ?Sized + Sync, L: Hit + ?Sized + Sync>(
+    mut builder: CameraBuilder,
+    configure: impl Fn(Camera) -> Camera,
+    world: &T,
+    lights: &L,
+    max_depth: u32,
+) -> Result<(), minifb::Error>
+where
+    T::Material: Copy,
+{
+    let mut camera = configure(builder.clone().build());
+    let width = camera.img_width() as usize;
+    let height = camera.img_height() as usize;
+
+    let mut window = Window::new("ray preview", width, height, WindowOptions::default())?;
+    window.set_target_fps(60);
+
+    let mut sums = vec![(Vec3::default(), 0.0); width * height];
+    let mut buffer = vec![0u32; width * height];
+    let mut pass = 0;
+
+    while window.is_open() && !window.is_key_down(Key::Escape) {
+        if let Some(moved) = apply_keys(&builder, &window.get_keys()) {
+            builder = moved;
+            camera = configure(builder.clone().build());
+            sums.fill((Vec3::default(), 0.0));
+            pass = 0;
+        }
+
+        let samples = camera.render_pass(pass..pass + 1, world, lights, max_depth);
+        pass += 1;
+
+        for ((sum, sample), pixel) in sums.iter_mut().zip(samples).zip(&mut buffer) {
+            sum.0 += sample.0;
+            sum.1 += sample.1;
+            let color = if sum.1 > 0.0 {
+                sum.0 / sum.1
+            } else {
+                Vec3::default()
+            };
+            let (r, g, b) = color.to_color();
+            *pixel = u32::from_be_bytes([0, r, g, b]);
+        }
+
+        window.set_title(&format!("ray preview - {pass} spp"));
+        window.update_with_buffer(&buffer, width, height)?;
+    }
+
+    Ok(())
+}
+
+/// Returns the builder moved according to the held `keys`, or `None` if no
+/// movement key is held.
+fn apply_keys(builder: &CameraBuilder, keys: &[Key]) -> Option<CameraBuilder> {
+    let (look_from, look_at, vup) = builder.view();
+    let forward = look_at - look_from;
+    let right = forward.cross(vup).unit();
+    let up = right.cross(forward).unit();
+    let step = forward.length() * MOVE_STEP;
+
+    let mut offset = Vec3::default();
+    let mut yaw = 0.0;
+    let mut pitch = 0.0;
+    for key in keys {
+        match key {
+            Key::W => offset += forward.unit() * step,
+            Key::S => offset -= forward.unit() * step,
+            Key::D => offset += right * step,
+            Key::A => offset -= right * step,
+            Key::E => offset += up * step,
+            Key::Q => offset -= up * step,
+            Key::Left => yaw += TURN_STEP,
+            Key::Right => yaw -= TURN_STEP,
+            Key::Up => pitch += TURN_STEP,
+            Key::Down => pitch -= TURN_STEP,
+            _ => (),
+        }
+    }
+    if offset == Vec3::default() && yaw == 0.0 && pitch == 0.0 {
+        return None;
+    }
+
+    let forward = forward.rotate(vup.unit(), yaw).rotate(right, pitch);
+    let look_from = look_from + offset;
+    Some(
+        builder
+            .clone()
+            .look_from(look_from)
+            .look_at(look_from + forward),
+    )
+}
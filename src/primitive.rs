@@ -0,0 +1,139 @@
+//! Built-in shapes stored by value.
+//!
+//! A [`Bvh<Primitive>`](crate::bvh::Bvh) keeps its shapes in one contiguous
+//! array and dispatches on the variant, where the default BVH over
+//! `Arc<dyn DynHit>` follows a pointer and makes a virtual call for every
+//! test, see the `bvh_storage` benchmark. Only spheres, quads, disks and
+//! triangles can be stored that way; anything else, such as instances or transformed
+//! objects, needs the trait objects. Their hits carry no [`ObjectId`](crate::hit::ObjectId),
+//! which only [`HitList`](crate::hit::HitList) entries have.
+
+use crate::{
+    Float,
+    aabb::Aabb,
+    geo::{Disk, Quad, Sphere, Triangle},
+    hit::{Hit, HitRecord},
+    interval::Interval,
+    material::{Material, Scatter},
+    ray::Ray,
+    sampler::Sampler,
+    vec3::Vec3,
+};
+
+/// One of the built-in shapes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Primitive<T = Material> {
+    Sphere(Sphere<T>),
+    Quad(Quad<T>),
+    Disk(Disk<T>),
+    Triangle(Triangle<T>),
+}
+
+impl<T> From<Sphere<T>> for Primitive<T> {
+    fn from(sphere: Sphere<T>) -> Self {
+        Self::Sphere(sphere)
+    }
+}
+
+impl<T> From<Quad<T>> for Primitive<T> {
+    fn from(quad: Quad<T>) -> Self {
+        Self::Quad(quad)
+    }
+}
+
+impl<T> From<Disk<T>> for Primitive<T> {
+    fn from(disk: Disk<T>) -> Self {
+        Self::Disk(disk)
+    }
+}
+
+impl<T> From<Triangle<T>> for Primitive<T> {
+    fn from(triangle: Triangle<T>) -> Self {
+        Self::Triangle(triangle)
+    }
+}
+
+impl<T: Copy + Scatter> Hit for Primitive<T> {
+    type Material = T;
+
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<Self::Material>> {
+        match self {
+            Self::Sphere(sphere) => sphere.hit(ray, ray_t),
+            Self::Quad(quad) => quad.hit(ray, ray_t),
+            Self::Disk(disk) => disk.hit(ray, ray_t),
+            Self::Triangle(triangle) => triangle.hit(ray, ray_t),
+        }
+    }
+
+    fn aabb(&self) -> Aabb {
+        match self {
+            Self::Sphere(sphere) => sphere.aabb(),
+            Self::Quad(quad) => quad.aabb(),
+            Self::Disk(disk) => disk.aabb(),
+            Self::Triangle(triangle) => triangle.aabb(),
+        }
+    }
+
+    fn count(&self) -> usize {
+        1
+    }
+
+    fn hit_any(&self, ray: &Ray, ray_t: Interval) -> bool {
+        match self {
+            Self::Sphere(sphere) => sphere.hit_any(ray, ray_t),
+            Self::Quad(quad) => quad.hit_any(ray, ray_t),
+            Self::Disk(disk) => disk.hit_any(ray, ray_t),
+            Self::Triangle(triangle) => triangle.hit_any(ray, ray_t),
+        }
+    }
+
+    fn pdf_value(&self, origin: Vec3, direction: Vec3, time: Float) -> Float {
+        match self {
+            Self::Sphere(sphere) => sphere.pdf_value(origin, direction, time),
+            Self::Quad(quad) => quad.pdf_value(origin, direction, time),
+            Self::Disk(disk) => disk.pdf_value(origin, direction, time),
+            Self::Triangle(triangle) => triangle.pdf_value(origin, direction, time),
+        }
+    }
+
+    fn random_to(&self, origin: Vec3, time: Float, sampler: &mut dyn Sampler) -> Vec3 {
+        match self {
+            Self::Sphere(sphere) => sphere.random_to(origin, time, sampler),
+            Self::Quad(quad) => quad.random_to(origin, time, sampler),
+            Self::Disk(disk) => disk.random_to(origin, time, sampler),
+            Self::Triangle(triangle) => triangle.random_to(origin, time, sampler),
+        }
+    }
+
+    fn power(&self) -> Float {
+        match self {
+            Self::Sphere(sphere) => sphere.power(),
+            Self::Quad(quad) => quad.power(),
+            Self::Disk(disk) => disk.power(),
+            Self::Triangle(triangle) => triangle.power(),
+        }
+    }
+
+    fn sample_surface(
+        &self,
+        time: Float,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(HitRecord<Self::Material>, Float)> {
+        match self {
+            Self::Sphere(sphere) => sphere.sample_surface(time, sampler),
+            Self::Quad(quad) => quad.sample_surface(time, sampler),
+            Self::Disk(disk) => disk.sample_surface(time, sampler),
+            Self::Triangle(triangle) => triangle.sample_surface(time, sampler),
+        }
+    }
+
+    fn triangulate(&self, triangles: &mut Vec<[Vec3; 3]>) {
+        match self {
+            Self::Sphere(sphere) => sphere.triangulate(triangles),
+            Self::Quad(quad) => quad.triangulate(triangles),
+            Self::Disk(disk) => disk.triangulate(triangles),
+            Self::Triangle(triangle) => triangle.triangulate(triangles),
+        }
+    }
+}
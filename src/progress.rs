@@ -0,0 +1,81 @@
+use std::{fmt::Debug, sync::Arc, time::Duration};
+
+/// Progress notification sent while rendering.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RenderEvent {
+    /// `done` of the `total` scanlines being rendered are finished.
+    RowDone {
+        done:    u32,
+        total:   u32,
+        elapsed: Duration,
+    },
+    /// A progressive pass finished, bringing every pixel to `samples`
+    /// samples.
+    PassDone { samples: u32, elapsed: Duration },
+    /// The whole image or region finished.
+    Finished { elapsed: Duration },
+}
+
+impl RenderEvent {
+    pub const fn elapsed(&self) -> Duration {
+        match *self {
+            Self::RowDone { elapsed, .. }
+            | Self::PassDone { elapsed, .. }
+            | Self::Finished { elapsed } => elapsed,
+        }
+    }
+
+    /// Fraction of the work done, in `[0, 1]`. Unknown for passes.
+    pub fn fraction(&self) -> Option<f64> {
+        match *self {
+            Self::RowDone { done, total, .. } => Some(done as f64 / total as f64),
+            Self::PassDone { .. } => None,
+            Self::Finished { .. } => Some(1.0),
+        }
+    }
+
+    /// Estimated time left, extrapolated from the elapsed time.
+    pub fn eta(&self) -> Option<Duration> {
+        let fraction = self.fraction()?;
+        if fraction <= 0.0 {
+            return None;
+        }
+        Some(self.elapsed().mul_f64((1.0 - fraction) / fraction))
+    }
+}
+
+/// Shared callback receiving the [`RenderEvent`]s of a camera, see
+/// [`Camera::with_progress`](crate::camera::Camera::with_progress).
+#[derive(Clone)]
+pub struct ProgressCallback(Arc<dyn Fn(RenderEvent) + Send + Sync>);
+
+impl Debug for ProgressCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("ProgressCallback").finish_non_exhaustive()
+    }
+}
+
+impl ProgressCallback {
+    pub fn new(callback: impl Fn(RenderEvent) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(callback))
+    }
+
+    pub fn call(&self, event: RenderEvent) {
+        (self.0)(event)
+    }
+}
+
+/// Progress callback printing the remaining scanlines and the estimated time
+/// left to stderr.
+pub fn log_to_stderr(event: RenderEvent) {
+    match event {
+        RenderEvent::RowDone { done, total, .. } => {
+            let eta = event.eta().unwrap_or_default();
+            eprintln!("Scanlines remaining {} (eta {:.0?})", total - done, eta);
+        }
+        RenderEvent::PassDone { samples, elapsed } => {
+            eprintln!("{samples} samples per pixel after {elapsed:.1?}");
+        }
+        RenderEvent::Finished { elapsed } => eprintln!("Done in {elapsed:.1?}."),
+    }
+}
@@ -0,0 +1,270 @@
+//! Four-wide BVH testing the bounds of all children of a node at once.
+
+use std::sync::Arc;
+
+use wide::CmpLe;
+#[cfg(feature = "f32")]
+use wide::f32x4 as Lanes;
+#[cfg(not(feature = "f32"))]
+use wide::f64x4 as Lanes;
+
+use crate::{
+    Float,
+    aabb::Aabb,
+    bvh::{Bvh, NodeKind, visit_node},
+    hit::{DynHit, Hit, HitRecord},
+    interval::Interval,
+    material::Material,
+    ray::Ray,
+    vec3::Vec3,
+};
+
+/// Deepest tree the traversal stack can hold.
+const MAX_DEPTH: usize = 64;
+
+/// BVH with four children per node, built by collapsing a binary [`Bvh`].
+///
+/// Each node stores the bounds of its children in structure of arrays
+/// layout, so a ray is tested against the four of them with SIMD.
+#[derive(Debug, Clone)]
+pub struct Qbvh {
+    nodes:      Vec<QbvhNode>,
+    primitives: Vec<Arc<dyn DynHit>>,
+    bbox:       Aabb,
+    count:      usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct QbvhNode {
+    min:      [Lanes; 3],
+    max:      [Lanes; 3],
+    children: [QbvhChild; 4],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum QbvhChild {
+    Empty,
+    Node(u32),
+    Leaf { first: u32, count: u32 },
+}
+
+impl Qbvh {
+    pub fn from_list(hit_list: &mut [Arc<dyn DynHit>]) -> Self {
+        Self::from_bvh(&Bvh::from_list(hit_list))
+    }
+
+    /// Collapses `bvh`, replacing each node by up to four of its descendants.
+    pub fn from_bvh(bvh: &Bvh) -> Self {
+        let mut nodes = Vec::new();
+        let root = &bvh.nodes()[0];
+        if let NodeKind::Leaf { first, count } = root.kind() {
+            // A single leaf still needs a node to hold it.
+            nodes.push(Self::node(bvh, &[0], |_| QbvhChild::Leaf { first, count }));
+        } else {
+            Self::build(bvh, 0, &mut nodes);
+        }
+
+        Self {
+            nodes,
+            primitives: bvh.primitives().to_vec(),
+            bbox: bvh.aabb(),
+            count: bvh.count(),
+        }
+    }
+
+    /// Appends the node replacing binary node `index` and its subtree to
+    /// `nodes`, returning its index.
+    fn build(bvh: &Bvh, index: usize, nodes: &mut Vec<QbvhNode>) -> u32 {
+        let binary = bvh.nodes();
+
+        // Open the largest interior node until there are four children.
+        let mut children = children_of(bvh, index);
+        while children.len() < 4 {
+            let largest = children
+                .iter()
+                .enumerate()
+                .filter(|&(_, &child)| matches!(binary[child].kind(), NodeKind::Interior { .. }))
+                .max_by(|(_, a), (_, b)| {
+                    binary[**a]
+                        .bbox()
+                        .surface_area()
+                        .total_cmp(&binary[**b].bbox().surface_area())
+                })
+                .map(|(i, _)| i);
+            let Some(largest) = largest else {
+                break;
+            };
+            let opened = children.swap_remove(largest);
+            children.extend(children_of(bvh, opened));
+        }
+
+        let position = nodes.len();
+        nodes.push(Self::node(bvh, &children, |_| QbvhChild::Empty));
+        for (slot, &child) in children.iter().enumerate() {
+            nodes[position].children[slot] = match binary[child].kind() {
+                NodeKind::Leaf { first, count } => QbvhChild::Leaf { first, count },
+                NodeKind::Interior { .. } => QbvhChild::Node(Self::build(bvh, child, nodes)),
+            };
+        }
+        position as u32
+    }
+
+    /// Node with the bounds of binary nodes `children` and children given by
+    /// `child`.
+    fn node(bvh: &Bvh, children: &[usize], child: impl Fn(usize) -> QbvhChild) -> QbvhNode {
+        let mut min = [[Float::INFINITY; 4]; 3];
+        let mut max = [[Float::NEG_INFINITY; 4]; 3];
+        let mut slots = [QbvhChild::Empty; 4];
+        for (slot, &index) in children.iter().enumerate() {
+            let bbox = bvh.nodes()[index].bbox();
+            for (axis, interval) in [bbox.x(), bbox.y(), bbox.z()].into_iter().enumerate() {
+                min[axis][slot] = interval.min();
+                max[axis][slot] = interval.max();
+            }
+            slots[slot] = child(index);
+        }
+
+        QbvhNode {
+            min:      min.map(Lanes::new),
+            max:      max.map(Lanes::new),
+            children: slots,
+        }
+    }
+
+    /// Bitmask of the children of `node` whose bounds the ray crosses within
+    /// `t_min..t_max`.
+    #[inline(always)]
+    fn hit_children(node: &QbvhNode, ray: &SimdRay, t_min: Float, t_max: Float) -> u32 {
+        let mut near = Lanes::splat(t_min);
+        let mut far = Lanes::splat(t_max);
+        for axis in 0..3 {
+            let t0 = (node.min[axis] - ray.origin[axis]) * ray.inv_direction[axis];
+            let t1 = (node.max[axis] - ray.origin[axis]) * ray.inv_direction[axis];
+            near = near.max(t0.min(t1));
+            far = far.min(t0.max(t1));
+        }
+        near.cmp_le(far).move_mask() as u32
+    }
+
+    fn leaf(&self, first: u32, count: u32) -> &[Arc<dyn DynHit>] {
+        &self.primitives[first as usize..(first + count) as usize]
+    }
+}
+
+/// Children of binary node `index`, or the node itself for a leaf.
+fn children_of(bvh: &Bvh, index: usize) -> Vec<usize> {
+    match bvh.nodes()[index].kind() {
+        NodeKind::Leaf { .. } => vec![index],
+        NodeKind::Interior { second_child } => vec![index + 1, second_child as usize],
+    }
+}
+
+/// Ray origin and inverse direction broadcast to every lane.
+struct SimdRay {
+    origin:        [Lanes; 3],
+    inv_direction: [Lanes; 3],
+}
+
+impl SimdRay {
+    fn new(ray: &Ray) -> Self {
+        let o = ray.origin();
+        let inv = ray.inv_direction();
+        Self {
+            origin:        [o.x(), o.y(), o.z()].map(Lanes::splat),
+            inv_direction: [inv.x(), inv.y(), inv.z()].map(Lanes::splat),
+        }
+    }
+}
+
+impl Hit for Qbvh {
+    type Material = Material;
+
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<Self::Material>> {
+        let simd_ray = SimdRay::new(ray);
+        let mut closest = None;
+        let mut max = ray_t.max();
+
+        let mut stack = [0u32; 3 * MAX_DEPTH];
+        let mut len = 1;
+        while len > 0 {
+            len -= 1;
+            let node = &self.nodes[stack[len] as usize];
+            visit_node();
+
+            let mask = Self::hit_children(node, &simd_ray, ray_t.min(), max);
+            for (slot, child) in node.children.iter().enumerate() {
+                if mask & (1 << slot) == 0 {
+                    continue;
+                }
+                match *child {
+                    QbvhChild::Empty => (),
+                    QbvhChild::Node(index) => {
+                        stack[len] = index;
+                        len += 1;
+                    }
+                    QbvhChild::Leaf { first, count } => {
+                        for obj in self.leaf(first, count) {
+                            if let Some(hit) = obj.hit(ray, ray_t.with_max(max)) {
+                                max = hit.t();
+                                closest = Some(hit);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        closest
+    }
+
+    fn hit_any(&self, ray: &Ray, ray_t: Interval) -> bool {
+        let simd_ray = SimdRay::new(ray);
+
+        let mut stack = [0u32; 3 * MAX_DEPTH];
+        let mut len = 1;
+        while len > 0 {
+            len -= 1;
+            let node = &self.nodes[stack[len] as usize];
+            visit_node();
+
+            let mask = Self::hit_children(node, &simd_ray, ray_t.min(), ray_t.max());
+            for (slot, child) in node.children.iter().enumerate() {
+                if mask & (1 << slot) == 0 {
+                    continue;
+                }
+                match *child {
+                    QbvhChild::Empty => (),
+                    QbvhChild::Node(index) => {
+                        stack[len] = index;
+                        len += 1;
+                    }
+                    QbvhChild::Leaf { first, count } => {
+                        if self
+                            .leaf(first, count)
+                            .iter()
+                            .any(|obj| obj.hit_any(ray, ray_t))
+                        {
+                            return true;
+                        }
+                    }
+                }
+            }
+        }
+
+        false
+    }
+
+    fn aabb(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn count(&self) -> usize {
+        self.count
+    }
+
+    fn triangulate(&self, triangles: &mut Vec<[Vec3; 3]>) {
+        for obj in &self.primitives {
+            obj.triangulate(triangles);
+        }
+    }
+}
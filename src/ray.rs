@@ -1,21 +1,66 @@
-use crate::vec3::Vec3;
+use crate::{Float, vec3::Vec3};
+
+/// What a ray is traced for, so objects can be hidden from some rays, see
+/// [`Visible`](crate::visibility::Visible).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RayKind {
+    /// Leaves the camera.
+    #[default]
+    Camera,
+    /// Follows a reflection, refraction or diffuse bounce off a surface.
+    Reflection,
+    /// Checks whether a light reaches a point.
+    Shadow,
+}
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Ray {
-    origin:    Vec3,
-    direction: Vec3,
-    time:      f64,
+    origin:        Vec3,
+    direction:     Vec3,
+    inv_direction: Vec3,
+    /// Whether each component of the direction is negative, `-0.0`
+    /// included.
+    negative:      [bool; 3],
+    time:          Float,
+    kind:          RayKind,
+    /// Width of the ray's footprint per unit of `t`, the cone a camera ray
+    /// stands for around it, zero for rays of no width.
+    spread:        Float,
 }
 
 impl Ray {
-    pub const fn new(origin: Vec3, dir: Vec3, time: f64) -> Self {
+    pub const fn new(origin: Vec3, dir: Vec3, time: Float) -> Self {
+        let inv_direction = Vec3::new(1.0 / dir.x(), 1.0 / dir.y(), 1.0 / dir.z());
         Self {
             origin,
             direction: dir,
+            inv_direction,
+            negative: [
+                inv_direction.x() < 0.0,
+                inv_direction.y() < 0.0,
+                inv_direction.z() < 0.0,
+            ],
             time,
+            kind: RayKind::Camera,
+            spread: 0.0,
         }
     }
 
+    /// Marks the ray as traced for `kind`, [`RayKind::Camera`] by default.
+    pub const fn with_kind(mut self, kind: RayKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// Widens the ray into a cone from its origin, `spread` wide one unit
+    /// of `t` along it, used to pick the detail of textures. Rays spawned
+    /// at hits start without spread.
+    pub const fn with_spread(mut self, spread: Float) -> Self {
+        self.spread = spread;
+        self
+    }
+
     pub const fn origin(&self) -> Vec3 {
         self.origin
     }
@@ -24,11 +69,31 @@ impl Ray {
         self.direction
     }
 
-    pub const fn time(&self) -> f64 {
+    /// Componentwise inverse of the direction, infinite along the axes the
+    /// ray is parallel to.
+    pub const fn inv_direction(&self) -> Vec3 {
+        self.inv_direction
+    }
+
+    /// Whether the direction points towards `-x`, `-y` and `-z`, used to
+    /// pick the near and far planes of each slab without comparing.
+    pub const fn negative(&self) -> [bool; 3] {
+        self.negative
+    }
+
+    pub const fn time(&self) -> Float {
         self.time
     }
 
-    pub fn at(&self, t: f64) -> Vec3 {
+    pub const fn kind(&self) -> RayKind {
+        self.kind
+    }
+
+    pub const fn spread(&self) -> Float {
+        self.spread
+    }
+
+    pub fn at(&self, t: Float) -> Vec3 {
         self.origin + self.direction * t
     }
 }
@@ -0,0 +1,117 @@
+//! Resampled importance sampling of lights with weighted reservoirs, after
+//! ReSTIR ("Spatiotemporal reservoir resampling for real-time ray tracing
+//! with dynamic direct lighting", Bitterli et al. 2020).
+//!
+//! Sampling lights by the power they emit ignores how far they are from the
+//! shaded point and whether they face it, which with hundreds of small
+//! emitters wastes most shadow rays on lights contributing next to nothing.
+//! With [`LightResampling`], the camera draws several candidate points on
+//! the lights, which only costs evaluating the light they would bring
+//! without its visibility, and keeps one of them with a probability
+//! proportional to that light in a [`Reservoir`]. The single shadow ray then
+//! goes to a light that matters. Candidates are not reused across pixels or
+//! frames.
+
+use crate::{
+    Float,
+    error::{Error, Result},
+    sampler::Sampler,
+};
+
+/// Settings of the light resampling enabled by
+/// [`Camera::with_light_resampling`](crate::camera::Camera::with_light_resampling).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LightResampling {
+    candidates: u32,
+}
+
+/// Weighted reservoir sampling: keeps one of a stream of samples with a
+/// probability proportional to its weight, without storing the others.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Reservoir<T> {
+    sample:     Option<T>,
+    weight_sum: Float,
+    count:      u32,
+}
+
+impl LightResampling {
+    /// Draws `candidates` points on the lights for every shadow ray. More
+    /// candidates cost more light evaluations but pick better lights.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `candidates` is zero, see [`LightResampling::try_new`].
+    pub fn new(candidates: u32) -> Self {
+        Self::try_new(candidates).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Creates the settings like [`LightResampling::new`], failing if
+    /// `candidates` is zero.
+    pub fn try_new(candidates: u32) -> Result<Self> {
+        if candidates == 0 {
+            return Err(Error::InvalidParameter(
+                "the number of light candidates must be positive",
+            ));
+        }
+        Ok(Self { candidates })
+    }
+
+    pub const fn candidates(&self) -> u32 {
+        self.candidates
+    }
+}
+
+impl<T> Reservoir<T> {
+    pub const fn new() -> Self {
+        Self {
+            sample:     None,
+            weight_sum: 0.0,
+            count:      0,
+        }
+    }
+
+    /// Streams `sample` with `weight` through the reservoir, replacing the
+    /// kept sample with probability `weight` over the sum of the weights
+    /// seen so far. Samples with no weight still count as seen.
+    pub fn update(&mut self, sample: T, weight: Float, sampler: &mut dyn Sampler) {
+        self.count += 1;
+        if weight.is_nan() || weight <= 0.0 {
+            return;
+        }
+        self.weight_sum += weight;
+        if sampler.next_1d() * self.weight_sum < weight {
+            self.sample = Some(sample);
+        }
+    }
+
+    pub const fn sample(&self) -> Option<&T> {
+        self.sample.as_ref()
+    }
+
+    pub const fn weight_sum(&self) -> Float {
+        self.weight_sum
+    }
+
+    /// Number of samples seen, including those with no weight.
+    pub const fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// Weight making the kept sample an unbiased estimate, given `target`,
+    /// the value of the function the weights were the ratio of to the
+    /// density of the samples. Stands in for the inverse of the density of
+    /// the kept sample, which is unknown.
+    pub fn contribution_weight(&self, target: Float) -> Float {
+        if self.count == 0 || target <= 0.0 {
+            return 0.0;
+        }
+        self.weight_sum / (self.count as Float * target)
+    }
+}
+
+impl<T> Default for Reservoir<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
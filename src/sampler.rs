@@ -0,0 +1,125 @@
+//! Sources of the random numbers rendering consumes.
+//!
+//! Everything that samples, from camera rays to materials and lights, draws
+//! its numbers from a [`Sampler`] passed down by the caller rather than from
+//! a global generator, so a render is fully determined by the samplers the
+//! camera creates and other sample sequences can be swapped in.
+
+use crate::{Float, interval::Interval, vec2::Vec2};
+
+/// Stream of sample values in `[0, 1)`.
+pub trait Sampler {
+    fn next_1d(&mut self) -> Float;
+
+    fn next_2d(&mut self) -> Vec2 {
+        let u = self.next_1d();
+        Vec2::new(u, self.next_1d())
+    }
+
+    /// Value uniformly distributed in `interval`.
+    fn next_range(&mut self, interval: Interval) -> Float {
+        interval.min() + self.next_1d() * interval.size()
+    }
+}
+
+/// Hashes `values` into a seed, every value changing all the bits of the
+/// result.
+pub const fn hash_seed(values: &[u64]) -> u64 {
+    let mut hash = 0;
+    let mut i = 0;
+    while i < values.len() {
+        hash = mix(hash ^ values[i]);
+        i += 1;
+    }
+    hash
+}
+
+/// Finalizer of SplitMix64, a bijection with good avalanche.
+const fn mix(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// PCG32 generator, https://www.pcg-random.org/
+///
+/// 64 bits of state and a 32 bit output, permuted by a random rotation. Tiny
+/// and fast enough to create one for every sample, yet of much better
+/// quality than a plain linear congruential generator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pcg32 {
+    state:     u64,
+    increment: u64,
+}
+
+/// Independent uniform random samples from a seeded [`Pcg32`].
+#[derive(Debug, Clone)]
+pub struct RandomSampler {
+    rng: Pcg32,
+}
+
+impl Pcg32 {
+    const MULTIPLIER: u64 = 0x5851_f42d_4c95_7f2d;
+    const DEFAULT_STREAM: u64 = 0xda3e_39cb_94b9_5bdb;
+
+    /// Generator starting from `seed` on the default stream.
+    pub const fn new(seed: u64) -> Self {
+        Self::with_stream(seed, Self::DEFAULT_STREAM)
+    }
+
+    /// Generator starting from `seed` on one of 2⁶³ independent `stream`s.
+    pub const fn with_stream(seed: u64, stream: u64) -> Self {
+        let mut rng = Self {
+            state:     0,
+            increment: (stream << 1) | 1,
+        };
+        rng.step();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.step();
+        rng
+    }
+
+    const fn step(&mut self) {
+        self.state = self
+            .state
+            .wrapping_mul(Self::MULTIPLIER)
+            .wrapping_add(self.increment);
+    }
+
+    pub const fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.step();
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rotation = (old >> 59) as u32;
+        xorshifted.rotate_right(rotation)
+    }
+
+    /// Uniform value in `[0, 1)`, with 24 random bits for `f32` and 32 for
+    /// `f64`.
+    #[cfg(not(feature = "f32"))]
+    pub const fn next_float(&mut self) -> Float {
+        self.next_u32() as Float * (1.0 / 4_294_967_296.0)
+    }
+
+    /// Uniform value in `[0, 1)`, with 24 random bits for `f32` and 32 for
+    /// `f64`.
+    #[cfg(feature = "f32")]
+    pub const fn next_float(&mut self) -> Float {
+        (self.next_u32() >> 8) as Float * (1.0 / 16_777_216.0)
+    }
+}
+
+impl RandomSampler {
+    pub const fn new(seed: u64) -> Self {
+        Self {
+            rng: Pcg32::new(seed),
+        }
+    }
+}
+
+impl Sampler for RandomSampler {
+    fn next_1d(&mut self) -> Float {
+        self.rng.next_float()
+    }
+}
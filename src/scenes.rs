@@ -0,0 +1,355 @@
+//! Built-in scenes, used by the binary and handy for examples and
+//! benchmarks.
+
+use std::{fmt, str::FromStr};
+
+#[cfg(feature = "gpu")]
+use crate::gpu::GpuScene;
+use crate::{
+    Float,
+    bvh::Bvh,
+    camera::{Camera, CameraBuilder},
+    geo::{Quad, Sphere, Triangle, cuboid},
+    hit::HitList,
+    interval::Interval,
+    light::LightList,
+    material::{Dielectric, Material, Scatter},
+    sampler::{RandomSampler, Sampler},
+    transform::{Transform, Transformed},
+    vec3::Vec3,
+};
+
+/// World, emitters and a camera framing them.
+#[derive(Debug)]
+pub struct Scene {
+    world:  Bvh,
+    lights: LightList,
+    camera: CameraBuilder,
+    #[cfg(feature = "gpu")]
+    gpu:    Option<GpuScene>,
+}
+
+/// Named built-in scene.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Preset {
+    /// Random field of small spheres around three large ones.
+    Spheres,
+    /// Two mirror triangles lit by a spherical light.
+    Triangles,
+    /// Five colored quads facing the camera.
+    Quads,
+    /// The Cornell box, see [`cornell_box`].
+    CornellBox,
+}
+
+impl Scene {
+    /// Creates a scene, registering every object of `lights` for light
+    /// sampling.
+    pub fn new(world: Bvh, lights: &HitList, camera: CameraBuilder) -> Self {
+        Self {
+            world,
+            lights: LightList::from_list(lights),
+            camera,
+            #[cfg(feature = "gpu")]
+            gpu: None,
+        }
+    }
+
+    /// Attaches the GPU version of the world, rendered by
+    /// [`gpu::render_or_cpu`](crate::gpu::render_or_cpu).
+    #[cfg(feature = "gpu")]
+    pub fn with_gpu(mut self, gpu: GpuScene) -> Self {
+        self.gpu = Some(gpu);
+        self
+    }
+
+    pub const fn world(&self) -> &Bvh {
+        &self.world
+    }
+
+    pub const fn lights(&self) -> &LightList {
+        &self.lights
+    }
+
+    /// GPU version of the world, if the scene has one.
+    #[cfg(feature = "gpu")]
+    pub const fn gpu(&self) -> Option<&GpuScene> {
+        self.gpu.as_ref()
+    }
+
+    /// Builder of the scene camera, to be adjusted before building.
+    pub fn camera(&self) -> CameraBuilder {
+        self.camera.clone()
+    }
+}
+
+impl Preset {
+    pub const ALL: [Self; 4] = [
+        Self::Spheres,
+        Self::Triangles,
+        Self::Quads,
+        Self::CornellBox,
+    ];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Spheres => "spheres",
+            Self::Triangles => "triangles",
+            Self::Quads => "quads",
+            Self::CornellBox => "cornell-box",
+        }
+    }
+
+    /// Builds the scene, drawing any random choices from `seed`.
+    pub fn build(self, seed: u64) -> Scene {
+        match self {
+            Self::Spheres => random_spheres(seed),
+            Self::Triangles => triangles(),
+            Self::Quads => quads(),
+            Self::CornellBox => cornell_box(),
+        }
+    }
+}
+
+impl fmt::Display for Preset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Preset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|preset| preset.name() == s)
+            .ok_or_else(|| format!("unknown scene `{s}`"))
+    }
+}
+
+/// Random field of small spheres, the same for a given `seed`.
+pub fn random_spheres(seed: u64) -> Scene {
+    let spheres = spheres_world(&mut RandomSampler::new(seed));
+    let mut world: HitList = spheres.iter().copied().collect();
+    let lights: HitList = spheres
+        .iter()
+        .copied()
+        .filter(|sphere| sphere.material().emit().is_some())
+        .collect();
+
+    let camera = Camera::builder()
+        .fov(20.0)
+        .look_from(Vec3::new(10.0, 20.0, 20.0))
+        .look_at(Vec3::new(0.0, 0.0, 0.0))
+        .focus_dist(30.0);
+    let scene = Scene::new(Bvh::from_list(world.list_mut()), &lights, camera);
+    #[cfg(feature = "gpu")]
+    let scene = scene.with_gpu(GpuScene::from_spheres(&spheres));
+    scene
+}
+
+pub fn triangles() -> Scene {
+    let (world, lights) = triangles_world();
+    let camera = Camera::builder()
+        .fov(20.0)
+        .look_from(Vec3::new(10.0, 20.0, 20.0))
+        .look_at(Vec3::new(0.0, 0.0, 0.0))
+        .focus_dist(30.0);
+    Scene::new(world, &lights, camera)
+}
+
+pub fn quads() -> Scene {
+    let (world, lights) = quads_world();
+    let camera = Camera::builder()
+        .fov(80.0)
+        .look_from(Vec3::new(0.0, 0.0, 9.0))
+        .look_at(Vec3::new(0.0, 0.0, 0.0))
+        .background(Vec3::new(0.7, 0.8, 1.0).into());
+    Scene::new(world, &lights, camera)
+}
+
+/// The standard Cornell box: a white room with a green left wall and a red
+/// right wall, lit by an area light in the ceiling, holding a tall and a
+/// short box rotated in place. Its soft shadows and color bleeding make it
+/// the canonical check of diffuse interreflection.
+pub fn cornell_box() -> Scene {
+    let mut world = HitList::new();
+    let mut lights = HitList::new();
+
+    let red = Material::lambertian(Vec3::new(0.65, 0.05, 0.05));
+    let white = Material::lambertian(Vec3::new(0.73, 0.73, 0.73));
+    let green = Material::lambertian(Vec3::new(0.12, 0.45, 0.15));
+    let light = Material::diffuse_light(Vec3::new(15.0, 15.0, 15.0));
+
+    let x = Vec3::new(555.0, 0.0, 0.0);
+    let y = Vec3::new(0.0, 555.0, 0.0);
+    let z = Vec3::new(0.0, 0.0, 555.0);
+    let origin = Vec3::default();
+
+    world.push(Quad::new(x, y, z, green));
+    world.push(Quad::new(origin, y, z, red));
+    world.push(Quad::new(origin, x, z, white));
+    world.push(Quad::new(x + y + z, -x, -z, white));
+    world.push(Quad::new(z, x, y, white));
+
+    let lamp = Quad::new(
+        Vec3::new(343.0, 554.0, 332.0),
+        Vec3::new(-130.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, -105.0),
+        light,
+    );
+    world.push(lamp);
+    lights.push(lamp);
+
+    let up = Vec3::new(0.0, 1.0, 0.0);
+    let tall = cuboid(origin, Vec3::new(165.0, 330.0, 165.0), white);
+    world.push(Transformed::new(
+        tall,
+        Transform::rotation(up, 15.0).with_translation(Vec3::new(265.0, 0.0, 295.0)),
+    ));
+    let short = cuboid(origin, Vec3::new(165.0, 165.0, 165.0), white);
+    world.push(Transformed::new(
+        short,
+        Transform::rotation(up, -18.0).with_translation(Vec3::new(130.0, 0.0, 65.0)),
+    ));
+
+    let camera = Camera::builder()
+        .aspect_ratio(1.0)
+        .fov(40.0)
+        .look_from(Vec3::new(278.0, 278.0, -800.0))
+        .look_at(Vec3::new(278.0, 278.0, 0.0));
+    Scene::new(Bvh::from_list(world.list_mut()), &lights, camera)
+}
+
+fn spheres_world(sampler: &mut dyn Sampler) -> Vec<Sphere<Material>> {
+    let material_ground = Material::metal(Vec3::new(0.7, 0.7, 0.7), 0.01);
+    let mut spheres = vec![Sphere::new(
+        Vec3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        material_ground,
+    )];
+
+    for a in -11..11 {
+        for b in -11..11 {
+            let mat = sampler.next_1d();
+            let center = Vec3::new(
+                a as Float + 0.8 * sampler.next_1d(),
+                0.2,
+                b as Float + 0.8 * sampler.next_1d(),
+            );
+
+            if (center - Vec3::new(4.0, 1.0, 0.0)).length() > 1.2
+                && (center - Vec3::new(0.0, 1.0, 0.0)).length() > 1.2
+                && (center - Vec3::new(-4.0, 1.0, 0.0)).length() > 1.2
+            {
+                let material = if mat < 0.4 {
+                    let albedo = Vec3::random(sampler).scale(Vec3::random(sampler));
+                    Material::lambertian(albedo)
+                } else if mat < 0.7 {
+                    let color_interval = Interval::new(0.6, 1.0);
+                    let fuzz_interval = Interval::new(0.0, 0.5);
+                    let albedo = Vec3::random_range(color_interval, sampler);
+                    let fuzz = sampler.next_range(fuzz_interval);
+                    Material::metal(albedo, fuzz)
+                } else if mat < 0.9 {
+                    Material::dialectric(1.5)
+                } else {
+                    let interval = Interval::new(0.2, 1.0);
+                    let color = Vec3::random_range(interval, sampler);
+                    Material::diffuse_light(color)
+                };
+                spheres.push(Sphere::new(center, 0.2, material));
+                if material.is_dielectric() {
+                    // Air bubble hollowing the glass.
+                    let material = Material::Dielectric(Dielectric::new(1.0).with_priority(1));
+                    spheres.push(Sphere::new(center, 0.2 * 0.6, material));
+                }
+            }
+        }
+    }
+
+    spheres
+}
+
+fn triangles_world() -> (Bvh, HitList) {
+    let mut world = HitList::new();
+    let mut lights = HitList::new();
+
+    let material_ground = Material::lambertian(Vec3::new(0.5, 0.5, 0.5));
+    let ground = Sphere::new(Vec3::new(0.0, -1000.0, 0.0), 1000.0, material_ground);
+    world.push(ground);
+
+    let metal = Vec3::new(0.9, 0.9, 0.9);
+    let material = Material::metal(metal, 0.0);
+
+    let a = Vec3::new(0.0, 0.0, 0.0);
+    let b = Vec3::new(-8.0, 0.0, 0.0);
+    let c = Vec3::new(-8.0, 10.0, 0.0);
+
+    let triangle = Triangle::new(a, b, c, material);
+    world.push(triangle);
+
+    let a = Vec3::new(0.0, 0.0, 0.0);
+    let b = Vec3::new(0.0, 10.0, 0.0);
+    let c = Vec3::new(-8.0, 10.0, 0.0);
+
+    let triangle = Triangle::new(a, b, c, material);
+    world.push(triangle);
+
+    let material = Material::diffuse_light(Vec3::new(1.0, 1.0, 1.0));
+    let sphere = Sphere::new(Vec3::new(-4.0, 1.0, 2.5), 1.0, material);
+    world.push(sphere);
+    lights.push(sphere);
+
+    (Bvh::from_list(world.list_mut()), lights)
+}
+
+fn quads_world() -> (Bvh, HitList) {
+    let mut world = HitList::new();
+
+    let left_red = Material::lambertian(Vec3::new(1.0, 0.2, 0.2));
+    let back_green = Material::lambertian(Vec3::new(0.2, 1.0, 0.2));
+    let right_blue = Material::lambertian(Vec3::new(0.2, 0.2, 1.0));
+    let upper_orange = Material::lambertian(Vec3::new(1.0, 0.5, 0.0));
+    let lower_teal = Material::lambertian(Vec3::new(0.2, 0.8, 0.8));
+
+    let left = Quad::new(
+        Vec3::new(-3.0, -2.0, 5.0),
+        Vec3::new(0.0, 0.0, -4.0),
+        Vec3::new(0.0, 4.0, 0.0),
+        left_red,
+    );
+    let back = Quad::new(
+        Vec3::new(-2.0, -2.0, 0.0),
+        Vec3::new(4.0, 0.0, 0.0),
+        Vec3::new(0.0, 4.0, 0.0),
+        back_green,
+    );
+    let right = Quad::new(
+        Vec3::new(3.0, -2.0, 1.0),
+        Vec3::new(0.0, 0.0, 4.0),
+        Vec3::new(0.0, 4.0, 0.0),
+        right_blue,
+    );
+    let upper = Quad::new(
+        Vec3::new(-2.0, 3.0, 1.0),
+        Vec3::new(4.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, 4.0),
+        upper_orange,
+    );
+    let lower = Quad::new(
+        Vec3::new(-2.0, -3.0, 5.0),
+        Vec3::new(4.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, -4.0),
+        lower_teal,
+    );
+
+    world.push(left);
+    world.push(right);
+    world.push(back);
+    world.push(upper);
+    world.push(lower);
+
+    (Bvh::from_list(world.list_mut()), HitList::new())
+}
@@ -0,0 +1,54 @@
+//! Rendering of numbered frame sequences, such as turntables.
+
+use std::{
+    fs::File,
+    io,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    Float,
+    camera::{Camera, CameraBuilder},
+    hit::Hit,
+};
+
+/// Renders every frame in `frames` with the camera `camera_at` returns for
+/// it, set to that [frame](Camera::with_frame), writing each beauty pass as
+/// `frame_0000.ppm`, `frame_0001.ppm`, ... into `directory`.
+pub fn render_sequence<T: Hit + ?Sized + Sync, L: Hit + ?Sized + Sync>(
+    frames: Range<u32>,
+    camera_at: impl Fn(u32) -> Camera,
+    world: &T,
+    lights: &L,
+    sample_count: u32,
+    max_depth: u32,
+    directory: impl AsRef<Path>,
+) -> io::Result<()>
+where
+    T::Material: Copy,
+{
+    for frame in frames {
+        let camera = camera_at(frame).with_frame(frame);
+        let image = camera.render_frame(world, lights, sample_count, max_depth);
+        let file = File::create(frame_path(directory.as_ref(), frame))?;
+        image.beauty().write_ppm(file)?;
+    }
+    Ok(())
+}
+
+/// Path of frame `frame` of a sequence written into `directory`.
+pub fn frame_path(directory: &Path, frame: u32) -> PathBuf {
+    directory.join(format!("frame_{frame:04}.ppm"))
+}
+
+/// Camera for each frame of a full turn of `frame_count` frames around the
+/// look-at point of `builder`, for use with [`render_sequence`].
+pub fn turntable(builder: CameraBuilder, frame_count: u32) -> impl Fn(u32) -> Camera {
+    move |frame| {
+        builder
+            .clone()
+            .orbit(360.0 * frame as Float / frame_count as Float)
+            .build()
+    }
+}
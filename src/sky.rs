@@ -0,0 +1,186 @@
+use crate::{
+    Float,
+    consts::{FRAC_PI_2, PI},
+    pdf::{Pdf, SpherePdf},
+    sampler::Sampler,
+    vec3::{Onb, Vec3},
+};
+
+/// Analytic daylight sky following Preetham, Shirley and Smits, "A Practical
+/// Analytic Model for Daylight" (1999), with an optional sun disk.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Sky {
+    sun_direction: Vec3,
+    turbidity:     Float,
+    intensity:     Float,
+
+    sun_radiance:   Vec3,
+    sun_cos_radius: Float,
+
+    sun_theta: Float,
+    zenith:    [Float; 3],
+    perez:     [[Float; 5]; 3],
+}
+
+impl Sky {
+    /// Creates a sky lit by a sun in `sun_direction` (pointing towards the
+    /// sun, `+y` being up). `turbidity` ranges from about 2 for a very clear
+    /// sky to 10 for a hazy one.
+    pub fn new(sun_direction: Vec3, turbidity: Float) -> Self {
+        let sun_direction = sun_direction.unit();
+        let t = turbidity;
+        let sun_theta = sun_direction.y().clamp(-1.0, 1.0).acos().min(FRAC_PI_2);
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * sun_theta);
+        let zenith_y = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+
+        let th = [
+            sun_theta * sun_theta * sun_theta,
+            sun_theta * sun_theta,
+            sun_theta,
+            1.0,
+        ];
+        let poly = |t2: [Float; 4], t1: [Float; 4], t0: [Float; 4]| -> Float {
+            (0..4)
+                .map(|i| (t * t * t2[i] + t * t1[i] + t0[i]) * th[i])
+                .sum()
+        };
+        let zenith_x = poly(
+            [0.00166, -0.00375, 0.00209, 0.0],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        );
+        let zenith_y_chroma = poly(
+            [0.00275, -0.00610, 0.00317, 0.0],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        );
+
+        let perez = [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+
+        Self {
+            sun_direction,
+            turbidity,
+            intensity: 0.05,
+            sun_radiance: Vec3::new(40000.0, 38000.0, 34000.0),
+            sun_cos_radius: Float::to_radians(0.265).cos(),
+            sun_theta,
+            zenith: [zenith_y, zenith_x, zenith_y_chroma],
+            perez,
+        }
+    }
+
+    /// Scales the sky luminance, which the model gives in kcd/m².
+    pub fn with_intensity(mut self, intensity: Float) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    /// Sets the radiance and angular diameter, in degrees, of the sun disk.
+    /// A zero radiance removes the disk.
+    pub fn with_sun(mut self, radiance: Vec3, angular_diameter: Float) -> Self {
+        self.sun_radiance = radiance;
+        self.sun_cos_radius = (angular_diameter / 2.0).to_radians().cos();
+        self
+    }
+
+    pub const fn sun_direction(&self) -> Vec3 {
+        self.sun_direction
+    }
+
+    pub const fn turbidity(&self) -> Float {
+        self.turbidity
+    }
+
+    fn has_sun(&self) -> bool {
+        self.sun_radiance != Vec3::default()
+    }
+
+    fn perez(coefficients: [Float; 5], theta: Float, gamma: Float) -> Float {
+        let [a, b, c, d, e] = coefficients;
+        let cos_gamma = gamma.cos();
+        (1.0 + a * (b / theta.cos()).exp())
+            * (1.0 + c * (d * gamma).exp() + e * cos_gamma * cos_gamma)
+    }
+
+    /// Radiance of the sky, and of the sun disk, along `direction`.
+    pub fn value(&self, direction: Vec3) -> Vec3 {
+        let direction = direction.unit();
+        // Below the horizon the sky keeps its horizon color.
+        let theta = direction.y().clamp(-1.0, 1.0).acos().min(FRAC_PI_2 - 0.001);
+        let cos_gamma = direction.dot(self.sun_direction).clamp(-1.0, 1.0);
+        let gamma = cos_gamma.acos();
+
+        let [lum, x, y]: [Float; 3] = std::array::from_fn(|i| {
+            self.zenith[i] * Self::perez(self.perez[i], theta, gamma)
+                / Self::perez(self.perez[i], 0.0, self.sun_theta)
+        });
+
+        let lum = lum * self.intensity;
+        let cie_x = x / y * lum;
+        let cie_z = (1.0 - x - y) / y * lum;
+        let sky = Vec3::new(
+            3.2406 * cie_x - 1.5372 * lum - 0.4986 * cie_z,
+            -0.9689 * cie_x + 1.8758 * lum + 0.0415 * cie_z,
+            0.0557 * cie_x - 0.2040 * lum + 1.0570 * cie_z,
+        );
+        let sky = sky.max(Vec3::default());
+
+        if self.has_sun() && cos_gamma >= self.sun_cos_radius {
+            sky + self.sun_radiance
+        } else {
+            sky
+        }
+    }
+
+    /// Density of [`Sky::random_direction`], which samples the sun disk
+    /// and the whole sphere with equal probability.
+    pub fn pdf_value(&self, direction: Vec3) -> Float {
+        let sphere = SpherePdf.value(direction);
+        if !self.has_sun() {
+            return sphere;
+        }
+
+        let cone = if direction.unit().dot(self.sun_direction) >= self.sun_cos_radius {
+            1.0 / (2.0 * PI * (1.0 - self.sun_cos_radius))
+        } else {
+            0.0
+        };
+        0.5 * sphere + 0.5 * cone
+    }
+
+    pub fn random_direction(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        if !self.has_sun() || sampler.next_1d() < 0.5 {
+            return SpherePdf.generate(sampler);
+        }
+
+        let cos_theta = 1.0 - sampler.next_1d() * (1.0 - self.sun_cos_radius);
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+        let phi = 2.0 * PI * sampler.next_1d();
+        let local = Vec3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, cos_theta);
+        Onb::new(self.sun_direction).transform(local)
+    }
+}
@@ -0,0 +1,237 @@
+//! Spectral rendering: light carried at sampled wavelengths instead of as
+//! red, green and blue.
+//!
+//! The [`SpectralPathTracer`] follows every camera sample at three
+//! wavelengths, a hero wavelength drawn uniformly over the visible range and
+//! two more spread evenly around it ("Hero Wavelength Spectral Sampling",
+//! Wilkie et al. 2014), one per component of the [`Vec3`] the rest of the
+//! renderer works with. Colors of materials, lights and backgrounds stay
+//! linear sRGB and are upsampled to smooth spectra where the path meets
+//! them. The light found is projected onto the CIE 1931 color matching
+//! functions and converted back to linear sRGB, white balanced so that a
+//! flat spectrum is white. Colors round-trip exactly unless saturated enough
+//! for their spectrum to go negative somewhere, where it is clamped.
+
+use std::sync::LazyLock;
+
+use crate::{
+    Float,
+    camera::Camera,
+    hit::Hit,
+    integrator::{FirstHit, Integrator, Transport, trace_path},
+    ray::Ray,
+    sampler::Sampler,
+    vec3::Vec3,
+};
+
+/// Shortest wavelength sampled, in nanometers.
+pub const LAMBDA_MIN: Float = 360.0;
+
+/// Longest wavelength sampled, in nanometers.
+pub const LAMBDA_MAX: Float = 830.0;
+
+/// Wavelengths, in nanometers, a spectral camera sample carries light at.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SampledWavelengths {
+    lambda: [Float; 3],
+}
+
+/// Path tracing at sampled wavelengths, renders the spectral mode.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SpectralPathTracer;
+
+/// Tables of the conversions between linear sRGB and spectra.
+#[derive(Debug)]
+struct Conversion {
+    /// Integral of each channel of [`rgb_matching`], the color of a flat
+    /// spectrum before white balance.
+    white:      Vec3,
+    /// Columns of the inverse of the matrix taking the weights of the
+    /// [`basis`] functions to the color of their sum.
+    to_weights: [Vec3; 3],
+}
+
+static CONVERSION: LazyLock<Conversion> = LazyLock::new(Conversion::new);
+
+impl SampledWavelengths {
+    /// Hero wavelength at `u` in `[0, 1)` of the visible range, with the
+    /// other two a third and two thirds of the range above it, wrapping
+    /// around.
+    pub fn sample(u: Float) -> Self {
+        let lambda = [0.0, 1.0, 2.0].map(|offset| sample_wavelength((u + offset / 3.0).fract()));
+        Self { lambda }
+    }
+
+    pub const fn lambda(&self) -> [Float; 3] {
+        self.lambda
+    }
+
+    pub const fn hero(&self) -> Float {
+        self.lambda[0]
+    }
+
+    /// Keeps the hero wavelength of `values` only, weighted to stand for
+    /// all three, once the others can no longer follow the path.
+    pub fn hero_only(&self, values: Vec3) -> Vec3 {
+        Vec3::new(3.0 * values.x(), 0.0, 0.0)
+    }
+
+    /// Values at the wavelengths of the smooth spectrum upsampled from the
+    /// linear sRGB color `rgb`.
+    pub fn from_rgb(&self, rgb: Vec3) -> Vec3 {
+        let conversion = &*CONVERSION;
+        let [to_red, to_green, to_blue] = conversion.to_weights;
+        let weights = to_red * rgb.x() + to_green * rgb.y() + to_blue * rgb.z();
+        let [a, b, c] = self
+            .lambda
+            .map(|lambda| basis(lambda).dot(weights).max(0.0));
+        Vec3::new(a, b, c)
+    }
+
+    /// Linear sRGB color of light with `values` at the wavelengths, an
+    /// estimate of the color of the whole spectrum.
+    pub fn to_rgb(&self, values: Vec3) -> Vec3 {
+        let [a, b, c] = self.lambda;
+        (wavelength_to_rgb(a) * values.x()
+            + wavelength_to_rgb(b) * values.y()
+            + wavelength_to_rgb(c) * values.z())
+            / 3.0
+    }
+}
+
+impl Integrator for SpectralPathTracer {
+    fn radiance<T: Hit + ?Sized, L: Hit + ?Sized>(
+        &self,
+        camera: &Camera,
+        ray: &Ray,
+        world: &T,
+        lights: &L,
+        max_depth: u32,
+        sampler: &mut dyn Sampler,
+    ) -> (Vec3, FirstHit)
+    where
+        T::Material: Copy,
+    {
+        let wavelengths = SampledWavelengths::sample(sampler.next_1d());
+        let transport = Transport::Spectral(&wavelengths);
+        let (values, first_hit) = trace_path(
+            camera, ray, world, lights, max_depth, transport, sampler, None,
+        );
+        let light_paths = first_hit
+            .light_paths()
+            .map(|values| wavelengths.to_rgb(values));
+        (
+            wavelengths.to_rgb(values),
+            first_hit.with_light_paths(light_paths),
+        )
+    }
+}
+
+impl Conversion {
+    fn new() -> Self {
+        let mut white = Vec3::default();
+        let mut projections = [Vec3::default(); 3];
+        for step in 0..=(LAMBDA_MAX - LAMBDA_MIN) as u32 {
+            let lambda = LAMBDA_MIN + step as Float;
+            let matching = rgb_matching(lambda);
+            let basis = basis(lambda);
+            white += matching;
+            projections[0] += matching * basis.x();
+            projections[1] += matching * basis.y();
+            projections[2] += matching * basis.z();
+        }
+
+        // Colors of the basis functions, white balanced, are the columns of
+        // the matrix to invert.
+        let [a, b, c] = projections.map(|projection| projection.scale(white.invert()));
+        let det = a.dot(b.cross(c));
+        let rows = [b.cross(c), c.cross(a), a.cross(b)].map(|row| row / det);
+        let to_weights = [
+            Vec3::new(rows[0].x(), rows[1].x(), rows[2].x()),
+            Vec3::new(rows[0].y(), rows[1].y(), rows[2].y()),
+            Vec3::new(rows[0].z(), rows[1].z(), rows[2].z()),
+        ];
+        Self { white, to_weights }
+    }
+}
+
+/// Wavelength at `u` in `[0, 1)` of the visible range, sampled uniformly.
+pub fn sample_wavelength(u: Float) -> Float {
+    LAMBDA_MIN + u * (LAMBDA_MAX - LAMBDA_MIN)
+}
+
+/// Linear sRGB color of light of wavelength `lambda`, sampled uniformly
+/// over the visible range, weighted so that its average is white.
+pub fn wavelength_to_rgb(lambda: Float) -> Vec3 {
+    (rgb_matching(lambda) * (LAMBDA_MAX - LAMBDA_MIN)).scale(CONVERSION.white.invert())
+}
+
+/// CIE 1931 color matching functions at `lambda` nanometers, from the
+/// multi-lobe fit of Wyman, Sloan and Shirley, "Simple Analytic
+/// Approximations to the CIE XYZ Color Matching Functions" (2013).
+pub fn cie_xyz(lambda: Float) -> Vec3 {
+    let lobe = |mean: Float, below: Float, above: Float| {
+        let sigma = if lambda < mean { below } else { above };
+        let t = (lambda - mean) / sigma;
+        (-0.5 * t * t).exp()
+    };
+    let x = 1.056 * lobe(599.8, 37.9, 31.0) + 0.362 * lobe(442.0, 16.0, 26.7)
+        - 0.065 * lobe(501.1, 20.4, 26.2);
+    let y = 0.821 * lobe(568.8, 46.9, 40.5) + 0.286 * lobe(530.9, 16.3, 31.1);
+    let z = 1.217 * lobe(437.0, 11.8, 36.0) + 0.681 * lobe(459.0, 26.0, 13.8);
+    Vec3::new(x, y, z)
+}
+
+/// Linear sRGB color of unit luminance of a blackbody at `kelvin`, from the
+/// cubic fit of the Planckian locus of Kang et al., "Design of advanced
+/// color temperature control system for HDTV applications" (2002).
+/// Temperatures are clamped to the 1667 K to 25000 K range of the fit, and
+/// the deep reds of the coolest ones to the sRGB gamut.
+#[allow(clippy::excessive_precision)]
+pub fn blackbody_rgb(kelvin: Float) -> Vec3 {
+    let t = kelvin.clamp(1667.0, 25000.0);
+    let (t2, t3) = (t * t, t * t * t);
+    let x = if t <= 4000.0 {
+        -0.2661239e9 / t3 - 0.2343589e6 / t2 + 0.8776956e3 / t + 0.179910
+    } else {
+        -3.0258469e9 / t3 + 2.1070379e6 / t2 + 0.2226347e3 / t + 0.240390
+    };
+    let (x2, x3) = (x * x, x * x * x);
+    let y = if t <= 2222.0 {
+        -1.1063814 * x3 - 1.34811020 * x2 + 2.18555832 * x - 0.20219683
+    } else if t <= 4000.0 {
+        -0.9549476 * x3 - 1.37418593 * x2 + 2.09137015 * x - 0.16748867
+    } else {
+        3.0817580 * x3 - 5.87338670 * x2 + 3.75112997 * x - 0.37001483
+    };
+    let xyz = Vec3::new(x / y, 1.0, (1.0 - x - y) / y);
+    let rgb = xyz_to_rgb(xyz);
+    Vec3::new(rgb.x().max(0.0), rgb.y().max(0.0), rgb.z().max(0.0))
+}
+
+/// Converts CIE XYZ to linear sRGB.
+pub fn xyz_to_rgb(xyz: Vec3) -> Vec3 {
+    Vec3::new(
+        xyz.dot(Vec3::new(3.2404542, -1.5371385, -0.4985314)),
+        xyz.dot(Vec3::new(-0.969266, 1.8760108, 0.041556)),
+        xyz.dot(Vec3::new(0.0556434, -0.2040259, 1.0572252)),
+    )
+}
+
+/// Linear sRGB color matching functions, before white balance.
+fn rgb_matching(lambda: Float) -> Vec3 {
+    xyz_to_rgb(cie_xyz(lambda))
+}
+
+/// Red, green and blue smooth basis functions summing to one at every
+/// wavelength, so that white upsamples to a flat spectrum.
+fn basis(lambda: Float) -> Vec3 {
+    let red = smoothstep(560.0, 620.0, lambda);
+    let blue = 1.0 - smoothstep(450.0, 510.0, lambda);
+    Vec3::new(red, 1.0 - red - blue, blue)
+}
+
+fn smoothstep(edge0: Float, edge1: Float, x: Float) -> Float {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
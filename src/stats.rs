@@ -0,0 +1,156 @@
+//! Render statistics: rays traced, intersection tests, BVH nodes visited and
+//! time spent per row.
+//!
+//! Counting only happens with the `stats` feature. Without it the counters
+//! compile to nothing and [`snapshot`] always returns zeros, so the hot paths
+//! pay nothing for them.
+
+use std::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// Event counted by the statistics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Counter {
+    /// Rays leaving the camera.
+    PrimaryRays,
+    /// Rays continuing a path after a bounce.
+    SecondaryRays,
+    /// Rays only testing visibility, towards lights or for occlusion.
+    ShadowRays,
+    /// Ray-primitive intersection tests.
+    IntersectionTests,
+    /// Acceleration structure nodes visited.
+    NodesVisited,
+}
+
+/// Snapshot of the statistics gathered since the last [`reset`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    primary_rays:       u64,
+    secondary_rays:     u64,
+    shadow_rays:        u64,
+    intersection_tests: u64,
+    nodes_visited:      u64,
+    rows:               u64,
+    row_time:           Duration,
+    slowest_row:        Duration,
+}
+
+const COUNTERS: usize = 5;
+
+static COUNTS: [AtomicU64; COUNTERS] = [const { AtomicU64::new(0) }; COUNTERS];
+static ROWS: AtomicU64 = AtomicU64::new(0);
+static ROW_NANOS: AtomicU64 = AtomicU64::new(0);
+static SLOWEST_ROW_NANOS: AtomicU64 = AtomicU64::new(0);
+
+/// Counts one `counter` event.
+#[inline(always)]
+pub(crate) fn count(counter: Counter) {
+    if cfg!(feature = "stats") {
+        COUNTS[counter as usize].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Records that a row of pixels took `elapsed` to render.
+#[inline(always)]
+pub(crate) fn record_row(elapsed: Duration) {
+    if cfg!(feature = "stats") {
+        let nanos = elapsed.as_nanos().min(u64::MAX as u128) as u64;
+        ROWS.fetch_add(1, Ordering::Relaxed);
+        ROW_NANOS.fetch_add(nanos, Ordering::Relaxed);
+        SLOWEST_ROW_NANOS.fetch_max(nanos, Ordering::Relaxed);
+    }
+}
+
+/// Statistics gathered by every thread since the last [`reset`].
+pub fn snapshot() -> Stats {
+    let get = |counter: Counter| COUNTS[counter as usize].load(Ordering::Relaxed);
+    Stats {
+        primary_rays:       get(Counter::PrimaryRays),
+        secondary_rays:     get(Counter::SecondaryRays),
+        shadow_rays:        get(Counter::ShadowRays),
+        intersection_tests: get(Counter::IntersectionTests),
+        nodes_visited:      get(Counter::NodesVisited),
+        rows:               ROWS.load(Ordering::Relaxed),
+        row_time:           Duration::from_nanos(ROW_NANOS.load(Ordering::Relaxed)),
+        slowest_row:        Duration::from_nanos(SLOWEST_ROW_NANOS.load(Ordering::Relaxed)),
+    }
+}
+
+/// Sets every statistic back to zero.
+pub fn reset() {
+    for count in &COUNTS {
+        count.store(0, Ordering::Relaxed);
+    }
+    ROWS.store(0, Ordering::Relaxed);
+    ROW_NANOS.store(0, Ordering::Relaxed);
+    SLOWEST_ROW_NANOS.store(0, Ordering::Relaxed);
+}
+
+impl Stats {
+    pub const fn get(&self, counter: Counter) -> u64 {
+        match counter {
+            Counter::PrimaryRays => self.primary_rays,
+            Counter::SecondaryRays => self.secondary_rays,
+            Counter::ShadowRays => self.shadow_rays,
+            Counter::IntersectionTests => self.intersection_tests,
+            Counter::NodesVisited => self.nodes_visited,
+        }
+    }
+
+    pub const fn rays(&self) -> u64 {
+        self.primary_rays + self.secondary_rays + self.shadow_rays
+    }
+
+    pub const fn rows(&self) -> u64 {
+        self.rows
+    }
+
+    /// Time spent rendering rows, summed over all threads.
+    pub const fn row_time(&self) -> Duration {
+        self.row_time
+    }
+
+    pub const fn slowest_row(&self) -> Duration {
+        self.slowest_row
+    }
+
+    pub fn mean_row_time(&self) -> Duration {
+        match self.rows {
+            0 => Duration::ZERO,
+            rows => self.row_time / rows.min(u32::MAX as u64) as u32,
+        }
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rays = self.rays().max(1) as f64;
+        writeln!(f, "rays traced:        {}", self.rays())?;
+        writeln!(f, "  primary:          {}", self.primary_rays)?;
+        writeln!(f, "  secondary:        {}", self.secondary_rays)?;
+        writeln!(f, "  shadow:           {}", self.shadow_rays)?;
+        writeln!(
+            f,
+            "intersection tests: {} ({:.1} per ray)",
+            self.intersection_tests,
+            self.intersection_tests as f64 / rays
+        )?;
+        writeln!(
+            f,
+            "nodes visited:      {} ({:.1} per ray)",
+            self.nodes_visited,
+            self.nodes_visited as f64 / rays
+        )?;
+        write!(
+            f,
+            "rows rendered:      {} ({:.2?} mean, {:.2?} slowest)",
+            self.rows,
+            self.mean_row_time(),
+            self.slowest_row
+        )
+    }
+}
@@ -0,0 +1,564 @@
+//! Textures: colors varying over a surface, looked up by the texture
+//! coordinates of its hits.
+//!
+//! Every [`Texture`] places its pattern on the surface with a
+//! [`UvTransform`], so that one texture can be tiled, shifted and turned
+//! across a surface without changing the pattern itself.
+//!
+//! The procedural patterns, wood, marble, brick and cells, are drawn in the
+//! plane of the texture coordinates, wood and marble distorted by gradient
+//! noise.
+//!
+//! [`ImageTexture`]s keep a pyramid of ever smaller copies of their image
+//! and read the level whose texels match the footprint of the ray on the
+//! surface, see [`Ray::with_spread`](crate::ray::Ray::with_spread), so that
+//! distant textures are averaged instead of aliasing.
+
+use std::fmt::{self, Debug};
+
+use crate::{
+    Float,
+    consts::TAU,
+    error::{Error, Result},
+    film::Image,
+    sampler::hash_seed,
+    vec2::Vec2,
+    vec3::Vec3,
+};
+
+/// Octaves of noise summed into turbulence, each twice the frequency and
+/// half the amplitude of the one before.
+const TURBULENCE_OCTAVES: u32 = 5;
+
+/// Placement of a pattern on a surface: a rotation about the center of the
+/// texture coordinates, then a scale repeating the pattern, then an offset.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UvTransform {
+    scale:    Vec2,
+    offset:   Vec2,
+    rotation: Float,
+}
+
+/// Color of a [`Texture`] in the coordinates of its pattern.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Pattern {
+    /// The same color everywhere.
+    Solid(Vec3),
+    /// Squares half a unit wide alternating between `even` and `odd`, the
+    /// square at the origin being `even`.
+    Checker { even: Vec3, odd: Vec3 },
+    /// Growth rings around the origin, `rings` per unit, fading from `light`
+    /// early wood to `dark` late wood, their radius distorted by up to about
+    /// `grain` ring spacings of noise.
+    Wood {
+        light: Vec3,
+        dark:  Vec3,
+        rings: Float,
+        grain: Float,
+    },
+    /// Thin `vein` colored stripes across `base`, `frequency` per unit along
+    /// the first coordinate, pushed around by `turbulence` stripe spacings
+    /// of turbulence.
+    Marble {
+        base:       Vec3,
+        vein:       Vec3,
+        frequency:  Float,
+        turbulence: Float,
+    },
+    /// Running bond of `brick` colored bricks half a unit wide and a
+    /// quarter tall, every other row shifted by half a brick, laid in
+    /// `mortar` joints `mortar_width` of a brick height wide.
+    Brick {
+        brick:        Vec3,
+        mortar:       Vec3,
+        mortar_width: Float,
+    },
+    /// Worley cellular noise: feature points scattered one per cell of a
+    /// grid `cells` per unit, colored from `near` to `far` by `distance`, a
+    /// distance to the closest of them.
+    Cells {
+        near:     Vec3,
+        far:      Vec3,
+        cells:    Float,
+        distance: CellDistance,
+    },
+    /// Image repeating every unit, its bottom left corner at the origin.
+    /// Not serialized.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Image(&'static ImageTexture),
+}
+
+/// Distance of a point to the feature points around it shading a
+/// [`Pattern::Cells`], in cells and clamped to one.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CellDistance {
+    /// To the closest feature point, `near` at the points themselves.
+    #[default]
+    F1,
+    /// To the second closest, ridged cells.
+    F2,
+    /// Between the two, zero along the edges between cells, for cracks and
+    /// scales.
+    F2MinusF1,
+}
+
+/// How an [`ImageTexture`] is read between its texels.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TextureFilter {
+    /// The texel the point falls in.
+    Nearest,
+    /// The four texels around the point, interpolated bilinearly.
+    Bilinear,
+    /// Bilinear reads of the two mip levels whose texels are closest in size
+    /// to the footprint of the ray, interpolated between.
+    #[default]
+    Trilinear,
+}
+
+/// Image with its mip pyramid: levels each half the size of the one before,
+/// averaged from it, down to a single texel.
+#[derive(Clone, PartialEq)]
+pub struct ImageTexture {
+    levels: Vec<Image>,
+    filter: TextureFilter,
+}
+
+/// Pattern placed on surfaces by a [`UvTransform`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Texture {
+    pattern:   Pattern,
+    #[cfg_attr(feature = "serde", serde(default))]
+    transform: UvTransform,
+}
+
+impl Default for UvTransform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl UvTransform {
+    pub const IDENTITY: Self = Self {
+        scale:    Vec2::new(1.0, 1.0),
+        offset:   Vec2::new(0.0, 0.0),
+        rotation: 0.0,
+    };
+
+    /// # Panics
+    ///
+    /// Panics if `u` or `v` is not finite and nonzero, see
+    /// [`UvTransform::try_with_tiling`].
+    pub fn with_tiling(self, u: Float, v: Float) -> Self {
+        self.try_with_tiling(u, v)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Repeats the pattern `u` times along the first texture coordinate and
+    /// `v` times along the second, mirrored for negative counts.
+    pub fn try_with_tiling(mut self, u: Float, v: Float) -> Result<Self> {
+        if !(u.is_finite() && v.is_finite()) || u == 0.0 || v == 0.0 {
+            return Err(Error::InvalidParameter(
+                "texture tiling must be finite and nonzero",
+            ));
+        }
+        self.scale = Vec2::new(u, v);
+        Ok(self)
+    }
+
+    /// Adds `offset` to the coordinates once tiled, sliding the pattern the
+    /// opposite way.
+    pub const fn with_offset(mut self, offset: Vec2) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Turns the pattern by `degrees` about the center of the texture
+    /// coordinates, counterclockwise in `(u, v)`.
+    pub const fn with_rotation(mut self, degrees: Float) -> Self {
+        self.rotation = degrees;
+        self
+    }
+
+    pub const fn tiling(&self) -> Vec2 {
+        self.scale
+    }
+
+    pub const fn offset(&self) -> Vec2 {
+        self.offset
+    }
+
+    pub const fn rotation_degrees(&self) -> Float {
+        self.rotation
+    }
+
+    /// Width in the pattern of a footprint `footprint` wide on the surface.
+    pub fn footprint(&self, footprint: Float) -> Float {
+        footprint * (self.scale.x() * self.scale.y()).abs().sqrt()
+    }
+
+    /// Coordinates in the pattern of the surface point at `uv`.
+    pub fn apply(&self, uv: Vec2) -> Vec2 {
+        let center = Vec2::new(0.5, 0.5);
+        let (sin, cos) = (-self.rotation).to_radians().sin_cos();
+        let d = uv - center;
+        let rotated = Vec2::new(d.x() * cos - d.y() * sin, d.x() * sin + d.y() * cos) + center;
+        Vec2::new(rotated.x() * self.scale.x(), rotated.y() * self.scale.y()) + self.offset
+    }
+}
+
+impl Pattern {
+    /// Color at `uv`, in the coordinates of the pattern, of a footprint
+    /// `footprint` wide.
+    pub fn value(&self, uv: Vec2, footprint: Float) -> Vec3 {
+        match *self {
+            Self::Solid(color) => color,
+            Self::Wood {
+                light,
+                dark,
+                rings,
+                grain,
+            } => {
+                let radius = rings * uv.length() + grain * gradient_noise(uv * (rings / 2.0));
+                let t = radius.fract();
+                light.lerp(dark, t * t)
+            }
+            Self::Marble {
+                base,
+                vein,
+                frequency,
+                turbulence: strength,
+            } => {
+                let phase = frequency * uv.u() + strength * turbulence(uv * frequency);
+                let t = (phase * TAU / 2.0).sin().abs();
+                vein.lerp(base, t.powf(0.25))
+            }
+            Self::Brick {
+                brick,
+                mortar,
+                mortar_width,
+            } => {
+                let (x, y) = (2.0 * uv.u(), 4.0 * uv.v());
+                let row = y.floor();
+                let x = x + if row.rem_euclid(2.0) == 0.0 { 0.0 } else { 0.5 };
+                let (x, y) = (x.fract(), y.fract());
+                // Bricks are twice as wide as tall, so joints take half the
+                // fraction of their width.
+                let half = mortar_width / 2.0;
+                let in_joint = y < half || y > 1.0 - half || x < half / 2.0 || x > 1.0 - half / 2.0;
+                if in_joint { mortar } else { brick }
+            }
+            Self::Cells {
+                near,
+                far,
+                cells,
+                distance,
+            } => {
+                let (f1, f2) = worley(uv * cells);
+                let d = match distance {
+                    CellDistance::F1 => f1,
+                    CellDistance::F2 => f2,
+                    CellDistance::F2MinusF1 => f2 - f1,
+                };
+                near.lerp(far, d.min(1.0))
+            }
+            Self::Image(image) => image.value(uv, footprint),
+            Self::Checker { even, odd } => {
+                let cells = (2.0 * uv.u()).floor() + (2.0 * uv.v()).floor();
+                if cells.rem_euclid(2.0) == 0.0 {
+                    even
+                } else {
+                    odd
+                }
+            }
+        }
+    }
+}
+
+impl From<Vec3> for Texture {
+    fn from(color: Vec3) -> Self {
+        Self::solid(color)
+    }
+}
+
+impl Texture {
+    pub const fn new(pattern: Pattern) -> Self {
+        Self {
+            pattern,
+            transform: UvTransform::IDENTITY,
+        }
+    }
+
+    pub const fn solid(color: Vec3) -> Self {
+        Self::new(Pattern::Solid(color))
+    }
+
+    pub const fn checker(even: Vec3, odd: Vec3) -> Self {
+        Self::new(Pattern::Checker { even, odd })
+    }
+
+    pub const fn wood(light: Vec3, dark: Vec3, rings: Float, grain: Float) -> Self {
+        Self::new(Pattern::Wood {
+            light,
+            dark,
+            rings,
+            grain,
+        })
+    }
+
+    pub const fn marble(base: Vec3, vein: Vec3, frequency: Float, turbulence: Float) -> Self {
+        Self::new(Pattern::Marble {
+            base,
+            vein,
+            frequency,
+            turbulence,
+        })
+    }
+
+    pub const fn brick(brick: Vec3, mortar: Vec3, mortar_width: Float) -> Self {
+        Self::new(Pattern::Brick {
+            brick,
+            mortar,
+            mortar_width,
+        })
+    }
+
+    pub const fn cells(near: Vec3, far: Vec3, cells: Float, distance: CellDistance) -> Self {
+        Self::new(Pattern::Cells {
+            near,
+            far,
+            cells,
+            distance,
+        })
+    }
+
+    pub const fn image(image: &'static ImageTexture) -> Self {
+        Self::new(Pattern::Image(image))
+    }
+
+    pub const fn with_transform(mut self, transform: UvTransform) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    pub const fn pattern(&self) -> Pattern {
+        self.pattern
+    }
+
+    pub const fn transform(&self) -> UvTransform {
+        self.transform
+    }
+
+    /// Color at the surface point with texture coordinates `uv`, averaged
+    /// over a footprint `footprint` wide in texture coordinates.
+    pub fn value(&self, uv: Vec2, footprint: Float) -> Vec3 {
+        self.pattern.value(
+            self.transform.apply(uv),
+            self.transform.footprint(footprint),
+        )
+    }
+}
+
+impl Debug for ImageTexture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ImageTexture")
+            .field("width", &self.width())
+            .field("height", &self.height())
+            .field("levels", &self.levels.len())
+            .field("filter", &self.filter)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ImageTexture {
+    /// # Panics
+    ///
+    /// Panics if `image` is empty, see [`ImageTexture::try_new`].
+    pub fn new(image: Image) -> Self {
+        Self::try_new(image).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Texture of `image`, building its mip pyramid, failing if the image
+    /// has no pixels.
+    pub fn try_new(image: Image) -> Result<Self> {
+        if image.width() == 0 || image.height() == 0 {
+            return Err(Error::InvalidParameter("texture image must not be empty"));
+        }
+        let mut levels = vec![image];
+        while let Some(last) = levels.last()
+            && (last.width() > 1 || last.height() > 1)
+        {
+            levels.push(downsample(last));
+        }
+        Ok(Self {
+            levels,
+            filter: TextureFilter::default(),
+        })
+    }
+
+    pub const fn with_filter(mut self, filter: TextureFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Moves the texture to memory that is never freed, so that the
+    /// [`Copy`] materials using it can share it. Textures are meant to be
+    /// leaked once and live as long as the program.
+    pub fn leak(self) -> &'static Self {
+        Box::leak(Box::new(self))
+    }
+
+    pub fn width(&self) -> u32 {
+        self.levels[0].width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.levels[0].height()
+    }
+
+    /// The mip pyramid, starting with the full image.
+    pub fn levels(&self) -> &[Image] {
+        &self.levels
+    }
+
+    pub const fn filter(&self) -> TextureFilter {
+        self.filter
+    }
+
+    /// Color at `uv`, repeating the image every unit, averaged over a
+    /// footprint `footprint` wide when filtered trilinearly.
+    pub fn value(&self, uv: Vec2, footprint: Float) -> Vec3 {
+        let uv = uv.fract();
+        match self.filter {
+            TextureFilter::Nearest => nearest(&self.levels[0], uv),
+            TextureFilter::Bilinear => bilinear(&self.levels[0], uv),
+            TextureFilter::Trilinear => {
+                let texels = footprint * self.width().max(self.height()) as Float;
+                let coarsest = (self.levels.len() - 1) as Float;
+                let level = texels.max(1.0).log2().min(coarsest);
+                let lower = level.floor();
+                let t = level - lower;
+                let value = bilinear(&self.levels[lower as usize], uv);
+                if t > 0.0 {
+                    let upper = bilinear(&self.levels[lower as usize + 1], uv);
+                    value * (1.0 - t) + upper * t
+                } else {
+                    value
+                }
+            }
+        }
+    }
+}
+
+/// Perlin gradient noise at `p`, zero at integer points and within about
+/// `[-0.7, 0.7]`, with gradients hashed from the corners of its cell.
+fn gradient_noise(p: Vec2) -> Float {
+    let (x0, y0) = (p.x().floor(), p.y().floor());
+    let (fx, fy) = (p.x() - x0, p.y() - y0);
+    let corner = |dx: Float, dy: Float| {
+        let hash = hash_seed(&[(x0 + dx) as i64 as u64, (y0 + dy) as i64 as u64]);
+        let angle = (hash >> 11) as Float / (1u64 << 53) as Float * TAU;
+        let (sin, cos) = angle.sin_cos();
+        cos * (fx - dx) + sin * (fy - dy)
+    };
+    let fade = |t: Float| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+    let (sx, sy) = (fade(fx), fade(fy));
+    let bottom = corner(0.0, 0.0) + (corner(1.0, 0.0) - corner(0.0, 0.0)) * sx;
+    let top = corner(0.0, 1.0) + (corner(1.0, 1.0) - corner(0.0, 1.0)) * sx;
+    bottom + (top - bottom) * sy
+}
+
+/// Distances from `p` to the closest and second closest feature points, one
+/// placed at random in every unit cell.
+fn worley(p: Vec2) -> (Float, Float) {
+    let (x0, y0) = (p.x().floor(), p.y().floor());
+    let (mut f1, mut f2) = (Float::INFINITY, Float::INFINITY);
+    for dy in -1..=1 {
+        for dx in -1..=1 {
+            let (x, y) = (x0 + dx as Float, y0 + dy as Float);
+            let hash = hash_seed(&[x as i64 as u64, y as i64 as u64]);
+            let jitter = Vec2::new(
+                (hash >> 40) as Float / (1u64 << 24) as Float,
+                (hash & 0xff_ffff) as Float / (1u64 << 24) as Float,
+            );
+            let d = (Vec2::new(x, y) + jitter - p).length();
+            if d < f1 {
+                (f1, f2) = (d, f1);
+            } else if d < f2 {
+                f2 = d;
+            }
+        }
+    }
+    (f1, f2)
+}
+
+/// Sum of the magnitudes of [`TURBULENCE_OCTAVES`] octaves of noise at `p`.
+fn turbulence(p: Vec2) -> Float {
+    (0..TURBULENCE_OCTAVES)
+        .map(|octave| {
+            let scale = (1 << octave) as Float;
+            gradient_noise(p * scale).abs() / scale
+        })
+        .sum()
+}
+
+/// Next level of a mip pyramid, half the size of `image` rounded up, each
+/// texel the average of the up to four below it.
+fn downsample(image: &Image) -> Image {
+    let mut level = Image::new(image.width().div_ceil(2), image.height().div_ceil(2));
+    for y in 0..level.height() {
+        for x in 0..level.width() {
+            let mut sum = Vec3::default();
+            let mut count = 0;
+            for (sx, sy) in
+                [(0, 0), (1, 0), (0, 1), (1, 1)].map(|(dx, dy)| (2 * x + dx, 2 * y + dy))
+            {
+                if sx < image.width() && sy < image.height() {
+                    sum += image.get(sx, sy);
+                    count += 1;
+                }
+            }
+            level.set(x, y, sum / count as Float);
+        }
+    }
+    level
+}
+
+/// Position in texels of `uv` in `[0, 1)` on `image`, `v` going up from
+/// the bottom row.
+fn texel_position(image: &Image, uv: Vec2) -> (Float, Float) {
+    (
+        uv.u() * image.width() as Float,
+        (1.0 - uv.v()) * image.height() as Float,
+    )
+}
+
+fn nearest(image: &Image, uv: Vec2) -> Vec3 {
+    let (x, y) = texel_position(image, uv);
+    let x = (x as u32).min(image.width() - 1);
+    let y = (y as u32).min(image.height() - 1);
+    image.get(x, y)
+}
+
+/// Bilinear interpolation of the texels around `uv`, wrapping around the
+/// edges of `image`.
+fn bilinear(image: &Image, uv: Vec2) -> Vec3 {
+    let (x, y) = texel_position(image, uv);
+    let (x, y) = (x - 0.5, y - 0.5);
+    let (x0, y0) = (x.floor(), y.floor());
+    let (tx, ty) = (x - x0, y - y0);
+
+    let (width, height) = (image.width() as i64, image.height() as i64);
+    let at = |dx: i64, dy: i64| {
+        let x = (x0 as i64 + dx).rem_euclid(width);
+        let y = (y0 as i64 + dy).rem_euclid(height);
+        image.get(x as u32, y as u32)
+    };
+    let top = at(0, 0) * (1.0 - tx) + at(1, 0) * tx;
+    let bottom = at(0, 1) * (1.0 - tx) + at(1, 1) * tx;
+    top * (1.0 - ty) + bottom * ty
+}
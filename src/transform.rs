@@ -0,0 +1,259 @@
+use crate::{
+    Float,
+    aabb::Aabb,
+    error::{Error, Result},
+    hit::{Hit, HitRecord},
+    interval::Interval,
+    ray::Ray,
+    sampler::Sampler,
+    vec3::{Mat4, Vec3},
+};
+
+/// Similarity transform: uniform scale, then rotation around an axis, then
+/// translation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Transform {
+    translation: Vec3,
+    axis:        Vec3,
+    rotation:    Float,
+    scale:       Float,
+}
+
+/// Object placed in the scene by a [`Transform`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Transformed<T> {
+    object:    T,
+    transform: Transform,
+    bbox:      Aabb,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self {
+        translation: Vec3::new(0.0, 0.0, 0.0),
+        axis:        Vec3::new(0.0, 1.0, 0.0),
+        rotation:    0.0,
+        scale:       1.0,
+    };
+
+    pub const fn translation(translation: Vec3) -> Self {
+        Self {
+            translation,
+            ..Self::IDENTITY
+        }
+    }
+
+    /// Rotation of `degrees` around `axis`, counterclockwise when looking
+    /// down the axis.
+    pub fn rotation(axis: Vec3, degrees: Float) -> Self {
+        Self {
+            axis: axis.unit(),
+            rotation: degrees,
+            ..Self::IDENTITY
+        }
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `scale` is not positive, see [`Transform::try_scaling`].
+    pub fn scaling(scale: Float) -> Self {
+        Self::IDENTITY.with_scale(scale)
+    }
+
+    /// Uniform scaling by `scale`, failing if it is not positive.
+    pub fn try_scaling(scale: Float) -> Result<Self> {
+        Self::IDENTITY.try_with_scale(scale)
+    }
+
+    pub const fn with_translation(mut self, translation: Vec3) -> Self {
+        self.translation = translation;
+        self
+    }
+
+    pub fn with_rotation(mut self, axis: Vec3, degrees: Float) -> Self {
+        self.axis = axis.unit();
+        self.rotation = degrees;
+        self
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `scale` is not positive, see [`Transform::try_with_scale`].
+    pub fn with_scale(self, scale: Float) -> Self {
+        self.try_with_scale(scale)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_with_scale(mut self, scale: Float) -> Result<Self> {
+        if scale.is_nan() || scale <= 0.0 {
+            return Err(Error::InvalidParameter("transform scale must be positive"));
+        }
+        self.scale = scale;
+        Ok(self)
+    }
+
+    pub const fn translation_vector(&self) -> Vec3 {
+        self.translation
+    }
+
+    pub const fn axis(&self) -> Vec3 {
+        self.axis
+    }
+
+    pub const fn rotation_degrees(&self) -> Float {
+        self.rotation
+    }
+
+    pub const fn scale(&self) -> Float {
+        self.scale
+    }
+
+    /// Matrix of the transform, for use with [`Mat4`] based code.
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::translation(self.translation)
+            * Mat4::rotation(self.axis, self.rotation)
+            * Mat4::scaling(Vec3::new(self.scale, self.scale, self.scale))
+    }
+
+    pub fn point(&self, p: Vec3) -> Vec3 {
+        self.vector(p) + self.translation
+    }
+
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        (v * self.scale).rotate(self.axis, self.rotation)
+    }
+
+    /// Transforms a normal, which only rotates since the scale is uniform.
+    pub fn normal(&self, n: Vec3) -> Vec3 {
+        n.rotate(self.axis, self.rotation)
+    }
+
+    pub fn inverse_point(&self, p: Vec3) -> Vec3 {
+        self.inverse_vector(p - self.translation)
+    }
+
+    pub fn inverse_vector(&self, v: Vec3) -> Vec3 {
+        v.rotate(self.axis, -self.rotation) / self.scale
+    }
+
+    /// Bounding box of `bbox` once transformed.
+    pub fn aabb(&self, bbox: Aabb) -> Aabb {
+        let (x, y, z) = (bbox.x(), bbox.y(), bbox.z());
+        let corners = [x.min(), x.max()].into_iter().flat_map(|cx| {
+            [y.min(), y.max()]
+                .into_iter()
+                .flat_map(move |cy| [z.min(), z.max()].map(|cz| Vec3::new(cx, cy, cz)))
+        });
+
+        let mut corners = corners.map(|c| self.point(c));
+        let first = corners.next().unwrap();
+        let (min, max) = corners.fold((first, first), |(min, max), c| (min.min(c), max.max(c)));
+        Aabb::from_points(min, max)
+    }
+}
+
+impl<T: Hit> Transformed<T> {
+    pub fn new(object: T, transform: Transform) -> Self {
+        let bbox = transform.aabb(object.aabb());
+        Self {
+            object,
+            transform,
+            bbox,
+        }
+    }
+
+    pub const fn object(&self) -> &T {
+        &self.object
+    }
+
+    pub const fn transform(&self) -> Transform {
+        self.transform
+    }
+}
+
+impl<T: Hit> Hit for Transformed<T> {
+    type Material = T::Material;
+
+    // Ray parameters are the same in both spaces, since directions are
+    // transformed along with the origin, and footprints shrink with them.
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<Self::Material>> {
+        let local = Ray::new(
+            self.transform.inverse_point(ray.origin()),
+            self.transform.inverse_vector(ray.direction()),
+            ray.time(),
+        )
+        .with_kind(ray.kind())
+        .with_spread(ray.spread() / self.transform.scale);
+        let rec = self.object.hit(&local, ray_t)?;
+        let point = self.transform.point(rec.point());
+        let normal = self.transform.normal(rec.normal());
+        Some(rec.with_geometry(point, normal))
+    }
+
+    fn hit_any(&self, ray: &Ray, ray_t: Interval) -> bool {
+        let local = Ray::new(
+            self.transform.inverse_point(ray.origin()),
+            self.transform.inverse_vector(ray.direction()),
+            ray.time(),
+        )
+        .with_kind(ray.kind());
+        self.object.hit_any(&local, ray_t)
+    }
+
+    fn aabb(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn count(&self) -> usize {
+        self.object.count()
+    }
+
+    // Similarity transforms preserve angles, so solid angle densities carry
+    // over unchanged.
+    fn pdf_value(&self, origin: Vec3, direction: Vec3, time: Float) -> Float {
+        self.object.pdf_value(
+            self.transform.inverse_point(origin),
+            self.transform.inverse_vector(direction),
+            time,
+        )
+    }
+
+    fn random_to(&self, origin: Vec3, time: Float, sampler: &mut dyn Sampler) -> Vec3 {
+        let local = self
+            .object
+            .random_to(self.transform.inverse_point(origin), time, sampler);
+        self.transform.vector(local)
+    }
+
+    fn power(&self) -> Float {
+        self.object.power() * self.transform.scale * self.transform.scale
+    }
+
+    // Areas grow with the square of the scale, so densities over them shrink.
+    fn sample_surface(
+        &self,
+        time: Float,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(HitRecord<Self::Material>, Float)> {
+        let (rec, pdf) = self.object.sample_surface(time, sampler)?;
+        let point = self.transform.point(rec.point());
+        let normal = self.transform.normal(rec.normal());
+        let scale = self.transform.scale;
+        Some((rec.with_geometry(point, normal), pdf / (scale * scale)))
+    }
+
+    fn triangulate(&self, triangles: &mut Vec<[Vec3; 3]>) {
+        let first = triangles.len();
+        self.object.triangulate(triangles);
+        for triangle in &mut triangles[first..] {
+            *triangle = triangle.map(|p| self.transform.point(p));
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+
+use crate::Float;
+
+/// Two component vector, mostly texture coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Vec2<T = Float> {
+    x: T,
+    y: T,
+}
+
+impl<T: Copy> Vec2<T> {
+    pub const fn new(x: T, y: T) -> Self {
+        Self { x, y }
+    }
+
+    pub const fn x(self) -> T {
+        self.x
+    }
+
+    pub const fn y(self) -> T {
+        self.y
+    }
+
+    /// First texture coordinate, the same as [`Vec2::x`].
+    pub const fn u(self) -> T {
+        self.x
+    }
+
+    /// Second texture coordinate, the same as [`Vec2::y`].
+    pub const fn v(self) -> T {
+        self.y
+    }
+}
+
+impl Vec2<Float> {
+    pub const fn length_squared(self) -> Float {
+        self.x * self.x + self.y * self.y
+    }
+
+    pub fn length(self) -> Float {
+        self.length_squared().sqrt()
+    }
+
+    pub const fn dot(self, other: Self) -> Float {
+        self.x * other.x + self.y * other.y
+    }
+
+    /// Point a fraction `t` of the way from `self` to `other`.
+    pub fn lerp(self, other: Self, t: Float) -> Self {
+        self + (other - self) * t
+    }
+
+    /// Wraps both components into `[0, 1)`, for repeating textures.
+    pub fn fract(self) -> Self {
+        Vec2::new(self.x - self.x.floor(), self.y - self.y.floor())
+    }
+}
+
+impl<T> Add for Vec2<T>
+where
+    T: Add<Output = T> + Copy,
+{
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Vec2::new(self.x + rhs.x, self.y + rhs.y)
+    }
+}
+
+impl<T> Sub for Vec2<T>
+where
+    T: Sub<Output = T> + Copy,
+{
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Vec2::new(self.x - rhs.x, self.y - rhs.y)
+    }
+}
+
+impl<T> Neg for Vec2<T>
+where
+    T: Neg<Output = T> + Copy,
+{
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        Vec2::new(-self.x, -self.y)
+    }
+}
+
+impl<T> Mul<T> for Vec2<T>
+where
+    T: Mul<Output = T> + Copy,
+{
+    type Output = Self;
+
+    fn mul(self, rhs: T) -> Self::Output {
+        Vec2::new(self.x * rhs, self.y * rhs)
+    }
+}
+
+impl<T> Div<T> for Vec2<T>
+where
+    T: Div<Output = T> + Copy,
+{
+    type Output = Self;
+
+    fn div(self, rhs: T) -> Self::Output {
+        Vec2::new(self.x / rhs, self.y / rhs)
+    }
+}
+
+impl<T> AddAssign for Vec2<T>
+where
+    Vec2<T>: Add<Output = Self> + Copy,
+{
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl<T> SubAssign for Vec2<T>
+where
+    Vec2<T>: Sub<Output = Self> + Copy,
+{
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl<T> From<[T; 2]> for Vec2<T> {
+    fn from([x, y]: [T; 2]) -> Self {
+        Self { x, y }
+    }
+}
+
+impl<T> From<Vec2<T>> for [T; 2] {
+    fn from(v: Vec2<T>) -> Self {
+        [v.x, v.y]
+    }
+}
@@ -1,12 +1,13 @@
 use std::{
     iter::Sum,
-    ops::{Add, Div, Mul, Neg, Sub},
+    ops::{Add, AddAssign, Div, DivAssign, Index, Mul, MulAssign, Neg, Sub, SubAssign},
 };
 
-use crate::{aabb::Axis, interval::Interval, random_0_1, random_range};
+use crate::{Float, aabb::Axis, consts::PI, interval::Interval, sampler::Sampler};
 
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
-pub struct Vec3<T = f64> {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Vec3<T = Float> {
     x: T,
     y: T,
     z: T,
@@ -38,32 +39,32 @@ impl<T: Copy> Vec3<T> {
     }
 }
 
-impl Vec3<f64> {
-    pub fn random() -> Self {
-        Vec3::new(random_0_1(), random_0_1(), random_0_1())
+impl Vec3<Float> {
+    pub fn random(sampler: &mut dyn Sampler) -> Self {
+        Vec3::new(sampler.next_1d(), sampler.next_1d(), sampler.next_1d())
     }
 
     pub const fn scale(self, scale: Vec3) -> Self {
         Vec3::new(self.x * scale.x, self.y * scale.y, self.z * scale.z)
     }
 
-    pub fn random_range(interval: Interval) -> Self {
+    pub fn random_range(interval: Interval, sampler: &mut dyn Sampler) -> Self {
         Vec3::new(
-            random_range(interval),
-            random_range(interval),
-            random_range(interval),
+            sampler.next_range(interval),
+            sampler.next_range(interval),
+            sampler.next_range(interval),
         )
     }
 
-    pub const fn length_squared(self) -> f64 {
+    pub const fn length_squared(self) -> Float {
         self.x * self.x + self.y * self.y + self.z * self.z
     }
 
-    pub fn length(self) -> f64 {
+    pub fn length(self) -> Float {
         self.length_squared().sqrt()
     }
 
-    pub const fn dot(self, other: Self) -> f64 {
+    pub const fn dot(self, other: Self) -> Float {
         self.x * other.x + self.y * other.y + self.z * other.z
     }
 
@@ -79,34 +80,96 @@ impl Vec3<f64> {
         self / self.length()
     }
 
-    pub fn random_unit() -> Self {
+    pub fn distance(self, other: Self) -> Float {
+        (other - self).length()
+    }
+
+    /// Point a fraction `t` of the way from `self` to `other`.
+    pub fn lerp(self, other: Self, t: Float) -> Self {
+        self + (other - self) * t
+    }
+
+    /// Component-wise minimum.
+    pub const fn min(self, other: Self) -> Self {
+        Vec3::new(
+            self.x.min(other.x),
+            self.y.min(other.y),
+            self.z.min(other.z),
+        )
+    }
+
+    /// Component-wise maximum.
+    pub const fn max(self, other: Self) -> Self {
+        Vec3::new(
+            self.x.max(other.x),
+            self.y.max(other.y),
+            self.z.max(other.z),
+        )
+    }
+
+    /// Clamps every component between those of `min` and `max`.
+    pub const fn clamp(self, min: Self, max: Self) -> Self {
+        self.max(min).min(max)
+    }
+
+    pub const fn abs(self) -> Self {
+        Vec3::new(self.x.abs(), self.y.abs(), self.z.abs())
+    }
+
+    /// Component of the vector along `onto`.
+    pub fn project(self, onto: Self) -> Self {
+        onto * (self.dot(onto) / onto.length_squared())
+    }
+
+    /// Angle between the two vectors in radians, accurate even when they are
+    /// nearly parallel, unlike the arccosine of the dot product.
+    pub fn angle_between(self, other: Self) -> Float {
+        let (a, b) = (self.unit(), other.unit());
+        2.0 * (a - b).length().atan2((a + b).length())
+    }
+
+    pub fn random_unit(sampler: &mut dyn Sampler) -> Self {
         let range = Interval::new(-1.0, 1.0);
-        let mut p = Self::random_range(range);
+        let mut p = Self::random_range(range, sampler);
         let mut lensq = p.length_squared();
 
         while lensq <= 1e-160 || lensq > 1.0 {
-            p = Self::random_range(range);
+            p = Self::random_range(range, sampler);
             lensq = p.length_squared();
         }
 
         p / lensq.sqrt()
     }
 
-    pub fn random_in_disk() -> Self {
+    pub fn random_in_disk(sampler: &mut dyn Sampler) -> Self {
         let range = Interval::new(-1.0, 1.0);
-        let mut p = Self::new(random_range(range), random_range(range), 0.0);
+        let mut p = Self::new(sampler.next_range(range), sampler.next_range(range), 0.0);
         let mut lensq = p.length_squared();
 
         while lensq > 1.0 {
-            p = Self::random_range(range);
+            p = Self::random_range(range, sampler);
             lensq = p.length_squared();
         }
 
         p
     }
 
-    pub fn random_on_hemisphere(normal: Self) -> Self {
-        let on_unit = Self::random_unit();
+    /// Random direction around `+z` distributed proportionally to the cosine
+    /// of its angle with `+z`.
+    pub fn random_cosine_direction(sampler: &mut dyn Sampler) -> Self {
+        let r1 = sampler.next_1d();
+        let r2 = sampler.next_1d();
+
+        let phi = 2.0 * PI * r1;
+        let x = phi.cos() * r2.sqrt();
+        let y = phi.sin() * r2.sqrt();
+        let z = (1.0 - r2).sqrt();
+
+        Self::new(x, y, z)
+    }
+
+    pub fn random_on_hemisphere(normal: Self, sampler: &mut dyn Sampler) -> Self {
+        let on_unit = Self::random_unit(sampler);
         if on_unit.dot(normal) > 0.0 {
             on_unit
         } else {
@@ -114,6 +177,16 @@ impl Vec3<f64> {
         }
     }
 
+    /// Random direction drawn uniformly from the cone around the unit
+    /// `axis` of the directions whose cosine with it is at least `cos_max`.
+    pub fn random_in_cone(axis: Self, cos_max: Float, sampler: &mut dyn Sampler) -> Self {
+        let cos_theta = 1.0 - sampler.next_1d() * (1.0 - cos_max);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * sampler.next_1d();
+        let local = Self::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+        Onb::new(axis).transform(local)
+    }
+
     pub fn near_zero(self) -> bool {
         let s = 1e-8;
         self.x.abs() < s && self.y.abs() < s && self.z.abs() < s
@@ -127,12 +200,329 @@ impl Vec3<f64> {
         Self::new(1.0 / self.x, 1.0 / self.y, 1.0 / self.z)
     }
 
-    pub fn refract(self, normal: Self, etai_over_etat: f64) -> Self {
+    pub fn refract(self, normal: Self, etai_over_etat: Float) -> Self {
         let cos_theta = self.neg().dot(normal).min(1.0);
         let r_perpendicular = (self + normal * cos_theta) * etai_over_etat;
-        let r_parallel = normal * -f64::sqrt(f64::abs(1.0 - r_perpendicular.length_squared()));
+        let r_parallel = normal * -Float::sqrt(Float::abs(1.0 - r_perpendicular.length_squared()));
         r_perpendicular + r_parallel
     }
+
+    /// Rotates the vector by `degrees` around the unit vector `axis`,
+    /// counterclockwise when looking down the axis.
+    pub fn rotate(self, axis: Self, degrees: Float) -> Self {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        (self * cos) + (axis.cross(self) * sin) + (axis * (axis.dot(self) * (1.0 - cos)))
+    }
+}
+
+/// 4x4 matrix of homogeneous transforms, stored by rows and applied to
+/// column vectors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mat4 {
+    rows: [[Float; 4]; 4],
+}
+
+/// Rotation quaternion `w + xi + yj + zk`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Quat {
+    w: Float,
+    v: Vec3,
+}
+
+/// Orthonormal basis whose `w` axis is aligned with a given direction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Onb {
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+}
+
+impl Onb {
+    pub fn new(n: Vec3) -> Self {
+        let w = n.unit();
+        let a = if w.x().abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let v = w.cross(a).unit();
+        let u = w.cross(v);
+
+        Self { u, v, w }
+    }
+
+    pub const fn u(&self) -> Vec3 {
+        self.u
+    }
+
+    pub const fn v(&self) -> Vec3 {
+        self.v
+    }
+
+    pub const fn w(&self) -> Vec3 {
+        self.w
+    }
+
+    /// Transforms `v`, given in basis coordinates, into world space.
+    pub fn transform(&self, v: Vec3) -> Vec3 {
+        (self.u * v.x()) + (self.v * v.y()) + (self.w * v.z())
+    }
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Mat4 {
+    pub const IDENTITY: Self = Self::new([
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]);
+
+    pub const fn new(rows: [[Float; 4]; 4]) -> Self {
+        Self { rows }
+    }
+
+    pub const fn translation(t: Vec3) -> Self {
+        Self::new([
+            [1.0, 0.0, 0.0, t.x()],
+            [0.0, 1.0, 0.0, t.y()],
+            [0.0, 0.0, 1.0, t.z()],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub const fn scaling(s: Vec3) -> Self {
+        Self::new([
+            [s.x(), 0.0, 0.0, 0.0],
+            [0.0, s.y(), 0.0, 0.0],
+            [0.0, 0.0, s.z(), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Rotation of `degrees` around `axis`, counterclockwise when looking
+    /// down the axis.
+    pub fn rotation(axis: Vec3, degrees: Float) -> Self {
+        Quat::from_axis_angle(axis, degrees).to_mat4()
+    }
+
+    pub const fn rows(&self) -> [[Float; 4]; 4] {
+        self.rows
+    }
+
+    pub const fn get(&self, row: usize, column: usize) -> Float {
+        self.rows[row][column]
+    }
+
+    pub const fn transpose(&self) -> Self {
+        let m = &self.rows;
+        let mut rows = [[0.0; 4]; 4];
+        let mut i = 0;
+        while i < 4 {
+            let mut j = 0;
+            while j < 4 {
+                rows[i][j] = m[j][i];
+                j += 1;
+            }
+            i += 1;
+        }
+        Self::new(rows)
+    }
+
+    /// Inverse matrix, or `None` if the matrix is singular.
+    pub fn inverse(&self) -> Option<Self> {
+        // Gauss-Jordan elimination with partial pivoting.
+        let mut m = self.rows;
+        let mut inv = Self::IDENTITY.rows;
+        for column in 0..4 {
+            let pivot = (column..4)
+                .max_by(|&a, &b| m[a][column].abs().total_cmp(&m[b][column].abs()))
+                .unwrap_or(column);
+            if m[pivot][column] == 0.0 {
+                return None;
+            }
+            m.swap(column, pivot);
+            inv.swap(column, pivot);
+
+            let scale = 1.0 / m[column][column];
+            for j in 0..4 {
+                m[column][j] *= scale;
+                inv[column][j] *= scale;
+            }
+            for row in (0..4).filter(|&row| row != column) {
+                let factor = m[row][column];
+                for j in 0..4 {
+                    m[row][j] -= factor * m[column][j];
+                    inv[row][j] -= factor * inv[column][j];
+                }
+            }
+        }
+        Some(Self::new(inv))
+    }
+
+    /// Transforms a point, dividing by the resulting `w` for projective
+    /// matrices.
+    pub fn transform_point(&self, p: Vec3) -> Vec3 {
+        let m = &self.rows;
+        let row = |i: usize| m[i][0] * p.x() + m[i][1] * p.y() + m[i][2] * p.z() + m[i][3];
+        let w = row(3);
+        let p = Vec3::new(row(0), row(1), row(2));
+        if w == 1.0 { p } else { p / w }
+    }
+
+    /// Transforms a direction, ignoring the translation.
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.rows;
+        let row = |i: usize| m[i][0] * v.x() + m[i][1] * v.y() + m[i][2] * v.z();
+        Vec3::new(row(0), row(1), row(2))
+    }
+
+    /// Transforms a surface normal by the inverse transpose, keeping it
+    /// perpendicular to transformed tangents. The result is not normalized.
+    /// Singular matrices leave the normal unchanged.
+    pub fn transform_normal(&self, n: Vec3) -> Vec3 {
+        match self.inverse() {
+            Some(inverse) => inverse.transpose().transform_vector(n),
+            None => n,
+        }
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Self;
+
+    /// Matrix applying `rhs` first, then `self`.
+    fn mul(self, rhs: Self) -> Self::Output {
+        let mut rows = [[0.0; 4]; 4];
+        for (i, row) in rows.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.rows[i][k] * rhs.rows[k][j]).sum();
+            }
+        }
+        Self::new(rows)
+    }
+}
+
+impl Default for Quat {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Quat {
+    pub const IDENTITY: Self = Self::new(1.0, Vec3::new(0.0, 0.0, 0.0));
+
+    /// Quaternion of real part `w` and imaginary part `v`.
+    pub const fn new(w: Float, v: Vec3) -> Self {
+        Self { w, v }
+    }
+
+    /// Rotation of `degrees` around `axis`, counterclockwise when looking
+    /// down the axis.
+    pub fn from_axis_angle(axis: Vec3, degrees: Float) -> Self {
+        let (sin, cos) = (degrees.to_radians() / 2.0).sin_cos();
+        Self::new(cos, axis.unit() * sin)
+    }
+
+    pub const fn w(&self) -> Float {
+        self.w
+    }
+
+    pub const fn v(&self) -> Vec3 {
+        self.v
+    }
+
+    pub const fn dot(&self, other: Self) -> Float {
+        self.w * other.w + self.v.dot(other.v)
+    }
+
+    pub fn length(&self) -> Float {
+        self.dot(*self).sqrt()
+    }
+
+    pub fn normalize(&self) -> Self {
+        let length = self.length();
+        Self::new(self.w / length, self.v / length)
+    }
+
+    /// Inverse rotation of a unit quaternion.
+    pub fn conjugate(&self) -> Self {
+        Self::new(self.w, -self.v)
+    }
+
+    pub fn rotate(&self, v: Vec3) -> Vec3 {
+        // v + 2w(q × v) + 2q × (q × v), with q the imaginary part.
+        let t = self.v.cross(v) * 2.0;
+        v + t * self.w + self.v.cross(t)
+    }
+
+    /// Spherical linear interpolation from `self` to `other` along the
+    /// shortest arc, at constant angular speed.
+    pub fn slerp(&self, other: Self, t: Float) -> Self {
+        let mut other = other;
+        let mut cos = self.dot(other);
+        if cos < 0.0 {
+            other = Self::new(-other.w, -other.v);
+            cos = -cos;
+        }
+
+        // Nearly equal rotations, where the sine below vanishes.
+        if cos > 0.9995 {
+            let w = self.w + (other.w - self.w) * t;
+            return Self::new(w, self.v.lerp(other.v, t)).normalize();
+        }
+
+        let angle = cos.acos();
+        let sin = angle.sin();
+        let a = ((1.0 - t) * angle).sin() / sin;
+        let b = (t * angle).sin() / sin;
+        Self::new(self.w * a + other.w * b, self.v * a + other.v * b)
+    }
+
+    /// Rotation matrix of a unit quaternion.
+    pub fn to_mat4(&self) -> Mat4 {
+        let (w, x, y, z) = (self.w, self.v.x(), self.v.y(), self.v.z());
+        Mat4::new([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+                0.0,
+            ],
+            [
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+                0.0,
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+}
+
+impl Mul for Quat {
+    type Output = Self;
+
+    /// Rotation applying `rhs` first, then `self`.
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self::new(
+            self.w * rhs.w - self.v.dot(rhs.v),
+            rhs.v * self.w + self.v * rhs.w + self.v.cross(rhs.v),
+        )
+    }
 }
 
 impl<T> Neg for Vec3<T>
@@ -181,7 +571,7 @@ where
 
 impl<T> Div<T> for Vec3<T>
 where
-    f64: Div<T, Output = T> + Copy,
+    Float: Div<T, Output = T> + Copy,
     Vec3<T>: Mul<T, Output = Self>,
 {
     type Output = Self;
@@ -191,6 +581,86 @@ where
     }
 }
 
+/// Component-wise product.
+impl<T> Mul for Vec3<T>
+where
+    T: Mul<Output = T> + Copy,
+{
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Vec3::new(self.x * rhs.x, self.y * rhs.y, self.z * rhs.z)
+    }
+}
+
+impl Mul<Vec3> for Float {
+    type Output = Vec3;
+
+    fn mul(self, rhs: Vec3) -> Self::Output {
+        rhs * self
+    }
+}
+
+impl<T> AddAssign for Vec3<T>
+where
+    Vec3<T>: Add<Output = Self> + Copy,
+{
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl<T> SubAssign for Vec3<T>
+where
+    Vec3<T>: Sub<Output = Self> + Copy,
+{
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl<T, Rhs> MulAssign<Rhs> for Vec3<T>
+where
+    Vec3<T>: Mul<Rhs, Output = Self> + Copy,
+{
+    fn mul_assign(&mut self, rhs: Rhs) {
+        *self = *self * rhs;
+    }
+}
+
+impl<T> DivAssign<T> for Vec3<T>
+where
+    Vec3<T>: Div<T, Output = Self> + Copy,
+{
+    fn div_assign(&mut self, rhs: T) {
+        *self = *self / rhs;
+    }
+}
+
+impl<T> Index<Axis> for Vec3<T> {
+    type Output = T;
+
+    fn index(&self, axis: Axis) -> &Self::Output {
+        match axis {
+            Axis::X => &self.x,
+            Axis::Y => &self.y,
+            Axis::Z => &self.z,
+        }
+    }
+}
+
+impl<T> From<[T; 3]> for Vec3<T> {
+    fn from([x, y, z]: [T; 3]) -> Self {
+        Self { x, y, z }
+    }
+}
+
+impl<T> From<Vec3<T>> for [T; 3] {
+    fn from(v: Vec3<T>) -> Self {
+        [v.x, v.y, v.z]
+    }
+}
+
 impl<T> Sum for Vec3<T>
 where
     Vec3<T>: Add<Output = Self> + Default,
@@ -0,0 +1,149 @@
+//! Per-object visibility to the kinds of rays.
+//!
+//! Wrapping an object in [`Visible`] hides it from some of camera, shadow
+//! and reflection rays: a backdrop seen only by the camera, a light blocker
+//! that only casts shadows, or an object missing from its own reflections.
+//! An emitter hidden from shadow rays also lights nothing through light
+//! sampling, since those rays must reach it.
+
+use crate::{
+    Float,
+    aabb::Aabb,
+    hit::{Hit, HitRecord},
+    interval::Interval,
+    ray::{Ray, RayKind},
+    sampler::Sampler,
+    vec3::Vec3,
+};
+
+/// Kinds of rays an object is visible to, all of them by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Visibility {
+    camera:     bool,
+    shadow:     bool,
+    reflection: bool,
+}
+
+/// Object seen only by the rays its [`Visibility`] includes.
+#[derive(Debug, Clone, Copy)]
+pub struct Visible<T> {
+    object:     T,
+    visibility: Visibility,
+}
+
+impl Default for Visibility {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl Visibility {
+    /// Visible to every ray.
+    pub const ALL: Self = Self {
+        camera:     true,
+        shadow:     true,
+        reflection: true,
+    };
+
+    /// Whether camera rays see the object.
+    pub const fn with_camera(mut self, visible: bool) -> Self {
+        self.camera = visible;
+        self
+    }
+
+    /// Whether the object casts shadows.
+    pub const fn with_shadow(mut self, visible: bool) -> Self {
+        self.shadow = visible;
+        self
+    }
+
+    /// Whether the object shows in reflections, refractions and indirect
+    /// light.
+    pub const fn with_reflection(mut self, visible: bool) -> Self {
+        self.reflection = visible;
+        self
+    }
+
+    pub const fn camera(self) -> bool {
+        self.camera
+    }
+
+    pub const fn shadow(self) -> bool {
+        self.shadow
+    }
+
+    pub const fn reflection(self) -> bool {
+        self.reflection
+    }
+
+    /// Whether rays of `kind` see the object.
+    pub const fn includes(self, kind: RayKind) -> bool {
+        match kind {
+            RayKind::Camera => self.camera,
+            RayKind::Shadow => self.shadow,
+            RayKind::Reflection => self.reflection,
+        }
+    }
+}
+
+impl<T: Hit> Visible<T> {
+    pub const fn new(object: T, visibility: Visibility) -> Self {
+        Self { object, visibility }
+    }
+
+    pub const fn object(&self) -> &T {
+        &self.object
+    }
+
+    pub const fn visibility(&self) -> Visibility {
+        self.visibility
+    }
+}
+
+impl<T: Hit> Hit for Visible<T> {
+    type Material = T::Material;
+
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<Self::Material>> {
+        if !self.visibility.includes(ray.kind()) {
+            return None;
+        }
+        self.object.hit(ray, ray_t)
+    }
+
+    fn hit_any(&self, ray: &Ray, ray_t: Interval) -> bool {
+        self.visibility.includes(ray.kind()) && self.object.hit_any(ray, ray_t)
+    }
+
+    fn aabb(&self) -> Aabb {
+        self.object.aabb()
+    }
+
+    fn count(&self) -> usize {
+        self.object.count()
+    }
+
+    fn pdf_value(&self, origin: Vec3, direction: Vec3, time: Float) -> Float {
+        self.object.pdf_value(origin, direction, time)
+    }
+
+    fn random_to(&self, origin: Vec3, time: Float, sampler: &mut dyn Sampler) -> Vec3 {
+        self.object.random_to(origin, time, sampler)
+    }
+
+    fn power(&self) -> Float {
+        self.object.power()
+    }
+
+    fn sample_surface(
+        &self,
+        time: Float,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(HitRecord<Self::Material>, Float)> {
+        self.object.sample_surface(time, sampler)
+    }
+
+    fn triangulate(&self, triangles: &mut Vec<[Vec3; 3]>) {
+        self.object.triangulate(triangles);
+    }
+}
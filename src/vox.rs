@@ -0,0 +1,260 @@
+//! Importer for MagicaVoxel `.vox` models.
+
+use std::{
+    fs::File,
+    io::{self, BufReader, Read},
+    path::Path,
+};
+
+use crate::{Float, geo::Quad, hit::HitList, material::Material, vec3::Vec3};
+
+/// Largest number of cells of a model along each axis the format allows.
+const MAX_SIZE: usize = 256;
+
+/// Voxel model read from a MagicaVoxel file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoxModel {
+    size:    [usize; 3],
+    /// Palette index of every cell, `0` meaning empty, indexed by
+    /// `x + size_x * (y + size_y * z)`.
+    cells:   Vec<u8>,
+    palette: Vec<Vec3>,
+}
+
+impl VoxModel {
+    /// Loads the first model of a `.vox` file.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        Self::from_vox(BufReader::new(file))
+    }
+
+    /// Reads the first model of a `.vox` file. Scene graph transforms and
+    /// any further models are ignored. Files without a palette chunk are
+    /// given a uniform gray palette.
+    pub fn from_vox<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != b"VOX " {
+            return Err(invalid_data("missing VOX header"));
+        }
+
+        let (id, content, children) = read_chunk(&mut reader)?;
+        if &id != b"MAIN" || !content.is_empty() {
+            return Err(invalid_data("missing MAIN chunk"));
+        }
+
+        let mut size = None;
+        let mut cells = None;
+        let mut palette = vec![Vec3::new(0.5, 0.5, 0.5); 256];
+
+        let mut children = &children[..];
+        while !children.is_empty() {
+            let (id, content, _) = read_chunk(&mut children)?;
+            match &id {
+                b"SIZE" if size.is_none() => {
+                    let mut content = &content[..];
+                    let dims = [
+                        read_u32(&mut content)? as usize,
+                        read_u32(&mut content)? as usize,
+                        read_u32(&mut content)? as usize,
+                    ];
+                    if dims.iter().any(|&dim| dim == 0 || dim > MAX_SIZE) {
+                        return Err(invalid_data("model size out of range"));
+                    }
+                    size = Some(dims);
+                }
+                b"XYZI" if cells.is_none() => {
+                    let [sx, sy, sz] = size.ok_or_else(|| invalid_data("XYZI before SIZE"))?;
+                    let mut grid = vec![0u8; sx * sy * sz];
+                    let mut content = &content[..];
+                    let count = read_u32(&mut content)? as usize;
+                    for _ in 0..count {
+                        let mut voxel = [0u8; 4];
+                        content.read_exact(&mut voxel)?;
+                        let [x, y, z, color] = voxel.map(|b| b as usize);
+                        if x >= sx || y >= sy || z >= sz {
+                            return Err(invalid_data("voxel outside the model"));
+                        }
+                        grid[x + sx * (y + sy * z)] = color as u8;
+                    }
+                    cells = Some(grid);
+                }
+                b"RGBA" => {
+                    // Entry `i` of the chunk is the color of palette index
+                    // `i + 1`.
+                    for (i, rgba) in content.chunks_exact(4).take(255).enumerate() {
+                        palette[i + 1] = srgb_to_linear(rgba[0], rgba[1], rgba[2]);
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        let size = size.ok_or_else(|| invalid_data("missing SIZE chunk"))?;
+        let cells = cells.ok_or_else(|| invalid_data("missing XYZI chunk"))?;
+        Ok(Self {
+            size,
+            cells,
+            palette,
+        })
+    }
+
+    /// Number of cells along `x`, `y` and `z`, `z` pointing up.
+    pub const fn size(&self) -> [usize; 3] {
+        self.size
+    }
+
+    /// Palette index of the voxel at `(x, y, z)`, or `None` if the cell is
+    /// empty or outside the model.
+    pub fn voxel(&self, x: usize, y: usize, z: usize) -> Option<u8> {
+        let [sx, sy, sz] = self.size;
+        if x >= sx || y >= sy || z >= sz {
+            return None;
+        }
+        match self.cells[x + sx * (y + sy * z)] {
+            0 => None,
+            color => Some(color),
+        }
+    }
+
+    /// Linear color of palette index `index`.
+    pub fn color(&self, index: u8) -> Vec3 {
+        self.palette[index as usize]
+    }
+
+    /// Builds the visible surface of the model out of quads with Lambertian
+    /// materials of the palette colors.
+    ///
+    /// Adjacent faces of the same color are merged into larger quads. The
+    /// model's `z` up axis is mapped to `+y`, each voxel is a cube of side
+    /// `voxel_size` and the model's corner is placed at `origin`.
+    pub fn to_hit_list(&self, origin: Vec3, voxel_size: Float) -> HitList {
+        let mut list = HitList::new();
+        // Voxel space to world space, keeping the handedness.
+        let to_world = |[x, y, z]: [Float; 3]| Vec3::new(x, z, -y) * voxel_size;
+
+        let at = |p: [isize; 3]| -> u8 {
+            if p.iter().any(|&c| c < 0) {
+                return 0;
+            }
+            self.voxel(p[0] as usize, p[1] as usize, p[2] as usize)
+                .unwrap_or(0)
+        };
+
+        for d in 0..3 {
+            let (u, v) = ((d + 1) % 3, (d + 2) % 3);
+            let (su, sv) = (self.size[u], self.size[v]);
+            let mut mask = vec![0i16; su * sv];
+
+            for k in 0..=self.size[d] {
+                // Faces on the plane between layers `k - 1` and `k`, positive
+                // for faces looking towards `+d`.
+                for j in 0..sv {
+                    for i in 0..su {
+                        let mut p = [0isize; 3];
+                        p[d] = k as isize;
+                        p[u] = i as isize;
+                        p[v] = j as isize;
+                        let b = at(p);
+                        p[d] -= 1;
+                        let a = at(p);
+                        mask[i + su * j] = match (a, b) {
+                            (0, 0) => 0,
+                            (a, 0) => a as i16,
+                            (0, b) => -(b as i16),
+                            _ => 0,
+                        };
+                    }
+                }
+
+                for j in 0..sv {
+                    let mut i = 0;
+                    while i < su {
+                        let face = mask[i + su * j];
+                        if face == 0 {
+                            i += 1;
+                            continue;
+                        }
+
+                        let mut width = 1;
+                        while i + width < su && mask[i + width + su * j] == face {
+                            width += 1;
+                        }
+                        let mut height = 1;
+                        while j + height < sv
+                            && (0..width).all(|w| mask[i + w + su * (j + height)] == face)
+                        {
+                            height += 1;
+                        }
+
+                        let mut corner = [0.0; 3];
+                        corner[d] = k as Float;
+                        corner[u] = i as Float;
+                        corner[v] = j as Float;
+                        let mut du = [0.0; 3];
+                        du[u] = width as Float;
+                        let mut dv = [0.0; 3];
+                        dv[v] = height as Float;
+
+                        let material = Material::lambertian(self.color(face.unsigned_abs() as u8));
+                        list.push(Quad::new(
+                            origin + to_world(corner),
+                            to_world(du),
+                            to_world(dv),
+                            material,
+                        ));
+
+                        for h in 0..height {
+                            for w in 0..width {
+                                mask[i + w + su * (j + h)] = 0;
+                            }
+                        }
+                        i += width;
+                    }
+                }
+            }
+        }
+
+        list
+    }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+/// Reads a chunk id with its content and children bytes.
+fn read_chunk<R: Read>(reader: &mut R) -> io::Result<([u8; 4], Vec<u8>, Vec<u8>)> {
+    let mut id = [0u8; 4];
+    reader.read_exact(&mut id)?;
+    let content_size = read_u32(reader)?;
+    let children_size = read_u32(reader)?;
+
+    let content = read_bytes(reader, content_size)?;
+    let children = read_bytes(reader, children_size)?;
+    Ok((id, content, children))
+}
+
+/// Reads `len` bytes, growing the buffer with the data actually read rather
+/// than trusting `len`, which may exceed what is left of a corrupt file.
+fn read_bytes<R: Read>(reader: &mut R, len: u32) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    reader.take(len.into()).read_to_end(&mut bytes)?;
+    if bytes.len() != len as usize {
+        return Err(invalid_data("truncated chunk"));
+    }
+    Ok(bytes)
+}
+
+/// Converts an 8-bit color to linear, matching the gamma 2 encoding used
+/// when writing images.
+fn srgb_to_linear(r: u8, g: u8, b: u8) -> Vec3 {
+    let linear = |c: u8| (c as Float / 255.0).powi(2);
+    Vec3::new(linear(r), linear(g), linear(b))
+}
@@ -0,0 +1,181 @@
+//! BVH persistence and editing: a saved tree loads back identical, corrupt
+//! files are rejected, and edited trees stay valid and hit what a fresh build
+//! over the same objects hits.
+
+use std::{io, sync::Arc};
+
+use ray::{
+    Float,
+    bvh::Bvh,
+    geo::Sphere,
+    hit::{Hit, HitList, ObjectId},
+    interval::Interval,
+    material::Material,
+    ray::Ray,
+    vec3::Vec3,
+};
+
+/// Grid of `n * n` small spheres in the `z = 0` plane.
+fn spheres(n: usize) -> HitList {
+    let mut list = HitList::new();
+    for i in 0..n {
+        for j in 0..n {
+            let center = Vec3::new(i as Float, j as Float, 0.0);
+            let material = Material::lambertian(Vec3::new(0.5, 0.5, 0.5));
+            list.push(Sphere::new(center, 0.3, material));
+        }
+    }
+    list
+}
+
+/// Rays shot down the `z` axis through every sphere center of the grid and
+/// halfway between them.
+fn probes(n: usize) -> Vec<Ray> {
+    (0..2 * n)
+        .flat_map(|i| (0..2 * n).map(move |j| (i, j)))
+        .map(|(i, j)| {
+            let origin = Vec3::new(i as Float / 2.0, j as Float / 2.0, 5.0);
+            Ray::new(origin, Vec3::new(0.0, 0.0, -1.0), 0.0)
+        })
+        .collect()
+}
+
+fn hits<T: Hit>(world: &T, rays: &[Ray]) -> Vec<Option<(Float, Option<ObjectId>)>> {
+    rays.iter()
+        .map(|ray| {
+            world
+                .hit(ray, Interval::new(0.001, Float::INFINITY))
+                .map(|hit| (hit.t(), hit.object_id()))
+        })
+        .collect()
+}
+
+fn build(list: &HitList) -> Bvh {
+    let mut objects = list.list().to_vec();
+    Bvh::from_list(&mut objects)
+}
+
+#[test]
+fn save_load_round_trip() {
+    let list = spheres(8);
+    let bvh = build(&list);
+
+    let mut bytes = Vec::new();
+    bvh.save(&mut bytes).unwrap();
+    let loaded = Bvh::load(bytes.as_slice(), list.list()).unwrap();
+
+    assert_eq!(loaded.nodes(), bvh.nodes());
+    let rays = probes(8);
+    assert_eq!(hits(&loaded, &rays), hits(&bvh, &rays));
+}
+
+#[test]
+fn load_rejects_corrupt_headers() {
+    let list = spheres(4);
+    let mut bytes = Vec::new();
+    build(&list).save(&mut bytes).unwrap();
+
+    // A node count far beyond what the primitives allow must fail before
+    // anything is allocated for it.
+    let mut huge = bytes.clone();
+    huge[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+    let err = Bvh::load(huge.as_slice(), list.list()).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+    let truncated = &bytes[..bytes.len() / 2];
+    assert!(Bvh::load(truncated, list.list()).is_err());
+}
+
+#[test]
+fn load_rejects_deep_chains() {
+    // Every interior node holds one primitive in its first child and the
+    // rest of the chain in its second, as deep as the primitives allow.
+    let n = 100;
+    let list = spheres(10);
+    let mut bytes = b"BVH1".to_vec();
+    bytes.extend((2 * n as u32 - 1).to_le_bytes());
+    bytes.extend((n as u32).to_le_bytes());
+    let mut node = |tag: u8, a: u32, b: u32| {
+        for _ in 0..3 {
+            bytes.extend((-100.0f64).to_le_bytes());
+            bytes.extend(100.0f64.to_le_bytes());
+        }
+        bytes.push(tag);
+        bytes.extend(a.to_le_bytes());
+        bytes.extend(b.to_le_bytes());
+    };
+    for i in 0..n as u32 - 1 {
+        node(1, 2 * i + 2, 0);
+        node(0, i, 1);
+    }
+    node(0, n as u32 - 1, 1);
+    for i in 0..n as u32 {
+        bytes.extend(i.to_le_bytes());
+    }
+
+    let err = Bvh::load(bytes.as_slice(), list.list()).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert!(err.to_string().contains("too deep"), "{err}");
+}
+
+#[test]
+fn edits_match_a_fresh_build() {
+    let n = 8;
+    let mut list = spheres(n);
+    let mut bvh = build(&list);
+    let ids: Vec<ObjectId> = list
+        .list()
+        .iter()
+        .filter_map(|obj| obj.object_id())
+        .collect();
+    let material = Material::lambertian(Vec3::new(0.5, 0.5, 0.5));
+
+    // Remove every third sphere, move every fifth halfway to its neighbor and
+    // add spheres between them, crowding into one corner, which must stay
+    // shallow enough to traverse.
+    for (i, &id) in ids.iter().enumerate() {
+        if i % 3 == 0 {
+            assert!(list.remove(id).is_some());
+            assert!(bvh.remove(id).is_some());
+        } else if i % 5 == 0 {
+            let center = Vec3::new((i / n) as Float + 0.5, (i % n) as Float, 0.0);
+            list.replace(id, Sphere::new(center, 0.3, material));
+            bvh.replace(id, list.get(id).unwrap().clone()).unwrap();
+        }
+        bvh.validate().unwrap();
+    }
+    for i in 0..2 * n {
+        let center = Vec3::new(0.5, i as Float / (4 * n) as Float, 0.0);
+        let id = list.push(Sphere::new(center, 0.01, material));
+        bvh.insert(list.get(id).unwrap().clone());
+        bvh.validate().unwrap();
+    }
+    assert!(bvh.remove(ids[0]).is_none());
+
+    let rays = probes(n);
+    assert_eq!(hits(&bvh, &rays), hits(&build(&list), &rays));
+    assert_eq!(bvh.count(), list.list().len());
+
+    // The saved tree refers to the edited list.
+    let mut bytes = Vec::new();
+    bvh.save(&mut bytes).unwrap();
+    let loaded = Bvh::load(bytes.as_slice(), list.list()).unwrap();
+    assert_eq!(hits(&loaded, &rays), hits(&bvh, &rays));
+}
+
+#[test]
+fn removing_everything_leaves_an_empty_tree() {
+    let list = spheres(4);
+    let mut bvh = build(&list);
+    for obj in list.list() {
+        bvh.remove(obj.object_id().unwrap()).unwrap();
+        bvh.validate().unwrap();
+    }
+    assert_eq!(bvh.count(), 0);
+    assert_eq!(hits(&bvh, &probes(4)), vec![None; 64]);
+
+    let material = Material::lambertian(Vec3::new(0.5, 0.5, 0.5));
+    bvh.insert(Arc::new(Sphere::new(Vec3::default(), 0.3, material)));
+    bvh.validate().unwrap();
+    assert_eq!(bvh.count(), 1);
+}
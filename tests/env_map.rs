@@ -0,0 +1,44 @@
+//! Radiance `.hdr` environment maps: well formed files load and corrupt
+//! headers are rejected as invalid data rather than panicking or exhausting
+//! memory.
+
+use std::io;
+
+use ray::background::EnvMap;
+
+/// Flat encoded `.hdr` file with `size` as its resolution line followed by
+/// `pixels` gray pixels.
+fn hdr(size: &str, pixels: usize) -> Vec<u8> {
+    let mut bytes = format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n{size}\n").into_bytes();
+    for _ in 0..pixels {
+        bytes.extend([128, 128, 128, 129]);
+    }
+    bytes
+}
+
+#[test]
+fn reads_flat_pixels() {
+    let map = EnvMap::from_hdr(hdr("-Y 2 +X 3", 6).as_slice()).unwrap();
+    assert_eq!((map.width(), map.height()), (3, 2));
+    assert_eq!(map.pixel(2, 1).x(), 1.0);
+}
+
+#[test]
+fn rejects_bad_sizes() {
+    for size in [
+        "-Y 1 +X 0",
+        "-Y 0 +X 4",
+        "-Y 100000 +X 100000",
+        "-Y 18446744073709551615 +X 18446744073709551615",
+    ] {
+        let err = EnvMap::from_hdr(hdr(size, 4).as_slice()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{size}");
+    }
+}
+
+#[test]
+fn rejects_truncated_pixels() {
+    // A plausible header promising far more pixels than the file holds.
+    let err = EnvMap::from_hdr(hdr("-Y 8000 +X 16000", 4).as_slice()).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+}
@@ -0,0 +1,143 @@
+//! Golden-image regression tests: every built-in scene is rendered small
+//! with a fixed seed and compared against the reference in `tests/golden`.
+//!
+//! Run with `RAY_BLESS=1` to write the references from the current renderer
+//! after an intended change in its output, and review the new images before
+//! committing them.
+
+use std::{
+    env,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Read},
+    path::PathBuf,
+};
+
+use ray::{Float, film::Image, scenes::Preset, vec3::Vec3};
+
+const WIDTH: u32 = 32;
+const SAMPLES: u32 = 16;
+const MAX_DEPTH: u32 = 8;
+const SEED: u64 = 0;
+
+/// Largest root mean square difference of the display encoded channels, in
+/// `[0, 1]`, that still counts as the same image. Loose enough for `f32`
+/// builds and floating point differences between platforms, tight enough to
+/// catch a changed material, sampler or traversal.
+const MAX_RMSE: f64 = 0.02;
+
+fn render(preset: Preset) -> Image {
+    let scene = preset.build(SEED);
+    scene
+        .camera()
+        .aspect_ratio(1.0)
+        .img_width(WIDTH)
+        .build()
+        .with_seed(SEED)
+        .render_frame(scene.world(), scene.lights(), SAMPLES, MAX_DEPTH)
+        .into_beauty()
+}
+
+fn reference_path(preset: Preset) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{preset}.pfm"))
+}
+
+/// Reads a PFM as written by [`Image::write_pfm`].
+fn read_pfm(path: &PathBuf) -> io::Result<Image> {
+    let invalid = |msg| io::Error::new(io::ErrorKind::InvalidData, msg);
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut header = Vec::new();
+    for _ in 0..3 {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        header.push(line.trim().to_owned());
+    }
+    if header[0] != "PF" || header[2] != "-1.0" {
+        return Err(invalid("not a little endian color PFM"));
+    }
+    let (width, height) = header[1]
+        .split_once(' ')
+        .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+        .ok_or_else(|| invalid("bad PFM size"))?;
+
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    if bytes.len() != width as usize * height as usize * 12 {
+        return Err(invalid("truncated PFM"));
+    }
+    let channel = |i: usize| f32::from_le_bytes(bytes[i * 4..i * 4 + 4].try_into().unwrap());
+
+    let mut image = Image::new(width, height);
+    for i in 0..width * height {
+        let c = 3 * i as usize;
+        let color = Vec3::new(
+            channel(c) as Float,
+            channel(c + 1) as Float,
+            channel(c + 2) as Float,
+        );
+        // PFM rows run from the bottom.
+        image.set(i % width, height - 1 - i / width, color);
+    }
+    Ok(image)
+}
+
+fn rmse(a: &Image, b: &Image) -> f64 {
+    let a = a.to_rgba8();
+    let b = b.to_rgba8();
+    let sum: f64 = a
+        .chunks_exact(4)
+        .zip(b.chunks_exact(4))
+        .flat_map(|(a, b)| (0..3).map(move |c| (a[c] as f64 - b[c] as f64) / 255.0))
+        .map(|d| d * d)
+        .sum();
+    (sum / (a.len() / 4 * 3) as f64).sqrt()
+}
+
+fn check(preset: Preset) {
+    let image = render(preset);
+    let path = reference_path(preset);
+    if env::var_os("RAY_BLESS").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        image.write_pfm(File::create(&path).unwrap()).unwrap();
+        return;
+    }
+
+    let reference = read_pfm(&path).unwrap_or_else(|err| {
+        panic!(
+            "cannot read {}: {err}, run with RAY_BLESS=1 to create it",
+            path.display()
+        )
+    });
+    assert_eq!(
+        (image.width(), image.height()),
+        (reference.width(), reference.height()),
+        "{preset}: size differs from the reference"
+    );
+    let rmse = rmse(&image, &reference);
+    assert!(
+        rmse <= MAX_RMSE,
+        "{preset}: RMSE {rmse:.4} against {} exceeds {MAX_RMSE}",
+        path.display()
+    );
+}
+
+#[test]
+fn spheres() {
+    check(Preset::Spheres);
+}
+
+#[test]
+fn triangles() {
+    check(Preset::Triangles);
+}
+
+#[test]
+fn quads() {
+    check(Preset::Quads);
+}
+
+#[test]
+fn cornell_box() {
+    check(Preset::CornellBox);
+}
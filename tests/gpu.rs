@@ -0,0 +1,50 @@
+//! GPU scene support: scenes the shader would render differently from the
+//! CPU must report it so that they fall back to the CPU.
+
+#![cfg(feature = "gpu")]
+
+use ray::{
+    geo::Sphere,
+    gpu::GpuScene,
+    material::{Dielectric, Dispersion, Material},
+    vec3::Vec3,
+};
+
+fn ball(material: Material) -> Sphere<Material> {
+    Sphere::new(Vec3::new(0.0, 0.0, -1.0), 0.5, material)
+}
+
+#[test]
+fn plain_materials_are_supported() {
+    let scene = GpuScene::from_spheres(&[
+        ball(Material::dialectric(1.5)),
+        Sphere::new(
+            Vec3::new(0.0, -100.5, -1.0),
+            100.0,
+            Material::lambertian(Vec3::new(0.5, 0.5, 0.5)),
+        ),
+    ]);
+    assert!(scene.is_supported());
+}
+
+#[test]
+fn nested_dielectrics_are_not_supported() {
+    let bubble = Material::Dielectric(Dielectric::new(1.0).with_priority(1));
+    let scene = GpuScene::from_spheres(&[
+        ball(Material::dialectric(1.5)),
+        Sphere::new(Vec3::new(0.0, 0.0, -1.0), 0.3, bubble),
+    ]);
+    assert!(!scene.is_supported());
+}
+
+#[test]
+fn dispersive_dielectrics_are_not_supported() {
+    let prism = Material::Dielectric(Dielectric::dispersive(Dispersion::SF11));
+    assert!(!GpuScene::from_spheres(&[ball(prism)]).is_supported());
+}
+
+#[test]
+fn isotropic_materials_are_not_supported() {
+    let fog = Material::isotropic(Vec3::new(0.8, 0.8, 0.8));
+    assert!(!GpuScene::from_spheres(&[ball(fog)]).is_supported());
+}
@@ -0,0 +1,53 @@
+//! IES LM-63 profiles: well formed files load and corrupt ones are rejected
+//! as invalid data rather than panicking.
+
+use std::io;
+
+use ray::ies::IesProfile;
+
+/// Type C profile with `counts` as its vertical and horizontal angle counts,
+/// followed by `data`.
+fn ies(tilt: &str, counts: &str, data: &str) -> String {
+    format!(
+        "IESNA:LM-63-2002\n[TEST] test\nTILT={tilt}\n1 1000 1 {counts} 1 1 0 0 0\n1 1 100\n{data}\n"
+    )
+}
+
+fn read(text: &str) -> io::Result<IesProfile> {
+    IesProfile::from_ies(text.as_bytes())
+}
+
+#[test]
+fn reads_type_c_profile() {
+    let profile = read(&ies("NONE", "3 1", "0 45 90\n0\n100 50 0")).unwrap();
+    assert_eq!(profile.vertical_angles(), [0.0, 45.0, 90.0]);
+    assert_eq!(profile.max_candela(), 100.0);
+
+    let tilted = ies("INCLUDE\n1\n2\n0 90\n1 1", "3 1", "0 45 90\n0\n100 50 0");
+    assert_eq!(read(&tilted).unwrap(), profile);
+}
+
+#[test]
+fn rejects_bad_counts() {
+    for (tilt, counts) in [
+        ("NONE", "1e30 1"),
+        ("NONE", "3 1e30"),
+        ("NONE", "3 -1"),
+        ("NONE", "2.5 1"),
+        ("NONE", "NaN 1"),
+        ("NONE", "100000 100000"),
+        ("INCLUDE\n1\n1e30", "3 1"),
+    ] {
+        let err = read(&ies(tilt, counts, "0 45 90\n0\n100 50 0")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{tilt} {counts}");
+    }
+}
+
+#[test]
+fn rejects_truncated_data() {
+    let err = read(&ies("NONE", "3 1", "0 45 90\n0\n100")).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+    let header = "IESNA:LM-63-2002\nTILT=NONE\n1 1000 1 3";
+    assert!(read(header).is_err());
+}